@@ -8,8 +8,14 @@ fn main() {
 }
 
 fn test() -> suruga::tls_result::TlsResult<()> {
+    let mut roots = suruga::RootStore::new();
+    try!(roots.add_pem_file(suruga::roots::SYSTEM_BUNDLE));
+    let mut config = suruga::ClientConfig::new();
+    config.server_name = Some("www.google.com".to_string());
+    config.verifier = Box::new(suruga::verify::WebPkiVerifier::new(roots));
+
     let stream = try!(TcpStream::connect("www.google.com:443"));
-    let mut client = try!(suruga::TlsClient::from_tcp(stream));
+    let mut client = try!(suruga::TlsClient::from_tcp_with_config(stream, config));
     let _len = try!(client.write(b"GET / HTTP/1.1\r\nHost: www.google.com\r\n\r\n"));
 
     let mut msg = vec![0u8; 100];
@@ -2,8 +2,7 @@ use util::{ReadExt, WriteExt};
 use tls_result::{TlsResult, TlsError, TlsErrorKind};
 use tls_item::TlsItem;
 
-// we treat every alert as fatal.
-tls_enum!(u8, enum AlertLevel {
+tls_enum!(u8, #[derive(Debug)] enum AlertLevel {
     warning(1),
     fatal(2)
 });
@@ -33,17 +32,25 @@ tls_enum!(u8, #[derive(Debug)] enum AlertDescription {
     protocol_version(70),
     insufficient_security(71),
     internal_error(80),
+    // RFC 7507
+    inappropriate_fallback(86),
     user_canceled(90),
     no_renegotiation(100),
-    unsupported_extension(110)
+    unsupported_extension(110),
 
     // RFC 6066
-    // certificate_unobtainable(111),
-    // unrecognized_name(112),
-    // bad_certificate_status_response(113),
-    // bad_certificate_hash_value(114),
+    certificate_unobtainable(111),
+    unrecognized_name(112),
+    bad_certificate_status_response(113),
+    bad_certificate_hash_value(114),
+
+    // RFC 7301
+    no_application_protocol(120)
 });
 
+/// Callback invoked for every warning alert which is ignored by the reader.
+pub type AlertCallback = Box<FnMut(&Alert) + Send + 'static>;
+
 impl AlertDescription {
     fn from_err(kind: TlsErrorKind) -> AlertDescription {
         match kind {
@@ -53,14 +60,46 @@ impl AlertDescription {
             TlsErrorKind::IllegalParameter => AlertDescription::illegal_parameter,
             TlsErrorKind::DecodeError => AlertDescription::decode_error,
             TlsErrorKind::DecryptError => AlertDescription::decrypt_error,
+            TlsErrorKind::HandshakeFailure => AlertDescription::handshake_failure,
+            TlsErrorKind::BadCertificate => AlertDescription::bad_certificate,
+            TlsErrorKind::CertificateRevoked => AlertDescription::certificate_revoked,
+            TlsErrorKind::CertificateExpired => AlertDescription::certificate_expired,
+            TlsErrorKind::CertificateUnknown => AlertDescription::certificate_unknown,
+            TlsErrorKind::UnknownCa => AlertDescription::unknown_ca,
+            TlsErrorKind::UnsupportedExtension => AlertDescription::unsupported_extension,
+            TlsErrorKind::BadCertificateStatusResponse => {
+                AlertDescription::bad_certificate_status_response
+            }
+            TlsErrorKind::ProtocolVersion => AlertDescription::protocol_version,
+            TlsErrorKind::InsufficientSecurity => AlertDescription::insufficient_security,
             TlsErrorKind::InternalError => AlertDescription::internal_error,
 
             // FIXME: we probably can't even send alert?
             TlsErrorKind::IoFailure => AlertDescription::internal_error,
             TlsErrorKind::AlertReceived => AlertDescription::close_notify,
+            TlsErrorKind::InappropriateFallback => AlertDescription::close_notify,
         }
 
     }
+
+    /// Returns `true` if the alert is fatal regardless of its `AlertLevel`.
+    ///
+    /// RFC 5246 7.2 says these messages are "always fatal". RFC 6066 alerts are
+    /// fatal too, except `unrecognized_name` which servers often send as a warning.
+    pub fn is_always_fatal(&self) -> bool {
+        match *self {
+            AlertDescription::close_notify |
+            AlertDescription::user_canceled |
+            AlertDescription::no_renegotiation |
+            AlertDescription::bad_certificate |
+            AlertDescription::unsupported_certificate |
+            AlertDescription::certificate_revoked |
+            AlertDescription::certificate_expired |
+            AlertDescription::certificate_unknown |
+            AlertDescription::unrecognized_name => false,
+            _ => true,
+        }
+    }
 }
 
 tls_struct!(struct Alert {
@@ -77,6 +116,18 @@ impl Alert {
         })
     }
 
+    pub fn new_warning(desc: AlertDescription) -> Alert {
+        Alert {
+            level: AlertLevel::warning,
+            description: desc,
+        }
+    }
+
+    /// Returns `true` if the alert signals a fatal error.
+    pub fn is_fatal(&self) -> bool {
+        self.level == AlertLevel::fatal || self.description.is_always_fatal()
+    }
+
     pub fn from_tls_err(err: &TlsError) -> Alert {
         Alert {
             level: AlertLevel::fatal,
@@ -0,0 +1,100 @@
+//! Base64 (RFC 4648 4) with the standard alphabet and padding.
+
+const ALPHABET: &'static [u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity((data.len() + 2) / 3 * 4);
+    for chunk in data.chunks(3) {
+        let b = [chunk[0],
+                 if chunk.len() > 1 { chunk[1] } else { 0 },
+                 if chunk.len() > 2 { chunk[2] } else { 0 }];
+        let n = (b[0] as usize) << 16 | (b[1] as usize) << 8 | b[2] as usize;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i)) & 0x3f] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn decode_char(c: u8) -> Option<u32> {
+    match c {
+        b'A'..=b'Z' => Some((c - b'A') as u32),
+        b'a'..=b'z' => Some((c - b'a') as u32 + 26),
+        b'0'..=b'9' => Some((c - b'0') as u32 + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+/// Decodes `data`. Whitespace, missing padding and non-zero trailing bits are rejected.
+pub fn decode(data: &str) -> Option<Vec<u8>> {
+    let data = data.as_bytes();
+    if data.len() % 4 != 0 {
+        return None;
+    }
+    let mut out = Vec::with_capacity(data.len() / 4 * 3);
+    for (i, chunk) in data.chunks(4).enumerate() {
+        let is_last = (i + 1) * 4 == data.len();
+        let padding = if !is_last {
+            0
+        } else if chunk[2] == b'=' && chunk[3] == b'=' {
+            2
+        } else if chunk[3] == b'=' {
+            1
+        } else {
+            0
+        };
+
+        let mut n = 0u32;
+        for &c in chunk[..(4 - padding)].iter() {
+            match decode_char(c) {
+                Some(bits) => n = (n << 6) | bits,
+                None => return None,
+            }
+        }
+        n <<= 6 * padding;
+
+        let bytes = [(n >> 16) as u8, (n >> 8) as u8, n as u8];
+        // the unused bits must be zero
+        if padding > 0 && bytes[3 - padding] != 0 {
+            return None;
+        }
+        out.extend(&bytes[..(3 - padding)]);
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{encode, decode};
+
+    #[test]
+    fn test_base64() {
+        // RFC 4648 10
+        let vectors: &[(&[u8], &str)] = &[
+            (b"", ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="),
+            (b"fooba", "Zm9vYmE="),
+            (b"foobar", "Zm9vYmFy"),
+        ];
+        for &(data, encoded) in vectors.iter() {
+            assert_eq!(encode(data), encoded);
+            assert_eq!(decode(encoded).unwrap(), data);
+        }
+        assert_eq!(decode("+/+/").unwrap(), vec![0xfb, 0xff, 0xbf]);
+
+        let bad = ["Zg", "Zg=", "Zg=a", "Zh==", "Zm9=", "Zg==Zg==", "Zm9v\n", "Zm!v", "=Zm9"];
+        for data in bad.iter() {
+            assert!(decode(data).is_none(), "{}", data);
+        }
+    }
+}
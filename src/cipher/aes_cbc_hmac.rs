@@ -0,0 +1,489 @@
+// AES-CBC with HMAC (RFC 5246 6.2.3.2), MAC-then-encrypt or
+// encrypt-then-MAC (RFC 7366).
+//
+// MAC-then-encrypt is vulnerable to padding oracles unless decryption takes
+// the same time regardless of padding and MAC validity (Lucky Thirteen,
+// http://www.isg.rhul.ac.uk/tls/TLStiming.pdf).
+// We check padding, compute HMAC and extract MAC without secret-dependent branches
+// or memory access, processing the maximum number of hash blocks for every record.
+
+use rand::{Rng, OsRng};
+
+use crypto::aes::Aes;
+use crypto::hash::{self, HashFunction};
+use crypto::hmac::hmac;
+use crypto::sha1;
+use crypto::sha2;
+use crypto::wrapping::*;
+use util::crypto_compare;
+use tls_result::TlsResult;
+use tls_result::TlsErrorKind::{BadRecordMac, InternalError};
+use super::{Encryptor, Decryptor, Aead};
+
+const BLOCK_LEN: usize = 16;
+
+// length of `seq_num || type || version` in `ad`
+const AD_PREFIX_LEN: usize = 8 + 1 + 2;
+
+// hash function for record MAC. SHA-1 and SHA-256 have the same block structure,
+// which `constant_time_hmac` relies on.
+struct MacHash {
+    hash: &'static HashFunction,
+    initial_state: &'static [u32],
+    compress: fn(&mut [w32], &[u8]),
+}
+
+static MAC_SHA1: MacHash = MacHash {
+    hash: &hash::SHA1,
+    initial_state: &sha1::INITIAL_STATE,
+    compress: sha1::compress,
+};
+
+static MAC_SHA256: MacHash = MacHash {
+    hash: &hash::SHA256,
+    initial_state: &sha2::SHA256_INITIAL_STATE,
+    compress: sha2::sha256_compress,
+};
+
+// all-one mask if `a == b`
+fn ct_eq(a: usize, b: usize) -> u8 {
+    let x = a ^ b;
+    let nonzero = (x | x.wrapping_neg()) >> (usize_bits() - 1);
+    (nonzero as u8).wrapping_sub(1)
+}
+
+// all-one mask if `a < b`. `a` and `b` must be less than 2^(bits - 1).
+fn ct_lt(a: usize, b: usize) -> u8 {
+    let lt = a.wrapping_sub(b) >> (usize_bits() - 1);
+    0u8.wrapping_sub(lt as u8)
+}
+
+fn usize_bits() -> usize {
+    ::std::mem::size_of::<usize>() * 8
+}
+
+// HMAC of `ad_prefix || len || data[..data_len]` where `len` is `data_len` as u16.
+// `data_len` is SECRET: the computation only depends on `data.len()`.
+fn constant_time_hmac(mac: &MacHash, key: &[u8], ad_prefix: &[u8],
+                      data: &[u8], data_len: usize) -> Vec<u8> {
+    let block_len = mac.hash.block_len;
+    debug_assert!(key.len() <= block_len);
+    debug_assert!(data_len <= data.len());
+
+    let mut ipad = vec![0x36u8; block_len];
+    let mut opad = vec![0x5cu8; block_len];
+    for i in 0..key.len() {
+        ipad[i] ^= key[i];
+        opad[i] ^= key[i];
+    }
+
+    let mut state: Vec<w32> = mac.initial_state.iter().map(|&v| w32(v)).collect();
+    (mac.compress)(&mut state, &ipad);
+
+    // the rest of inner hash input: ad_prefix || len || data
+    let mut input = ad_prefix.to_vec();
+    input.push((data_len >> 8) as u8);
+    input.push(data_len as u8);
+    let prefix_len = input.len();
+    input.extend(data);
+
+    // SECRET
+    let msg_len = prefix_len + data_len;
+    let bit_len = ((block_len + msg_len) as u64) * 8;
+    let last_block = (msg_len + 8) / block_len;
+
+    let max_msg_len = input.len();
+    let num_blocks = (max_msg_len + 8) / block_len + 1;
+
+    let mut result = state.clone();
+    let mut block = vec![0u8; block_len];
+    for b in 0..num_blocks {
+        let is_last = ct_eq(b, last_block);
+        for j in 0..block_len {
+            let k = b * block_len + j;
+            let byte = if k < input.len() { input[k] } else { 0 };
+            let mut v = (byte & ct_lt(k, msg_len)) | (0x80 & ct_eq(k, msg_len));
+            if j >= block_len - 8 {
+                let len_byte = (bit_len >> (8 * (block_len - 1 - j))) as u8;
+                v |= len_byte & is_last;
+            }
+            block[j] = v;
+        }
+        (mac.compress)(&mut state, &block);
+
+        let mask = w32(is_last as u32) * w32(0x01010101);
+        for i in 0..state.len() {
+            result[i] = result[i] ^ ((result[i] ^ state[i]) & mask);
+        }
+    }
+
+    let mut inner = Vec::with_capacity(result.len() * 4);
+    for v in result.iter() {
+        for i in (0..4).rev() {
+            inner.push((*v >> (8 * i)).to_w8().0);
+        }
+    }
+    inner.truncate(mac.hash.output_len);
+
+    opad.extend(&inner);
+    (mac.hash.digest)(&opad)
+}
+
+fn record_mac(mac: &MacHash, key: &[u8], ad_prefix: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut msg = ad_prefix.to_vec();
+    msg.push((data.len() >> 8) as u8);
+    msg.push(data.len() as u8);
+    msg.extend(data);
+    hmac(mac.hash, key, &msg)
+}
+
+fn cbc_encrypt(aes: &Aes, iv: &[u8], data: &mut [u8]) {
+    let mut prev = [0u8; BLOCK_LEN];
+    prev.copy_from_slice(iv);
+    for chunk in data.chunks_mut(BLOCK_LEN) {
+        for i in 0..BLOCK_LEN {
+            prev[i] ^= chunk[i];
+        }
+        aes.encrypt_block(&mut prev);
+        chunk.copy_from_slice(&prev);
+    }
+}
+
+fn cbc_decrypt(aes: &Aes, iv: &[u8], data: &mut [u8]) {
+    let mut prev = [0u8; BLOCK_LEN];
+    prev.copy_from_slice(iv);
+    for chunk in data.chunks_mut(BLOCK_LEN) {
+        let mut block = [0u8; BLOCK_LEN];
+        block.copy_from_slice(chunk);
+        let encrypted = block;
+        aes.decrypt_block(&mut block);
+        for i in 0..BLOCK_LEN {
+            chunk[i] = block[i] ^ prev[i];
+        }
+        prev = encrypted;
+    }
+}
+
+// append padding so that `data.len()` becomes a multiple of block length
+fn pad(data: &mut Vec<u8>) {
+    let pad_len = BLOCK_LEN - 1 - data.len() % BLOCK_LEN;
+    for _ in 0..(pad_len + 1) {
+        data.push(pad_len as u8);
+    }
+}
+
+struct AesCbcHmacEncryptor {
+    mac: &'static MacHash,
+    encrypt_then_mac: bool,
+    mac_key: Vec<u8>,
+    aes: Aes,
+    rng: OsRng,
+}
+
+impl Encryptor for AesCbcHmacEncryptor {
+    fn encrypt(&mut self, _nonce: &[u8], data: &[u8], ad: &[u8]) -> Vec<u8> {
+        let ad_prefix = &ad[..AD_PREFIX_LEN];
+
+        let mut iv = [0u8; BLOCK_LEN];
+        self.rng.fill_bytes(&mut iv);
+
+        let mut encrypted = data.to_vec();
+        if !self.encrypt_then_mac {
+            let mac = record_mac(self.mac, &self.mac_key, ad_prefix, data);
+            encrypted.extend(&mac);
+        }
+        pad(&mut encrypted);
+        cbc_encrypt(&self.aes, &iv, &mut encrypted);
+
+        let mut ret = iv.to_vec();
+        ret.extend(&encrypted);
+        if self.encrypt_then_mac {
+            let mac = record_mac(self.mac, &self.mac_key, ad_prefix, &ret);
+            ret.extend(&mac);
+        }
+        ret
+    }
+}
+
+struct AesCbcHmacDecryptor {
+    mac: &'static MacHash,
+    encrypt_then_mac: bool,
+    mac_key: Vec<u8>,
+    aes: Aes,
+}
+
+impl AesCbcHmacDecryptor {
+    fn decrypt_encrypt_then_mac(&self, data: &[u8], ad_prefix: &[u8]) -> TlsResult<Vec<u8>> {
+        let mac_len = self.mac.hash.output_len;
+        let len = data.len();
+        if len < BLOCK_LEN * 2 + mac_len || (len - mac_len) % BLOCK_LEN != 0 {
+            return tls_err!(BadRecordMac, "wrong record length: {}", len);
+        }
+
+        let (encrypted, mac_expected) = data.split_at(len - mac_len);
+        let mac_computed = record_mac(self.mac, &self.mac_key, ad_prefix, encrypted);
+        if !crypto_compare(&mac_computed, mac_expected) {
+            return tls_err!(BadRecordMac, "wrong mac");
+        }
+
+        let (iv, encrypted) = encrypted.split_at(BLOCK_LEN);
+        let mut plain = encrypted.to_vec();
+        cbc_decrypt(&self.aes, iv, &mut plain);
+
+        // MAC is already verified, so padding check does not have to be constant-time.
+        let n = plain.len();
+        let pad_len = plain[n - 1] as usize;
+        if pad_len + 1 > n || plain[(n - 1 - pad_len)..].iter().any(|&b| b as usize != pad_len) {
+            return tls_err!(BadRecordMac, "wrong padding");
+        }
+        plain.truncate(n - 1 - pad_len);
+        Ok(plain)
+    }
+
+    fn decrypt_mac_then_encrypt(&self, data: &[u8], ad_prefix: &[u8]) -> TlsResult<Vec<u8>> {
+        let mac_len = self.mac.hash.output_len;
+        let len = data.len();
+        // IV, then at least mac and one padding byte
+        let min_len = BLOCK_LEN + (mac_len + 1 + BLOCK_LEN - 1) / BLOCK_LEN * BLOCK_LEN;
+        if len < min_len || len % BLOCK_LEN != 0 {
+            return tls_err!(BadRecordMac, "wrong record length: {}", len);
+        }
+
+        let (iv, encrypted) = data.split_at(BLOCK_LEN);
+        // SECRET
+        let mut plain = encrypted.to_vec();
+        cbc_decrypt(&self.aes, iv, &mut plain);
+        let n = plain.len();
+
+        // SECRET
+        // from now, every branch and memory access must not depend on `plain`.
+        let mut pad_len = plain[n - 1] as usize;
+        let mut good = !ct_lt(n, pad_len + 1 + mac_len);
+
+        let to_check = if n < 256 { n } else { 256 };
+        for i in 0..to_check {
+            let b = plain[n - 1 - i];
+            let in_padding = !ct_lt(pad_len, i);
+            good &= !(in_padding & !ct_eq(b as usize, pad_len));
+        }
+        // if padding is wrong, continue as if there is no padding.
+        pad_len &= 0usize.wrapping_sub((good & 1) as usize);
+        let data_len = n - 1 - mac_len - pad_len;
+
+        // extract MAC at `plain[data_len..(data_len + mac_len)]`.
+        // scan every possible position so that memory access does not depend on `data_len`.
+        let scan_start = if n > mac_len + 256 { n - mac_len - 256 } else { 0 };
+        let mut mac_expected = vec![0u8; mac_len];
+        for j in scan_start..n {
+            let offset = j.wrapping_sub(data_len);
+            for k in 0..mac_len {
+                mac_expected[k] |= plain[j] & ct_eq(offset, k);
+            }
+        }
+
+        let mac_computed = constant_time_hmac(self.mac, &self.mac_key, ad_prefix,
+                                              &plain[..(n - 1 - mac_len)], data_len);
+        let mut diff = 0u8;
+        for k in 0..mac_len {
+            diff |= mac_computed[k] ^ mac_expected[k];
+        }
+        good &= ct_eq(diff as usize, 0);
+
+        // padding error and MAC error must be indistinguishable.
+        if good == 0 {
+            return tls_err!(BadRecordMac, "wrong mac");
+        }
+
+        plain.truncate(data_len);
+        Ok(plain)
+    }
+}
+
+impl Decryptor for AesCbcHmacDecryptor {
+    fn decrypt(&mut self, _nonce: &[u8], data: &[u8], ad: &[u8]) -> TlsResult<Vec<u8>> {
+        let ad_prefix = &ad[..AD_PREFIX_LEN];
+        if self.encrypt_then_mac {
+            self.decrypt_encrypt_then_mac(data, ad_prefix)
+        } else {
+            self.decrypt_mac_then_encrypt(data, ad_prefix)
+        }
+    }
+
+    #[inline(always)]
+    fn mac_len(&self) -> usize {
+        self.mac.hash.output_len
+    }
+}
+
+pub struct AesCbcHmac {
+    key_size: usize,
+    mac: &'static MacHash,
+    encrypt_then_mac: bool,
+}
+
+impl AesCbcHmac {
+    pub fn aes_128_sha1() -> AesCbcHmac {
+        AesCbcHmac {
+            key_size: 16,
+            mac: &MAC_SHA1,
+            encrypt_then_mac: false,
+        }
+    }
+
+    pub fn aes_128_sha256() -> AesCbcHmac {
+        AesCbcHmac {
+            key_size: 16,
+            mac: &MAC_SHA256,
+            encrypt_then_mac: false,
+        }
+    }
+
+    // split `mac_key || enc_key`
+    fn split_key(&self, key: Vec<u8>) -> (Vec<u8>, Aes) {
+        let mac_key_size = self.mac_key_size();
+        assert_eq!(key.len(), mac_key_size + self.key_size);
+        let aes = Aes::new(&key[mac_key_size..]);
+        let mut mac_key = key;
+        mac_key.truncate(mac_key_size);
+        (mac_key, aes)
+    }
+}
+
+impl Aead for AesCbcHmac {
+    #[inline(always)]
+    fn key_size(&self) -> usize {
+        self.key_size
+    }
+
+    // TLS 1.2 uses explicit IV for each record
+    #[inline(always)]
+    fn fixed_iv_len(&self) -> usize {
+        0
+    }
+
+    #[inline(always)]
+    fn mac_len(&self) -> usize {
+        self.mac.hash.output_len
+    }
+
+    #[inline(always)]
+    fn mac_key_size(&self) -> usize {
+        self.mac.hash.output_len
+    }
+
+    fn encrypt_then_mac(&self) -> Option<Box<Aead>> {
+        Some(Box::new(AesCbcHmac {
+            key_size: self.key_size,
+            mac: self.mac,
+            encrypt_then_mac: true,
+        }) as Box<Aead>)
+    }
+
+    fn new_encryptor(&self, key: Vec<u8>) -> TlsResult<Box<Encryptor + Send + 'static>> {
+        let rng = match OsRng::new() {
+            Ok(rng) => rng,
+            Err(..) => return tls_err!(InternalError, "failed to create OsRng"),
+        };
+        let (mac_key, aes) = self.split_key(key);
+        let encryptor = AesCbcHmacEncryptor {
+            mac: self.mac,
+            encrypt_then_mac: self.encrypt_then_mac,
+            mac_key: mac_key,
+            aes: aes,
+            rng: rng,
+        };
+        Ok(Box::new(encryptor) as Box<Encryptor + Send>)
+    }
+
+    fn new_decryptor(&self, key: Vec<u8>) -> TlsResult<Box<Decryptor + Send + 'static>> {
+        let (mac_key, aes) = self.split_key(key);
+        let decryptor = AesCbcHmacDecryptor {
+            mac: self.mac,
+            encrypt_then_mac: self.encrypt_then_mac,
+            mac_key: mac_key,
+            aes: aes,
+        };
+        Ok(Box::new(decryptor) as Box<Decryptor + Send>)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tls_result::TlsErrorKind::BadRecordMac;
+    use crypto::hmac::hmac;
+    use super::super::Aead;
+    use super::{AesCbcHmac, MAC_SHA1, MAC_SHA256, constant_time_hmac};
+
+    fn ad(seq: u8) -> Vec<u8> {
+        // seq_num, type, version, length (ignored)
+        vec![0, 0, 0, 0, 0, 0, 0, seq, 23, 3, 3, 0, 0]
+    }
+
+    #[test]
+    fn test_constant_time_hmac() {
+        let data: Vec<u8> = (0..300).map(|i| i as u8).collect();
+        for mac in [&MAC_SHA1, &MAC_SHA256].iter() {
+            let key = vec![0x0bu8; mac.hash.output_len];
+            for data_len in 0..data.len() {
+                let mut msg = ad(1)[..11].to_vec();
+                msg.push((data_len >> 8) as u8);
+                msg.push(data_len as u8);
+                msg.extend(&data[..data_len]);
+                let expected = hmac(mac.hash, &key, &msg);
+
+                let computed = constant_time_hmac(mac, &key, &ad(1)[..11], &data, data_len);
+                assert_eq!(computed, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_decrypt_mac_then_encrypt() {
+        // generated by python `hmac` and `cryptography` with IV 0x03...
+        static ENCRYPTED: &'static [u8] =
+            b"\x03\x03\x03\x03\x03\x03\x03\x03\x03\x03\x03\x03\x03\x03\x03\x03\
+              \xa0\x65\x99\x4b\x8b\x84\x00\x26\xda\x0a\x05\xbe\x63\x31\xfe\x30\
+              \x4c\x1b\xd8\xf2\x8e\xaf\xe6\xa4\x2b\xe7\x65\x7a\x76\x25\x4a\x40\
+              \xe1\x79\x8c\xdf\x1e\x31\x60\xc5\xf1\xb5\xa3\x2a\x21\x5d\x84\x22";
+
+        let mut key = vec![1u8; 20];
+        key.extend(&[2u8; 16]);
+        let mut decryptor = AesCbcHmac::aes_128_sha1().new_decryptor(key).unwrap();
+        let plain = decryptor.decrypt(&[], ENCRYPTED, &ad(1)).unwrap();
+        assert_eq!(&plain[..], b"hello, world");
+
+        // wrong sequence number
+        assert!(decryptor.decrypt(&[], ENCRYPTED, &ad(2)).is_err());
+    }
+
+    #[test]
+    fn test_aes_cbc_hmac() {
+        let suites = [AesCbcHmac::aes_128_sha1(), AesCbcHmac::aes_128_sha256()];
+        for suite in suites.iter() {
+            let etm = suite.encrypt_then_mac().unwrap();
+            for aead in [suite as &Aead, &*etm].iter() {
+                let key_len = aead.mac_key_size() + aead.key_size();
+                let key: Vec<u8> = (0..key_len).map(|i| i as u8).collect();
+                let mut encryptor = aead.new_encryptor(key.clone()).unwrap();
+                let mut decryptor = aead.new_decryptor(key).unwrap();
+
+                for len in 0..40 {
+                    let plain: Vec<u8> = (0..len).map(|i| (i * 7) as u8).collect();
+                    let encrypted = encryptor.encrypt(&[], &plain, &ad(1));
+                    let decrypted = decryptor.decrypt(&[], &encrypted, &ad(1)).unwrap();
+                    assert_eq!(decrypted, plain);
+
+                    // every modification must be detected
+                    for i in 0..encrypted.len() {
+                        let mut modified = encrypted.clone();
+                        modified[i] ^= 0x80;
+                        let err = decryptor.decrypt(&[], &modified, &ad(1)).unwrap_err();
+                        assert_eq!(err.kind, BadRecordMac);
+                    }
+                    assert!(decryptor.decrypt(&[], &encrypted[..(encrypted.len() - 16)],
+                                              &ad(1)).is_err());
+                }
+            }
+        }
+    }
+}
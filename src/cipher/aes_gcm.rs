@@ -0,0 +1,132 @@
+// AES-GCM cipher suites (RFC 5288).
+//
+// The nonce is `salt || explicit_nonce`, where the 4-byte salt is the fixed IV from key block
+// and the 8-byte explicit nonce is sent before each record. We use the sequence number as
+// the explicit nonce, so that nonces never repeat under the same key.
+
+use crypto::gcm::{AesGcm, NONCE_LEN, TAG_LEN};
+use tls_result::TlsResult;
+use tls_result::TlsErrorKind::BadRecordMac;
+use super::{Encryptor, Decryptor, Aead};
+
+const SALT_LEN: usize = 4;
+const EXPLICIT_NONCE_LEN: usize = 8;
+
+// split `enc_key || salt`
+fn split_key(key: &[u8]) -> (AesGcm, Vec<u8>) {
+    let (enc_key, salt) = key.split_at(key.len() - SALT_LEN);
+    (AesGcm::new(enc_key), salt.to_vec())
+}
+
+fn nonce(salt: &[u8], explicit_nonce: &[u8]) -> Vec<u8> {
+    let mut nonce = Vec::with_capacity(NONCE_LEN);
+    nonce.extend(salt);
+    nonce.extend(explicit_nonce);
+    nonce
+}
+
+struct AesGcmEncryptor {
+    gcm: AesGcm,
+    salt: Vec<u8>,
+}
+
+impl Encryptor for AesGcmEncryptor {
+    fn encrypt(&mut self, seq_num: &[u8], data: &[u8], ad: &[u8]) -> Vec<u8> {
+        let mut ret = seq_num.to_vec();
+        ret.extend(&self.gcm.seal(&nonce(&self.salt, seq_num), ad, data));
+        ret
+    }
+}
+
+struct AesGcmDecryptor {
+    gcm: AesGcm,
+    salt: Vec<u8>,
+}
+
+impl Decryptor for AesGcmDecryptor {
+    fn decrypt(&mut self, _seq_num: &[u8], data: &[u8], ad: &[u8]) -> TlsResult<Vec<u8>> {
+        if data.len() < EXPLICIT_NONCE_LEN + TAG_LEN {
+            return tls_err!(BadRecordMac, "message too short");
+        }
+        let (explicit_nonce, data) = data.split_at(EXPLICIT_NONCE_LEN);
+        match self.gcm.open(&nonce(&self.salt, explicit_nonce), ad, data) {
+            Some(plain) => Ok(plain),
+            None => tls_err!(BadRecordMac, "wrong mac"),
+        }
+    }
+
+    // the record reader subtracts this from the record length to get the length
+    // in `ad`, so the explicit nonce is counted too.
+    fn mac_len(&self) -> usize {
+        EXPLICIT_NONCE_LEN + TAG_LEN
+    }
+}
+
+pub struct AesGcmAead {
+    key_size: usize,
+}
+
+impl AesGcmAead {
+    pub fn aes_128() -> AesGcmAead {
+        AesGcmAead { key_size: 16 }
+    }
+}
+
+impl Aead for AesGcmAead {
+    #[inline(always)]
+    fn key_size(&self) -> usize {
+        self.key_size
+    }
+
+    #[inline(always)]
+    fn fixed_iv_len(&self) -> usize {
+        SALT_LEN
+    }
+
+    #[inline(always)]
+    fn mac_len(&self) -> usize {
+        TAG_LEN
+    }
+
+    fn new_encryptor(&self, key: Vec<u8>) -> TlsResult<Box<Encryptor + Send + 'static>> {
+        let (gcm, salt) = split_key(&key);
+        Ok(Box::new(AesGcmEncryptor { gcm: gcm, salt: salt }) as Box<Encryptor + Send>)
+    }
+
+    fn new_decryptor(&self, key: Vec<u8>) -> TlsResult<Box<Decryptor + Send + 'static>> {
+        let (gcm, salt) = split_key(&key);
+        Ok(Box::new(AesGcmDecryptor { gcm: gcm, salt: salt }) as Box<Decryptor + Send>)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tls_result::TlsErrorKind::BadRecordMac;
+    use super::super::Aead;
+    use super::AesGcmAead;
+
+    #[test]
+    fn test_aes_gcm_record() {
+        let aead = AesGcmAead::aes_128();
+        let key: Vec<u8> = (0..20).collect();
+        let mut encryptor = aead.new_encryptor(key.clone()).unwrap();
+        let mut decryptor = aead.new_decryptor(key).unwrap();
+
+        let seq_num = [0, 0, 0, 0, 0, 0, 0, 5];
+        let ad = [0, 0, 0, 0, 0, 0, 0, 5, 23, 3, 3, 0, 5];
+        let encrypted = encryptor.encrypt(&seq_num, b"hello", &ad);
+        assert_eq!(encrypted.len(), 8 + 5 + 16);
+        assert_eq!(&encrypted[..8], &seq_num[..]);
+        assert_eq!(encrypted.len() - decryptor.mac_len(), 5);
+
+        // the explicit nonce from the record is used, not the sequence number
+        let plain = decryptor.decrypt(&[0; 8], &encrypted, &ad).unwrap();
+        assert_eq!(&plain[..], b"hello");
+
+        let mut bad = encrypted.clone();
+        bad[0] ^= 1;
+        assert_eq!(decryptor.decrypt(&[0; 8], &bad, &ad).unwrap_err().kind, BadRecordMac);
+        assert_eq!(decryptor.decrypt(&[0; 8], &encrypted[..23], &ad).unwrap_err().kind,
+                   BadRecordMac);
+    }
+}
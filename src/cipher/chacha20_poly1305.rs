@@ -1,13 +1,17 @@
 // Implements AEAD_CHACHA20_POLY1305
-// unfortunately, there is no concrete standard yet. some drafts exist:
+// `ChaCha20Poly1305` follows draft-agl-tls-chacha20poly1305-04 for the 0xcc13 cipher suite.
+// some drafts exist:
 // http://tools.ietf.org/html/draft-agl-tls-chacha20poly1305-01
 // http://tools.ietf.org/html/draft-agl-tls-chacha20poly1305-04
 // they differ in detail, so here we follow google/boringssl implementation.
 // openssl 1.0.2-aead branch seems to implement draft 01.
+//
+// `ChaCha20Poly1305Rfc7905` is the standard construction (RFC 7905, RFC 8439)
+// used by the PSK cipher suites.
 
 use crypto::chacha20::ChaCha20;
 use crypto::poly1305;
-use util::u64_le_array;
+use util::{u64_le_array, crypto_compare};
 use tls_result::TlsResult;
 use tls_result::TlsErrorKind::BadRecordMac;
 use super::{Encryptor, Decryptor, Aead};
@@ -118,18 +122,154 @@ impl Aead for ChaCha20Poly1305 {
     }
 
     #[inline(always)]
-    fn new_encryptor(&self, key: Vec<u8>) -> Box<Encryptor + Send + 'static> {
+    fn new_encryptor(&self, key: Vec<u8>) -> TlsResult<Box<Encryptor + Send + 'static>> {
         let encryptor = ChaCha20Poly1305Encryptor {
             key: key,
         };
-        Box::new(encryptor) as Box<Encryptor + Send>
+        Ok(Box::new(encryptor) as Box<Encryptor + Send>)
     }
 
     #[inline(always)]
-    fn new_decryptor(&self, key: Vec<u8>) -> Box<Decryptor + Send + 'static> {
+    fn new_decryptor(&self, key: Vec<u8>) -> TlsResult<Box<Decryptor + Send + 'static>> {
         let decryptor = ChaCha20Poly1305Decryptor {
             key: key,
         };
-        Box::new(decryptor) as Box<Decryptor + Send>
+        Ok(Box::new(decryptor) as Box<Decryptor + Send>)
+    }
+}
+
+// RFC 7905: the record nonce is the fixed IV xored with the sequence number.
+const FIXED_IV_LEN: usize = 12;
+
+// RFC 8439 2.8: data and ciphertext are padded to 16 bytes, lengths follow.
+fn compute_mac_rfc7905(poly_key: &[u8], encrypted: &[u8], ad: &[u8]) -> [u8; MAC_LEN] {
+    fn push_padded(vec: &mut Vec<u8>, data: &[u8]) {
+        vec.extend(data);
+        let padding = (16 - data.len() % 16) % 16;
+        vec.extend(&[0u8; 16][..padding]);
+    }
+
+    let mut msg = Vec::new();
+    push_padded(&mut msg, ad);
+    push_padded(&mut msg, encrypted);
+    msg.extend(&u64_le_array(ad.len() as u64));
+    msg.extend(&u64_le_array(encrypted.len() as u64));
+
+    let mut r = [0u8; MAC_LEN];
+    r.copy_from_slice(&poly_key[..MAC_LEN]);
+    let mut k = [0u8; MAC_LEN];
+    k.copy_from_slice(&poly_key[MAC_LEN..(2 * MAC_LEN)]);
+
+    poly1305::authenticate(&msg, &r, &k)
+}
+
+// `key` is `enc_key || fixed_iv`. returns ChaCha20 for the record, with the first block
+// already taken as the Poly1305 key.
+fn rfc7905_chacha20(key: &[u8], seq_num: &[u8]) -> (ChaCha20, [u8; 64]) {
+    let (enc_key, iv) = key.split_at(KEY_LEN);
+    let mut nonce = [0u8; FIXED_IV_LEN];
+    nonce.copy_from_slice(iv);
+    for i in 0..seq_num.len() {
+        nonce[FIXED_IV_LEN - seq_num.len() + i] ^= seq_num[i];
+    }
+    let mut chacha20 = ChaCha20::new_ietf(enc_key, &nonce);
+    let poly1305_key = chacha20.next();
+    (chacha20, poly1305_key)
+}
+
+struct Rfc7905Encryptor {
+    key: Vec<u8>,
+}
+
+impl Encryptor for Rfc7905Encryptor {
+    fn encrypt(&mut self, seq_num: &[u8], data: &[u8], ad: &[u8]) -> Vec<u8> {
+        let (mut chacha20, poly1305_key) = rfc7905_chacha20(&self.key, seq_num);
+        let mut encrypted = chacha20.encrypt(data);
+        let mac = compute_mac_rfc7905(&poly1305_key, &encrypted, ad);
+        encrypted.extend(&mac);
+        encrypted
+    }
+}
+
+struct Rfc7905Decryptor {
+    key: Vec<u8>,
+}
+
+impl Decryptor for Rfc7905Decryptor {
+    fn decrypt(&mut self, seq_num: &[u8], data: &[u8], ad: &[u8]) -> TlsResult<Vec<u8>> {
+        if data.len() < MAC_LEN {
+            return tls_err!(BadRecordMac, "message too short");
+        }
+        let (encrypted, mac_expected) = data.split_at(data.len() - MAC_LEN);
+        let (mut chacha20, poly1305_key) = rfc7905_chacha20(&self.key, seq_num);
+        let mac_computed = compute_mac_rfc7905(&poly1305_key, encrypted, ad);
+        // SECRET
+        let plain = chacha20.encrypt(encrypted);
+        if !crypto_compare(&mac_computed, mac_expected) {
+            return tls_err!(BadRecordMac, "wrong mac");
+        }
+        Ok(plain)
+    }
+
+    fn mac_len(&self) -> usize {
+        MAC_LEN
+    }
+}
+
+pub struct ChaCha20Poly1305Rfc7905;
+
+impl Aead for ChaCha20Poly1305Rfc7905 {
+    fn key_size(&self) -> usize {
+        KEY_LEN
+    }
+
+    fn fixed_iv_len(&self) -> usize {
+        FIXED_IV_LEN
+    }
+
+    fn mac_len(&self) -> usize {
+        MAC_LEN
+    }
+
+    fn new_encryptor(&self, key: Vec<u8>) -> TlsResult<Box<Encryptor + Send + 'static>> {
+        Ok(Box::new(Rfc7905Encryptor { key: key }) as Box<Encryptor + Send>)
+    }
+
+    fn new_decryptor(&self, key: Vec<u8>) -> TlsResult<Box<Decryptor + Send + 'static>> {
+        Ok(Box::new(Rfc7905Decryptor { key: key }) as Box<Decryptor + Send>)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cipher::Aead;
+    use super::ChaCha20Poly1305Rfc7905;
+
+    #[test]
+    fn test_chacha20_poly1305_rfc7905() {
+        // RFC 8439 2.8.2, with the nonce split into the fixed IV and sequence number 0
+        let mut key: Vec<u8> = (0x80..0xa0).collect();
+        key.extend(b"\x07\x00\x00\x00\x40\x41\x42\x43\x44\x45\x46\x47");
+        let seq_num = [0u8; 8];
+        let ad = b"\x50\x51\x52\x53\xc0\xc1\xc2\xc3\xc4\xc5\xc6\xc7";
+        let plain: &[u8] = b"Ladies and Gentlemen of the class of '99: If I could offer you \
+                             only one tip for the future, sunscreen would be it.";
+        let expected_tag = b"\x1a\xe1\x0b\x59\x4f\x09\xe2\x6a\
+                             \x7e\x90\x2e\xcb\xd0\x60\x06\x91";
+
+        let aead = ChaCha20Poly1305Rfc7905;
+        let mut encryptor = aead.new_encryptor(key.clone()).unwrap();
+        let encrypted = encryptor.encrypt(&seq_num, plain, ad);
+        assert_eq!(encrypted.len(), plain.len() + 16);
+        assert_eq!(&encrypted[..4], b"\xd3\x1a\x8d\x34");
+        assert_eq!(&encrypted[plain.len()..], expected_tag);
+
+        let mut decryptor = aead.new_decryptor(key.clone()).unwrap();
+        assert_eq!(decryptor.decrypt(&seq_num, &encrypted, ad).unwrap(), plain);
+        let mut bad = encrypted.clone();
+        bad[0] ^= 1;
+        assert!(decryptor.decrypt(&seq_num, &bad, ad).is_err());
+        // the sequence number changes the nonce
+        assert!(decryptor.decrypt(&[0, 0, 0, 0, 0, 0, 0, 1], &encrypted, ad).is_err());
     }
 }
@@ -0,0 +1,475 @@
+//! Finite field Diffie-Hellman key exchange (`DHE_RSA`, RFC 5246).
+//!
+//! The groups of RFC 7919 are accepted as they are. Other groups sent by the server must be
+//! safe primes of `MIN_BITS` to `MAX_BITS` bits, which is checked on every handshake.
+
+use std::io::Cursor;
+use rand::{Rng, OsRng};
+
+use crypto::bigint::Modulus;
+use util::{ReadExt, WriteExt};
+use tls_result::TlsResult;
+use tls_result::TlsErrorKind::{IllegalParameter, DecryptError, InsufficientSecurity};
+use tls_item::TlsItem;
+use handshake::{NamedCurve, ECPointFormat};
+use signature::{DigitallySigned, SignatureAndHashAlgorithm};
+use super::KeyExchange;
+
+/// Smallest group accepted.
+pub const MIN_BITS: usize = 2048;
+/// Largest group accepted, which bounds the cost of the exponentiations.
+pub const MAX_BITS: usize = 8192;
+
+// bytes of the private exponent. RFC 7919 5.2 asks for at least twice the security
+// strength of the group, which is about 400 bits for ffdhe8192.
+const EXPONENT_LEN: usize = 64;
+// Miller-Rabin rounds for (p - 1) / 2 of groups which are not RFC 7919 groups.
+// the group is signed by the server, so this only guards against broken configurations.
+const PRIME_TEST_ROUNDS: usize = 16;
+
+tls_vec!(DhData = u8(1, (1 << 16) - 1));
+
+tls_struct!(struct ServerDhParams {
+    dh_p: DhData,
+    dh_g: DhData,
+    dh_ys: DhData
+});
+
+tls_struct!(struct DheServerKeyExchange {
+    params: ServerDhParams,
+    signed_params: DigitallySigned
+});
+
+// RFC 7919 Appendix A. the generator is 2.
+const FFDHE2048_P: &'static str = "\
+    FFFFFFFFFFFFFFFFADF85458A2BB4A9AAFDC5620273D3CF1D8B9C583CE2D3695\
+    A9E13641146433FBCC939DCE249B3EF97D2FE363630C75D8F681B202AEC4617A\
+    D3DF1ED5D5FD65612433F51F5F066ED0856365553DED1AF3B557135E7F57C935\
+    984F0C70E0E68B77E2A689DAF3EFE8721DF158A136ADE73530ACCA4F483A797A\
+    BC0AB182B324FB61D108A94BB2C8E3FBB96ADAB760D7F4681D4F42A3DE394DF4\
+    AE56EDE76372BB190B07A7C8EE0A6D709E02FCE1CDF7E2ECC03404CD28342F61\
+    9172FE9CE98583FF8E4F1232EEF28183C3FE3B1B4C6FAD733BB5FCBC2EC22005\
+    C58EF1837D1683B2C6F34A26C1B2EFFA886B423861285C97FFFFFFFFFFFFFFFF";
+
+const FFDHE3072_P: &'static str = "\
+    FFFFFFFFFFFFFFFFADF85458A2BB4A9AAFDC5620273D3CF1D8B9C583CE2D3695\
+    A9E13641146433FBCC939DCE249B3EF97D2FE363630C75D8F681B202AEC4617A\
+    D3DF1ED5D5FD65612433F51F5F066ED0856365553DED1AF3B557135E7F57C935\
+    984F0C70E0E68B77E2A689DAF3EFE8721DF158A136ADE73530ACCA4F483A797A\
+    BC0AB182B324FB61D108A94BB2C8E3FBB96ADAB760D7F4681D4F42A3DE394DF4\
+    AE56EDE76372BB190B07A7C8EE0A6D709E02FCE1CDF7E2ECC03404CD28342F61\
+    9172FE9CE98583FF8E4F1232EEF28183C3FE3B1B4C6FAD733BB5FCBC2EC22005\
+    C58EF1837D1683B2C6F34A26C1B2EFFA886B4238611FCFDCDE355B3B6519035B\
+    BC34F4DEF99C023861B46FC9D6E6C9077AD91D2691F7F7EE598CB0FAC186D91C\
+    AEFE130985139270B4130C93BC437944F4FD4452E2D74DD364F2E21E71F54BFF\
+    5CAE82AB9C9DF69EE86D2BC522363A0DABC521979B0DEADA1DBF9A42D5C4484E\
+    0ABCD06BFA53DDEF3C1B20EE3FD59D7C25E41D2B66C62E37FFFFFFFFFFFFFFFF";
+
+const FFDHE4096_P: &'static str = "\
+    FFFFFFFFFFFFFFFFADF85458A2BB4A9AAFDC5620273D3CF1D8B9C583CE2D3695\
+    A9E13641146433FBCC939DCE249B3EF97D2FE363630C75D8F681B202AEC4617A\
+    D3DF1ED5D5FD65612433F51F5F066ED0856365553DED1AF3B557135E7F57C935\
+    984F0C70E0E68B77E2A689DAF3EFE8721DF158A136ADE73530ACCA4F483A797A\
+    BC0AB182B324FB61D108A94BB2C8E3FBB96ADAB760D7F4681D4F42A3DE394DF4\
+    AE56EDE76372BB190B07A7C8EE0A6D709E02FCE1CDF7E2ECC03404CD28342F61\
+    9172FE9CE98583FF8E4F1232EEF28183C3FE3B1B4C6FAD733BB5FCBC2EC22005\
+    C58EF1837D1683B2C6F34A26C1B2EFFA886B4238611FCFDCDE355B3B6519035B\
+    BC34F4DEF99C023861B46FC9D6E6C9077AD91D2691F7F7EE598CB0FAC186D91C\
+    AEFE130985139270B4130C93BC437944F4FD4452E2D74DD364F2E21E71F54BFF\
+    5CAE82AB9C9DF69EE86D2BC522363A0DABC521979B0DEADA1DBF9A42D5C4484E\
+    0ABCD06BFA53DDEF3C1B20EE3FD59D7C25E41D2B669E1EF16E6F52C3164DF4FB\
+    7930E9E4E58857B6AC7D5F42D69F6D187763CF1D5503400487F55BA57E31CC7A\
+    7135C886EFB4318AED6A1E012D9E6832A907600A918130C46DC778F971AD0038\
+    092999A333CB8B7A1A1DB93D7140003C2A4ECEA9F98D0ACC0A8291CDCEC97DCF\
+    8EC9B55A7F88A46B4DB5A851F44182E1C68A007E5E655F6AFFFFFFFFFFFFFFFF";
+
+const FFDHE6144_P: &'static str = "\
+    FFFFFFFFFFFFFFFFADF85458A2BB4A9AAFDC5620273D3CF1D8B9C583CE2D3695\
+    A9E13641146433FBCC939DCE249B3EF97D2FE363630C75D8F681B202AEC4617A\
+    D3DF1ED5D5FD65612433F51F5F066ED0856365553DED1AF3B557135E7F57C935\
+    984F0C70E0E68B77E2A689DAF3EFE8721DF158A136ADE73530ACCA4F483A797A\
+    BC0AB182B324FB61D108A94BB2C8E3FBB96ADAB760D7F4681D4F42A3DE394DF4\
+    AE56EDE76372BB190B07A7C8EE0A6D709E02FCE1CDF7E2ECC03404CD28342F61\
+    9172FE9CE98583FF8E4F1232EEF28183C3FE3B1B4C6FAD733BB5FCBC2EC22005\
+    C58EF1837D1683B2C6F34A26C1B2EFFA886B4238611FCFDCDE355B3B6519035B\
+    BC34F4DEF99C023861B46FC9D6E6C9077AD91D2691F7F7EE598CB0FAC186D91C\
+    AEFE130985139270B4130C93BC437944F4FD4452E2D74DD364F2E21E71F54BFF\
+    5CAE82AB9C9DF69EE86D2BC522363A0DABC521979B0DEADA1DBF9A42D5C4484E\
+    0ABCD06BFA53DDEF3C1B20EE3FD59D7C25E41D2B669E1EF16E6F52C3164DF4FB\
+    7930E9E4E58857B6AC7D5F42D69F6D187763CF1D5503400487F55BA57E31CC7A\
+    7135C886EFB4318AED6A1E012D9E6832A907600A918130C46DC778F971AD0038\
+    092999A333CB8B7A1A1DB93D7140003C2A4ECEA9F98D0ACC0A8291CDCEC97DCF\
+    8EC9B55A7F88A46B4DB5A851F44182E1C68A007E5E0DD9020BFD64B645036C7A\
+    4E677D2C38532A3A23BA4442CAF53EA63BB454329B7624C8917BDD64B1C0FD4C\
+    B38E8C334C701C3ACDAD0657FCCFEC719B1F5C3E4E46041F388147FB4CFDB477\
+    A52471F7A9A96910B855322EDB6340D8A00EF092350511E30ABEC1FFF9E3A26E\
+    7FB29F8C183023C3587E38DA0077D9B4763E4E4B94B2BBC194C6651E77CAF992\
+    EEAAC0232A281BF6B3A739C1226116820AE8DB5847A67CBEF9C9091B462D538C\
+    D72B03746AE77F5E62292C311562A846505DC82DB854338AE49F5235C95B9117\
+    8CCF2DD5CACEF403EC9D1810C6272B045B3B71F9DC6B80D63FDD4A8E9ADB1E69\
+    62A69526D43161C1A41D570D7938DAD4A40E329CD0E40E65FFFFFFFFFFFFFFFF";
+
+const FFDHE8192_P: &'static str = "\
+    FFFFFFFFFFFFFFFFADF85458A2BB4A9AAFDC5620273D3CF1D8B9C583CE2D3695\
+    A9E13641146433FBCC939DCE249B3EF97D2FE363630C75D8F681B202AEC4617A\
+    D3DF1ED5D5FD65612433F51F5F066ED0856365553DED1AF3B557135E7F57C935\
+    984F0C70E0E68B77E2A689DAF3EFE8721DF158A136ADE73530ACCA4F483A797A\
+    BC0AB182B324FB61D108A94BB2C8E3FBB96ADAB760D7F4681D4F42A3DE394DF4\
+    AE56EDE76372BB190B07A7C8EE0A6D709E02FCE1CDF7E2ECC03404CD28342F61\
+    9172FE9CE98583FF8E4F1232EEF28183C3FE3B1B4C6FAD733BB5FCBC2EC22005\
+    C58EF1837D1683B2C6F34A26C1B2EFFA886B4238611FCFDCDE355B3B6519035B\
+    BC34F4DEF99C023861B46FC9D6E6C9077AD91D2691F7F7EE598CB0FAC186D91C\
+    AEFE130985139270B4130C93BC437944F4FD4452E2D74DD364F2E21E71F54BFF\
+    5CAE82AB9C9DF69EE86D2BC522363A0DABC521979B0DEADA1DBF9A42D5C4484E\
+    0ABCD06BFA53DDEF3C1B20EE3FD59D7C25E41D2B669E1EF16E6F52C3164DF4FB\
+    7930E9E4E58857B6AC7D5F42D69F6D187763CF1D5503400487F55BA57E31CC7A\
+    7135C886EFB4318AED6A1E012D9E6832A907600A918130C46DC778F971AD0038\
+    092999A333CB8B7A1A1DB93D7140003C2A4ECEA9F98D0ACC0A8291CDCEC97DCF\
+    8EC9B55A7F88A46B4DB5A851F44182E1C68A007E5E0DD9020BFD64B645036C7A\
+    4E677D2C38532A3A23BA4442CAF53EA63BB454329B7624C8917BDD64B1C0FD4C\
+    B38E8C334C701C3ACDAD0657FCCFEC719B1F5C3E4E46041F388147FB4CFDB477\
+    A52471F7A9A96910B855322EDB6340D8A00EF092350511E30ABEC1FFF9E3A26E\
+    7FB29F8C183023C3587E38DA0077D9B4763E4E4B94B2BBC194C6651E77CAF992\
+    EEAAC0232A281BF6B3A739C1226116820AE8DB5847A67CBEF9C9091B462D538C\
+    D72B03746AE77F5E62292C311562A846505DC82DB854338AE49F5235C95B9117\
+    8CCF2DD5CACEF403EC9D1810C6272B045B3B71F9DC6B80D63FDD4A8E9ADB1E69\
+    62A69526D43161C1A41D570D7938DAD4A40E329CCFF46AAA36AD004CF600C838\
+    1E425A31D951AE64FDB23FCEC9509D43687FEB69EDD1CC5E0B8CC3BDF64B10EF\
+    86B63142A3AB8829555B2F747C932665CB2C0F1CC01BD70229388839D2AF05E4\
+    54504AC78B7582822846C0BA35C35F5C59160CC046FD8251541FC68C9C86B022\
+    BB7099876A460E7451A8A93109703FEE1C217E6C3826E52C51AA691E0E423CFC\
+    99E9E31650C1217B624816CDAD9A95F9D5B8019488D9C0A0A1FE3075A577E231\
+    83F81D4A3F2FA4571EFC8CE0BA8A4FE8B6855DFE72B0A66EDED2FBABFBE58A30\
+    FAFABE1C5D71A87E2F741EF8C1FE86FEA6BBFDE530677F0D97D11D49F7A8443D\
+    0822E506A9F4614E011E2A94838FF88CD68C8BB7C5C6424CFFFFFFFFFFFFFFFF";
+
+/// Groups of RFC 7919, offered in the `elliptic_curves` (`supported_groups`) extension.
+pub const FFDHE_GROUPS: &'static [NamedCurve] = &[
+    NamedCurve::ffdhe2048,
+    NamedCurve::ffdhe3072,
+    NamedCurve::ffdhe4096,
+    NamedCurve::ffdhe6144,
+    NamedCurve::ffdhe8192,
+];
+
+fn ffdhe_prime(group: NamedCurve) -> &'static str {
+    match group {
+        NamedCurve::ffdhe2048 => FFDHE2048_P,
+        NamedCurve::ffdhe3072 => FFDHE3072_P,
+        NamedCurve::ffdhe4096 => FFDHE4096_P,
+        NamedCurve::ffdhe6144 => FFDHE6144_P,
+        NamedCurve::ffdhe8192 => FFDHE8192_P,
+        _ => unreachable!(),
+    }
+}
+
+fn from_hex(hex: &str) -> Vec<u8> {
+    let hex = hex.as_bytes();
+    hex.chunks(2).map(|pair| {
+        let digit = |c: u8| (c as char).to_digit(16).unwrap() as u8;
+        (digit(pair[0]) << 4) | digit(pair[1])
+    }).collect()
+}
+
+fn strip_zeros(b: &[u8]) -> &[u8] {
+    let start = b.iter().position(|&c| c != 0).unwrap_or(b.len());
+    &b[start..]
+}
+
+/// Returns the RFC 7919 group with prime `p` and generator `g` (big-endian), if any.
+pub fn named_group(p: &[u8], g: &[u8]) -> Option<NamedCurve> {
+    if strip_zeros(g) != b"\x02" {
+        return None;
+    }
+    let p = strip_zeros(p);
+    FFDHE_GROUPS.iter().map(|&group| group).find(|&group| {
+        let prime = ffdhe_prime(group);
+        prime.len() == 2 * p.len() && from_hex(prime) == p
+    })
+}
+
+// `b >> 1` for big-endian `b`
+fn shift_right(b: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(b.len());
+    let mut carry = 0u8;
+    for &byte in b.iter() {
+        out.push((byte >> 1) | carry);
+        carry = byte << 7;
+    }
+    out
+}
+
+// Miller-Rabin test with random bases. `n` is odd and public.
+fn is_probable_prime(n: &[u8], rounds: usize, rng: &mut OsRng) -> bool {
+    let modulus = match Modulus::new(n) {
+        Some(modulus) => modulus,
+        None => return false,
+    };
+    let one = modulus.one();
+    let minus_one = modulus.sub(&modulus.zero(), &one);
+
+    // n - 1 = d * 2^s with odd d
+    let mut d = modulus.to_bytes(&minus_one);
+    let mut s = 0;
+    while d[d.len() - 1] & 1 == 0 {
+        d = shift_right(&d);
+        s += 1;
+    }
+
+    let mut random = vec![0u8; modulus.byte_len() + 8];
+    for _ in 0..rounds {
+        let base = loop {
+            rng.fill_bytes(&mut random);
+            let base = modulus.reduce_bytes(&random);
+            if modulus.is_nonzero(&base) == 1 && base != one && base != minus_one {
+                break base;
+            }
+        };
+        let mut x = modulus.pow(&base, &d);
+        if x == one || x == minus_one {
+            continue;
+        }
+        let mut witness = true;
+        for _ in 1..s {
+            x = modulus.mul(&x, &x);
+            if x == minus_one {
+                witness = false;
+                break;
+            }
+        }
+        if witness {
+            return false;
+        }
+    }
+    true
+}
+
+/// Checks that `p` is a safe prime, i.e. `(p - 1) / 2` is also a prime.
+pub fn is_safe_prime(p: &[u8], rng: &mut OsRng) -> bool {
+    let p = strip_zeros(p);
+    // p = 2q + 1 with odd q, and p is not a multiple of 3 (256 = 1 mod 3)
+    if p.len() == 0 || p[p.len() - 1] & 3 != 3 {
+        return false;
+    }
+    if p.iter().fold(0u64, |acc, &b| acc + b as u64) % 3 == 0 {
+        return false;
+    }
+    if !is_probable_prime(&shift_right(p), PRIME_TEST_ROUNDS, rng) {
+        return false;
+    }
+    // Pocklington: if q > sqrt(p) is a prime, 2^(p - 1) = 1 and gcd(2^2 - 1, p) = 1,
+    // then p is a prime.
+    let modulus = Modulus::new(p).unwrap();
+    let mut two = modulus.zero();
+    two[0] = 2;
+    let mut p_minus_1 = p.to_vec();
+    let last = p_minus_1.len() - 1;
+    p_minus_1[last] -= 1;
+    modulus.pow(&two, &p_minus_1) == modulus.one()
+}
+
+/// Checks that `p` and `g` are acceptable: an RFC 7919 group, or a safe prime of
+/// `MIN_BITS` to `MAX_BITS` bits with a generator in `2..(p - 1)`.
+pub fn check_group(p: &[u8], g: &[u8], rng: &mut OsRng) -> TlsResult<()> {
+    if named_group(p, g).is_some() {
+        return Ok(());
+    }
+    let modulus = match Modulus::new(p) {
+        Some(modulus) => modulus,
+        None => return tls_err!(IllegalParameter, "bad DH prime"),
+    };
+    if modulus.bit_len() < MIN_BITS {
+        return tls_err!(InsufficientSecurity, "DH group too small: {} bits", modulus.bit_len());
+    }
+    if modulus.bit_len() > MAX_BITS {
+        return tls_err!(IllegalParameter, "DH group too large: {} bits", modulus.bit_len());
+    }
+    if check_public(&modulus, g).is_none() {
+        return tls_err!(IllegalParameter, "bad DH generator");
+    }
+    if !is_safe_prime(p, rng) {
+        return tls_err!(InsufficientSecurity, "DH prime is not a safe prime");
+    }
+    Ok(())
+}
+
+// parses `y` and checks `1 < y < p - 1` (RFC 7919 5.1).
+fn check_public(p: &Modulus, y: &[u8]) -> Option<Vec<u32>> {
+    let y = match p.from_bytes(y) {
+        Some(y) => y,
+        None => return None,
+    };
+    let minus_one = p.sub(&p.zero(), &p.one());
+    if p.is_nonzero(&y) == 0 || y == p.one() || y == minus_one {
+        return None;
+    }
+    Some(y)
+}
+
+pub struct DiffieHellman;
+
+impl KeyExchange for DiffieHellman {
+    fn supported_groups(&self) -> Vec<NamedCurve> {
+        FFDHE_GROUPS.to_vec()
+    }
+
+    fn verify_server_params(&self,
+                            data: &[u8],
+                            client_random: &[u8],
+                            server_random: &[u8],
+                            spki: &[u8],
+                            offered: &[SignatureAndHashAlgorithm]) -> TlsResult<()> {
+        let mut reader = Cursor::new(data);
+        let dh_params: DheServerKeyExchange = try!(TlsItem::tls_read(&mut reader));
+
+        // RFC 5246 7.4.3: signed over randoms and `ServerDHParams`
+        let mut signed = Vec::new();
+        signed.extend(client_random);
+        signed.extend(server_random);
+        try!(dh_params.params.tls_write(&mut signed));
+        try!(dh_params.signed_params.check_offered(offered));
+        match dh_params.signed_params.verify(spki, &signed) {
+            Ok(()) => Ok(()),
+            Err(err) => tls_err!(DecryptError, "bad ServerKeyExchange signature: {}", err.desc),
+        }
+    }
+
+    fn compute_keys(&self,
+                    data: &[u8],
+                    _groups: &[NamedCurve],
+                    _point_formats: &[ECPointFormat],
+                    rng: &mut OsRng) -> TlsResult<(Vec<u8>, Vec<u8>)> {
+        let mut reader = Cursor::new(data);
+        let dh_params: DheServerKeyExchange = try!(TlsItem::tls_read(&mut reader));
+        let params = &dh_params.params;
+
+        try!(check_group(&params.dh_p, &params.dh_g, rng));
+        let p = Modulus::new(&params.dh_p).unwrap();
+        let g = p.from_bytes(&params.dh_g).unwrap();
+        let ys = match check_public(&p, &params.dh_ys) {
+            Some(ys) => ys,
+            None => return tls_err!(IllegalParameter, "server sent strange public key"),
+        };
+
+        let mut x = [0u8; EXPONENT_LEN];
+        rng.fill_bytes(&mut x);
+        let yc = p.to_bytes(&p.pow(&g, &x));
+        // RFC 5246 8.1.2: leading zero bytes of Z are stripped
+        let z = p.to_bytes(&p.pow(&ys, &x));
+        let pre_master_secret = strip_zeros(&z).to_vec();
+
+        let public = try!(DhData::new(yc));
+        let mut data = Vec::new();
+        try!(public.tls_write(&mut data));
+
+        Ok((data, pre_master_secret))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+    use rand::{Rng, OsRng};
+
+    use cipher::KeyExchange;
+    use crypto::bigint::Modulus;
+    use handshake::NamedCurve;
+    use signature::SUPPORTED_ALGORITHMS;
+    use tls_item::TlsItem;
+    use tls_result::TlsErrorKind::{IllegalParameter, InsufficientSecurity};
+    use test::unhex;
+    use x509::Certificate;
+    use super::{DiffieHellman, DhData, check_group, named_group, FFDHE2048_P};
+
+    static SERVER: &'static [u8] = include_bytes!("../../testdata/test_server.der");
+    // signed by `test_server.key` with client random 0x01.. and server random 0x02..
+    static SERVER_KEY_EXCHANGE: &'static [u8] = include_bytes!("../../testdata/ske_dhe_rsa.bin");
+    static SAFE_PRIME: &'static [u8] = include_bytes!("../../testdata/dh_safe_prime.bin");
+    static UNSAFE_PRIME: &'static [u8] = include_bytes!("../../testdata/dh_unsafe_prime.bin");
+
+    // RFC 2409 6.2, the 1024-bit MODP group
+    static MODP1024_P: &'static str = "\
+        FFFFFFFFFFFFFFFFC90FDAA22168C234C4C6628B80DC1CD129024E088A67CC74\
+        020BBEA63B139B22514A08798E3404DDEF9519B3CD3A431B302B0A6DF25F1437\
+        4FE1356D6D51C245E485B576625E7EC6F44C42E9A637ED6B0BFF5CB6F406B7ED\
+        EE386BFB5A899FA5AE9F24117C4B1FE649286651ECE65381FFFFFFFFFFFFFFFF";
+
+    #[test]
+    fn test_verify_server_params() {
+        let spki = Certificate::parse(SERVER).unwrap().subject_public_key_info;
+        let client_random = [1u8; 32];
+        let server_random = [2u8; 32];
+        let kex = DiffieHellman;
+        let offered = SUPPORTED_ALGORITHMS;
+        kex.verify_server_params(SERVER_KEY_EXCHANGE, &client_random, &server_random, spki,
+                                 offered).unwrap();
+        assert!(kex.verify_server_params(SERVER_KEY_EXCHANGE, &server_random, &client_random,
+                                         spki, offered).is_err());
+        let mut params = SERVER_KEY_EXCHANGE.to_vec();
+        params[100] ^= 1;
+        assert!(kex.verify_server_params(&params, &client_random, &server_random, spki,
+                                         offered).is_err());
+    }
+
+    // `ServerKeyExchange` with a dummy signature
+    fn server_key_exchange(p: &[u8], g: &[u8], ys: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for value in [p, g, ys].iter() {
+            DhData::new(value.to_vec()).unwrap().tls_write(&mut data).unwrap();
+        }
+        data.extend(&[4, 1, 0, 1, 0]);
+        data
+    }
+
+    #[test]
+    fn test_compute_keys() {
+        let mut rng = OsRng::new().unwrap();
+        let p_bytes = unhex(FFDHE2048_P);
+        let p = Modulus::new(&p_bytes).unwrap();
+        let g = p.from_bytes(&[2]).unwrap();
+        let mut y = [0u8; 32];
+        rng.fill_bytes(&mut y);
+        let ys = p.to_bytes(&p.pow(&g, &y));
+
+        let kex = DiffieHellman;
+        let data = server_key_exchange(&p_bytes, &[2], &ys);
+        let (public, pre_master_secret) = kex.compute_keys(&data, &[], &[], &mut rng).unwrap();
+        let yc: DhData = TlsItem::tls_read(&mut Cursor::new(&public)).unwrap();
+        let yc = p.from_bytes(&yc).unwrap();
+        let z = p.to_bytes(&p.pow(&yc, &y));
+        let start = z.iter().position(|&b| b != 0).unwrap();
+        assert_eq!(pre_master_secret, &z[start..]);
+
+        let minus_one = p.to_bytes(&p.sub(&p.zero(), &p.one()));
+        for bad in [&[0u8][..], &[1], &minus_one, &p_bytes].iter() {
+            let data = server_key_exchange(&p_bytes, &[2], bad);
+            let err = kex.compute_keys(&data, &[], &[], &mut rng).unwrap_err();
+            assert_eq!(err.kind, IllegalParameter);
+        }
+        let data = server_key_exchange(&unhex(MODP1024_P), &[2], &[2]);
+        let err = kex.compute_keys(&data, &[], &[], &mut rng).unwrap_err();
+        assert_eq!(err.kind, InsufficientSecurity);
+    }
+
+    #[test]
+    fn test_check_group() {
+        let mut rng = OsRng::new().unwrap();
+        let ffdhe2048 = unhex(FFDHE2048_P);
+        assert_eq!(named_group(&ffdhe2048, &[2]), Some(NamedCurve::ffdhe2048));
+        let mut padded = vec![0u8];
+        padded.extend(&ffdhe2048);
+        assert_eq!(named_group(&padded, &[0, 2]), Some(NamedCurve::ffdhe2048));
+        assert_eq!(named_group(&ffdhe2048, &[5]), None);
+        assert!(check_group(&ffdhe2048, &[2], &mut rng).is_ok());
+
+        assert!(check_group(SAFE_PRIME, &[2], &mut rng).is_ok());
+        assert_eq!(check_group(UNSAFE_PRIME, &[2], &mut rng).unwrap_err().kind,
+                   InsufficientSecurity);
+        assert_eq!(check_group(SAFE_PRIME, &[1], &mut rng).unwrap_err().kind, IllegalParameter);
+        assert_eq!(check_group(&unhex(MODP1024_P), &[2], &mut rng).unwrap_err().kind,
+                   InsufficientSecurity);
+        let mut even = ffdhe2048.clone();
+        even[255] ^= 1;
+        assert_eq!(check_group(&even, &[2], &mut rng).unwrap_err().kind, IllegalParameter);
+    }
+}
@@ -4,11 +4,14 @@ use rand::{Rng, OsRng};
 use crypto::wrapping::Wrapping as W;
 use util::{ReadExt, WriteExt};
 use tls_result::TlsResult;
-use tls_result::TlsErrorKind::IllegalParameter;
+use tls_result::TlsErrorKind::{IllegalParameter, DecryptError};
 use tls_item::TlsItem;
+use crypto::bigint::Modulus;
 use crypto::p256;
-use handshake::NamedCurve;
-use signature::DigitallySigned;
+use crypto::p384::{self, P384};
+use crypto::{mlkem, x25519};
+use handshake::{NamedCurve, ECPointFormat};
+use signature::{DigitallySigned, SignatureAndHashAlgorithm};
 use super::KeyExchange;
 
 tls_vec!(EcData = u8(1, (1 << 8) - 1));
@@ -94,59 +97,545 @@ tls_enum_struct!(u8, enum EcParameters {
     named_curve(NamedCurve) = 3
 });
 
-tls_struct!(struct ServerEcdhParams {
-    curve_params: EcParameters,
-    public: EcData
-});
+// public keys of groups with `long_shares`
+tls_vec!(KemShare = u8(1, (1 << 16) - 1));
+
+/// `ServerECDHParams`. `public` is sent as `ECPoint`, or as `KemShare` if the group
+/// has `long_shares`.
+pub struct ServerEcdhParams {
+    pub curve_params: EcParameters,
+    pub public: Vec<u8>,
+}
+
+fn has_long_shares(curve_params: &EcParameters) -> bool {
+    let EcParameters::named_curve(curve) = *curve_params;
+    find_group(curve).map_or(false, |group| group.long_shares())
+}
+
+// writes a public key of the group of `curve_params` with its length prefix
+fn write_public<W: WriteExt>(curve_params: &EcParameters,
+                             public: &[u8],
+                             writer: &mut W) -> TlsResult<()> {
+    if has_long_shares(curve_params) {
+        try!(try!(KemShare::new(public.to_vec())).tls_write(writer));
+    } else {
+        try!(try!(EcData::new(public.to_vec())).tls_write(writer));
+    }
+    Ok(())
+}
+
+impl TlsItem for ServerEcdhParams {
+    fn tls_write<W: WriteExt>(&self, writer: &mut W) -> TlsResult<()> {
+        try!(self.curve_params.tls_write(writer));
+        write_public(&self.curve_params, &self.public, writer)
+    }
+
+    fn tls_read<R: ReadExt>(reader: &mut R) -> TlsResult<ServerEcdhParams> {
+        let curve_params: EcParameters = try!(TlsItem::tls_read(reader));
+        let public = if has_long_shares(&curve_params) {
+            let public: KemShare = try!(TlsItem::tls_read(reader));
+            public.unwrap()
+        } else {
+            let public: EcData = try!(TlsItem::tls_read(reader));
+            public.unwrap()
+        };
+        Ok(ServerEcdhParams {
+            curve_params: curve_params,
+            public: public,
+        })
+    }
+
+    fn tls_size(&self) -> u64 {
+        let prefix_size = if has_long_shares(&self.curve_params) { 2 } else { 1 };
+        self.curve_params.tls_size() + prefix_size + self.public.len() as u64
+    }
+}
 
 tls_struct!(struct EcdheServerKeyExchange {
     params: ServerEcdhParams,
     signed_params: DigitallySigned
 });
 
+/// A named group usable for ECDHE.
+pub trait EcGroup: Sync {
+    fn name(&self) -> NamedCurve;
+
+    /// Generates a key pair and computes the shared secret with the server public key,
+    /// which may use any of `point_formats`. Returns (client public key, shared secret).
+    fn key_exchange(&self,
+                    server_public: &[u8],
+                    point_formats: &[ECPointFormat],
+                    rng: &mut OsRng) -> TlsResult<(Vec<u8>, Vec<u8>)>;
+
+    /// Whether public keys may be longer than `ECPoint` allows. They are sent with
+    /// a 16-bit length instead.
+    fn long_shares(&self) -> bool {
+        false
+    }
+}
+
+/// X25519MLKEM768 (draft-ietf-tls-ecdhe-mlkem) adapted to TLS 1.2, where the server
+/// sends its share first. The server share is an ML-KEM-768 encapsulation key followed
+/// by an X25519 public key, and the client share is the ML-KEM ciphertext followed by
+/// an X25519 public key. The shared secret is the ML-KEM secret followed by the X25519
+/// secret. TLS 1.2 has no standard for this, so only servers doing the same choose it.
+struct X25519MlKem768Group;
+
+impl EcGroup for X25519MlKem768Group {
+    fn name(&self) -> NamedCurve {
+        NamedCurve::X25519MLKEM768
+    }
+
+    fn key_exchange(&self,
+                    server_public: &[u8],
+                    _point_formats: &[ECPointFormat],
+                    rng: &mut OsRng) -> TlsResult<(Vec<u8>, Vec<u8>)> {
+        if server_public.len() != mlkem::ENCAPSULATION_KEY_LEN + x25519::KEY_LEN {
+            return tls_err!(IllegalParameter,
+                            "bad X25519MLKEM768 share length: {}",
+                            server_public.len());
+        }
+        let (ek, server_x25519) = server_public.split_at(mlkem::ENCAPSULATION_KEY_LEN);
+
+        // SECRET
+        let mut m = [0u8; mlkem::SEED_LEN];
+        rng.fill_bytes(&mut m);
+        let (mut shared_secret, mut public) = match mlkem::encaps(ek, &m) {
+            Some(ret) => ret,
+            None => return tls_err!(IllegalParameter, "server sent strange ML-KEM key"),
+        };
+
+        // SECRET
+        let mut x = [0u8; x25519::KEY_LEN];
+        rng.fill_bytes(&mut x);
+        public.extend(x25519::x25519(&x, &x25519::BASE_POINT));
+        let xy = x25519::x25519(&x, server_x25519);
+        // RFC 7748 6.1: all zero iff the server key has small order
+        if xy.iter().fold(0, |acc, &b| acc | b) == 0 {
+            return tls_err!(IllegalParameter, "server sent small-order X25519 key");
+        }
+        shared_secret.extend(xy);
+
+        Ok((public, shared_secret))
+    }
+
+    fn long_shares(&self) -> bool {
+        true
+    }
+}
+
+struct P256Group;
+
+impl EcGroup for P256Group {
+    fn name(&self) -> NamedCurve {
+        NamedCurve::secp256r1
+    }
+
+    fn key_exchange(&self,
+                    server_public: &[u8],
+                    point_formats: &[ECPointFormat],
+                    rng: &mut OsRng) -> TlsResult<(Vec<u8>, Vec<u8>)> {
+        let compressed = point_formats.contains(&ECPointFormat::ansiX962_compressed_prime);
+        p256_shared_secret(server_public, compressed, rng)
+    }
+}
+
+struct P384Group;
+
+impl EcGroup for P384Group {
+    fn name(&self) -> NamedCurve {
+        NamedCurve::secp384r1
+    }
+
+    fn key_exchange(&self,
+                    server_public: &[u8],
+                    point_formats: &[ECPointFormat],
+                    rng: &mut OsRng) -> TlsResult<(Vec<u8>, Vec<u8>)> {
+        let compressed = point_formats.contains(&ECPointFormat::ansiX962_compressed_prime);
+        p384_shared_secret(server_public, compressed, rng)
+    }
+}
+
+/// Compiled-in groups, in preference order. `TlsClient` offers X25519MLKEM768 only if
+/// `ClientConfig::hybrid_key_exchange` is set.
+pub static GROUPS: &'static [&'static EcGroup] = &[
+    &P256Group, &P384Group, &X25519MlKem768Group,
+];
+
+/// Returns the group named `name`, if compiled in.
+pub fn find_group(name: NamedCurve) -> Option<&'static EcGroup> {
+    GROUPS.iter().find(|group| group.name() == name).map(|group| *group)
+}
+
 pub struct EllipticDiffieHellman;
 
 impl KeyExchange for EllipticDiffieHellman {
-    fn compute_keys(&self, data: &[u8], rng: &mut OsRng) -> TlsResult<(Vec<u8>, Vec<u8>)> {
+    fn supported_groups(&self) -> Vec<NamedCurve> {
+        GROUPS.iter().map(|group| group.name()).collect()
+    }
+
+    fn verify_server_params(&self,
+                            data: &[u8],
+                            client_random: &[u8],
+                            server_random: &[u8],
+                            spki: &[u8],
+                            offered: &[SignatureAndHashAlgorithm]) -> TlsResult<()> {
+        let mut reader = Cursor::new(data);
+        let ecdh_params: EcdheServerKeyExchange = try!(TlsItem::tls_read(&mut reader));
+
+        // RFC 4492 5.4: signed over randoms and `ServerECDHParams`
+        let mut signed = Vec::new();
+        signed.extend(client_random);
+        signed.extend(server_random);
+        try!(ecdh_params.params.tls_write(&mut signed));
+        try!(ecdh_params.signed_params.check_offered(offered));
+        match ecdh_params.signed_params.verify(spki, &signed) {
+            Ok(()) => Ok(()),
+            Err(err) => tls_err!(DecryptError, "bad ServerKeyExchange signature: {}", err.desc),
+        }
+    }
+
+    fn compute_keys(&self,
+                    data: &[u8],
+                    groups: &[NamedCurve],
+                    point_formats: &[ECPointFormat],
+                    rng: &mut OsRng) -> TlsResult<(Vec<u8>, Vec<u8>)> {
         let mut reader = Cursor::new(data);
         let ecdh_params: EcdheServerKeyExchange = try!(TlsItem::tls_read(&mut reader));
+        compute_shared_secret(&ecdh_params.params, groups, point_formats, rng)
+    }
+}
+
+/// Generates a key pair and computes the shared secret with the server key of `params`.
+/// The server must have chosen one of the offered `groups`.
+/// Returns (`ClientECDiffieHellmanPublic`, shared secret).
+pub fn compute_shared_secret(params: &ServerEcdhParams,
+                             groups: &[NamedCurve],
+                             point_formats: &[ECPointFormat],
+                             rng: &mut OsRng) -> TlsResult<(Vec<u8>, Vec<u8>)> {
+    // RFC 4492 5.4
+    let EcParameters::named_curve(curve) = params.curve_params;
+    let group = match find_group(curve) {
+        Some(group) if groups.contains(&curve) => group,
+        _ => return tls_err!(IllegalParameter, "server chose curve not offered: {:?}", curve),
+    };
+    let (public, shared_secret) = try!(group.key_exchange(&params.public, point_formats, rng));
+
+    // we don't support client cert. send public key explicitly.
+    let mut data = Vec::new();
+    try!(write_public(&params.curve_params, &public, &mut data));
+
+    Ok((data, shared_secret))
+}
+
+// returns (client public key, shared secret).
+// `gy` may be compressed only if `compressed` is set.
+fn p256_shared_secret(gy: &[u8],
+                      compressed: bool,
+                      rng: &mut OsRng) -> TlsResult<(Vec<u8>, Vec<u8>)> {
+    let gy = match gy.first() {
+        Some(&0x04) => p256::NPoint256::from_uncompressed_bytes(gy),
+        Some(&0x02) | Some(&0x03) if compressed => p256::NPoint256::from_compressed_bytes(gy),
+        _ => None,
+    };
+    let gy = match gy {
+        None => {
+            return tls_err!(IllegalParameter, "server sent strange public key");
+        }
+        Some(gy) => gy,
+    };
+    let gy = gy.to_point();
 
-        let gy = &ecdh_params.params.public;
-        let gy = p256::NPoint256::from_uncompressed_bytes(gy);
-        let gy = match gy {
-            None => {
-                return tls_err!(IllegalParameter, "server sent strange public key");
+    fn get_random_x(rng: &mut OsRng) -> p256::int256::Int256 {
+        loop {
+            let mut x = p256::int256::ZERO;
+            for i in 0..8 {
+                x.v[i] = W(rng.next_u32());
             }
-            Some(gy) => gy,
-        };
-        let gy = gy.to_point();
+            let xx = x.reduce_once(W(0));
+            let x_is_okay = xx.compare(&x);
+            if x_is_okay == W(0) {
+                return x;
+            }
+        }
+    }
 
-        fn get_random_x(rng: &mut OsRng) -> p256::int256::Int256 {
-            loop {
-                let mut x = p256::int256::ZERO;
-                for i in 0..8 {
-                    x.v[i] = W(rng.next_u32());
-                }
-                let xx = x.reduce_once(W(0));
-                let x_is_okay = xx.compare(&x);
-                if x_is_okay == W(0) {
-                    return x;
-                }
+    let x = get_random_x(rng);
+    let gx = p256::G.mult_scalar(&x).normalize().to_uncompressed_bytes();
+    let gxy = gy.mult_scalar(&x).normalize();
+    Ok((gx, gxy.x.to_bytes()))
+}
+
+// returns (client public key, shared secret).
+// `gy` may be compressed only if `compressed` is set.
+fn p384_shared_secret(gy: &[u8],
+                      compressed: bool,
+                      rng: &mut OsRng) -> TlsResult<(Vec<u8>, Vec<u8>)> {
+    let curve = P384::new();
+    let gy = match gy.first() {
+        Some(&0x04) => curve.from_uncompressed_bytes(gy),
+        Some(&0x02) | Some(&0x03) if compressed => curve.from_compressed_bytes(gy),
+        _ => None,
+    };
+    let gy = match gy {
+        Some(gy) => curve.to_point(&gy),
+        None => return tls_err!(IllegalParameter, "server sent strange public key"),
+    };
+
+    // SECRET. uniform in [1, n - 1]
+    let n = Modulus::new(p384::N).unwrap();
+    let mut x = [0u8; p384::BYTE_LEN];
+    loop {
+        rng.fill_bytes(&mut x);
+        if let Some(v) = n.from_bytes(&x) {
+            if n.is_nonzero(&v) == 1 {
+                break;
             }
         }
+    }
+
+    let gx = curve.normalize(&curve.mult_scalar(&curve.generator(), &x));
+    let gxy = curve.normalize(&curve.mult_scalar(&gy, &x));
+    match (gx, gxy) {
+        (Some(gx), Some(gxy)) => Ok((gx.to_uncompressed_bytes(), gxy.x)),
+        _ => tls_err!(IllegalParameter, "server sent strange public key"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+    use rand::OsRng;
+
+    use cipher::KeyExchange;
+    use crypto::p256;
+    use crypto::p384::P384;
+    use crypto::{mlkem, x25519};
+    use crypto::wrapping::Wrapping as W;
+    use handshake::{NamedCurve, ECPointFormat};
+    use signature::{SignatureAndHashAlgorithm, HashAlgorithm, SignatureAlgorithm,
+                    SUPPORTED_ALGORITHMS};
+    use tls_item::TlsItem;
+    use tls_result::TlsErrorKind::IllegalParameter;
+    use x509::Certificate;
+    use super::{EllipticDiffieHellman, EcData, KemShare, EcParameters, ServerEcdhParams,
+                compute_shared_secret, find_group};
+
+    static SERVER: &'static [u8] = include_bytes!("../../testdata/test_server.der");
+    static CLIENT: &'static [u8] = include_bytes!("../../testdata/test_client.der");
+    // signed by `test_server.key` with client random 0x01.. and server random 0x02..
+    static SERVER_KEY_EXCHANGE: &'static [u8] = include_bytes!("../../testdata/ske_ecdhe_rsa.bin");
+    // same params, signed with rsa_pss_rsae_sha256
+    static SERVER_KEY_EXCHANGE_PSS: &'static [u8] =
+        include_bytes!("../../testdata/ske_ecdhe_rsa_pss.bin");
+
+    #[test]
+    fn test_verify_server_params() {
+        let spki = Certificate::parse(SERVER).unwrap().subject_public_key_info;
+        let client_random = [1u8; 32];
+        let server_random = [2u8; 32];
+        let kex = EllipticDiffieHellman;
+        let offered = SUPPORTED_ALGORITHMS;
+        kex.verify_server_params(SERVER_KEY_EXCHANGE, &client_random, &server_random, spki,
+                                 offered).unwrap();
+
+        // randoms are signed too
+        assert!(kex.verify_server_params(SERVER_KEY_EXCHANGE, &server_random, &client_random,
+                                         spki, offered).is_err());
+        let mut params = SERVER_KEY_EXCHANGE.to_vec();
+        params[10] ^= 1;
+        assert!(kex.verify_server_params(&params, &client_random, &server_random, spki,
+                                         offered).is_err());
+
+        kex.verify_server_params(SERVER_KEY_EXCHANGE_PSS, &client_random, &server_random, spki,
+                                 offered).unwrap();
+        let mut params = SERVER_KEY_EXCHANGE_PSS.to_vec();
+        let len = params.len();
+        params[len - 1] ^= 1;
+        assert!(kex.verify_server_params(&params, &client_random, &server_random, spki,
+                                         offered).is_err());
+        // PKCS#1 v1.5 signature labeled as PSS
+        let mut params = SERVER_KEY_EXCHANGE.to_vec();
+        params[69] = 8;
+        params[70] = 4;
+        assert!(kex.verify_server_params(&params, &client_random, &server_random, spki,
+                                         offered).is_err());
+
+        // the signature algorithm must have been offered
+        let pss_only = [SignatureAndHashAlgorithm {
+            hash: HashAlgorithm::intrinsic,
+            signature: SignatureAlgorithm::rsa_pss_rsae_sha256,
+        }];
+        let err = kex.verify_server_params(SERVER_KEY_EXCHANGE, &client_random, &server_random,
+                                           spki, &pss_only).unwrap_err();
+        assert_eq!(err.kind, IllegalParameter);
+        kex.verify_server_params(SERVER_KEY_EXCHANGE_PSS, &client_random, &server_random, spki,
+                                 &pss_only).unwrap();
+
+        // the key type must match the signature algorithm
+        let spki = Certificate::parse(CLIENT).unwrap().subject_public_key_info;
+        assert!(kex.verify_server_params(SERVER_KEY_EXCHANGE, &client_random, &server_random,
+                                         spki, offered).is_err());
+    }
+
+    #[test]
+    fn test_groups() {
+        let groups = EllipticDiffieHellman.supported_groups();
+        assert_eq!(groups,
+                   vec![NamedCurve::secp256r1, NamedCurve::secp384r1, NamedCurve::X25519MLKEM768]);
+        for &group in groups.iter() {
+            assert_eq!(find_group(group).unwrap().name(), group);
+        }
+        assert!(find_group(NamedCurve::secp521r1).is_none());
+
+        // offered for DHE only
+        let mut rng = OsRng::new().unwrap();
+        let formats = [ECPointFormat::uncompressed];
+        let mut offered = groups.clone();
+        offered.push(NamedCurve::ffdhe2048);
+        let params = ServerEcdhParams {
+            curve_params: EcParameters::named_curve(NamedCurve::ffdhe2048),
+            public: vec![4; 65],
+        };
+        let err = compute_shared_secret(&params, &offered, &formats, &mut rng).unwrap_err();
+        assert_eq!(err.kind, IllegalParameter);
+    }
+
+    #[test]
+    fn test_p384_shared_secret() {
+        let mut rng = OsRng::new().unwrap();
+        let groups = EllipticDiffieHellman.supported_groups();
+        let curve = P384::new();
+        let formats = [ECPointFormat::uncompressed];
+
+        // server key pair with private key 2
+        let public = curve.normalize(&curve.mult_scalar(&curve.generator(), &[2])).unwrap();
+        let params = ServerEcdhParams {
+            curve_params: EcParameters::named_curve(NamedCurve::secp384r1),
+            public: public.to_uncompressed_bytes(),
+        };
+
+        let (data, secret) = compute_shared_secret(&params, &groups, &formats, &mut rng).unwrap();
+        let client_public: EcData = TlsItem::tls_read(&mut Cursor::new(&data[..])).unwrap();
+        assert_eq!(client_public.len(), 97);
+        let client_public = curve.from_uncompressed_bytes(&client_public).unwrap();
+        let shared = curve.mult_scalar(&curve.to_point(&client_public), &[2]);
+        assert_eq!(secret, curve.normalize(&shared).unwrap().x);
+
+        // not on the curve
+        let mut bad = public.to_uncompressed_bytes();
+        bad[96] ^= 1;
+        let params = ServerEcdhParams {
+            curve_params: EcParameters::named_curve(NamedCurve::secp384r1),
+            public: bad,
+        };
+        let err = compute_shared_secret(&params, &groups, &formats, &mut rng).unwrap_err();
+        assert_eq!(err.kind, IllegalParameter);
+
+        let params = ServerEcdhParams {
+            curve_params: EcParameters::named_curve(NamedCurve::secp521r1),
+            public: public.to_uncompressed_bytes(),
+        };
+        let err = compute_shared_secret(&params, &groups, &formats, &mut rng).unwrap_err();
+        assert_eq!(err.kind, IllegalParameter);
+    }
 
-        let x = get_random_x(rng);
-        let gx = p256::G.mult_scalar(&x).normalize().to_uncompressed_bytes();
-        let gxy = gy.mult_scalar(&x).normalize();
-        let pre_master_secret = gxy.x.to_bytes();
+    #[test]
+    fn test_compressed_point() {
+        let mut rng = OsRng::new().unwrap();
+        let groups = EllipticDiffieHellman.supported_groups();
+        let uncompressed = [ECPointFormat::uncompressed];
+        let compressed = [ECPointFormat::uncompressed, ECPointFormat::ansiX962_compressed_prime];
 
-        // we don't support client cert. send public key explicitly.
-        let public = try!(EcData::new(gx));
+        // server key pairs with private key 2 (odd y) and 3 (even y)
+        for k in 2..4 {
+            let mut private = p256::int256::ZERO;
+            private.v[0] = W(k);
+            let public = p256::G.mult_scalar(&private).normalize();
+            let params = ServerEcdhParams {
+                curve_params: EcParameters::named_curve(NamedCurve::secp256r1),
+                public: public.to_compressed_bytes(),
+            };
 
+            // not negotiated
+            let err = compute_shared_secret(&params, &groups, &uncompressed, &mut rng)
+                          .unwrap_err();
+            assert_eq!(err.kind, IllegalParameter);
+
+            // the client key is uncompressed anyway
+            let (data, secret) = compute_shared_secret(&params, &groups, &compressed, &mut rng)
+                                     .unwrap();
+            let client_public: EcData = TlsItem::tls_read(&mut Cursor::new(&data[..])).unwrap();
+            let client_public = p256::NPoint256::from_uncompressed_bytes(&client_public).unwrap();
+            let shared = client_public.to_point().mult_scalar(&private).normalize();
+            assert_eq!(secret, shared.x.to_bytes());
+        }
+    }
+
+    #[test]
+    fn test_x25519_mlkem768() {
+        let mut rng = OsRng::new().unwrap();
+        let groups = EllipticDiffieHellman.supported_groups();
+        let formats = [ECPointFormat::uncompressed];
+
+        // server key pairs
+        let (ek, dk) = mlkem::key_gen(&[1; 32], &[2; 32]);
+        let server_x25519 = [3u8; 32];
+        let mut server_public = ek.clone();
+        server_public.extend(x25519::x25519(&server_x25519, &x25519::BASE_POINT));
+        let params = ServerEcdhParams {
+            curve_params: EcParameters::named_curve(NamedCurve::X25519MLKEM768),
+            public: server_public.clone(),
+        };
+
+        // the share does not fit in `ECPoint`
         let mut data = Vec::new();
-        try!(public.tls_write(&mut data));
-        let public = data;
+        params.tls_write(&mut data).unwrap();
+        assert_eq!(data.len() as u64, params.tls_size());
+        assert_eq!(&data[..5], b"\x03\x11\xec\x04\xc0");
+        let read: ServerEcdhParams = TlsItem::tls_read(&mut Cursor::new(&data[..])).unwrap();
+        assert_eq!(read.public, server_public);
 
-        Ok((public, pre_master_secret))
+        let (data, secret) = compute_shared_secret(&params, &groups, &formats, &mut rng).unwrap();
+        let client_public: KemShare = TlsItem::tls_read(&mut Cursor::new(&data[..])).unwrap();
+        assert_eq!(client_public.len(), mlkem::CIPHERTEXT_LEN + x25519::KEY_LEN);
+        let (c, client_x25519) = client_public.split_at(mlkem::CIPHERTEXT_LEN);
+        let mut expected = mlkem::decaps(&dk, c).unwrap();
+        expected.extend(x25519::x25519(&server_x25519, client_x25519));
+        assert_eq!(secret, expected);
+
+        // not offered
+        let default = [NamedCurve::secp256r1, NamedCurve::secp384r1];
+        let err = compute_shared_secret(&params, &default, &formats, &mut rng).unwrap_err();
+        assert_eq!(err.kind, IllegalParameter);
+
+        // truncated
+        let params = ServerEcdhParams {
+            curve_params: EcParameters::named_curve(NamedCurve::X25519MLKEM768),
+            public: server_public[1..].to_vec(),
+        };
+        let err = compute_shared_secret(&params, &groups, &formats, &mut rng).unwrap_err();
+        assert_eq!(err.kind, IllegalParameter);
+
+        // X25519 key of small order
+        let mut bad = ek.clone();
+        bad.extend(&[0; 32]);
+        let params = ServerEcdhParams {
+            curve_params: EcParameters::named_curve(NamedCurve::X25519MLKEM768),
+            public: bad,
+        };
+        let err = compute_shared_secret(&params, &groups, &formats, &mut rng).unwrap_err();
+        assert_eq!(err.kind, IllegalParameter);
+
+        // ML-KEM key with a coefficient of 0xfff
+        let mut bad = server_public.clone();
+        bad[0] = 0xff;
+        bad[1] |= 0x0f;
+        let params = ServerEcdhParams {
+            curve_params: EcParameters::named_curve(NamedCurve::X25519MLKEM768),
+            public: bad,
+        };
+        let err = compute_shared_secret(&params, &groups, &formats, &mut rng).unwrap_err();
+        assert_eq!(err.kind, IllegalParameter);
     }
 }
@@ -2,21 +2,44 @@ use rand::OsRng;
 
 use util::{ReadExt, WriteExt};
 use tls_result::TlsResult;
-use tls_result::TlsErrorKind::UnexpectedMessage;
+use tls_result::TlsErrorKind::{UnexpectedMessage, InternalError};
 use tls_item::TlsItem;
-use self::chacha20_poly1305::ChaCha20Poly1305;
+use handshake::{NamedCurve, ECPointFormat};
+use signature::SignatureAndHashAlgorithm;
+use x509::Certificate;
+use self::chacha20_poly1305::{ChaCha20Poly1305, ChaCha20Poly1305Rfc7905};
+use self::aes_cbc_hmac::AesCbcHmac;
+use self::aes_gcm::AesGcmAead;
 use self::ecdhe::EllipticDiffieHellman;
+use self::dhe::DiffieHellman;
+use self::psk::{Psk, EcdhePsk, PresharedKey};
+use self::rsa::RsaKeyExchange;
 
 pub mod prf;
 pub mod ecdhe;
+pub mod dhe;
+pub mod psk;
+pub mod rsa;
 pub mod chacha20_poly1305;
+pub mod aes_cbc_hmac;
+pub mod aes_gcm;
 
+// record protection of a cipher suite.
+// block ciphers with HMAC (`aes_cbc_hmac`) also implement this.
 pub trait Aead {
     fn key_size(&self) -> usize;
     fn fixed_iv_len(&self) -> usize;
     fn mac_len(&self) -> usize;
-    fn new_encryptor(&self, key: Vec<u8>) -> Box<Encryptor + Send + 'static>;
-    fn new_decryptor(&self, key: Vec<u8>) -> Box<Decryptor + Send + 'static>;
+
+    // MAC key length in key block. AEAD ciphers have no MAC key.
+    fn mac_key_size(&self) -> usize { 0 }
+
+    // encrypt-then-MAC variant (RFC 7366). `None` for AEAD ciphers.
+    fn encrypt_then_mac(&self) -> Option<Box<Aead>> { None }
+
+    // `key` is `mac_key || enc_key || fixed_iv` from key block.
+    fn new_encryptor(&self, key: Vec<u8>) -> TlsResult<Box<Encryptor + Send + 'static>>;
+    fn new_decryptor(&self, key: Vec<u8>) -> TlsResult<Box<Decryptor + Send + 'static>>;
 }
 
 pub trait Encryptor {
@@ -32,20 +55,82 @@ pub trait Decryptor {
 }
 
 pub trait KeyExchange {
+    /// Groups offered in the `elliptic_curves` extension for this key exchange,
+    /// in preference order.
+    fn supported_groups(&self) -> Vec<NamedCurve>;
+
+    /// Verifies the signature of ServerKeyExchange `data` made with the key of the server
+    /// certificate `spki` (raw DER `SubjectPublicKeyInfo`) and one of the `offered`
+    /// signature algorithms.
+    fn verify_server_params(&self,
+                            data: &[u8],
+                            client_random: &[u8],
+                            server_random: &[u8],
+                            spki: &[u8],
+                            offered: &[SignatureAndHashAlgorithm]) -> TlsResult<()>;
+
     // return (client_key_exchange_data, pre_master_secret)
-    fn compute_keys(&self, data: &[u8], rng: &mut OsRng) -> TlsResult<(Vec<u8>, Vec<u8>)>;
+    // `groups` are the groups offered in the `elliptic_curves` extension, and
+    // `point_formats` the `ECPointFormat`s the server public key may use.
+    fn compute_keys(&self,
+                    data: &[u8],
+                    groups: &[NamedCurve],
+                    point_formats: &[ECPointFormat],
+                    rng: &mut OsRng) -> TlsResult<(Vec<u8>, Vec<u8>)>;
+
+    /// `true` if the server is authenticated by a pre-shared key (RFC 4279) instead of
+    /// a certificate. Then `compute_psk_keys` is used instead of `compute_keys`.
+    fn is_psk(&self) -> bool {
+        false
+    }
+
+    /// Like `compute_keys`, with `psk`. `data` is empty if the server sent no
+    /// ServerKeyExchange.
+    fn compute_psk_keys(&self,
+                        _data: &[u8],
+                        _psk: &PresharedKey,
+                        _groups: &[NamedCurve],
+                        _point_formats: &[ECPointFormat],
+                        _rng: &mut OsRng) -> TlsResult<(Vec<u8>, Vec<u8>)> {
+        tls_err!(InternalError, "not a PSK key exchange")
+    }
+
+    /// `true` if the server sends no ServerKeyExchange and the key of the server
+    /// certificate is used directly (static RSA). Then `compute_static_keys` is used
+    /// instead of `compute_keys`.
+    fn is_static(&self) -> bool {
+        false
+    }
+
+    /// Like `compute_keys`, with the server certificate `leaf` and the version
+    /// offered in ClientHello.
+    fn compute_static_keys(&self,
+                           _leaf: &Certificate,
+                           _client_version: (u8, u8),
+                           _rng: &mut OsRng) -> TlsResult<(Vec<u8>, Vec<u8>)> {
+        tls_err!(InternalError, "not a static key exchange")
+    }
 }
 
+// signaling cipher suite values (SCSV) follow `@signaling`.
+// they are sent in ClientHello but never negotiated.
 macro_rules! cipher_suite {
     ($(
-        $id:ident = $kex:ident, $cipher:ident, $mac:ident, $v1:expr, $v2:expr;
-    )+) => (
+        $id:ident = $kex:ident, $cipher:expr, $mac:ident, $v1:expr, $v2:expr;
+    )+
+    @signaling
+    $(
+        $sid:ident = $sv1:expr, $sv2:expr;
+    )*) => (
         #[allow(non_camel_case_types)]
         #[derive(Copy, Clone, PartialEq, Debug)]
         pub enum CipherSuite {
             $(
                 $id,
             )+
+            $(
+                $sid,
+            )*
             UnknownCipherSuite,
         }
 
@@ -55,7 +140,7 @@ macro_rules! cipher_suite {
                     $(
                         CipherSuite::$id => Box::new($cipher) as Box<Aead>,
                     )+
-                    CipherSuite::UnknownCipherSuite => unreachable!(),
+                    _ => unreachable!(),
                 }
             }
 
@@ -64,12 +149,22 @@ macro_rules! cipher_suite {
                     $(
                         CipherSuite::$id => Box::new($kex) as Box<KeyExchange>,
                     )+
-                    CipherSuite::UnknownCipherSuite => unreachable!(),
+                    _ => unreachable!(),
                 }
             }
 
             // this can be different for some cipher suites
             pub fn verify_data_len(&self) -> usize { 12 }
+
+            /// Returns `true` if `self` is a signaling cipher suite value.
+            pub fn is_signaling(&self) -> bool {
+                match *self {
+                    $(
+                        CipherSuite::$sid => true,
+                    )*
+                    _ => false,
+                }
+            }
         }
 
         impl TlsItem for CipherSuite {
@@ -81,6 +176,13 @@ macro_rules! cipher_suite {
                         return Ok(());
                     }
                 )+
+                $(
+                    if *self == CipherSuite::$sid {
+                        try!(writer.write_u8($sv1));
+                        try!(writer.write_u8($sv2));
+                        return Ok(());
+                    }
+                )*
 
                 return tls_err!(UnexpectedMessage, "unexpected CipherSuite: {:?}", self);
             }
@@ -93,6 +195,11 @@ macro_rules! cipher_suite {
                         return Ok(CipherSuite::$id);
                     }
                 )+
+                $(
+                    if id1 == $sv1 && id2 == $sv2 {
+                        return Ok(CipherSuite::$sid);
+                    }
+                )*
                 // client may send cipher suites we don't know
                 return Ok(CipherSuite::UnknownCipherSuite);
             }
@@ -111,4 +218,54 @@ cipher_suite!(
     EllipticDiffieHellman, ChaCha20Poly1305, MAC_SHA256, 0xcc, 0x13;
     // TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256 =
     // EllipticDiffieHellman ChaCha20Poly1305 MAC_SHA256 0xcc 0x14;
+
+    // RFC 5289
+    TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA256 =
+    EllipticDiffieHellman, AesCbcHmac::aes_128_sha256(), MAC_SHA256, 0xc0, 0x27;
+    // RFC 4492
+    TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA =
+    EllipticDiffieHellman, AesCbcHmac::aes_128_sha1(), MAC_SHA1, 0xc0, 0x13;
+
+    // RFC 5289, RFC 4492
+    TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256 =
+    EllipticDiffieHellman, AesGcmAead::aes_128(), MAC_SHA256, 0xc0, 0x2b;
+    TLS_ECDHE_ECDSA_WITH_AES_128_CBC_SHA256 =
+    EllipticDiffieHellman, AesCbcHmac::aes_128_sha256(), MAC_SHA256, 0xc0, 0x23;
+    TLS_ECDHE_ECDSA_WITH_AES_128_CBC_SHA =
+    EllipticDiffieHellman, AesCbcHmac::aes_128_sha1(), MAC_SHA1, 0xc0, 0x09;
+
+    // RFC 5246
+    TLS_DHE_RSA_WITH_AES_128_CBC_SHA256 =
+    DiffieHellman, AesCbcHmac::aes_128_sha256(), MAC_SHA256, 0x00, 0x67;
+    TLS_DHE_RSA_WITH_AES_128_CBC_SHA =
+    DiffieHellman, AesCbcHmac::aes_128_sha1(), MAC_SHA1, 0x00, 0x33;
+
+    // RFC 5489, RFC 7905
+    TLS_ECDHE_PSK_WITH_CHACHA20_POLY1305_SHA256 =
+    EcdhePsk, ChaCha20Poly1305Rfc7905, MAC_SHA256, 0xcc, 0xac;
+    TLS_ECDHE_PSK_WITH_AES_128_CBC_SHA256 =
+    EcdhePsk, AesCbcHmac::aes_128_sha256(), MAC_SHA256, 0xc0, 0x37;
+    TLS_ECDHE_PSK_WITH_AES_128_CBC_SHA =
+    EcdhePsk, AesCbcHmac::aes_128_sha1(), MAC_SHA1, 0xc0, 0x35;
+    // RFC 4279, RFC 5487, RFC 7905
+    TLS_PSK_WITH_CHACHA20_POLY1305_SHA256 =
+    Psk, ChaCha20Poly1305Rfc7905, MAC_SHA256, 0xcc, 0xab;
+    TLS_PSK_WITH_AES_128_CBC_SHA256 =
+    Psk, AesCbcHmac::aes_128_sha256(), MAC_SHA256, 0x00, 0xae;
+    TLS_PSK_WITH_AES_128_CBC_SHA =
+    Psk, AesCbcHmac::aes_128_sha1(), MAC_SHA1, 0x00, 0x8c;
+
+    // static RSA, only if `ClientConfig::rsa_key_exchange` is set. RFC 5288, RFC 5246
+    TLS_RSA_WITH_AES_128_GCM_SHA256 =
+    RsaKeyExchange, AesGcmAead::aes_128(), MAC_SHA256, 0x00, 0x9c;
+    TLS_RSA_WITH_AES_128_CBC_SHA256 =
+    RsaKeyExchange, AesCbcHmac::aes_128_sha256(), MAC_SHA256, 0x00, 0x3c;
+    TLS_RSA_WITH_AES_128_CBC_SHA =
+    RsaKeyExchange, AesCbcHmac::aes_128_sha1(), MAC_SHA1, 0x00, 0x2f;
+
+    @signaling
+    // RFC 5746
+    TLS_EMPTY_RENEGOTIATION_INFO_SCSV = 0x00, 0xff;
+    // RFC 7507
+    TLS_FALLBACK_SCSV = 0x56, 0x00;
 );
@@ -0,0 +1,254 @@
+//! Key exchanges authenticated by a pre-shared key: `PSK` (RFC 4279) and `ECDHE_PSK`
+//! (RFC 5489). The server sends no certificate, and ServerKeyExchange, if any, is not signed.
+
+use std::io::Cursor;
+use rand::OsRng;
+
+use util::{ReadExt, WriteExt};
+use tls_result::TlsResult;
+use tls_result::TlsErrorKind::{InternalError, DecodeError, UnexpectedMessage};
+use tls_item::TlsItem;
+use handshake::{NamedCurve, ECPointFormat};
+use signature::SignatureAndHashAlgorithm;
+use super::KeyExchange;
+use super::ecdhe::{self, ServerEcdhParams, EllipticDiffieHellman};
+
+/// Maximum length of identities and keys.
+pub const MAX_LEN: usize = (1 << 16) - 1;
+
+tls_vec!(PskIdentity = u8(0, (1 << 16) - 1));
+
+tls_struct!(struct EcdhePskServerKeyExchange {
+    psk_identity_hint: PskIdentity,
+    params: ServerEcdhParams
+});
+
+/// A pre-shared key, and the identity sent in ClientKeyExchange to select it.
+pub struct PresharedKey {
+    pub identity: Vec<u8>,
+    // SECRET
+    pub key: Vec<u8>,
+}
+
+impl PresharedKey {
+    pub fn new(identity: Vec<u8>, key: Vec<u8>) -> PresharedKey {
+        PresharedKey {
+            identity: identity,
+            key: key,
+        }
+    }
+
+    /// Checks the lengths of the identity and the key.
+    pub fn check(&self) -> TlsResult<()> {
+        if self.identity.len() > MAX_LEN {
+            return tls_err!(InternalError, "PSK identity too long: {}", self.identity.len());
+        }
+        if self.key.len() == 0 || self.key.len() > MAX_LEN {
+            return tls_err!(InternalError, "bad PSK length: {}", self.key.len());
+        }
+        Ok(())
+    }
+}
+
+/// Returns `psk_identity_hint` of ServerKeyExchange `data`, which is the first field
+/// for all PSK key exchanges. Empty `data` means the server sent no ServerKeyExchange.
+pub fn identity_hint(data: &[u8]) -> TlsResult<Option<Vec<u8>>> {
+    if data.len() == 0 {
+        return Ok(None);
+    }
+    let hint: PskIdentity = try!(TlsItem::tls_read(&mut Cursor::new(data)));
+    Ok(Some(hint.unwrap()))
+}
+
+// RFC 4279 2: `other_secret` and the key, each prefixed by its 16-bit length.
+fn pre_master_secret(other_secret: &[u8], psk: &[u8]) -> Vec<u8> {
+    let mut secret = Vec::with_capacity(4 + other_secret.len() + psk.len());
+    secret.push((other_secret.len() >> 8) as u8);
+    secret.push(other_secret.len() as u8);
+    secret.extend(other_secret);
+    secret.push((psk.len() >> 8) as u8);
+    secret.push(psk.len() as u8);
+    secret.extend(psk);
+    secret
+}
+
+fn write_identity(psk: &PresharedKey) -> TlsResult<Vec<u8>> {
+    let identity = try!(PskIdentity::new(psk.identity.clone()));
+    let mut data = Vec::new();
+    try!(identity.tls_write(&mut data));
+    Ok(data)
+}
+
+/// `PSK` key exchange (RFC 4279 2). ServerKeyExchange only carries an optional identity hint.
+pub struct Psk;
+
+impl KeyExchange for Psk {
+    fn supported_groups(&self) -> Vec<NamedCurve> {
+        Vec::new()
+    }
+
+    fn is_psk(&self) -> bool {
+        true
+    }
+
+    fn verify_server_params(&self,
+                            _: &[u8],
+                            _: &[u8],
+                            _: &[u8],
+                            _: &[u8],
+                            _: &[SignatureAndHashAlgorithm]) -> TlsResult<()> {
+        tls_err!(InternalError, "PSK key exchange has no signature")
+    }
+
+    fn compute_keys(&self,
+                    _data: &[u8],
+                    _groups: &[NamedCurve],
+                    _point_formats: &[ECPointFormat],
+                    _rng: &mut OsRng) -> TlsResult<(Vec<u8>, Vec<u8>)> {
+        tls_err!(InternalError, "PSK key exchange needs a pre-shared key")
+    }
+
+    fn compute_psk_keys(&self,
+                        data: &[u8],
+                        psk: &PresharedKey,
+                        _groups: &[NamedCurve],
+                        _point_formats: &[ECPointFormat],
+                        _rng: &mut OsRng) -> TlsResult<(Vec<u8>, Vec<u8>)> {
+        if data.len() > 0 {
+            let mut reader = Cursor::new(data);
+            let _hint: PskIdentity = try!(TlsItem::tls_read(&mut reader));
+            if reader.position() as usize != data.len() {
+                return tls_err!(DecodeError, "trailing data in ServerKeyExchange");
+            }
+        }
+        // other_secret is as many zero bytes as the key
+        let zeros = vec![0u8; psk.key.len()];
+        Ok((try!(write_identity(psk)), pre_master_secret(&zeros, &psk.key)))
+    }
+}
+
+/// `ECDHE_PSK` key exchange (RFC 5489 2), with the identity hint and unsigned ECDH params
+/// in ServerKeyExchange.
+pub struct EcdhePsk;
+
+impl KeyExchange for EcdhePsk {
+    fn supported_groups(&self) -> Vec<NamedCurve> {
+        EllipticDiffieHellman.supported_groups()
+    }
+
+    fn is_psk(&self) -> bool {
+        true
+    }
+
+    fn verify_server_params(&self,
+                            _: &[u8],
+                            _: &[u8],
+                            _: &[u8],
+                            _: &[u8],
+                            _: &[SignatureAndHashAlgorithm]) -> TlsResult<()> {
+        tls_err!(InternalError, "PSK key exchange has no signature")
+    }
+
+    fn compute_keys(&self,
+                    _data: &[u8],
+                    _groups: &[NamedCurve],
+                    _point_formats: &[ECPointFormat],
+                    _rng: &mut OsRng) -> TlsResult<(Vec<u8>, Vec<u8>)> {
+        tls_err!(InternalError, "PSK key exchange needs a pre-shared key")
+    }
+
+    fn compute_psk_keys(&self,
+                        data: &[u8],
+                        psk: &PresharedKey,
+                        groups: &[NamedCurve],
+                        point_formats: &[ECPointFormat],
+                        rng: &mut OsRng) -> TlsResult<(Vec<u8>, Vec<u8>)> {
+        if data.len() == 0 {
+            return tls_err!(UnexpectedMessage, "ServerKeyExchange is missing");
+        }
+        let mut reader = Cursor::new(data);
+        let server_key_exchange: EcdhePskServerKeyExchange =
+            try!(TlsItem::tls_read(&mut reader));
+        let (public, shared_secret) = try!(ecdhe::compute_shared_secret(
+            &server_key_exchange.params, groups, point_formats, rng));
+
+        let mut client_key_exchange = try!(write_identity(psk));
+        client_key_exchange.extend(&public);
+        Ok((client_key_exchange, pre_master_secret(&shared_secret, &psk.key)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+    use rand::OsRng;
+
+    use cipher::KeyExchange;
+    use cipher::ecdhe::EcData;
+    use crypto::p256;
+    use handshake::{NamedCurve, ECPointFormat};
+    use tls_item::TlsItem;
+    use tls_result::TlsErrorKind::{DecodeError, UnexpectedMessage};
+    use super::{Psk, EcdhePsk, PresharedKey, PskIdentity, identity_hint};
+
+    fn psk() -> PresharedKey {
+        PresharedKey::new(b"device-1".to_vec(), b"\x0b\x0c\x0d".to_vec())
+    }
+
+    #[test]
+    fn test_psk() {
+        let mut rng = OsRng::new().unwrap();
+        let psk = psk();
+        assert!(psk.check().is_ok());
+        assert!(PresharedKey::new(Vec::new(), Vec::new()).check().is_err());
+        assert!(PresharedKey::new(vec![0; 1 << 16], vec![1]).check().is_err());
+
+        let expected_key_exchange = b"\x00\x08device-1";
+        let expected_secret = b"\x00\x03\x00\x00\x00\x00\x03\x0b\x0c\x0d";
+        for data in [&b""[..], b"\x00\x00", b"\x00\x04hint"].iter() {
+            let (key_exchange, secret) = Psk.compute_psk_keys(data, &psk, &[], &[], &mut rng)
+                                            .unwrap();
+            assert_eq!(&key_exchange[..], &expected_key_exchange[..]);
+            assert_eq!(&secret[..], &expected_secret[..]);
+        }
+        assert!(Psk.compute_psk_keys(b"\x00\x04hi", &psk, &[], &[], &mut rng).is_err());
+        let err = Psk.compute_psk_keys(b"\x00\x02hint", &psk, &[], &[], &mut rng).unwrap_err();
+        assert_eq!(err.kind, DecodeError);
+
+        assert_eq!(identity_hint(b"").unwrap(), None);
+        assert_eq!(identity_hint(b"\x00\x04hint").unwrap(), Some(b"hint".to_vec()));
+    }
+
+    #[test]
+    fn test_ecdhe_psk() {
+        let mut rng = OsRng::new().unwrap();
+        let psk = psk();
+
+        // server key pair with private key 2
+        let mut private = p256::int256::ZERO;
+        private.v[0] = ::crypto::wrapping::Wrapping(2);
+        let public = p256::G.mult_scalar(&private).normalize().to_uncompressed_bytes();
+        let mut data = b"\x00\x04hint\x03\x00\x17".to_vec();
+        EcData::new(public).unwrap().tls_write(&mut data).unwrap();
+
+        let groups = [NamedCurve::secp256r1];
+        let formats = [ECPointFormat::uncompressed];
+        let (key_exchange, secret) = EcdhePsk.compute_psk_keys(&data, &psk, &groups, &formats,
+                                                               &mut rng).unwrap();
+        let mut reader = Cursor::new(&key_exchange[..]);
+        let identity: PskIdentity = TlsItem::tls_read(&mut reader).unwrap();
+        assert_eq!(&identity[..], b"device-1");
+        let client_public: EcData = TlsItem::tls_read(&mut reader).unwrap();
+        assert_eq!(reader.position() as usize, key_exchange.len());
+
+        let client_public = p256::NPoint256::from_uncompressed_bytes(&client_public).unwrap();
+        let shared = client_public.to_point().mult_scalar(&private).normalize().x.to_bytes();
+        let mut expected = vec![0, 32];
+        expected.extend(&shared);
+        expected.extend(b"\x00\x03\x0b\x0c\x0d");
+        assert_eq!(secret, expected);
+
+        let err = EcdhePsk.compute_psk_keys(b"", &psk, &groups, &formats, &mut rng).unwrap_err();
+        assert_eq!(err.kind, UnexpectedMessage);
+    }
+}
@@ -0,0 +1,193 @@
+//! Static RSA key exchange (RFC 5246 7.4.7.1).
+//!
+//! The client encrypts the premaster secret to the key of the server certificate, and the
+//! server sends no ServerKeyExchange. There is no forward secrecy, so the cipher suites are
+//! only allowed if `ClientConfig::rsa_key_exchange` is set.
+//!
+//! `decrypt_pre_master_secret` is the server side. It must not reveal whether decryption
+//! failed, or it becomes a padding oracle (Bleichenbacher 1998, ROBOT 2017).
+
+use std::io::Cursor;
+use rand::{Rng, OsRng};
+
+use crypto::rsa::RsaPrivateKey;
+use key::PrivateKey;
+use util::{ReadExt, WriteExt};
+use x509::{Certificate, PublicKey, KEY_USAGE_KEY_ENCIPHERMENT};
+use tls_result::TlsResult;
+use tls_result::TlsErrorKind::{InternalError, DecodeError, BadCertificate};
+use tls_item::TlsItem;
+use handshake::{NamedCurve, ECPointFormat};
+use signature::SignatureAndHashAlgorithm;
+use super::KeyExchange;
+
+pub const PRE_MASTER_SECRET_LEN: usize = 48;
+
+tls_vec!(EncryptedPreMasterSecret = u8(0, (1 << 16) - 1));
+
+pub struct RsaKeyExchange;
+
+impl KeyExchange for RsaKeyExchange {
+    fn supported_groups(&self) -> Vec<NamedCurve> {
+        Vec::new()
+    }
+
+    fn verify_server_params(&self,
+                            _: &[u8],
+                            _: &[u8],
+                            _: &[u8],
+                            _: &[u8],
+                            _: &[SignatureAndHashAlgorithm]) -> TlsResult<()> {
+        tls_err!(InternalError, "RSA key exchange has no ServerKeyExchange")
+    }
+
+    fn compute_keys(&self,
+                    _data: &[u8],
+                    _groups: &[NamedCurve],
+                    _point_formats: &[ECPointFormat],
+                    _rng: &mut OsRng) -> TlsResult<(Vec<u8>, Vec<u8>)> {
+        tls_err!(InternalError, "RSA key exchange needs the server certificate")
+    }
+
+    fn is_static(&self) -> bool {
+        true
+    }
+
+    fn compute_static_keys(&self,
+                           leaf: &Certificate,
+                           client_version: (u8, u8),
+                           rng: &mut OsRng) -> TlsResult<(Vec<u8>, Vec<u8>)> {
+        // RFC 5280 4.2.1.3
+        if let Some(usage) = try!(leaf.key_usage()) {
+            if usage & KEY_USAGE_KEY_ENCIPHERMENT == 0 {
+                return tls_err!(BadCertificate, "server key is not for key encipherment");
+            }
+        }
+        let key = match try!(PublicKey::parse(leaf.subject_public_key_info)) {
+            PublicKey::Rsa(key) => key,
+            _ => return tls_err!(BadCertificate, "server key is not an RSA key"),
+        };
+
+        // `client_version` is the version offered in ClientHello, not the negotiated one,
+        // so that the server can detect version rollback.
+        let mut pre_master_secret = vec![client_version.0, client_version.1];
+        let mut random = [0u8; PRE_MASTER_SECRET_LEN - 2];
+        rng.fill_bytes(&mut random);
+        pre_master_secret.extend(&random);
+
+        let encrypted = match key.encrypt_pkcs1(&pre_master_secret, rng) {
+            Some(encrypted) => encrypted,
+            None => return tls_err!(InternalError, "failed to encrypt premaster secret"),
+        };
+        let encrypted = try!(EncryptedPreMasterSecret::new(encrypted));
+        let mut data = Vec::new();
+        try!(encrypted.tls_write(&mut data));
+
+        Ok((data, pre_master_secret))
+    }
+}
+
+/// Decrypts the premaster secret in ClientKeyExchange `data` with the server `key`.
+/// `client_version` is the version of ClientHello.
+///
+/// Following RFC 5246 7.4.7.1, a random premaster secret is returned instead of an error
+/// if the padding, the length or the version is wrong, and the handshake fails later
+/// at Finished. Only malformed messages (which are public) are errors.
+pub fn decrypt_pre_master_secret(key: &PrivateKey,
+                                 client_version: (u8, u8),
+                                 data: &[u8],
+                                 rng: &mut OsRng) -> TlsResult<Vec<u8>> {
+    let key: &RsaPrivateKey = match key.rsa_private_key() {
+        Some(key) => key,
+        None => return tls_err!(InternalError, "RSA key exchange needs an RSA key"),
+    };
+
+    let mut reader = Cursor::new(data);
+    let encrypted: EncryptedPreMasterSecret = try!(TlsItem::tls_read(&mut reader));
+    if reader.position() as usize != data.len() {
+        return tls_err!(DecodeError, "trailing data in ClientKeyExchange");
+    }
+
+    let mut random = [0u8; PRE_MASTER_SECRET_LEN];
+    rng.fill_bytes(&mut random);
+    // SECRET
+    let decrypted = match key.decrypt_pkcs1_implicit(&encrypted, &random) {
+        Some(decrypted) => decrypted,
+        None => return tls_err!(DecodeError, "bad encrypted premaster secret"),
+    };
+
+    // all-one mask if the version matches
+    let diff = (decrypted[0] ^ client_version.0) | (decrypted[1] ^ client_version.1);
+    let good = ((diff as u32).wrapping_sub(1) >> 8) as u8;
+    let pre_master_secret = decrypted.iter().zip(random.iter()).map(|(&m, &r)| {
+        (m & good) | (r & !good)
+    }).collect();
+    Ok(pre_master_secret)
+}
+
+#[cfg(test)]
+mod test {
+    use rand::OsRng;
+
+    use cipher::KeyExchange;
+    use key::PrivateKey;
+    use x509::Certificate;
+    use tls_item::TlsItem;
+    use super::{RsaKeyExchange, EncryptedPreMasterSecret, decrypt_pre_master_secret};
+
+    static SERVER: &'static [u8] = include_bytes!("../../testdata/test_server.der");
+    static SERVER_KEY: &'static str = include_str!("../../testdata/test_server.key");
+    // no key encipherment
+    static CA: &'static [u8] = include_bytes!("../../testdata/test_ca.der");
+
+    fn client_key_exchange(encrypted: Vec<u8>) -> Vec<u8> {
+        let mut data = Vec::new();
+        EncryptedPreMasterSecret::new(encrypted).unwrap().tls_write(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_rsa_key_exchange() {
+        let mut rng = OsRng::new().unwrap();
+        let key = PrivateKey::from_pem(SERVER_KEY).unwrap();
+        let leaf = Certificate::parse(SERVER).unwrap();
+
+        let (data, secret) = RsaKeyExchange.compute_static_keys(&leaf, (3, 3), &mut rng)
+                                           .unwrap();
+        assert_eq!(secret.len(), 48);
+        assert_eq!(&secret[..2], &[3, 3]);
+        assert_eq!(data.len(), 2 + 256);
+
+        let decrypted = decrypt_pre_master_secret(&key, (3, 3), &data, &mut rng).unwrap();
+        assert_eq!(decrypted, secret);
+
+        // version rollback
+        let decrypted = decrypt_pre_master_secret(&key, (3, 2), &data, &mut rng).unwrap();
+        assert_eq!(decrypted.len(), 48);
+        assert!(decrypted != secret);
+
+        // bad padding
+        let mut bad = data.clone();
+        bad[100] ^= 1;
+        let decrypted = decrypt_pre_master_secret(&key, (3, 3), &bad, &mut rng).unwrap();
+        assert_eq!(decrypted.len(), 48);
+        assert!(decrypted != secret);
+
+        // valid padding with a wrong length
+        let public = key.rsa_private_key().unwrap().public_key();
+        let short = public.encrypt_pkcs1(&secret[..47], &mut rng).unwrap();
+        let decrypted =
+            decrypt_pre_master_secret(&key, (3, 3), &client_key_exchange(short), &mut rng)
+                .unwrap();
+        assert_eq!(decrypted.len(), 48);
+        assert!(&decrypted[..47] != &secret[..47]);
+
+        // malformed messages
+        assert!(decrypt_pre_master_secret(&key, (3, 3), &data[..100], &mut rng).is_err());
+        let long = client_key_exchange(vec![0xff; 256]);
+        assert!(decrypt_pre_master_secret(&key, (3, 3), &long, &mut rng).is_err());
+
+        let ca = Certificate::parse(CA).unwrap();
+        assert!(RsaKeyExchange.compute_static_keys(&ca, (3, 3), &mut rng).is_err());
+    }
+}
@@ -1,19 +1,635 @@
 use std::io;
 use std::io::prelude::*;
-use std::net::TcpStream;
+use std::fs::File;
+use std::net::{TcpStream, IpAddr};
+use std::path::Path;
 use std::cmp;
+use std::mem;
 use rand::{Rng, OsRng};
 
 use alert;
 use tls_result::{TlsResult, TlsError, TlsErrorKind};
-use tls_result::TlsErrorKind::{UnexpectedMessage, InternalError, DecryptError, IllegalParameter};
-use util::{SurugaError, crypto_compare};
+use tls_result::TlsErrorKind::{UnexpectedMessage, InternalError, DecryptError, IllegalParameter,
+                               HandshakeFailure, AlertReceived, DecodeError,
+                               UnsupportedExtension, ProtocolVersion, CertificateRevoked,
+                               BadCertificateStatusResponse, BadCertificate,
+                               CertificateUnknown};
+use util::{SurugaError, crypto_compare, unix_time};
 use cipher::{self, Aead};
 use cipher::prf::Prf;
+use cipher::psk::{self, PresharedKey};
 use crypto::sha2::sha256;
 use tls_item::{TlsItem, DummyItem};
-use handshake::{self, Handshake};
-use tls::{TlsReader, TlsWriter, TLS_VERSION};
+use handshake::{self, Handshake, ClientCertificateType};
+use signature::{self, SigningKey, SignatureAlgorithm, SignatureAndHashAlgorithm};
+use x509;
+use ocsp::{self, CertStatus};
+use ct;
+use crl;
+use base64;
+use pem;
+use key::PrivateKey;
+use roots::RootStore;
+use verify::{ServerCertVerifier, WebPkiVerifier};
+use tls::{TlsReader, TlsWriter, TLS_VERSION, DEFAULT_MAX_WARNING_ALERTS, RECORD_MAX_LEN,
+          TLS_1_2, TLS_1_3};
+use tls::Message::{HandshakeMessage, ChangeCipherSpecMessage, AlertMessage,
+                   ApplicationDataMessage};
+
+/// What to do when the server sends HelloRequest.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum RenegotiationPolicy {
+    /// Reply with a `no_renegotiation` warning alert.
+    Refuse,
+    /// Renegotiate if the server supports secure renegotiation (RFC 5746),
+    /// otherwise refuse.
+    Secure,
+}
+
+/// Certificate chain and private key for client authentication.
+pub struct ClientCertificate {
+    /// DER certificates, leaf first.
+    pub chain: Vec<Vec<u8>>,
+    pub key: Box<SigningKey + Send>,
+}
+
+impl ClientCertificate {
+    /// Loads a PEM certificate chain (leaf first) and the private key of the leaf
+    /// (see `PrivateKey::from_pem`).
+    pub fn from_pem(chain: &str, key: &str) -> TlsResult<ClientCertificate> {
+        let chain = try!(pem::parse_certificates(chain));
+        let key = try!(PrivateKey::from_pem(key));
+        {
+            let leaf = match chain.first() {
+                Some(leaf) => try!(x509::Certificate::parse(leaf)),
+                None => return tls_err!(InternalError, "no certificate"),
+            };
+            if leaf.subject_public_key_info != &key.public_key_info()[..] {
+                return tls_err!(InternalError, "private key does not match the certificate");
+            }
+        }
+        Ok(ClientCertificate {
+            chain: chain,
+            key: Box::new(key),
+        })
+    }
+}
+
+/// OCSP stapling (RFC 6066 8) options.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct OcspPolicy {
+    /// Send the `status_request` extension. Stapled responses are always verified
+    /// and invalid ones abort the handshake.
+    pub request: bool,
+    /// Abort the handshake if the stapled response says the certificate is revoked.
+    pub fail_on_revoked: bool,
+    /// Abort the handshake if the certificate has the TLS Feature `status_request`
+    /// extension (RFC 7633, "must-staple") but no good response is stapled.
+    /// Only effective when `request` is set.
+    pub enforce_must_staple: bool,
+}
+
+impl OcspPolicy {
+    pub fn new() -> OcspPolicy {
+        OcspPolicy {
+            request: true,
+            fail_on_revoked: true,
+            enforce_must_staple: true,
+        }
+    }
+}
+
+/// Revocation checking of the server certificate chain with local CRLs (RFC 5280 5).
+pub struct CrlPolicy {
+    /// DER CRLs. Each certificate of the path validated by `ClientConfig::verifier` is
+    /// checked against CRLs signed by its issuer. The trust anchor and self-signed
+    /// certificates are not checked.
+    pub crls: Vec<Vec<u8>>,
+    /// Abort the handshake if no fresh CRL is available for a certificate ("hard-fail").
+    /// Otherwise such certificates are accepted ("soft-fail").
+    /// Revoked certificates are always rejected.
+    pub hard_fail: bool,
+}
+
+impl CrlPolicy {
+    pub fn new() -> CrlPolicy {
+        CrlPolicy {
+            crls: Vec::new(),
+            hard_fail: false,
+        }
+    }
+
+    /// Reads a DER CRL from `path`.
+    pub fn add_file<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let mut data = Vec::new();
+        try!(try!(File::open(path)).read_to_end(&mut data));
+        self.crls.push(data);
+        Ok(())
+    }
+}
+
+/// Public key pinning with SHA-256 hashes of `SubjectPublicKeyInfo`
+/// (like `pin-sha256` of RFC 7469).
+///
+/// Pins are matched against the path validated by `ClientConfig::verifier`, from the leaf
+/// up to the trust anchor, which the server need not send. Pins are checked in addition to
+/// the verifier; for self-signed deployments, use it with
+/// `verify::danger::NoCertificateVerification`, which takes the received chain as the path.
+pub struct PinPolicy {
+    pins: Vec<[u8; 32]>,
+    /// Only match the leaf certificate.
+    pub leaf_only: bool,
+}
+
+impl PinPolicy {
+    pub fn new() -> PinPolicy {
+        PinPolicy {
+            pins: Vec::new(),
+            leaf_only: false,
+        }
+    }
+
+    /// Adds a base64 SHA-256 pin, e.g. the output of
+    /// `openssl x509 -pubkey -noout | openssl pkey -pubin -outform DER |
+    /// openssl dgst -sha256 -binary | base64`.
+    pub fn add_pin(&mut self, pin: &str) -> TlsResult<()> {
+        match base64::decode(pin) {
+            Some(ref hash) if hash.len() == 32 => {
+                let mut pin = [0u8; 32];
+                pin.copy_from_slice(hash);
+                self.pins.push(pin);
+                Ok(())
+            }
+            _ => tls_err!(InternalError, "bad pin: {}", pin),
+        }
+    }
+
+    /// Checks whether `spki` (raw DER `SubjectPublicKeyInfo`) is pinned.
+    pub fn matches(&self, spki: &[u8]) -> bool {
+        let hash = sha256(spki);
+        self.pins.iter().any(|pin| &pin[..] == &hash[..])
+    }
+}
+
+/// Certificate Transparency (RFC 6962) requirements for the server certificate.
+pub struct CtConfig {
+    /// Logs whose SCTs are accepted.
+    pub logs: Vec<ct::Log>,
+    /// Decides whether the valid SCTs are enough. The handshake is aborted otherwise.
+    pub policy: Box<ct::CtPolicy + Send>,
+}
+
+/// Options for `TlsClient`.
+pub struct ClientConfig {
+    /// DNS name or IP address of the server, used to verify its certificate.
+    /// DNS names are also sent in the `server_name` extension.
+    pub server_name: Option<String>,
+    /// Verifies the server certificate chain. The default `WebPkiVerifier` has no
+    /// trust anchors, so it must be replaced to connect to any server.
+    pub verifier: Box<ServerCertVerifier + Send>,
+    /// Number of consecutive warning alerts accepted before the connection is aborted.
+    pub max_warning_alerts: usize,
+    /// Called for each warning alert which is ignored.
+    pub alert_callback: Option<alert::AlertCallback>,
+    pub renegotiation: RenegotiationPolicy,
+    /// Maximum plaintext length of records sent by the client.
+    /// Small records reduce latency and help constrained peers.
+    pub fragment_len: usize,
+    /// Request the RFC 6066 `max_fragment_length` extension.
+    pub max_fragment_length: Option<handshake::MaxFragmentLength>,
+    /// Advertise the RFC 8449 `record_size_limit` extension.
+    /// The limit must be in `64...RECORD_MAX_LEN`.
+    pub record_size_limit: Option<u16>,
+    /// Cipher suites offered, in order of preference.
+    pub cipher_suites: Vec<cipher::CipherSuite>,
+    /// Offer the RFC 7366 `encrypt_then_mac` extension.
+    /// It is used for CBC cipher suites if the server supports it.
+    pub encrypt_then_mac: bool,
+    /// Offer `ansiX962_compressed_prime` in the `ec_point_formats` extension.
+    /// If the server lists it too, it may send a compressed ECDHE public key.
+    /// The client always sends uncompressed keys.
+    pub ec_point_compression: bool,
+    /// Lowest protocol version accepted, as `(major, minor)`.
+    /// Only TLS 1.2 can be negotiated today, so this must be `TLS_VERSION`.
+    /// The range is there for versions to come.
+    pub min_version: (u8, u8),
+    /// Highest protocol version offered. Must be `TLS_VERSION` too for now.
+    pub max_version: (u8, u8),
+    /// Set when the connection is a retry after a failure with a higher version,
+    /// e.g. by another TLS implementation. `TLS_FALLBACK_SCSV` (RFC 7507) is sent so that
+    /// the server can detect downgrade attacks. If it fails with `InappropriateFallback`,
+    /// do not retry again.
+    pub fallback: bool,
+    /// Signature algorithms sent in the `signature_algorithms` extension, in order of
+    /// preference. ServerKeyExchange must be signed with one of them, and CertificateVerify
+    /// uses one of them. Must be a non-empty subset of `signature::SUPPORTED_ALGORITHMS`.
+    pub signature_algorithms: Vec<SignatureAndHashAlgorithm>,
+    /// Candidates for client authentication. When the server requests a certificate,
+    /// the first one matching its CA names and signature algorithms is sent.
+    pub client_certificates: Vec<ClientCertificate>,
+    pub ocsp: OcspPolicy,
+    pub crl: Option<CrlPolicy>,
+    /// If set, the server certificate chain must contain a pinned key.
+    pub pins: Option<PinPolicy>,
+    /// If set, request SCTs with the `signed_certificate_timestamp` extension and
+    /// enforce the policy.
+    pub ct: Option<CtConfig>,
+    /// Pre-shared key for `PSK` and `ECDHE_PSK` cipher suites (RFC 4279, RFC 5489).
+    /// Required if `cipher_suites` contains any of them.
+    pub psk: Option<PresharedKey>,
+    /// Allow static RSA key exchange (`TLS_RSA_*`) in `cipher_suites`, for legacy servers.
+    /// It has no forward secrecy, so it is rejected unless this is set.
+    pub rsa_key_exchange: bool,
+    /// Allow MAC-then-encrypt CBC cipher suites (`*_CBC_*`) in `cipher_suites`, for legacy
+    /// appliances. They are not in the default list and are rejected unless this is set.
+    pub cbc_cipher_suites: bool,
+    /// Offer the hybrid X25519MLKEM768 group for ECDHE, after P-256 and P-384.
+    /// Its TLS 1.2 adaptation is not standard, so only servers doing the same choose it.
+    pub hybrid_key_exchange: bool,
+}
+
+impl ClientConfig {
+    pub fn new() -> ClientConfig {
+        ClientConfig {
+            server_name: None,
+            verifier: Box::new(WebPkiVerifier::new(RootStore::new())),
+            max_warning_alerts: DEFAULT_MAX_WARNING_ALERTS,
+            alert_callback: None,
+            renegotiation: RenegotiationPolicy::Refuse,
+            fragment_len: RECORD_MAX_LEN,
+            max_fragment_length: None,
+            record_size_limit: None,
+            cipher_suites: vec!(
+                cipher::CipherSuite::TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256,
+                cipher::CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256,
+            ),
+            encrypt_then_mac: true,
+            ec_point_compression: false,
+            min_version: TLS_VERSION,
+            max_version: TLS_VERSION,
+            fallback: false,
+            signature_algorithms: signature::SUPPORTED_ALGORITHMS.to_vec(),
+            client_certificates: Vec::new(),
+            ocsp: OcspPolicy::new(),
+            crl: None,
+            pins: None,
+            ct: None,
+            psk: None,
+            rsa_key_exchange: false,
+            cbc_cipher_suites: false,
+            hybrid_key_exchange: false,
+        }
+    }
+}
+
+/// Checks that ServerHello only contains extensions we sent, each at most once.
+fn check_server_extensions(server_hello: &handshake::ServerHello,
+                           offered: &[u16]) -> TlsResult<()> {
+    if let Some(ref extensions) = server_hello.extensions {
+        let mut found = Vec::new();
+        for ext in extensions.iter() {
+            let ty = ext.extension_type();
+            if !offered.contains(&ty) {
+                return tls_err!(UnsupportedExtension, "unexpected extension: {}", ty);
+            }
+            if found.contains(&ty) {
+                return tls_err!(DecodeError, "duplicate extension: {}", ty);
+            }
+            found.push(ty);
+        }
+    }
+    Ok(())
+}
+
+/// Record size limits negotiated by `max_fragment_length` or `record_size_limit`.
+#[derive(Debug, PartialEq)]
+struct RecordSizeLimits {
+    // limit of records sent by the client
+    send: Option<usize>,
+    // limit of records sent by the server
+    recv: Option<usize>,
+    // if true, limits apply only to protected records (RFC 8449).
+    // otherwise they apply right after ServerHello (RFC 6066).
+    protected_only: bool,
+}
+
+fn negotiate_record_size(config: &ClientConfig,
+                         server_hello: &handshake::ServerHello) -> TlsResult<RecordSizeLimits> {
+    let mut max_fragment_length = None;
+    let mut record_size_limit = None;
+    if let Some(ref extensions) = server_hello.extensions {
+        for ext in extensions.iter() {
+            match *ext {
+                handshake::Extension::max_fragment_length(len) => max_fragment_length = Some(len),
+                handshake::Extension::record_size_limit(limit) => record_size_limit = Some(limit),
+                _ => {}
+            }
+        }
+    }
+
+    match (max_fragment_length, record_size_limit) {
+        (Some(_), Some(_)) => {
+            tls_err!(IllegalParameter, "both max_fragment_length and record_size_limit found")
+        }
+        (Some(len), None) => {
+            if config.max_fragment_length != Some(len) {
+                return tls_err!(IllegalParameter, "max_fragment_length mismatch: {:?}", len);
+            }
+            Ok(RecordSizeLimits {
+                send: Some(len.len()),
+                recv: Some(len.len()),
+                protected_only: false,
+            })
+        }
+        (None, Some(limit)) => {
+            if limit < 64 {
+                return tls_err!(IllegalParameter, "record_size_limit too small: {}", limit);
+            }
+            let send = cmp::min(limit as usize, RECORD_MAX_LEN);
+            Ok(RecordSizeLimits {
+                send: Some(send),
+                recv: config.record_size_limit.map(|limit| limit as usize),
+                protected_only: true,
+            })
+        }
+        (None, None) => {
+            Ok(RecordSizeLimits {
+                send: None,
+                recv: None,
+                protected_only: false,
+            })
+        }
+    }
+}
+
+/// Chooses a client certificate and its signature algorithm for CertificateRequest
+/// (RFC 5246 7.4.4, 7.4.6). Returns `None` if nothing matches.
+fn select_client_certificate(certificates: &[ClientCertificate],
+                             offered: &[SignatureAndHashAlgorithm],
+                             request: &handshake::CertificateRequest)
+                             -> TlsResult<Option<(usize, SignatureAndHashAlgorithm)>> {
+    for (i, cert) in certificates.iter().enumerate() {
+        if request.certificate_authorities.len() > 0 {
+            // any certificate in the chain may be issued by a requested CA
+            let mut found = false;
+            for der in cert.chain.iter() {
+                let parsed = match x509::Certificate::parse(der) {
+                    Ok(parsed) => parsed,
+                    Err(err) => {
+                        return tls_err!(InternalError, "bad client certificate: {}", err.desc);
+                    }
+                };
+                if request.certificate_authorities.iter().any(|name| &name[..] == parsed.issuer) {
+                    found = true;
+                    break;
+                }
+            }
+            if !found {
+                continue;
+            }
+        }
+
+        for algorithm in cert.key.algorithms().into_iter() {
+            let cert_type = match algorithm.signature {
+                SignatureAlgorithm::rsa => ClientCertificateType::rsa_sign,
+                SignatureAlgorithm::ecdsa => ClientCertificateType::ecdsa_sign,
+                // RFC 8422 5.5: ed25519 and ed448 use ecdsa_sign
+                SignatureAlgorithm::ed25519 |
+                SignatureAlgorithm::ed448 => ClientCertificateType::ecdsa_sign,
+                SignatureAlgorithm::rsa_pss_rsae_sha256 |
+                SignatureAlgorithm::rsa_pss_rsae_sha384 |
+                SignatureAlgorithm::rsa_pss_rsae_sha512 => ClientCertificateType::rsa_sign,
+                _ => continue,
+            };
+            if request.certificate_types.contains(&cert_type) &&
+               request.supported_signature_algorithms.contains(&algorithm) &&
+               offered.contains(&algorithm) {
+                return Ok(Some((i, algorithm)));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Finds the issuer of `leaf` among `certs`.
+fn find_issuer<'a>(leaf: &x509::Certificate,
+                   certs: &[&'a [u8]]) -> TlsResult<Option<x509::Certificate<'a>>> {
+    for der in certs.iter() {
+        let cert = try!(x509::Certificate::parse(der));
+        if cert.subject == leaf.issuer {
+            return Ok(Some(cert));
+        }
+    }
+    Ok(None)
+}
+
+/// Checks the stapled OCSP response for the validated `path` of the server certificate
+/// (leaf first, ending with the trust anchor) at `now`.
+fn check_ocsp_staple(policy: &OcspPolicy,
+                     path: &[&[u8]],
+                     staple: Option<&[u8]>,
+                     now: i64) -> TlsResult<()> {
+    if path.len() == 0 {
+        return Ok(());
+    }
+    let leaf = try!(x509::Certificate::parse(path[0]));
+    let must_staple = policy.enforce_must_staple && try!(ocsp::must_staple(&leaf));
+
+    let status = match staple {
+        Some(staple) => {
+            // the issuer is next in the path, even if the server omitted it
+            if path.len() < 2 {
+                return tls_err!(BadCertificateStatusResponse,
+                                "issuer of the certificate not found");
+            }
+            let issuer = try!(x509::Certificate::parse(path[1]));
+            Some(try!(ocsp::verify_response(staple, &leaf, &issuer, now)))
+        }
+        None => None,
+    };
+
+    match status {
+        Some(CertStatus::Revoked(..)) if policy.fail_on_revoked => {
+            tls_err!(CertificateRevoked, "server certificate is revoked")
+        }
+        Some(CertStatus::Good) | Some(CertStatus::Revoked(..)) => Ok(()),
+        Some(CertStatus::Unknown) | None if must_staple => {
+            tls_err!(BadCertificateStatusResponse,
+                     "must-staple certificate without good OCSP response")
+        }
+        Some(CertStatus::Unknown) | None => Ok(()),
+    }
+}
+
+/// Checks the validated `path` (leaf first, ending with the trust anchor) of the server
+/// certificate against CRLs at `now`.
+fn check_crls(policy: &CrlPolicy, path: &[&[u8]], now: i64) -> TlsResult<()> {
+    let mut crls = Vec::new();
+    for der in policy.crls.iter() {
+        match crl::Crl::parse(der) {
+            Ok(crl) => crls.push(crl),
+            Err(err) => debug!("ignoring CRL: {}", err.desc),
+        }
+    }
+
+    // the anchor is trusted as it is
+    let anchor = path.len().saturating_sub(1);
+    for (i, der) in path[..anchor].iter().enumerate() {
+        let cert = try!(x509::Certificate::parse(der));
+        if cert.subject == cert.issuer {
+            continue;
+        }
+        let status = match try!(find_issuer(&cert, &path[(i + 1)..])) {
+            Some(issuer) => crl::cert_status(&crls, &cert, &issuer, now),
+            None => CertStatus::Unknown,
+        };
+        match status {
+            CertStatus::Revoked(..) => {
+                return tls_err!(CertificateRevoked, "certificate {} of the path is revoked", i);
+            }
+            CertStatus::Unknown if policy.hard_fail => {
+                return tls_err!(CertificateUnknown, "no valid CRL for certificate {}", i);
+            }
+            CertStatus::Good | CertStatus::Unknown => {}
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the validated `path` of the server certificate (leaf first, ending with the
+/// trust anchor) contains a pinned key.
+fn check_pins(policy: &PinPolicy, path: &[&[u8]]) -> TlsResult<()> {
+    if path.len() == 0 {
+        return tls_err!(BadCertificate, "no server certificate");
+    }
+    let certs = if policy.leaf_only { &path[..1] } else { path };
+    for der in certs.iter() {
+        let cert = try!(x509::Certificate::parse(der));
+        if policy.matches(cert.subject_public_key_info) {
+            return Ok(());
+        }
+    }
+    tls_err!(BadCertificate, "no pinned key in the server certificate path")
+}
+
+/// Checks SCTs for the validated `path` of the server certificate (leaf first, ending with
+/// the trust anchor) at `now`.
+/// `tls_scts` is the list sent in the `signed_certificate_timestamp` extension.
+fn check_scts(config: &CtConfig,
+              path: &[&[u8]],
+              tls_scts: Option<&handshake::SignedCertificateTimestampList>,
+              now: i64) -> TlsResult<()> {
+    if path.len() == 0 {
+        return tls_err!(BadCertificate, "no server certificate");
+    }
+    let leaf = try!(x509::Certificate::parse(path[0]));
+    // the issuer is next in the path, even if the server omitted it
+    let issuer = match path.get(1) {
+        Some(der) => Some(try!(x509::Certificate::parse(der))),
+        None => None,
+    };
+    let scts = try!(ct::verify_scts(&config.logs, path[0], issuer.as_ref(), tls_scts, now));
+    if !config.policy.is_compliant(&leaf, &scts) {
+        return tls_err!(BadCertificate,
+                        "certificate transparency policy not met: {} valid SCTs",
+                        scts.len());
+    }
+    Ok(())
+}
+
+/// Checks the downgrade sentinel in `ServerHello.random` (RFC 8446 4.1.3).
+/// A server supporting a higher version sets it when it negotiates a lower one, so an attacker
+/// can't force the downgrade. The TLS 1.3 sentinel only means something if we offered
+/// TLS 1.3, i.e. `max_version` is at least `TLS_1_3`.
+fn check_downgrade_sentinel(server_version: (u8, u8),
+                            server_random: &[u8],
+                            max_version: (u8, u8)) -> TlsResult<()> {
+    const DOWNGRADE_TLS12: &'static [u8] = b"DOWNGRD\x01";
+    const DOWNGRADE_TLS11: &'static [u8] = b"DOWNGRD\x00";
+
+    let sentinel = &server_random[(server_random.len() - 8)..];
+    let downgraded = if server_version < TLS_1_2 {
+        sentinel == DOWNGRADE_TLS11
+    } else {
+        server_version == TLS_1_2 && max_version >= TLS_1_3 && sentinel == DOWNGRADE_TLS12
+    };
+    if downgraded {
+        return tls_err!(IllegalParameter, "downgrade to {:?} detected", server_version);
+    }
+    Ok(())
+}
+
+/// Returns the encrypt-then-MAC variant of `aead` if the server accepted
+/// `encrypt_then_mac` (RFC 7366 3).
+fn negotiate_encrypt_then_mac(aead: Box<Aead>,
+                              server_hello: &handshake::ServerHello) -> TlsResult<Box<Aead>> {
+    let mut found = false;
+    if let Some(ref extensions) = server_hello.extensions {
+        for ext in extensions.iter() {
+            if let handshake::Extension::encrypt_then_mac(..) = *ext {
+                found = true;
+            }
+        }
+    }
+
+    if !found {
+        return Ok(aead);
+    }
+    match aead.encrypt_then_mac() {
+        Some(aead) => Ok(aead),
+        None => tls_err!(IllegalParameter, "encrypt_then_mac with AEAD cipher suite"),
+    }
+}
+
+/// Returns the point formats the server may use for its ECDHE public key (RFC 4492 5.4).
+/// Compressed points need to be listed in `ec_point_formats` by both sides.
+fn negotiate_point_formats(config: &ClientConfig,
+                           server_hello: &handshake::ServerHello)
+                           -> Vec<handshake::ECPointFormat> {
+    let mut formats = vec![handshake::ECPointFormat::uncompressed];
+    if !config.ec_point_compression {
+        return formats;
+    }
+    if let Some(ref extensions) = server_hello.extensions {
+        for ext in extensions.iter() {
+            if let handshake::Extension::ec_point_formats(ref list) = *ext {
+                let compressed = handshake::ECPointFormat::ansiX962_compressed_prime;
+                if list.contains(&compressed) {
+                    formats.push(compressed);
+                }
+            }
+        }
+    }
+    formats
+}
+
+/// Checks `renegotiation_info` of ServerHello (RFC 5746 3.4, 3.5).
+/// `expected` is empty for the initial handshake, otherwise it is
+/// `client_verify_data || server_verify_data` of the previous handshake.
+///
+/// Returns whether the server supports secure renegotiation.
+fn check_renegotiation_info(server_hello: &handshake::ServerHello,
+                            expected: &[u8]) -> TlsResult<bool> {
+    let mut found = None;
+    if let Some(ref extensions) = server_hello.extensions {
+        for ext in extensions.iter() {
+            if let handshake::Extension::renegotiation_info(ref data) = *ext {
+                found = Some(&data[..]);
+            }
+        }
+    }
+
+    match found {
+        None if expected.len() == 0 => Ok(false),
+        None => tls_err!(HandshakeFailure, "renegotiation_info not found"),
+        Some(data) => {
+            if data.len() != expected.len() || !crypto_compare(data, expected) {
+                return tls_err!(HandshakeFailure, "wrong renegotiation_info");
+            }
+            Ok(true)
+        }
+    }
+}
 
 // handshake is done during construction.
 pub struct TlsClient<R: Read, W: Write> {
@@ -21,16 +637,87 @@ pub struct TlsClient<R: Read, W: Write> {
     pub writer: TlsWriter<W>,
     pub rng: OsRng,
     buf: Vec<u8>,
+    config: ClientConfig,
+    // true if the server supports RFC 5746.
+    secure_renegotiation: bool,
+    // verify_data of the last handshake. empty before the first handshake is done.
+    client_verify_data: Vec<u8>,
+    server_verify_data: Vec<u8>,
+    // negotiated protocol version
+    version: (u8, u8),
+    // DER OCSP response stapled by the server
+    ocsp_response: Option<Vec<u8>>,
+    // psk_identity_hint sent by the server in a PSK handshake
+    psk_identity_hint: Option<Vec<u8>>,
+    // leaf certificate of the first handshake. renegotiation must keep it.
+    server_certificate: Option<Vec<u8>>,
 }
 
 impl<R: Read, W: Write> TlsClient<R, W> {
+    /// Uses `ClientConfig::new()`, whose verifier trusts no CA, so every server
+    /// certificate is rejected.
+    #[deprecated(note = "the default verifier has no trust anchors; use `with_config`")]
     pub fn new(reader: R, writer: W, rng: OsRng) -> TlsResult<TlsClient<R, W>> {
-        let mut client = TlsClient {
-            reader: TlsReader::new(reader),
-            writer: TlsWriter::new(writer),
-            rng: rng,
-            buf: Vec::new(),
-        };
+        TlsClient::with_config(reader, writer, rng, ClientConfig::new())
+    }
+
+    pub fn with_config(reader: R,
+                       writer: W,
+                       rng: OsRng,
+                       mut config: ClientConfig) -> TlsResult<TlsClient<R, W>> {
+        let mut reader = TlsReader::new(reader);
+        reader.set_max_warning_alerts(config.max_warning_alerts);
+        if let Some(callback) = config.alert_callback.take() {
+            reader.set_alert_callback(callback);
+        }
+
+        if config.fragment_len == 0 || config.fragment_len > RECORD_MAX_LEN {
+            return tls_err!(InternalError, "bad fragment_len: {}", config.fragment_len);
+        }
+        if let Some(limit) = config.record_size_limit {
+            if limit < 64 || limit as usize > RECORD_MAX_LEN {
+                return tls_err!(InternalError, "bad record_size_limit: {}", limit);
+            }
+        }
+        if config.cipher_suites.len() == 0 ||
+           config.cipher_suites.iter().any(|suite| suite.is_signaling()) {
+            return tls_err!(InternalError, "bad cipher_suites: {:?}", config.cipher_suites);
+        }
+        match config.psk {
+            Some(ref psk) => try!(psk.check()),
+            None => {
+                if config.cipher_suites.iter().any(|suite| suite.new_kex().is_psk()) {
+                    return tls_err!(InternalError, "PSK cipher suites need a pre-shared key");
+                }
+            }
+        }
+        if config.signature_algorithms.len() == 0 ||
+           config.signature_algorithms.iter().any(|alg| {
+               !signature::SUPPORTED_ALGORITHMS.contains(alg)
+           }) {
+            return tls_err!(InternalError,
+                            "bad signature_algorithms: {:?}",
+                            config.signature_algorithms);
+        }
+        if !config.rsa_key_exchange &&
+           config.cipher_suites.iter().any(|suite| suite.new_kex().is_static()) {
+            return tls_err!(InternalError, "static RSA key exchange is not enabled");
+        }
+        // block ciphers with HMAC have a MAC key
+        if !config.cbc_cipher_suites &&
+           config.cipher_suites.iter().any(|suite| suite.new_aead().mac_key_size() > 0) {
+            return tls_err!(InternalError, "CBC cipher suites are not enabled");
+        }
+        if config.min_version != TLS_VERSION || config.max_version != TLS_VERSION {
+            return tls_err!(InternalError,
+                            "unsupported version range: {:?} {:?}",
+                            config.min_version,
+                            config.max_version);
+        }
+        let mut writer = TlsWriter::new(writer);
+        writer.set_max_fragment_len(config.fragment_len);
+
+        let mut client = TlsClient::from_parts(reader, writer, rng, config);
 
         // handshake failed. send alert if necessary
         match client.handshake() {
@@ -40,6 +727,40 @@ impl<R: Read, W: Write> TlsClient<R, W> {
         Ok(client)
     }
 
+    // a client on top of `reader` and `writer`, before the first handshake.
+    // `config` must have been checked.
+    fn from_parts(reader: TlsReader<R>,
+                  writer: TlsWriter<W>,
+                  rng: OsRng,
+                  config: ClientConfig) -> TlsClient<R, W> {
+        TlsClient {
+            reader: reader,
+            writer: writer,
+            rng: rng,
+            buf: Vec::new(),
+            config: config,
+            secure_renegotiation: false,
+            client_verify_data: Vec::new(),
+            server_verify_data: Vec::new(),
+            version: TLS_VERSION,
+            ocsp_response: None,
+            psk_identity_hint: None,
+            server_certificate: None,
+        }
+    }
+
+    /// DER `OCSPResponse` stapled by the server in the last handshake, if any.
+    /// It has been verified against the server certificate.
+    pub fn ocsp_response(&self) -> Option<&[u8]> {
+        self.ocsp_response.as_ref().map(|response| &response[..])
+    }
+
+    /// `psk_identity_hint` sent by the server in the last handshake, if any.
+    /// Only set for PSK cipher suites.
+    pub fn psk_identity_hint(&self) -> Option<&[u8]> {
+        self.psk_identity_hint.as_ref().map(|hint| &hint[..])
+    }
+
     #[inline]
     pub fn reader(&mut self) -> &mut R {
         self.reader.get_mut()
@@ -50,12 +771,29 @@ impl<R: Read, W: Write> TlsClient<R, W> {
         self.writer.get_mut()
     }
 
+    // read a handshake message. HelloRequest is ignored during handshake (RFC 5246 7.4.1.1)
+    // and application data is buffered during renegotiation.
+    fn read_handshake(&mut self) -> TlsResult<Handshake> {
+        let renegotiating = self.client_verify_data.len() > 0;
+        loop {
+            match try!(self.reader.read_message()) {
+                HandshakeMessage(Handshake::hello_request(..)) => {}
+                HandshakeMessage(handshake) => return Ok(handshake),
+                ApplicationDataMessage(data) if renegotiating => self.buf.extend(&data),
+                AlertMessage(alert) => {
+                    return tls_err!(AlertReceived, "alert: {:?}", alert.description);
+                }
+                _ => return tls_err!(UnexpectedMessage, "expected Handshake"),
+            }
+        }
+    }
+
     // this does not send alert when error occurs
     fn handshake(&mut self) -> TlsResult<()> {
         // expect specific HandshakeMessage. otherwise return Err
         macro_rules! expect {
             ($var:ident) => ({
-                match try!(self.reader.read_handshake()) {
+                match try!(self.read_handshake()) {
                     handshake::Handshake::$var(data) => data,
                     _ => return tls_err!(UnexpectedMessage, "unexpected handshake message found"),
                 }
@@ -69,32 +807,115 @@ impl<R: Read, W: Write> TlsClient<R, W> {
         };
         let random = try!(handshake::Random::new(cli_random.clone()));
 
-        // the only cipher we currently support
-        let cipher_suite = cipher::CipherSuite::TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256;
+        let mut curve_list = Vec::new();
+        for suite in self.config.cipher_suites.iter() {
+            for group in suite.new_kex().supported_groups().into_iter() {
+                if !curve_list.contains(&group) {
+                    curve_list.push(group);
+                }
+            }
+        }
+        if !self.config.hybrid_key_exchange {
+            curve_list.retain(|&group| group != handshake::NamedCurve::X25519MLKEM768);
+        }
+        let offered_groups = curve_list.clone();
+
+        // curves are not needed if only PSK is offered
+        let mut extensions = Vec::new();
+        if curve_list.len() > 0 {
+            let curve_list = try!(handshake::Extension::new_elliptic_curve_list(curve_list));
+            extensions.push(curve_list);
+
+            let mut format_list = vec!(handshake::ECPointFormat::uncompressed);
+            if self.config.ec_point_compression {
+                format_list.push(handshake::ECPointFormat::ansiX962_compressed_prime);
+            }
+            let format_list = try!(handshake::Extension::new_ec_point_formats(format_list));
+            extensions.push(format_list);
+        }
 
-        let curve_list = vec!(handshake::NamedCurve::secp256r1);
-        let curve_list = try!(handshake::Extension::new_elliptic_curve_list(curve_list));
+        let algorithms = self.config.signature_algorithms.clone();
+        extensions.push(try!(handshake::Extension::new_signature_algorithms(algorithms)));
+
+        if let Some(ref name) = self.config.server_name {
+            let name = name.trim_end_matches('.');
+            if name.parse::<IpAddr>().is_err() {
+                extensions.push(try!(handshake::Extension::new_server_name(name)));
+            }
+        }
+
+        if let Some(len) = self.config.max_fragment_length {
+            extensions.push(try!(handshake::Extension::new_max_fragment_length(len)));
+        }
+        if let Some(limit) = self.config.record_size_limit {
+            extensions.push(try!(handshake::Extension::new_record_size_limit(limit)));
+        }
+        if self.config.encrypt_then_mac {
+            extensions.push(handshake::Extension::encrypt_then_mac(DummyItem));
+        }
+        if self.config.ocsp.request {
+            extensions.push(try!(handshake::Extension::new_status_request()));
+        }
+        if self.config.ct.is_some() {
+            extensions.push(try!(handshake::Extension::new_signed_certificate_timestamp()));
+        }
 
-        let format_list = vec!(handshake::ECPointFormat::uncompressed);
-        let format_list = try!(handshake::Extension::new_ec_point_formats(format_list));
+        // RFC 5746: the initial handshake uses SCSV,
+        // renegotiation sends verify_data of the previous handshake.
+        let mut cipher_suites = self.config.cipher_suites.clone();
+        let renegotiation_info = {
+            let mut data = self.client_verify_data.clone();
+            data.extend(&self.server_verify_data);
+            data
+        };
+        if renegotiation_info.len() == 0 {
+            cipher_suites.push(cipher::CipherSuite::TLS_EMPTY_RENEGOTIATION_INFO_SCSV);
+            if self.config.fallback {
+                cipher_suites.push(cipher::CipherSuite::TLS_FALLBACK_SCSV);
+            }
+        } else {
+            let ext = try!(handshake::Extension::new_renegotiation_info(
+                self.client_verify_data.clone()));
+            extensions.push(ext);
+        }
 
-        let extensions = vec!(curve_list, format_list);
+        let offered_extensions = {
+            let mut offered: Vec<u16> = extensions.iter().map(|ext| ext.extension_type()).collect();
+            // SCSV allows the server to send renegotiation_info
+            offered.push(0xff01);
+            offered
+        };
 
-        let client_hello = try!(Handshake::new_client_hello(random, cipher_suite, extensions));
+        let client_hello = try!(Handshake::new_client_hello(self.config.max_version,
+                                                            random,
+                                                            cipher_suites,
+                                                            extensions));
         try!(self.writer.write_handshake(&client_hello));
 
         let server_hello_data = expect!(server_hello);
         {
             let server_major = server_hello_data.server_version.major;
             let server_minor = server_hello_data.server_version.minor;
-            if (server_major, server_minor) != TLS_VERSION {
-                return tls_err!(IllegalParameter,
+            let server_version = (server_major, server_minor);
+            // an attack is reported as such, rather than as a version mismatch
+            try!(check_downgrade_sentinel(server_version,
+                                          &server_hello_data.random[..],
+                                          self.config.max_version));
+            if server_version < self.config.min_version ||
+               server_version > self.config.max_version {
+                return tls_err!(ProtocolVersion,
                                 "wrong server version: {} {}",
                                 server_major,
                                 server_minor);
             }
+            // renegotiation must keep the version
+            if renegotiation_info.len() > 0 && server_version != self.version {
+                return tls_err!(ProtocolVersion, "version changed during renegotiation");
+            }
+            self.version = server_version;
+            self.reader.set_version(server_version);
 
-            if server_hello_data.cipher_suite != cipher_suite {
+            if !self.config.cipher_suites.contains(&server_hello_data.cipher_suite) {
                 return tls_err!(IllegalParameter,
                                 "cipher suite mismatch: found {:?}",
                                 server_hello_data.cipher_suite);
@@ -104,83 +925,285 @@ impl<R: Read, W: Write> TlsClient<R, W> {
                 return tls_err!(IllegalParameter, "compression method mismatch");
             }
 
-            // FIXME: check if server sent unknown extension
-            // it is currently done by just not understanding any extensions
-            // other than we used.
+            try!(check_server_extensions(&server_hello_data, &offered_extensions));
+
+            self.secure_renegotiation = try!(check_renegotiation_info(&server_hello_data,
+                                                                      &renegotiation_info));
         }
+        let cipher_suite = server_hello_data.cipher_suite;
+        let aead = try!(negotiate_encrypt_then_mac(cipher_suite.new_aead(), &server_hello_data));
 
-        // we always expect certificate.
-        let certificate_list = expect!(certificate);
-        // TODO: cert validation not implemented yet
+        let record_size_limits = try!(negotiate_record_size(&self.config, &server_hello_data));
+        {
+            let send_len = match record_size_limits.send {
+                Some(len) => cmp::min(len, self.config.fragment_len),
+                None => self.config.fragment_len,
+            };
+            self.writer.set_max_fragment_len(send_len);
+            let recv_len = record_size_limits.recv.unwrap_or(RECORD_MAX_LEN);
+            if !record_size_limits.protected_only {
+                self.reader.set_max_fragment_len(recv_len);
+            }
+        }
 
-        // we always use server key exchange
-        let server_key_ex_data = expect!(server_key_exchange);
+        let mut status_request_accepted = false;
+        let mut tls_scts = None;
+        if let Some(ref extensions) = server_hello_data.extensions {
+            for ext in extensions.iter() {
+                match *ext {
+                    handshake::Extension::server_name(Some(..)) => {
+                        return tls_err!(IllegalParameter, "non-empty server_name");
+                    }
+                    handshake::Extension::status_request(None) => status_request_accepted = true,
+                    handshake::Extension::status_request(Some(..)) => {
+                        return tls_err!(IllegalParameter, "non-empty status_request");
+                    }
+                    handshake::Extension::signed_certificate_timestamp(Some(ref list)) => {
+                        tls_scts = Some(list);
+                    }
+                    handshake::Extension::signed_certificate_timestamp(None) => {
+                        return tls_err!(DecodeError, "empty signed_certificate_timestamp");
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let point_formats = negotiate_point_formats(&self.config, &server_hello_data);
         let kex = cipher_suite.new_kex();
-        let (key_data, pre_master_secret) = try!(kex.compute_keys(&server_key_ex_data,
-                                                                  &mut self.rng));
+        let is_psk = kex.is_psk();
+        let is_static = kex.is_static();
 
-        expect!(server_hello_done);
+        // a message already read which belongs to the next step
+        let mut pending = None;
+
+        // PSK key exchanges have no Certificate (RFC 4279 2), and ServerKeyExchange is
+        // omitted if the server has no identity hint.
+        let (certificate_list, certificate_status, server_key_ex_data) = if is_psk {
+            match try!(self.read_handshake()) {
+                Handshake::server_key_exchange(data) => (None, None, Some(data)),
+                message @ Handshake::server_hello_done(..) => {
+                    pending = Some(message);
+                    (None, None, None)
+                }
+                _ => return tls_err!(UnexpectedMessage, "unexpected handshake message found"),
+            }
+        } else {
+            let certificate_list = expect!(certificate);
+
+            // CertificateStatus is only allowed if the server accepted `status_request`,
+            // and even then the server may omit it (RFC 6066 8).
+            let (certificate_status, next) = match try!(self.read_handshake()) {
+                Handshake::certificate_status(status) if status_request_accepted => {
+                    (Some(status), try!(self.read_handshake()))
+                }
+                message => (None, message),
+            };
+            // static RSA has no ServerKeyExchange. the others always have it.
+            let server_key_ex_data = match next {
+                Handshake::server_key_exchange(data) if !is_static => Some(data),
+                message @ Handshake::certificate_request(..) |
+                message @ Handshake::server_hello_done(..) if is_static => {
+                    pending = Some(message);
+                    None
+                }
+                _ => return tls_err!(UnexpectedMessage, "unexpected handshake message found"),
+            };
+            (Some(certificate_list), certificate_status, server_key_ex_data)
+        };
+        if let Some(ref certificate_list) = certificate_list {
+            let staple = certificate_status.as_ref().map(|status| &status.response[..]);
+            let now = unix_time();
+            let server_name = self.config.server_name.as_ref().map(|name| &name[..]);
+            let path = try!(self.config.verifier.verify_server_cert(certificate_list,
+                                                                    server_name,
+                                                                    now));
+            let path: Vec<&[u8]> = path.iter().map(|cert| &cert[..]).collect();
+            try!(check_ocsp_staple(&self.config.ocsp, &path, staple, now));
+            self.ocsp_response = staple.map(|staple| staple.to_vec());
+            if let Some(ref crl) = self.config.crl {
+                try!(check_crls(crl, &path, now));
+            }
+            if let Some(ref pins) = self.config.pins {
+                try!(check_pins(pins, &path));
+            }
+            if let Some(ref ct) = self.config.ct {
+                try!(check_scts(ct, &path, tls_scts, now));
+            }
+        }
+        // renegotiation must not switch the server identity,
+        // or the triple handshake attack becomes possible (RFC 7627 1).
+        let server_certificate = certificate_list.as_ref()
+                                                 .and_then(|list| list.first())
+                                                 .map(|cert| cert[..].to_vec());
+        if renegotiation_info.len() > 0 && server_certificate != self.server_certificate {
+            return tls_err!(BadCertificate, "server certificate changed during renegotiation");
+        }
+        self.server_certificate = server_certificate;
+        let (key_data, pre_master_secret) = {
+            let data = server_key_ex_data.as_ref().map_or(&[][..], |data| &data[..]);
+            self.psk_identity_hint = None;
+            match certificate_list {
+                None => {
+                    self.ocsp_response = None;
+                    self.psk_identity_hint = try!(psk::identity_hint(data));
+                    let psk = match self.config.psk {
+                        Some(ref psk) => psk,
+                        None => return tls_err!(InternalError, "no pre-shared key"),
+                    };
+                    try!(kex.compute_psk_keys(data, psk, &offered_groups, &point_formats,
+                                              &mut self.rng))
+                }
+                Some(ref certificate_list) => {
+                    let leaf = match certificate_list.first() {
+                        Some(leaf) => try!(x509::Certificate::parse(leaf)),
+                        None => return tls_err!(BadCertificate, "no server certificate"),
+                    };
+                    if is_static {
+                        try!(kex.compute_static_keys(&leaf,
+                                                     self.config.max_version,
+                                                     &mut self.rng))
+                    } else {
+                        try!(kex.verify_server_params(data,
+                                                      &cli_random,
+                                                      &server_hello_data.random[..],
+                                                      leaf.subject_public_key_info,
+                                                      &self.config.signature_algorithms));
+                        try!(kex.compute_keys(data,
+                                              &offered_groups,
+                                              &point_formats,
+                                              &mut self.rng))
+                    }
+                }
+            }
+        };
+
+        // server may request client authentication, except for PSK
+        let next = match pending {
+            Some(message) => message,
+            None => try!(self.read_handshake()),
+        };
+        let certificate_request = match next {
+            Handshake::certificate_request(request) if !is_psk => {
+                expect!(server_hello_done);
+                Some(request)
+            }
+            Handshake::server_hello_done(..) => None,
+            _ => return tls_err!(UnexpectedMessage, "unexpected handshake message found"),
+        };
+
+        // if no certificate matches, send an empty list and let the server decide
+        // (RFC 5246 7.4.6).
+        let (client_certificate, signer) = match certificate_request {
+            None => (None, None),
+            Some(ref request) => {
+                let certs = &self.config.client_certificates;
+                let offered = &self.config.signature_algorithms;
+                match try!(select_client_certificate(certs, offered, request)) {
+                    Some((i, algorithm)) => {
+                        let chain = certs[i].chain.clone();
+                        (Some(try!(Handshake::new_certificate(chain))), Some((i, algorithm)))
+                    }
+                    None => (Some(try!(Handshake::new_certificate(Vec::new()))), None),
+                }
+            }
+        };
+        if let Some(ref certificate) = client_certificate {
+            try!(self.writer.write_handshake(certificate));
+        }
 
         let client_key_exchange = try!(Handshake::new_client_key_exchange(key_data));
         try!(self.writer.write_handshake(&client_key_exchange));
 
+        let server_random = server_hello_data.random[..].to_vec();
+
+        // FIXME we should get "raw" packet data and hash them incrementally
+        let mut msgs = {
+            let mut msgs = Vec::new();
+            try!(client_hello.tls_write(&mut msgs));
+            try!(Handshake::server_hello(server_hello_data).tls_write(&mut msgs));
+            if let Some(certificate_list) = certificate_list {
+                try!(Handshake::certificate(certificate_list).tls_write(&mut msgs));
+            }
+            if let Some(status) = certificate_status {
+                try!(Handshake::certificate_status(status).tls_write(&mut msgs));
+            }
+            if let Some(server_key_ex_data) = server_key_ex_data {
+                try!(Handshake::server_key_exchange(server_key_ex_data).tls_write(&mut msgs));
+            }
+            if let Some(request) = certificate_request {
+                try!(Handshake::certificate_request(request).tls_write(&mut msgs));
+            }
+            try!(Handshake::server_hello_done(DummyItem).tls_write(&mut msgs));
+            if let Some(ref certificate) = client_certificate {
+                try!(certificate.tls_write(&mut msgs));
+            }
+            try!(client_key_exchange.tls_write(&mut msgs));
+            msgs
+        };
+
+        // CertificateVerify signs all handshake messages so far (RFC 5246 7.4.8)
+        if let Some((i, algorithm)) = signer {
+            let signature = try!(self.config.client_certificates[i].key.sign(algorithm, &msgs));
+            let certificate_verify = try!(Handshake::new_certificate_verify(algorithm, signature));
+            try!(self.writer.write_handshake(&certificate_verify));
+            try!(certificate_verify.tls_write(&mut msgs));
+        }
+
         try!(self.writer.write_change_cipher_spec());
 
         // SECRET
         let master_secret = {
             let mut label_seed = b"master secret".to_vec();
             label_seed.extend(&cli_random);
-            label_seed.extend(&server_hello_data.random[..]);
+            label_seed.extend(&server_random);
 
             let mut prf = Prf::new(pre_master_secret, label_seed);
             prf.get_bytes(48)
         };
 
-        let aead = cipher_suite.new_aead();
-
         // SECRET
         let read_key = {
             let mut label_seed = b"key expansion".to_vec();
-            label_seed.extend(&server_hello_data.random[..]);
+            label_seed.extend(&server_random);
             label_seed.extend(&cli_random);
 
             let mut prf = Prf::new(master_secret.clone(), label_seed);
 
-            // mac_key is not used in AEAD configuration.
+            // key block is mac keys, enc keys, then ivs (RFC 5246 6.3).
+            // AEAD ciphers have no mac key, and chacha20-poly1305 does not use iv.
+            let mac_key_size = aead.mac_key_size();
+            let enc_key_size = aead.key_size();
+            let iv_len = aead.fixed_iv_len();
 
-            let enc_key_length = aead.key_size();
+            let client_mac_key = prf.get_bytes(mac_key_size);
+            let server_mac_key = prf.get_bytes(mac_key_size);
+            let client_enc_key = prf.get_bytes(enc_key_size);
+            let server_enc_key = prf.get_bytes(enc_key_size);
+            let client_iv = prf.get_bytes(iv_len);
+            let server_iv = prf.get_bytes(iv_len);
 
-            let write_key = prf.get_bytes(enc_key_length);
-            let encryptor = aead.new_encryptor(write_key);
+            let mut write_key = client_mac_key;
+            write_key.extend(&client_enc_key);
+            write_key.extend(&client_iv);
+            let encryptor = try!(aead.new_encryptor(write_key));
             self.writer.set_encryptor(encryptor);
 
             // this will be set after receiving ChangeCipherSpec.
-            let read_key = prf.get_bytes(enc_key_length);
-
-            // chacha20-poly1305 does not use iv.
-
+            let mut read_key = server_mac_key;
+            read_key.extend(&server_enc_key);
+            read_key.extend(&server_iv);
             read_key
         };
 
-        // FIXME we should get "raw" packet data and hash them incrementally
-        let msgs = {
-            let mut msgs = Vec::new();
-            try!(client_hello.tls_write(&mut msgs));
-            try!(Handshake::server_hello(server_hello_data).tls_write(&mut msgs));
-            try!(Handshake::certificate(certificate_list).tls_write(&mut msgs));
-            try!(Handshake::server_key_exchange(server_key_ex_data).tls_write(&mut msgs));
-            try!(Handshake::server_hello_done(DummyItem).tls_write(&mut msgs));
-            try!(client_key_exchange.tls_write(&mut msgs));
-            msgs
-        };
-
         // this only verifies Handshake messages! what about others?
-        // ApplicationData messages are not permitted until now.
-        // ChangeCipherSpec messages are only permitted after ClinetKeyExchange.
+        // ApplicationData messages are only permitted during renegotiation, and are
+        // buffered by `read_handshake`.
+        // ChangeCipherSpec messages are only permitted after ClientKeyExchange.
         // Alert messages can be problematic - they are not verified and
         // can be broken into several records. This leads to alert attack.
-        // since we don't accept strange alerts, all "normal" alert messages are
-        // treated as error, so now we can assert that we haven't received alerts.
+        // partial alerts, fatal alerts and close_notify abort the handshake.
+        // other warnings are skipped by `TlsReader`, up to `max_warning_alerts` in a row.
         let verify_hash = sha256(&msgs);
 
         let client_verify_data = {
@@ -191,7 +1214,7 @@ impl<R: Read, W: Write> TlsClient<R, W> {
             let mut prf = Prf::new(master_secret.clone(), label_seed);
             prf.get_bytes(cipher_suite.verify_data_len())
         };
-        let finished = try!(Handshake::new_finished(client_verify_data));
+        let finished = try!(Handshake::new_finished(client_verify_data.clone()));
         try!(self.writer.write_handshake(&finished));
 
         // Although client->server is encrypted, server->client isn't yet.
@@ -199,7 +1222,10 @@ impl<R: Read, W: Write> TlsClient<R, W> {
         try!(self.reader.read_change_cipher_spec());
 
         // from now server starts encryption.
-        self.reader.set_decryptor(aead.new_decryptor(read_key));
+        self.reader.set_decryptor(try!(aead.new_decryptor(read_key)));
+        if record_size_limits.protected_only {
+            self.reader.set_max_fragment_len(record_size_limits.recv.unwrap_or(RECORD_MAX_LEN));
+        }
 
         let server_finished = expect!(finished);
         {
@@ -230,14 +1256,49 @@ impl<R: Read, W: Write> TlsClient<R, W> {
             }
         }
 
+        self.client_verify_data = client_verify_data;
+        self.server_verify_data = server_finished.to_vec();
+
         Ok(())
     }
 
+    /// Read an application data record.
+    /// Returns an empty vector at the end of stream, i.e. after `close_notify`.
+    /// HelloRequest is answered according to `ClientConfig::renegotiation`.
+    pub fn read_application_data(&mut self) -> TlsResult<Vec<u8>> {
+        loop {
+            // data received during renegotiation comes first
+            if self.buf.len() > 0 {
+                return Ok(mem::replace(&mut self.buf, Vec::new()));
+            }
+            match try!(self.reader.read_message()) {
+                // empty records are allowed and carry nothing
+                ApplicationDataMessage(ref data) if data.len() == 0 => {}
+                ApplicationDataMessage(data) => return Ok(data),
+                // only close_notify comes here: end of stream
+                AlertMessage(..) => return Ok(Vec::new()),
+                HandshakeMessage(Handshake::hello_request(..)) => {
+                    if self.config.renegotiation == RenegotiationPolicy::Secure &&
+                       self.secure_renegotiation {
+                        try!(self.handshake());
+                    } else {
+                        let alert = alert::Alert::new_warning(
+                            alert::AlertDescription::no_renegotiation);
+                        try!(self.writer.write_alert(&alert));
+                    }
+                }
+                HandshakeMessage(..) => {
+                    return tls_err!(UnexpectedMessage, "unexpected Handshake message");
+                }
+                ChangeCipherSpecMessage => {
+                    return tls_err!(UnexpectedMessage, "unexpected ChangeCipherSpec");
+                }
+            }
+        }
+    }
+
     pub fn close(&mut self) -> TlsResult<()> {
-        let alert_data = alert::Alert {
-            level: alert::AlertLevel::fatal,
-            description: alert::AlertDescription::close_notify,
-        };
+        let alert_data = alert::Alert::new_warning(alert::AlertDescription::close_notify);
         try!(self.writer.write_alert(&alert_data));
         Ok(())
     }
@@ -246,7 +1307,10 @@ impl<R: Read, W: Write> TlsClient<R, W> {
     // (it may be different to `err`, because writing alert can fail)
     pub fn send_tls_alert(&mut self, err: TlsError) -> TlsError {
         match err.kind {
-            TlsErrorKind::IoFailure => return err,
+            // the peer has already closed the connection
+            TlsErrorKind::IoFailure |
+            TlsErrorKind::AlertReceived |
+            TlsErrorKind::InappropriateFallback => return err,
             _ => {
                 let alert = alert::Alert::from_tls_err(&err);
                 let result = self.writer.write_alert(&alert);
@@ -260,7 +1324,16 @@ impl<R: Read, W: Write> TlsClient<R, W> {
 }
 
 impl TlsClient<TcpStream, TcpStream> {
+    /// Uses `ClientConfig::new()`, whose verifier trusts no CA, so every server
+    /// certificate is rejected.
+    #[deprecated(note = "the default verifier has no trust anchors; use `from_tcp_with_config`")]
     pub fn from_tcp(stream: TcpStream) -> TlsResult<TlsClient<TcpStream, TcpStream>> {
+        TlsClient::from_tcp_with_config(stream, ClientConfig::new())
+    }
+
+    pub fn from_tcp_with_config(stream: TcpStream,
+                                config: ClientConfig)
+                                -> TlsResult<TlsClient<TcpStream, TcpStream>> {
         let rng = match OsRng::new() {
             Ok(rng) => rng,
             Err(..) => return tls_err!(InternalError, "failed to create OsRng"),
@@ -268,7 +1341,7 @@ impl TlsClient<TcpStream, TcpStream> {
 
         let reader = try!(stream.try_clone());
         let writer = stream;
-        TlsClient::new(reader, writer, rng)
+        TlsClient::with_config(reader, writer, rng, config)
     }
 }
 
@@ -312,9 +1385,12 @@ impl<R: Read, W: Write> Read for TlsClient<R, W> {
         while pos < len {
             let remaining = len - pos;
             if self.buf.len() == 0 {
-                let data = match self.reader.read_application_data() {
+                let data = match self.read_application_data() {
+                    // end of stream
+                    Ok(ref data) if data.len() == 0 => break,
                     Ok(data) => data,
-                    Err(_err) => {
+                    Err(err) => {
+                        self.send_tls_alert(err);
                         break; // FIXME: stop if EOF. otherwise raise error?
                     }
                 };
@@ -332,3 +1408,913 @@ impl<R: Read, W: Write> Read for TlsClient<R, W> {
         Ok(pos)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::io;
+    use std::io::prelude::*;
+    use std::io::Cursor;
+    use std::cmp;
+    use std::sync::mpsc::{channel, Sender, Receiver};
+    use rand::OsRng;
+
+    use test::null_tls;
+    use tls_item::{TlsItem, DummyItem};
+    use handshake::{Extension, ExtensionVec, ServerHello, ProtocolVersion, Random, SessionId,
+                    CompressionMethod, Handshake};
+    use cipher::CipherSuite;
+    use tls::{TlsReader, TlsWriter};
+    use handshake::{MaxFragmentLength, ECPointFormat};
+    use super::{TlsClient, ClientConfig, RenegotiationPolicy, RecordSizeLimits,
+                check_renegotiation_info, check_server_extensions, negotiate_record_size,
+                negotiate_encrypt_then_mac, negotiate_point_formats, check_downgrade_sentinel,
+                ClientCertificate, select_client_certificate, OcspPolicy, check_ocsp_staple,
+                CtConfig, check_scts, CrlPolicy, check_crls, PinPolicy, check_pins};
+    use handshake::{CertificateRequest, CertificiateTypeVec, ClientCertificateType,
+                    DistinguishedName, DistinguishedNameVec};
+    use signature::{SigningKey, SignatureAndHashAlgorithm, SignatureAndHashAlgorithmVec,
+                    HashAlgorithm, SignatureAlgorithm, SUPPORTED_ALGORITHMS};
+    use tls_result::TlsResult;
+    use tls_result::TlsErrorKind::{InternalError, IllegalParameter};
+    use x509;
+    use pem;
+
+    fn server_hello(extensions: Vec<Extension>) -> ServerHello {
+        ServerHello {
+            server_version: ProtocolVersion { major: 3, minor: 3 },
+            random: Random::new(vec![0; 32]).unwrap(),
+            session_id: SessionId::new(Vec::new()).unwrap(),
+            cipher_suite: CipherSuite::TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256,
+            compression_method: CompressionMethod::null,
+            extensions: Some(ExtensionVec::new(extensions).unwrap()),
+        }
+    }
+
+    #[test]
+    fn test_check_renegotiation_info() {
+        let empty = Extension::new_renegotiation_info(Vec::new()).unwrap();
+        assert_eq!(check_renegotiation_info(&server_hello(vec![empty]), &[]).unwrap(), true);
+        assert_eq!(check_renegotiation_info(&server_hello(vec![]), &[]).unwrap(), false);
+
+        let verify_data = [1u8; 24];
+        let ok = Extension::new_renegotiation_info(verify_data.to_vec()).unwrap();
+        assert!(check_renegotiation_info(&server_hello(vec![ok]), &verify_data).is_ok());
+
+        let bad = Extension::new_renegotiation_info(vec![2u8; 24]).unwrap();
+        assert!(check_renegotiation_info(&server_hello(vec![bad]), &verify_data).is_err());
+        assert!(check_renegotiation_info(&server_hello(vec![]), &verify_data).is_err());
+
+        // initial handshake with non-empty renegotiation_info
+        let bad = Extension::new_renegotiation_info(vec![2u8; 24]).unwrap();
+        assert!(check_renegotiation_info(&server_hello(vec![bad]), &[]).is_err());
+    }
+
+    #[test]
+    fn test_check_server_extensions() {
+        let limit = Extension::new_record_size_limit(1000).unwrap();
+        assert!(check_server_extensions(&server_hello(vec![limit]), &[28]).is_ok());
+        let limit = Extension::new_record_size_limit(1000).unwrap();
+        assert!(check_server_extensions(&server_hello(vec![limit]), &[1]).is_err());
+
+        let dup = vec![Extension::new_record_size_limit(1000).unwrap(),
+                       Extension::new_record_size_limit(1000).unwrap()];
+        assert!(check_server_extensions(&server_hello(dup), &[28]).is_err());
+    }
+
+    #[test]
+    fn test_negotiate_encrypt_then_mac() {
+        let etm = Extension::encrypt_then_mac(DummyItem);
+
+        // record length of empty data tells which one is used:
+        // IV || enc(mac || padding) or IV || enc(padding) || mac
+        let cbc = CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA;
+        let ad = [0u8; 13];
+        let aead = negotiate_encrypt_then_mac(cbc.new_aead(), &server_hello(vec![])).unwrap();
+        let mut encryptor = aead.new_encryptor(vec![0; 36]).unwrap();
+        assert_eq!(encryptor.encrypt(&[], &[], &ad).len(), 16 + 32);
+
+        let aead = negotiate_encrypt_then_mac(cbc.new_aead(), &server_hello(vec![etm])).unwrap();
+        let mut encryptor = aead.new_encryptor(vec![0; 36]).unwrap();
+        assert_eq!(encryptor.encrypt(&[], &[], &ad).len(), 16 + 16 + 20);
+
+        let etm = Extension::encrypt_then_mac(DummyItem);
+        let chacha = CipherSuite::TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256;
+        assert!(negotiate_encrypt_then_mac(chacha.new_aead(), &server_hello(vec![])).is_ok());
+        assert!(negotiate_encrypt_then_mac(chacha.new_aead(), &server_hello(vec![etm])).is_err());
+    }
+
+    #[test]
+    fn test_negotiate_point_formats() {
+        let uncompressed = vec![ECPointFormat::uncompressed];
+        let both = vec![ECPointFormat::uncompressed, ECPointFormat::ansiX962_compressed_prime];
+        let formats = |config: &ClientConfig, server_formats: Option<Vec<ECPointFormat>>| {
+            let extensions = server_formats.map_or(vec![], |list| {
+                vec![Extension::new_ec_point_formats(list).unwrap()]
+            });
+            negotiate_point_formats(config, &server_hello(extensions))
+        };
+
+        let mut config = ClientConfig::new();
+        assert!(formats(&config, Some(both.clone())) == uncompressed);
+
+        config.ec_point_compression = true;
+        assert!(formats(&config, Some(both.clone())) == both);
+        assert!(formats(&config, Some(uncompressed.clone())) == uncompressed);
+        assert!(formats(&config, None) == uncompressed);
+    }
+
+    #[test]
+    fn test_negotiate_record_size() {
+        let mut config = ClientConfig::new();
+        config.max_fragment_length = Some(MaxFragmentLength::length_1024);
+        config.record_size_limit = Some(2000);
+
+        let limits = negotiate_record_size(&config, &server_hello(vec![])).unwrap();
+        assert_eq!(limits, RecordSizeLimits { send: None, recv: None, protected_only: false });
+
+        let ext = Extension::new_max_fragment_length(MaxFragmentLength::length_1024).unwrap();
+        let limits = negotiate_record_size(&config, &server_hello(vec![ext])).unwrap();
+        assert_eq!(limits,
+                   RecordSizeLimits { send: Some(1024), recv: Some(1024), protected_only: false });
+
+        let ext = Extension::new_record_size_limit(100).unwrap();
+        let limits = negotiate_record_size(&config, &server_hello(vec![ext])).unwrap();
+        assert_eq!(limits,
+                   RecordSizeLimits { send: Some(100), recv: Some(2000), protected_only: true });
+
+        // server must echo the same value
+        let ext = Extension::new_max_fragment_length(MaxFragmentLength::length_512).unwrap();
+        assert!(negotiate_record_size(&config, &server_hello(vec![ext])).is_err());
+
+        let ext = Extension::new_record_size_limit(63).unwrap();
+        assert!(negotiate_record_size(&config, &server_hello(vec![ext])).is_err());
+
+        let both = vec![Extension::new_max_fragment_length(MaxFragmentLength::length_1024).unwrap(),
+                        Extension::new_record_size_limit(100).unwrap()];
+        assert!(negotiate_record_size(&config, &server_hello(both)).is_err());
+    }
+
+    #[test]
+    fn test_refuse_hello_request() {
+        // HelloRequest, then ApplicationData "hi"
+        let input = vec![0x16, 0x03, 0x03, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00,
+                         0x17, 0x03, 0x03, 0x00, 0x02, b'h', b'i'];
+        let mut output = Vec::new();
+        {
+            let (reader, writer) = null_tls(Cursor::new(input), &mut output);
+            let mut config = ClientConfig::new();
+            config.renegotiation = RenegotiationPolicy::Secure;
+            let mut client = TlsClient::from_parts(reader, writer, OsRng::new().unwrap(), config);
+            // after the first handshake with a server which does not support RFC 5746
+            client.client_verify_data = vec![0; 12];
+            client.server_verify_data = vec![0; 12];
+            let mut buf = [0u8; 2];
+            client.read(&mut buf).unwrap();
+            assert_eq!(&buf, b"hi");
+        }
+
+        // warning(no_renegotiation)
+        assert_eq!(output, vec![0x15, 0x03, 0x03, 0x00, 0x02, 0x01, 100]);
+    }
+
+    #[test]
+    fn test_close_notify() {
+        // ApplicationData "hi", an empty ApplicationData, then warning(close_notify)
+        let input = vec![0x17, 0x03, 0x03, 0x00, 0x02, b'h', b'i',
+                         0x17, 0x03, 0x03, 0x00, 0x00,
+                         0x15, 0x03, 0x03, 0x00, 0x02, 0x01, 0x00];
+        let (reader, writer) = null_tls(Cursor::new(input.clone()), Vec::new());
+        let mut client = TlsClient::from_parts(reader, writer, OsRng::new().unwrap(),
+                                               ClientConfig::new());
+        assert_eq!(client.read_application_data().unwrap(), b"hi");
+        assert_eq!(client.read_application_data().unwrap(), b"");
+
+        let (reader, writer) = null_tls(Cursor::new(input), Vec::new());
+        let mut client = TlsClient::from_parts(reader, writer, OsRng::new().unwrap(),
+                                               ClientConfig::new());
+        let mut data = Vec::new();
+        client.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"hi");
+    }
+
+    // one direction of an in-memory connection to a server thread
+    struct PipeReader {
+        rx: Receiver<Vec<u8>>,
+        buf: Vec<u8>,
+    }
+
+    struct PipeWriter(Sender<Vec<u8>>);
+
+    fn pipe() -> (PipeReader, PipeWriter) {
+        let (tx, rx) = channel();
+        (PipeReader { rx: rx, buf: Vec::new() }, PipeWriter(tx))
+    }
+
+    impl Read for PipeReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            while self.buf.len() == 0 {
+                match self.rx.recv() {
+                    Ok(data) => self.buf = data,
+                    // the other end is gone
+                    Err(..) => return Ok(0),
+                }
+            }
+            let len = cmp::min(buf.len(), self.buf.len());
+            buf[..len].copy_from_slice(&self.buf[..len]);
+            self.buf.drain(..len);
+            Ok(len)
+        }
+    }
+
+    impl Write for PipeWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            match self.0.send(buf.to_vec()) {
+                Ok(()) => Ok(buf.len()),
+                Err(..) => Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed")),
+            }
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    const PSK_KEY: &'static [u8] = &[0x42; 32];
+
+    // the server side of a TLS_PSK_WITH_CHACHA20_POLY1305_SHA256 handshake.
+    // `verify_data` holds both verify_data of the last handshake, and is updated.
+    // `interleaved` is sent with the old keys right after ClientHello.
+    fn serve_psk_handshake(reader: &mut TlsReader<PipeReader>,
+                           writer: &mut TlsWriter<PipeWriter>,
+                           verify_data: &mut Vec<u8>,
+                           interleaved: Option<&[u8]>) {
+        use cipher::prf::Prf;
+        use crypto::sha2::sha256;
+
+        let prf = |secret: &[u8], label: &[u8], seed: &[u8], len: usize| {
+            let mut label_seed = label.to_vec();
+            label_seed.extend(seed);
+            Prf::new(secret.to_vec(), label_seed).get_bytes(len)
+        };
+
+        let mut msgs = Vec::new();
+        let client_hello = reader.read_handshake().unwrap();
+        client_hello.tls_write(&mut msgs).unwrap();
+        let cli_random = match client_hello {
+            Handshake::client_hello(ref hello) => hello.random[..].to_vec(),
+            _ => panic!("expected ClientHello"),
+        };
+        if let Some(data) = interleaved {
+            writer.write_application_data(data).unwrap();
+        }
+
+        let suite = CipherSuite::TLS_PSK_WITH_CHACHA20_POLY1305_SHA256;
+        let mut hello = server_hello(vec![
+            Extension::new_renegotiation_info(verify_data.clone()).unwrap()
+        ]);
+        hello.cipher_suite = suite;
+        let serv_random = hello.random[..].to_vec();
+        for message in vec![Handshake::server_hello(hello),
+                            Handshake::server_hello_done(DummyItem)] {
+            writer.write_handshake(&message).unwrap();
+            message.tls_write(&mut msgs).unwrap();
+        }
+
+        let client_key_exchange = reader.read_handshake().unwrap();
+        client_key_exchange.tls_write(&mut msgs).unwrap();
+
+        // RFC 4279 2: an all-zero `other_secret` as long as the key
+        let mut pre_master_secret = vec![0, PSK_KEY.len() as u8];
+        pre_master_secret.extend(&[0; 32]);
+        pre_master_secret.extend(&[0, PSK_KEY.len() as u8]);
+        pre_master_secret.extend(PSK_KEY);
+        let mut seed = cli_random.clone();
+        seed.extend(&serv_random);
+        let master_secret = prf(&pre_master_secret, b"master secret", &seed, 48);
+
+        let aead = suite.new_aead();
+        let (key_len, iv_len) = (aead.key_size(), aead.fixed_iv_len());
+        let mut seed = serv_random;
+        seed.extend(&cli_random);
+        let key_block = prf(&master_secret, b"key expansion", &seed, 2 * (key_len + iv_len));
+        let mut client_key = key_block[..key_len].to_vec();
+        client_key.extend(&key_block[2 * key_len..2 * key_len + iv_len]);
+        let mut server_key = key_block[key_len..2 * key_len].to_vec();
+        server_key.extend(&key_block[2 * key_len + iv_len..]);
+
+        reader.read_change_cipher_spec().unwrap();
+        reader.set_decryptor(aead.new_decryptor(client_key).unwrap());
+        let expected = prf(&master_secret, b"client finished", &sha256(&msgs), 12);
+        let finished = reader.read_handshake().unwrap();
+        let client_verify_data = match finished {
+            Handshake::finished(ref data) => data[..].to_vec(),
+            _ => panic!("expected Finished"),
+        };
+        assert_eq!(client_verify_data, expected);
+        finished.tls_write(&mut msgs).unwrap();
+
+        writer.write_change_cipher_spec().unwrap();
+        writer.set_encryptor(aead.new_encryptor(server_key).unwrap());
+        let server_verify_data = prf(&master_secret, b"server finished", &sha256(&msgs), 12);
+        let finished = Handshake::new_finished(server_verify_data.clone()).unwrap();
+        writer.write_handshake(&finished).unwrap();
+
+        *verify_data = client_verify_data;
+        verify_data.extend(&server_verify_data);
+    }
+
+    #[test]
+    fn test_secure_renegotiation() {
+        use std::thread;
+        use cipher::psk::PresharedKey;
+
+        let (client_reader, server_writer) = pipe();
+        let (server_reader, client_writer) = pipe();
+        let server = thread::spawn(move || {
+            let mut reader = TlsReader::new(server_reader);
+            let mut writer = TlsWriter::new(server_writer);
+            let mut verify_data = Vec::new();
+            serve_psk_handshake(&mut reader, &mut writer, &mut verify_data, None);
+            writer.write_application_data(b"before").unwrap();
+            writer.write_handshake(&Handshake::hello_request(DummyItem)).unwrap();
+            serve_psk_handshake(&mut reader, &mut writer, &mut verify_data, Some(b"during"));
+            writer.write_application_data(b"after").unwrap();
+            assert_eq!(reader.read_application_data().unwrap(), b"ping");
+        });
+
+        let mut config = ClientConfig::new();
+        config.cipher_suites = vec![CipherSuite::TLS_PSK_WITH_CHACHA20_POLY1305_SHA256];
+        config.psk = Some(PresharedKey::new(b"client".to_vec(), PSK_KEY.to_vec()));
+        config.renegotiation = RenegotiationPolicy::Secure;
+        let rng = OsRng::new().unwrap();
+        let mut client = TlsClient::with_config(client_reader, client_writer, rng, config)
+                             .unwrap();
+        let first_verify_data = client.server_verify_data.clone();
+        assert_eq!(client.read_application_data().unwrap(), b"before");
+
+        // HelloRequest, then "during" is buffered while the client renegotiates
+        assert_eq!(client.read_application_data().unwrap(), b"during");
+        assert!(client.server_verify_data != first_verify_data);
+
+        // both directions use the new keys
+        assert_eq!(client.read_application_data().unwrap(), b"after");
+        client.write_all(b"ping").unwrap();
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_renegotiation_keeps_server_certificate() {
+        use handshake::{Asn1Cert, CertificateList};
+        use tls_item::ObscureData;
+        use tls_result::TlsErrorKind::BadCertificate;
+        use verify::danger::NoCertificateVerification;
+
+        static FIRST: &'static [u8] = include_bytes!("../testdata/test_server.der");
+        static OTHER: &'static [u8] = include_bytes!("../testdata/pki/leaf.der");
+
+        // HelloRequest and a renegotiation with another certificate, up to
+        // `ServerKeyExchange` which is read before the certificate is checked
+        let verify_data = vec![1; 24];
+        let (_, mut writer) = null_tls(Cursor::new(Vec::new()), Vec::new());
+        writer.write_handshake(&Handshake::hello_request(DummyItem)).unwrap();
+        let renegotiation_info = Extension::new_renegotiation_info(verify_data).unwrap();
+        writer.write_handshake(&Handshake::server_hello(server_hello(vec![renegotiation_info])))
+              .unwrap();
+        let chain = vec![Asn1Cert::new(OTHER.to_vec()).unwrap()];
+        let chain = CertificateList::new(chain).unwrap();
+        writer.write_handshake(&Handshake::certificate(chain)).unwrap();
+        let ske = ObscureData::new(Vec::new());
+        writer.write_handshake(&Handshake::server_key_exchange(ske)).unwrap();
+        let input = writer.get_mut().clone();
+
+        let (reader, writer) = null_tls(Cursor::new(input), Vec::new());
+        let mut config = ClientConfig::new();
+        config.verifier = Box::new(NoCertificateVerification);
+        config.renegotiation = RenegotiationPolicy::Secure;
+        let mut client = TlsClient::from_parts(reader, writer, OsRng::new().unwrap(), config);
+        // after the first handshake with FIRST
+        client.secure_renegotiation = true;
+        client.client_verify_data = vec![1; 12];
+        client.server_verify_data = vec![1; 12];
+        client.server_certificate = Some(FIRST.to_vec());
+        assert_eq!(client.read_application_data().unwrap_err().kind, BadCertificate);
+    }
+
+    #[test]
+    fn test_scsv() {
+        let mut data = Vec::new();
+        CipherSuite::TLS_EMPTY_RENEGOTIATION_INFO_SCSV.tls_write(&mut data).unwrap();
+        assert_eq!(data, vec![0x00, 0xff]);
+        let suite: CipherSuite = TlsItem::tls_read(&mut &data[..]).unwrap();
+        assert_eq!(suite, CipherSuite::TLS_EMPTY_RENEGOTIATION_INFO_SCSV);
+        assert!(suite.is_signaling());
+
+        let mut data = Vec::new();
+        CipherSuite::TLS_FALLBACK_SCSV.tls_write(&mut data).unwrap();
+        assert_eq!(data, vec![0x56, 0x00]);
+    }
+
+    #[test]
+    fn test_config_key_exchanges() {
+        fn connect(config: ClientConfig) -> TlsResult<()> {
+            let rng = OsRng::new().unwrap();
+            TlsClient::with_config(&b""[..], Vec::new(), rng, config).map(|_| ())
+        }
+
+        // PSK cipher suites need a key
+        let mut config = ClientConfig::new();
+        config.cipher_suites = vec![CipherSuite::TLS_PSK_WITH_CHACHA20_POLY1305_SHA256];
+        assert_eq!(connect(config).unwrap_err().kind, InternalError);
+
+        // CBC cipher suites are opt-in
+        let mut config = ClientConfig::new();
+        config.cipher_suites.push(CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA);
+        assert_eq!(connect(config).unwrap_err().kind, InternalError);
+        let mut config = ClientConfig::new();
+        config.cipher_suites.push(CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA);
+        config.cbc_cipher_suites = true;
+        assert!(connect(config).unwrap_err().kind != InternalError);
+
+        // static RSA is opt-in
+        let mut config = ClientConfig::new();
+        config.cipher_suites.push(CipherSuite::TLS_RSA_WITH_AES_128_GCM_SHA256);
+        assert_eq!(connect(config).unwrap_err().kind, InternalError);
+
+        // SHA-1 signatures are not supported
+        let mut config = ClientConfig::new();
+        config.signature_algorithms.push(SignatureAndHashAlgorithm {
+            hash: HashAlgorithm::sha1,
+            signature: SignatureAlgorithm::rsa,
+        });
+        assert_eq!(connect(config).unwrap_err().kind, InternalError);
+
+        let mut config = ClientConfig::new();
+        config.signature_algorithms.clear();
+        assert_eq!(connect(config).unwrap_err().kind, InternalError);
+
+        // configuration is fine, but there is no server
+        let mut config = ClientConfig::new();
+        config.cipher_suites.push(CipherSuite::TLS_RSA_WITH_AES_128_GCM_SHA256);
+        config.rsa_key_exchange = true;
+        assert!(connect(config).unwrap_err().kind != InternalError);
+    }
+
+    #[test]
+    fn test_hybrid_key_exchange() {
+        use handshake::{Handshake, NamedCurve};
+        use tls::TlsReader;
+
+        fn offered_groups(config: ClientConfig) -> Vec<NamedCurve> {
+            let mut output = Vec::new();
+            let rng = OsRng::new().unwrap();
+            assert!(TlsClient::with_config(&b""[..], &mut output, rng, config).is_err());
+            let mut reader = TlsReader::new(Cursor::new(output));
+            let hello = match reader.read_handshake().unwrap() {
+                Handshake::client_hello(hello) => hello,
+                _ => panic!("not ClientHello"),
+            };
+            let extensions = hello.extensions.unwrap();
+            for extension in extensions.iter() {
+                if let Extension::elliptic_curves(ref list) = *extension {
+                    return list.to_vec();
+                }
+            }
+            panic!("no elliptic_curves");
+        }
+
+        let default = vec![NamedCurve::secp256r1, NamedCurve::secp384r1];
+        assert_eq!(offered_groups(ClientConfig::new()), default);
+
+        let mut config = ClientConfig::new();
+        config.hybrid_key_exchange = true;
+        assert_eq!(offered_groups(config),
+                   vec![NamedCurve::secp256r1, NamedCurve::secp384r1, NamedCurve::X25519MLKEM768]);
+    }
+
+    struct DummyKey(SignatureAlgorithm);
+
+    impl SigningKey for DummyKey {
+        fn algorithms(&self) -> Vec<SignatureAndHashAlgorithm> {
+            vec![SignatureAndHashAlgorithm { hash: HashAlgorithm::sha256, signature: self.0 }]
+        }
+
+        fn sign(&self, _: SignatureAndHashAlgorithm, _: &[u8]) -> TlsResult<Vec<u8>> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[test]
+    fn test_select_client_certificate() {
+        static CA: &'static [u8] = include_bytes!("../testdata/test_ca.der");
+        static CLIENT: &'static [u8] = include_bytes!("../testdata/test_client.der");
+
+        let certs = vec![
+            ClientCertificate {
+                chain: vec![CA.to_vec()],
+                key: Box::new(DummyKey(SignatureAlgorithm::rsa)),
+            },
+            ClientCertificate {
+                chain: vec![CLIENT.to_vec()],
+                key: Box::new(DummyKey(SignatureAlgorithm::ecdsa)),
+            },
+        ];
+
+        let request = |types: Vec<ClientCertificateType>, cas: Vec<Vec<u8>>| {
+            let algorithms = vec![
+                SignatureAndHashAlgorithm {
+                    hash: HashAlgorithm::sha256,
+                    signature: SignatureAlgorithm::rsa,
+                },
+                SignatureAndHashAlgorithm {
+                    hash: HashAlgorithm::sha256,
+                    signature: SignatureAlgorithm::ecdsa,
+                },
+            ];
+            let cas = cas.into_iter().map(|ca| DistinguishedName::new(ca).unwrap()).collect();
+            CertificateRequest {
+                certificate_types: CertificiateTypeVec::new(types).unwrap(),
+                supported_signature_algorithms: SignatureAndHashAlgorithmVec::new(algorithms)
+                                                    .unwrap(),
+                certificate_authorities: DistinguishedNameVec::new(cas).unwrap(),
+            }
+        };
+
+        let offered = SUPPORTED_ALGORITHMS;
+        let any = request(vec![ClientCertificateType::rsa_sign,
+                               ClientCertificateType::ecdsa_sign], vec![]);
+        let (i, algorithm) = select_client_certificate(&certs, offered, &any).unwrap().unwrap();
+        assert_eq!(i, 0);
+        assert_eq!(algorithm.signature, SignatureAlgorithm::rsa);
+
+        // the algorithm must also be in our own `signature_algorithms`
+        let ecdsa_sha256 = [SignatureAndHashAlgorithm {
+            hash: HashAlgorithm::sha256,
+            signature: SignatureAlgorithm::ecdsa,
+        }];
+        let (i, _) = select_client_certificate(&certs, &ecdsa_sha256, &any).unwrap().unwrap();
+        assert_eq!(i, 1);
+
+        let ecdsa_only = request(vec![ClientCertificateType::ecdsa_sign], vec![]);
+        let (i, algorithm) = select_client_certificate(&certs, offered, &ecdsa_only).unwrap().unwrap();
+        assert_eq!(i, 1);
+        assert_eq!(algorithm.signature, SignatureAlgorithm::ecdsa);
+
+        // self-signed CA is issued by itself, client certificate by CA
+        let ca_name = x509::Certificate::parse(CA).unwrap().subject.to_vec();
+        let by_ca = request(vec![ClientCertificateType::ecdsa_sign], vec![ca_name]);
+        let (i, _) = select_client_certificate(&certs, offered, &by_ca).unwrap().unwrap();
+        assert_eq!(i, 1);
+
+        let client_name = x509::Certificate::parse(CLIENT).unwrap().subject.to_vec();
+        let by_other = request(vec![ClientCertificateType::rsa_sign,
+                                    ClientCertificateType::ecdsa_sign], vec![client_name]);
+        assert!(select_client_certificate(&certs, offered, &by_other).unwrap().is_none());
+
+        let dss_only = request(vec![ClientCertificateType::dss_sign], vec![]);
+        assert!(select_client_certificate(&certs, offered, &dss_only).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_client_certificate_from_pem() {
+        static CLIENT: &'static [u8] = include_bytes!("../testdata/test_client.der");
+        static SERVER_CHAIN: &'static str = include_str!("../testdata/test_server_chain.pem");
+        static SERVER_KEY: &'static str = include_str!("../testdata/test_server.key");
+        static CLIENT_KEY: &'static str = include_str!("../testdata/test_client.key");
+
+        let client = pem::encode(pem::CERTIFICATE, CLIENT);
+        let cert = ClientCertificate::from_pem(&client, CLIENT_KEY).unwrap();
+        assert_eq!(cert.chain, vec![CLIENT.to_vec()]);
+        assert_eq!(cert.key.algorithms()[0].signature, SignatureAlgorithm::ecdsa);
+
+        let cert = ClientCertificate::from_pem(SERVER_CHAIN, SERVER_KEY).unwrap();
+        assert_eq!(cert.chain.len(), 2);
+
+        assert!(ClientCertificate::from_pem(&client, SERVER_KEY).is_err());
+        assert!(ClientCertificate::from_pem("", CLIENT_KEY).is_err());
+    }
+
+    #[test]
+    fn test_server_version() {
+        use handshake::Handshake;
+        use tls::TlsWriter;
+        use tls_result::TlsErrorKind;
+
+        // TLS 1.1 ServerHello, without and with the downgrade sentinel
+        let tests = [(vec![0u8; 8], TlsErrorKind::ProtocolVersion),
+                     (b"DOWNGRD\x00".to_vec(), IllegalParameter)];
+        for &(ref sentinel, kind) in tests.iter() {
+            let mut hello = server_hello(vec![]);
+            hello.server_version = ProtocolVersion { major: 3, minor: 2 };
+            let mut random = vec![0u8; 24];
+            random.extend(sentinel);
+            hello.random = Random::new(random).unwrap();
+            let mut writer = TlsWriter::new(Vec::new());
+            writer.write_handshake(&Handshake::server_hello(hello)).unwrap();
+            let input = writer.get_mut().clone();
+
+            let rng = OsRng::new().unwrap();
+            let result = TlsClient::with_config(Cursor::new(input), Vec::new(), rng,
+                                                ClientConfig::new());
+            assert_eq!(result.err().unwrap().kind, kind);
+        }
+
+        // lower versions are not implemented
+        let mut config = ClientConfig::new();
+        config.min_version = (3, 2);
+        let rng = OsRng::new().unwrap();
+        let result = TlsClient::with_config(&b""[..], Vec::new(), rng, config);
+        assert_eq!(result.err().unwrap().kind, InternalError);
+    }
+
+    #[test]
+    fn test_check_downgrade_sentinel() {
+        let mut random = vec![0u8; 32];
+        assert!(check_downgrade_sentinel((3, 3), &random, (3, 3)).is_ok());
+        assert!(check_downgrade_sentinel((3, 1), &random, (3, 3)).is_ok());
+
+        // only a client offering TLS 1.3 can tell
+        random[24..].copy_from_slice(b"DOWNGRD\x01");
+        assert!(check_downgrade_sentinel((3, 3), &random, (3, 3)).is_ok());
+        let err = check_downgrade_sentinel((3, 3), &random, (3, 4)).unwrap_err();
+        assert_eq!(err.kind, IllegalParameter);
+
+        random[24..].copy_from_slice(b"DOWNGRD\x00");
+        assert!(check_downgrade_sentinel((3, 3), &random, (3, 3)).is_ok());
+        assert!(check_downgrade_sentinel((3, 3), &random, (3, 4)).is_ok());
+        let err = check_downgrade_sentinel((3, 2), &random, (3, 3)).unwrap_err();
+        assert_eq!(err.kind, IllegalParameter);
+    }
+
+    #[test]
+    fn test_check_ocsp_staple() {
+        use handshake::{Asn1Cert, CertificateList};
+        use tls_result::TlsErrorKind::{CertificateRevoked, BadCertificateStatusResponse};
+        use roots::RootStore;
+        use verify::{ServerCertVerifier, WebPkiVerifier};
+
+        static CA: &'static [u8] = include_bytes!("../testdata/test_ca.der");
+        static SERVER: &'static [u8] = include_bytes!("../testdata/test_server.der");
+        static MUST_STAPLE: &'static [u8] = include_bytes!("../testdata/test_must_staple.der");
+        static OCSP_GOOD: &'static [u8] = include_bytes!("../testdata/ocsp_good.der");
+        static OCSP_REVOKED: &'static [u8] = include_bytes!("../testdata/ocsp_revoked.der");
+        static OCSP_MUST_STAPLE: &'static [u8] =
+            include_bytes!("../testdata/ocsp_must_staple.der");
+
+        // an hour after the responses were produced
+        let now = 1792328292 + 3600;
+        let policy = OcspPolicy::new();
+        let lenient = OcspPolicy {
+            request: true,
+            fail_on_revoked: false,
+            enforce_must_staple: false,
+        };
+
+        assert!(check_ocsp_staple(&policy, &[SERVER, CA], Some(OCSP_GOOD), now).is_ok());
+        assert!(check_ocsp_staple(&policy, &[SERVER, CA], None, now).is_ok());
+        assert!(check_ocsp_staple(&policy, &[], None, now).is_ok());
+
+        let err = check_ocsp_staple(&policy, &[SERVER, CA], Some(OCSP_REVOKED), now).unwrap_err();
+        assert_eq!(err.kind, CertificateRevoked);
+        assert!(check_ocsp_staple(&lenient, &[SERVER, CA], Some(OCSP_REVOKED), now).is_ok());
+
+        // invalid responses always fail
+        let err = check_ocsp_staple(&lenient, &[SERVER], Some(OCSP_GOOD), now).unwrap_err();
+        assert_eq!(err.kind, BadCertificateStatusResponse);
+        let err = check_ocsp_staple(&lenient, &[SERVER, CA], Some(OCSP_MUST_STAPLE), now)
+                      .unwrap_err();
+        assert_eq!(err.kind, BadCertificateStatusResponse);
+
+        let chain = [MUST_STAPLE, CA];
+        assert!(check_ocsp_staple(&policy, &chain, Some(OCSP_MUST_STAPLE), now).is_ok());
+        let err = check_ocsp_staple(&policy, &chain, None, now).unwrap_err();
+        assert_eq!(err.kind, BadCertificateStatusResponse);
+        assert!(check_ocsp_staple(&lenient, &chain, None, now).is_ok());
+
+        // the server omits the CA, which the verifier adds to the path
+        let mut roots = RootStore::new();
+        roots.add(CA.to_vec()).unwrap();
+        let verifier = WebPkiVerifier::new(roots);
+        let chain = CertificateList::new(vec![Asn1Cert::new(SERVER.to_vec()).unwrap()]).unwrap();
+        let path = verifier.verify_server_cert(&chain, Some("localhost"), now).unwrap();
+        let path: Vec<&[u8]> = path.iter().map(|cert| &cert[..]).collect();
+        assert_eq!(path, vec![SERVER, CA]);
+        assert!(check_ocsp_staple(&policy, &path, Some(OCSP_GOOD), now).is_ok());
+    }
+
+    #[test]
+    fn test_check_scts() {
+        use ct::{Log, MinimumLogs};
+        use handshake::{Asn1Cert, CertificateList, SignedCertificateTimestampList};
+        use tls_result::TlsErrorKind::BadCertificate;
+        use roots::RootStore;
+        use verify::{ServerCertVerifier, WebPkiVerifier};
+
+        static CA: &'static [u8] = include_bytes!("../testdata/test_ca.der");
+        static SERVER: &'static [u8] = include_bytes!("../testdata/test_server.der");
+        static EMBEDDED: &'static [u8] = include_bytes!("../testdata/test_ct_embedded.der");
+        static SERVER_SCTS: &'static [u8] = include_bytes!("../testdata/sct_list_server.bin");
+        static LOG_EC: &'static [u8] = include_bytes!("../testdata/test_ct_log_ec.der");
+        static LOG_RSA: &'static [u8] = include_bytes!("../testdata/test_ct_log_rsa.der");
+
+        let now = 1792328292 + 3600;
+        let config = |n| CtConfig {
+            logs: vec![Log::new(LOG_EC.to_vec()), Log::new(LOG_RSA.to_vec())],
+            policy: Box::new(MinimumLogs(n)),
+        };
+        let list: SignedCertificateTimestampList =
+            TlsItem::tls_read(&mut &SERVER_SCTS[..]).unwrap();
+
+        assert!(check_scts(&config(1), &[SERVER], Some(&list), now).is_ok());
+        assert!(check_scts(&config(2), &[EMBEDDED, CA], None, now).is_ok());
+        // embedded SCTs and the TLS extension count together
+        assert!(check_scts(&config(3), &[EMBEDDED, CA], Some(&list), now).is_err());
+
+        let err = check_scts(&config(2), &[SERVER, CA], Some(&list), now).unwrap_err();
+        assert_eq!(err.kind, BadCertificate);
+        // the issuer is needed for embedded SCTs
+        assert!(check_scts(&config(1), &[EMBEDDED], None, now).is_err());
+        assert!(check_scts(&config(0), &[], None, now).is_err());
+
+        // the server omits the CA, which the verifier adds to the path
+        let mut roots = RootStore::new();
+        roots.add(CA.to_vec()).unwrap();
+        let verifier = WebPkiVerifier::new(roots);
+        let chain = CertificateList::new(vec![Asn1Cert::new(EMBEDDED.to_vec()).unwrap()])
+                        .unwrap();
+        let path = verifier.verify_server_cert(&chain, Some("localhost"), now).unwrap();
+        let path: Vec<&[u8]> = path.iter().map(|cert| &cert[..]).collect();
+        assert_eq!(path, vec![EMBEDDED, CA]);
+        assert!(check_scts(&config(2), &path, None, now).is_ok());
+    }
+
+    #[test]
+    fn test_check_crls() {
+        use tls_result::TlsErrorKind::{CertificateRevoked, CertificateUnknown};
+
+        static CA: &'static [u8] = include_bytes!("../testdata/test_ca.der");
+        static SERVER: &'static [u8] = include_bytes!("../testdata/test_server.der");
+        static MUST_STAPLE: &'static [u8] = include_bytes!("../testdata/test_must_staple.der");
+        static LEAF: &'static [u8] = include_bytes!("../testdata/pki/leaf.der");
+        static INTER: &'static [u8] = include_bytes!("../testdata/pki/inter.der");
+        static CRL_EMPTY: &'static [u8] = include_bytes!("../testdata/crl_empty.der");
+        static CRL_REVOKED: &'static [u8] = include_bytes!("../testdata/crl_revoked.der");
+
+        let now = 1792328873 + 3600;
+        let mut policy = CrlPolicy::new();
+        policy.add_file("testdata/crl_revoked.der").unwrap();
+        assert_eq!(&policy.crls[0][..], CRL_REVOKED);
+
+        let err = check_crls(&policy, &[SERVER, CA], now).unwrap_err();
+        assert_eq!(err.kind, CertificateRevoked);
+        assert!(check_crls(&policy, &[MUST_STAPLE, CA], now).is_ok());
+        // soft-fail: no CRL of the intermediate, or the CRL is stale
+        assert!(check_crls(&policy, &[LEAF, INTER, CA], now).is_ok());
+        assert!(check_crls(&policy, &[SERVER, CA], 5000000000).is_ok());
+
+        let hard = CrlPolicy {
+            crls: vec![CRL_EMPTY.to_vec(), b"garbage".to_vec()],
+            hard_fail: true,
+        };
+        assert!(check_crls(&hard, &[SERVER, CA], now).is_ok());
+        // a directly trusted leaf is the anchor
+        assert!(check_crls(&hard, &[SERVER], now).is_ok());
+        let err = check_crls(&hard, &[LEAF, INTER, CA], now).unwrap_err();
+        assert_eq!(err.kind, CertificateUnknown);
+        let err = check_crls(&hard, &[SERVER, CA], 5000000000).unwrap_err();
+        assert_eq!(err.kind, CertificateUnknown);
+    }
+
+    #[test]
+    fn test_check_crls_validated_path() {
+        use handshake::{Asn1Cert, CertificateList};
+        use tls_result::TlsErrorKind::CertificateRevoked;
+        use roots::RootStore;
+        use verify::{ServerCertVerifier, WebPkiVerifier};
+
+        static CA: &'static [u8] = include_bytes!("../testdata/test_ca.der");
+        static LEAF: &'static [u8] = include_bytes!("../testdata/pki/leaf.der");
+        static INTER: &'static [u8] = include_bytes!("../testdata/pki/inter.der");
+        static CRL_EMPTY: &'static [u8] = include_bytes!("../testdata/crl_empty.der");
+        static CRL_INTER: &'static [u8] = include_bytes!("../testdata/pki/crl_inter.der");
+        static CRL_INTER_REVOKED: &'static [u8] =
+            include_bytes!("../testdata/pki/crl_inter_revoked.der");
+
+        let now = 1792328873 + 3600;
+        let mut roots = RootStore::new();
+        roots.add(CA.to_vec()).unwrap();
+        let verifier = WebPkiVerifier::new(roots);
+        // the server omits the root
+        let chain = vec![Asn1Cert::new(LEAF.to_vec()).unwrap(),
+                         Asn1Cert::new(INTER.to_vec()).unwrap()];
+        let chain = CertificateList::new(chain).unwrap();
+        let path = verifier.verify_server_cert(&chain, Some("leaf.example"), now).unwrap();
+        let path: Vec<&[u8]> = path.iter().map(|cert| &cert[..]).collect();
+        assert_eq!(path, vec![LEAF, INTER, CA]);
+
+        // CRLs of the root and the intermediate
+        let mut hard = CrlPolicy::new();
+        hard.hard_fail = true;
+        hard.crls = vec![CRL_EMPTY.to_vec(), CRL_INTER.to_vec()];
+        assert!(check_crls(&hard, &path, now).is_ok());
+
+        hard.crls = vec![CRL_EMPTY.to_vec(), CRL_INTER_REVOKED.to_vec()];
+        let err = check_crls(&hard, &path, now).unwrap_err();
+        assert_eq!(err.kind, CertificateRevoked);
+    }
+
+    #[test]
+    fn test_check_pins() {
+        use base64;
+        use crypto::sha2::sha256;
+        use handshake::{Asn1Cert, CertificateList};
+        use tls_result::TlsErrorKind::BadCertificate;
+        use roots::RootStore;
+        use verify::{ServerCertVerifier, WebPkiVerifier};
+
+        static CA: &'static [u8] = include_bytes!("../testdata/test_ca.der");
+        static SERVER: &'static [u8] = include_bytes!("../testdata/test_server.der");
+        static CLIENT: &'static [u8] = include_bytes!("../testdata/test_client.der");
+
+        let pin = |der: &[u8]| {
+            let cert = x509::Certificate::parse(der).unwrap();
+            base64::encode(&sha256(cert.subject_public_key_info))
+        };
+
+        let mut policy = PinPolicy::new();
+        assert!(policy.add_pin("not a pin").is_err());
+        assert!(policy.add_pin("AAAA").is_err());
+        // computed with openssl as in `PinPolicy::add_pin`
+        policy.add_pin("aUpZspyeZw9vc1fYJDo44YCV0Ha/9wypmNyv5Cc2eE8=").unwrap();
+        assert!(check_pins(&policy, &[SERVER, CA]).is_ok());
+        let err = check_pins(&policy, &[SERVER]).unwrap_err();
+        assert_eq!(err.kind, BadCertificate);
+        assert!(check_pins(&policy, &[]).is_err());
+
+        // the server omits the pinned CA, which the verifier adds to the path
+        let now = 1792328292 + 3600;
+        let mut roots = RootStore::new();
+        roots.add(CA.to_vec()).unwrap();
+        let verifier = WebPkiVerifier::new(roots);
+        let chain = CertificateList::new(vec![Asn1Cert::new(SERVER.to_vec()).unwrap()]).unwrap();
+        let path = verifier.verify_server_cert(&chain, Some("localhost"), now).unwrap();
+        let path: Vec<&[u8]> = path.iter().map(|cert| &cert[..]).collect();
+        assert!(check_pins(&policy, &path).is_ok());
+
+        policy.leaf_only = true;
+        assert!(check_pins(&policy, &[SERVER, CA]).is_err());
+        policy.add_pin(&pin(SERVER)).unwrap();
+        assert!(check_pins(&policy, &[SERVER, CA]).is_ok());
+        assert!(check_pins(&policy, &[SERVER]).is_ok());
+        assert!(check_pins(&policy, &[CLIENT, CA]).is_err());
+    }
+
+    #[test]
+    fn test_self_signed_with_pin() {
+        use base64;
+        use crypto::sha2::sha256;
+        use handshake::{Asn1Cert, CertificateList, Handshake};
+        use tls::TlsWriter;
+        use tls_item::ObscureData;
+        use tls_result::TlsErrorKind::{BadCertificate, IoFailure, UnknownCa};
+        use verify::danger::NoCertificateVerification;
+
+        static SELF_SIGNED: &'static [u8] = include_bytes!("../testdata/pki/root_b.der");
+        static OTHER: &'static [u8] = include_bytes!("../testdata/test_ca.der");
+
+        let pin = |der: &[u8]| {
+            let cert = x509::Certificate::parse(der).unwrap();
+            base64::encode(&sha256(cert.subject_public_key_info))
+        };
+
+        // the certificate is checked after `ServerKeyExchange` is read, which is left empty
+        let mut writer = TlsWriter::new(Vec::new());
+        writer.write_handshake(&Handshake::server_hello(server_hello(vec![]))).unwrap();
+        let chain = vec![Asn1Cert::new(SELF_SIGNED.to_vec()).unwrap()];
+        let chain = CertificateList::new(chain).unwrap();
+        writer.write_handshake(&Handshake::certificate(chain)).unwrap();
+        let ske = ObscureData::new(Vec::new());
+        writer.write_handshake(&Handshake::server_key_exchange(ske)).unwrap();
+        let input = writer.get_mut().clone();
+        let connect = |config: ClientConfig| {
+            let rng = OsRng::new().unwrap();
+            let result = TlsClient::with_config(Cursor::new(input.clone()), Vec::new(), rng,
+                                                config);
+            result.err().unwrap().kind
+        };
+
+        // not trusted by the default verifier
+        assert_eq!(connect(ClientConfig::new()), UnknownCa);
+
+        let pinned = |der: &[u8]| {
+            let mut pins = PinPolicy::new();
+            pins.add_pin(&pin(der)).unwrap();
+            let mut config = ClientConfig::new();
+            config.verifier = Box::new(NoCertificateVerification);
+            config.pins = Some(pins);
+            config
+        };
+        // accepted, then the client runs out of `ServerKeyExchange` data
+        assert_eq!(connect(pinned(SELF_SIGNED)), IoFailure);
+        assert_eq!(connect(pinned(OTHER)), BadCertificate);
+    }
+}
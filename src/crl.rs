@@ -0,0 +1,274 @@
+//! Certificate revocation lists (RFC 5280 5).
+//!
+//! Only complete CRLs signed by the certificate issuer are supported. CRLs with unknown
+//! critical extensions, such as delta CRLs or issuing distribution points, are rejected.
+
+use der::{self, Reader};
+use ocsp::{CertStatus, CLOCK_SKEW};
+use x509::{self, AlgorithmIdentifier, Certificate, Extension, KEY_USAGE_CRL_SIGN};
+use tls_result::TlsResult;
+use tls_result::TlsErrorKind::{DecodeError, DecryptError};
+
+const AUTHORITY_KEY_IDENTIFIER: &'static [u8] = b"\x55\x1d\x23";
+const CRL_NUMBER: &'static [u8] = b"\x55\x1d\x14";
+const REASON_CODE: &'static [u8] = b"\x55\x1d\x15";
+const INVALIDITY_DATE: &'static [u8] = b"\x55\x1d\x18";
+
+pub struct RevokedCertificate<'a> {
+    pub serial_number: &'a [u8],
+    pub revocation_date: i64,
+    /// `CRLReason` (RFC 5280 5.3.1), if present
+    pub reason: Option<u64>,
+}
+
+pub struct Crl<'a> {
+    /// raw DER of `TBSCertList`, which is signed by the issuer
+    pub tbs_cert_list: &'a [u8],
+    pub signature: AlgorithmIdentifier<'a>,
+    /// raw DER of the issuer `Name`
+    pub issuer: &'a [u8],
+    pub this_update: i64,
+    pub next_update: Option<i64>,
+    pub revoked_certificates: Vec<RevokedCertificate<'a>>,
+    pub extensions: Vec<Extension<'a>>,
+    pub signature_algorithm: AlgorithmIdentifier<'a>,
+    pub signature_value: &'a [u8],
+}
+
+fn check_critical_extensions(extensions: &[Extension], known: &[&[u8]]) -> TlsResult<()> {
+    for ext in extensions.iter() {
+        if ext.critical && !known.contains(&ext.id) {
+            return tls_err!(DecodeError, "unsupported critical CRL extension");
+        }
+    }
+    Ok(())
+}
+
+fn parse_revoked_certificate<'a>(reader: &mut Reader<'a>)
+                                 -> TlsResult<RevokedCertificate<'a>> {
+    let mut entry = try!(reader.read_nested(der::SEQUENCE));
+    let serial_number = try!(entry.read(der::INTEGER));
+    let revocation_date = try!(x509::read_time(&mut entry));
+    let extensions = if entry.is_empty() {
+        Vec::new()
+    } else {
+        try!(x509::parse_extensions(&mut entry))
+    };
+    try!(entry.finish());
+
+    // e.g. `certificateIssuer` of indirect CRLs
+    try!(check_critical_extensions(&extensions, &[REASON_CODE, INVALIDITY_DATE]));
+    let reason = match extensions.iter().find(|ext| ext.id == REASON_CODE) {
+        Some(ext) => {
+            let mut reader = Reader::new(ext.value);
+            let value = try!(reader.read(der::ENUMERATED));
+            try!(reader.finish());
+            if value.len() != 1 {
+                return tls_err!(DecodeError, "bad CRL reason code");
+            }
+            Some(value[0] as u64)
+        }
+        None => None,
+    };
+
+    Ok(RevokedCertificate {
+        serial_number: serial_number,
+        revocation_date: revocation_date,
+        reason: reason,
+    })
+}
+
+impl<'a> Crl<'a> {
+    /// Parses DER `CertificateList`.
+    pub fn parse(data: &'a [u8]) -> TlsResult<Crl<'a>> {
+        let mut reader = Reader::new(data);
+        let mut crl = try!(reader.read_nested(der::SEQUENCE));
+        try!(reader.finish());
+
+        let tbs_cert_list = try!(crl.read_raw(der::SEQUENCE));
+        let signature_algorithm = try!(x509::AlgorithmIdentifier::parse(&mut crl));
+        let signature_value = try!(crl.read_bit_string());
+        try!(crl.finish());
+
+        let mut tbs = try!(Reader::new(tbs_cert_list).read_nested(der::SEQUENCE));
+        let version = if tbs.peek_tag() == Some(der::INTEGER) {
+            try!(tbs.read_small_unsigned())
+        } else {
+            0
+        };
+        if version > 1 {
+            return tls_err!(DecodeError, "unknown CRL version: {}", version);
+        }
+        let signature = try!(x509::AlgorithmIdentifier::parse(&mut tbs));
+        let issuer = try!(tbs.read_raw(der::SEQUENCE));
+        let this_update = try!(x509::read_time(&mut tbs));
+        let next_update = match tbs.peek_tag() {
+            Some(der::UTC_TIME) | Some(der::GENERALIZED_TIME) => {
+                Some(try!(x509::read_time(&mut tbs)))
+            }
+            _ => None,
+        };
+
+        let mut revoked_certificates = Vec::new();
+        if let Some(revoked) = try!(tbs.read_optional(der::SEQUENCE)) {
+            let mut revoked = Reader::new(revoked);
+            while !revoked.is_empty() {
+                revoked_certificates.push(try!(parse_revoked_certificate(&mut revoked)));
+            }
+        }
+
+        let extensions = match try!(tbs.read_optional(der::context(0))) {
+            Some(exts) => {
+                let mut reader = Reader::new(exts);
+                let extensions = try!(x509::parse_extensions(&mut reader));
+                try!(reader.finish());
+                extensions
+            }
+            None => Vec::new(),
+        };
+        try!(tbs.finish());
+        if version == 0 && extensions.len() > 0 {
+            return tls_err!(DecodeError, "extensions in v1 CRL");
+        }
+        try!(check_critical_extensions(&extensions, &[AUTHORITY_KEY_IDENTIFIER, CRL_NUMBER]));
+
+        Ok(Crl {
+            tbs_cert_list: tbs_cert_list,
+            signature: signature,
+            issuer: issuer,
+            this_update: this_update,
+            next_update: next_update,
+            revoked_certificates: revoked_certificates,
+            extensions: extensions,
+            signature_algorithm: signature_algorithm,
+            signature_value: signature_value,
+        })
+    }
+
+    /// Checks whether this CRL is signed by `issuer`.
+    pub fn verify_signature(&self, issuer: &Certificate) -> TlsResult<()> {
+        if self.issuer != issuer.subject {
+            return tls_err!(DecryptError, "CRL issuer mismatch");
+        }
+        if let Some(usage) = try!(issuer.key_usage()) {
+            if usage & KEY_USAGE_CRL_SIGN == 0 {
+                return tls_err!(DecryptError, "CRL issuer without cRLSign key usage");
+            }
+        }
+        if self.signature.algorithm != self.signature_algorithm.algorithm ||
+           self.signature.parameters != self.signature_algorithm.parameters {
+            return tls_err!(DecodeError, "mismatched signature algorithms");
+        }
+        x509::verify_signed_data(issuer.subject_public_key_info,
+                                 &self.signature_algorithm,
+                                 self.tbs_cert_list,
+                                 self.signature_value)
+    }
+
+    /// Checks whether this CRL is current at `now`. CRLs without `nextUpdate`
+    /// are never considered fresh.
+    pub fn is_fresh(&self, now: i64) -> bool {
+        match self.next_update {
+            Some(next_update) => {
+                self.this_update <= now + CLOCK_SKEW && now - CLOCK_SKEW <= next_update
+            }
+            None => false,
+        }
+    }
+
+    /// Finds the entry for `serial_number`.
+    pub fn find(&self, serial_number: &[u8]) -> Option<&RevokedCertificate<'a>> {
+        self.revoked_certificates.iter().find(|entry| entry.serial_number == serial_number)
+    }
+}
+
+/// Determines the status of `cert` issued by `issuer` at `now` from `crls`.
+/// CRLs of other issuers, with bad signatures or out of date are ignored, and
+/// `Unknown` is returned if no CRL remains.
+pub fn cert_status(crls: &[Crl], cert: &Certificate, issuer: &Certificate, now: i64)
+                   -> CertStatus {
+    let mut status = CertStatus::Unknown;
+    for crl in crls.iter() {
+        if crl.issuer != cert.issuer || !crl.is_fresh(now) ||
+           crl.verify_signature(issuer).is_err() {
+            continue;
+        }
+        match crl.find(cert.serial_number) {
+            Some(entry) => return CertStatus::Revoked(entry.revocation_date),
+            None => status = CertStatus::Good,
+        }
+    }
+    status
+}
+
+#[cfg(test)]
+mod test {
+    use ocsp::CertStatus;
+    use x509::Certificate;
+    use super::{Crl, cert_status};
+
+    static CA: &'static [u8] = include_bytes!("../testdata/test_ca.der");
+    static SERVER: &'static [u8] = include_bytes!("../testdata/test_server.der");
+    static CLIENT: &'static [u8] = include_bytes!("../testdata/test_client.der");
+    static CRL_EMPTY: &'static [u8] = include_bytes!("../testdata/crl_empty.der");
+    static CRL_REVOKED: &'static [u8] = include_bytes!("../testdata/crl_revoked.der");
+    static CRL_CRITICAL: &'static [u8] = include_bytes!("../testdata/crl_critical.der");
+
+    // 2026-10-18 13:07:53, when the CRLs are issued
+    const THIS_UPDATE: i64 = 1792328873;
+
+    #[test]
+    fn test_parse_crl() {
+        let crl = Crl::parse(CRL_REVOKED).unwrap();
+        let ca = Certificate::parse(CA).unwrap();
+        assert_eq!(crl.issuer, ca.subject);
+        assert_eq!(crl.this_update, THIS_UPDATE);
+        assert!(crl.next_update.unwrap() > THIS_UPDATE);
+        assert_eq!(crl.revoked_certificates.len(), 1);
+        let entry = crl.find(&[3]).unwrap();
+        assert_eq!(entry.revocation_date, THIS_UPDATE);
+        // keyCompromise
+        assert_eq!(entry.reason, Some(1));
+        assert!(crl.find(&[2]).is_none());
+        crl.verify_signature(&ca).unwrap();
+
+        let empty = Crl::parse(CRL_EMPTY).unwrap();
+        assert_eq!(empty.revoked_certificates.len(), 0);
+        assert_eq!(empty.extensions.len(), 2);
+
+        // unknown critical extension
+        assert!(Crl::parse(CRL_CRITICAL).is_err());
+        assert!(Crl::parse(&CRL_REVOKED[..(CRL_REVOKED.len() - 1)]).is_err());
+
+        let server = Certificate::parse(SERVER).unwrap();
+        assert!(crl.verify_signature(&server).is_err());
+        let mut bad = CRL_REVOKED.to_vec();
+        let len = bad.len();
+        bad[len - 10] ^= 1;
+        assert!(Crl::parse(&bad).unwrap().verify_signature(&ca).is_err());
+    }
+
+    #[test]
+    fn test_cert_status() {
+        let ca = Certificate::parse(CA).unwrap();
+        let server = Certificate::parse(SERVER).unwrap();
+        let client = Certificate::parse(CLIENT).unwrap();
+        let empty = Crl::parse(CRL_EMPTY).unwrap();
+        let revoked = Crl::parse(CRL_REVOKED).unwrap();
+        let now = THIS_UPDATE + 3600;
+
+        assert_eq!(cert_status(&[], &server, &ca, now), CertStatus::Unknown);
+        assert_eq!(cert_status(&[empty], &server, &ca, now), CertStatus::Good);
+
+        let crls = [Crl::parse(CRL_EMPTY).unwrap(), revoked];
+        assert_eq!(cert_status(&crls, &server, &ca, now), CertStatus::Revoked(THIS_UPDATE));
+        assert_eq!(cert_status(&crls, &client, &ca, now), CertStatus::Good);
+
+        // not yet valid, or expired in 2126
+        assert_eq!(cert_status(&crls, &server, &ca, THIS_UPDATE - 3600), CertStatus::Unknown);
+        assert_eq!(cert_status(&crls, &server, &ca, 5000000000), CertStatus::Unknown);
+
+        // signed by someone else
+        assert_eq!(cert_status(&crls, &server, &server, now), CertStatus::Unknown);
+    }
+}
@@ -0,0 +1,235 @@
+// AES (FIPS-197) without lookup tables.
+// http://csrc.nist.gov/publications/fips/fips197/fips-197.pdf
+//
+// S-box is computed as GF(2^8) inversion followed by the affine transform,
+// eight bytes at once packed in a `u64`. slow, but does not leak the key via cache timing.
+
+const LO_BITS: u64 = 0x0101010101010101;
+
+// multiply each byte of `a` and `b` in GF(2^8) = GF(2)[x] / (x^8 + x^4 + x^3 + x + 1).
+fn gf_mult8(a: u64, b: u64) -> u64 {
+    let mut a = a;
+    let mut b = b;
+    let mut p = 0u64;
+    for _ in 0..8 {
+        let mask = (b & LO_BITS) * 0xff;
+        p ^= a & mask;
+        let hi = (a >> 7) & LO_BITS;
+        a = ((a << 1) & !LO_BITS) ^ (hi * 0x1b);
+        b = (b >> 1) & !(LO_BITS << 7);
+    }
+    p
+}
+
+// byte-wise `x^254`, which is `x^-1` if `x != 0`.
+fn gf_inverse8(x: u64) -> u64 {
+    let x2 = gf_mult8(x, x);
+    let x3 = gf_mult8(x2, x);
+    let x6 = gf_mult8(x3, x3);
+    let x12 = gf_mult8(x6, x6);
+    let x15 = gf_mult8(x12, x3);
+    let x30 = gf_mult8(x15, x15);
+    let x60 = gf_mult8(x30, x30);
+    let x120 = gf_mult8(x60, x60);
+    let x240 = gf_mult8(x120, x120);
+    let x252 = gf_mult8(x240, x12);
+    gf_mult8(x252, x2)
+}
+
+// byte-wise rotate left
+fn rotl8(x: u64, n: usize) -> u64 {
+    let lo_mask = LO_BITS * ((1u64 << n) - 1);
+    ((x << n) & !lo_mask) | ((x >> (8 - n)) & lo_mask)
+}
+
+fn sub_bytes8(x: u64) -> u64 {
+    let b = gf_inverse8(x);
+    b ^ rotl8(b, 1) ^ rotl8(b, 2) ^ rotl8(b, 3) ^ rotl8(b, 4) ^ (LO_BITS * 0x63)
+}
+
+fn inv_sub_bytes8(x: u64) -> u64 {
+    let b = rotl8(x, 1) ^ rotl8(x, 3) ^ rotl8(x, 6) ^ (LO_BITS * 0x05);
+    gf_inverse8(b)
+}
+
+fn pack(b: &[u8]) -> u64 {
+    let mut x = 0u64;
+    for i in 0..8 {
+        x |= (b[i] as u64) << (8 * i);
+    }
+    x
+}
+
+fn unpack(x: u64, b: &mut [u8]) {
+    for i in 0..8 {
+        b[i] = (x >> (8 * i)) as u8;
+    }
+}
+
+fn sub_bytes(state: &mut [u8; 16], inverse: bool) {
+    for half in state.chunks_mut(8) {
+        let x = pack(half);
+        let y = if inverse { inv_sub_bytes8(x) } else { sub_bytes8(x) };
+        unpack(y, half);
+    }
+}
+
+// state[r + 4 * c] is row r, column c.
+fn shift_rows(state: &mut [u8; 16], inverse: bool) {
+    let old = *state;
+    for r in 1..4 {
+        for c in 0..4 {
+            let from = if inverse { (c + 4 - r) % 4 } else { (c + r) % 4 };
+            state[r + 4 * c] = old[r + 4 * from];
+        }
+    }
+}
+
+fn xtime(x: u8) -> u8 {
+    (x << 1) ^ ((x >> 7) * 0x1b)
+}
+
+fn mix_columns(state: &mut [u8; 16]) {
+    for c in 0..4 {
+        let a0 = state[4 * c];
+        let a1 = state[4 * c + 1];
+        let a2 = state[4 * c + 2];
+        let a3 = state[4 * c + 3];
+        let all = a0 ^ a1 ^ a2 ^ a3;
+        state[4 * c] = a0 ^ all ^ xtime(a0 ^ a1);
+        state[4 * c + 1] = a1 ^ all ^ xtime(a1 ^ a2);
+        state[4 * c + 2] = a2 ^ all ^ xtime(a2 ^ a3);
+        state[4 * c + 3] = a3 ^ all ^ xtime(a3 ^ a0);
+    }
+}
+
+fn inv_mix_columns(state: &mut [u8; 16]) {
+    // InvMixColumns = MixColumns . (multiply by {04}x^2 + {05})
+    for c in 0..4 {
+        let u = xtime(xtime(state[4 * c] ^ state[4 * c + 2]));
+        let v = xtime(xtime(state[4 * c + 1] ^ state[4 * c + 3]));
+        state[4 * c] ^= u;
+        state[4 * c + 1] ^= v;
+        state[4 * c + 2] ^= u;
+        state[4 * c + 3] ^= v;
+    }
+    mix_columns(state);
+}
+
+fn add_round_key(state: &mut [u8; 16], key: &[u8; 16]) {
+    for i in 0..16 {
+        state[i] ^= key[i];
+    }
+}
+
+pub struct Aes {
+    // SECRET
+    round_keys: Vec<[u8; 16]>,
+}
+
+impl Aes {
+    // key: SECRET. 16 or 32 bytes.
+    pub fn new(key: &[u8]) -> Aes {
+        let nk = key.len() / 4;
+        assert!(key.len() == 16 || key.len() == 32);
+        let rounds = nk + 6;
+
+        let mut w: Vec<[u8; 4]> = Vec::with_capacity(4 * (rounds + 1));
+        for i in 0..nk {
+            w.push([key[4 * i], key[4 * i + 1], key[4 * i + 2], key[4 * i + 3]]);
+        }
+
+        let mut rcon = 1u8;
+        for i in nk..(4 * (rounds + 1)) {
+            let mut temp = w[i - 1];
+            if i % nk == 0 || (nk > 6 && i % nk == 4) {
+                let mut bytes = [0u8; 8];
+                if i % nk == 0 {
+                    // RotWord
+                    bytes[..4].copy_from_slice(&[temp[1], temp[2], temp[3], temp[0]]);
+                } else {
+                    bytes[..4].copy_from_slice(&temp);
+                }
+                let sub = sub_bytes8(pack(&bytes));
+                unpack(sub, &mut bytes);
+                temp = [bytes[0], bytes[1], bytes[2], bytes[3]];
+                if i % nk == 0 {
+                    temp[0] ^= rcon;
+                    rcon = xtime(rcon);
+                }
+            }
+            let prev = w[i - nk];
+            w.push([prev[0] ^ temp[0], prev[1] ^ temp[1], prev[2] ^ temp[2], prev[3] ^ temp[3]]);
+        }
+
+        let mut round_keys = Vec::with_capacity(rounds + 1);
+        for r in 0..(rounds + 1) {
+            let mut k = [0u8; 16];
+            for c in 0..4 {
+                k[4 * c..4 * c + 4].copy_from_slice(&w[4 * r + c]);
+            }
+            round_keys.push(k);
+        }
+
+        Aes {
+            round_keys: round_keys,
+        }
+    }
+
+    pub fn encrypt_block(&self, block: &mut [u8; 16]) {
+        let rounds = self.round_keys.len() - 1;
+        add_round_key(block, &self.round_keys[0]);
+        for r in 1..rounds {
+            sub_bytes(block, false);
+            shift_rows(block, false);
+            mix_columns(block);
+            add_round_key(block, &self.round_keys[r]);
+        }
+        sub_bytes(block, false);
+        shift_rows(block, false);
+        add_round_key(block, &self.round_keys[rounds]);
+    }
+
+    pub fn decrypt_block(&self, block: &mut [u8; 16]) {
+        let rounds = self.round_keys.len() - 1;
+        add_round_key(block, &self.round_keys[rounds]);
+        for r in (1..rounds).rev() {
+            shift_rows(block, true);
+            sub_bytes(block, true);
+            add_round_key(block, &self.round_keys[r]);
+            inv_mix_columns(block);
+        }
+        shift_rows(block, true);
+        sub_bytes(block, true);
+        add_round_key(block, &self.round_keys[0]);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Aes;
+
+    #[test]
+    fn test_aes() {
+        // FIPS-197 Appendix C
+        static VALUES: &'static [(&'static [u8], &'static [u8], &'static [u8])] = &[
+            (b"\x00\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0a\x0b\x0c\x0d\x0e\x0f",
+             b"\x00\x11\x22\x33\x44\x55\x66\x77\x88\x99\xaa\xbb\xcc\xdd\xee\xff",
+             b"\x69\xc4\xe0\xd8\x6a\x7b\x04\x30\xd8\xcd\xb7\x80\x70\xb4\xc5\x5a"),
+            (b"\x00\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0a\x0b\x0c\x0d\x0e\x0f\
+               \x10\x11\x12\x13\x14\x15\x16\x17\x18\x19\x1a\x1b\x1c\x1d\x1e\x1f",
+             b"\x00\x11\x22\x33\x44\x55\x66\x77\x88\x99\xaa\xbb\xcc\xdd\xee\xff",
+             b"\x8e\xa2\xb7\xca\x51\x67\x45\xbf\xea\xfc\x49\x90\x4b\x49\x60\x89"),
+        ];
+
+        for &(key, plain, expected) in VALUES.iter() {
+            let aes = Aes::new(key);
+            let mut block = [0u8; 16];
+            block.copy_from_slice(plain);
+            aes.encrypt_block(&mut block);
+            assert_eq!(&block, expected);
+            aes.decrypt_block(&mut block);
+            assert_eq!(&block, plain);
+        }
+    }
+}
@@ -0,0 +1,356 @@
+// modular arithmetic over an arbitrary odd modulus, for RSA and finite field DH.
+//
+// numbers are little-endian `u32` limbs with the same length as the modulus,
+// and always reduced. multiplication uses Montgomery reduction
+// (https://www.microsoft.com/en-us/research/wp-content/uploads/1996/01/j37acmon.pdf).
+//
+// operations do not branch on or index memory by values,
+// so they can be used with SECRET operands. the modulus is public.
+
+pub struct Modulus {
+    m: Vec<u32>,
+    // -m^-1 mod 2^32
+    m0_inv: u32,
+    // R^2 mod m where R = 2^(32 * m.len())
+    rr: Vec<u32>,
+    // length of the minimal big-endian encoding of m
+    byte_len: usize,
+}
+
+// return !0 if a == b, 0 otherwise.
+fn ct_eq_mask(a: u32, b: u32) -> u32 {
+    let x = a ^ b;
+    // the top bit of `x | -x` is set iff x != 0
+    ((x | x.wrapping_neg()) >> 31).wrapping_sub(1)
+}
+
+// if mask == !0, returns b. if mask == 0, returns a.
+fn select(mask: u32, a: &[u32], b: &[u32]) -> Vec<u32> {
+    a.iter().zip(b.iter()).map(|(&a, &b)| a ^ (mask & (a ^ b))).collect()
+}
+
+// return (a - b mod 2^(32 * len), borrow)
+fn sub_limbs(a: &[u32], b: &[u32]) -> (Vec<u32>, u32) {
+    let mut v = Vec::with_capacity(a.len());
+    let mut borrow = 0u64;
+    for i in 0..a.len() {
+        let d = (a[i] as u64).wrapping_sub(b[i] as u64).wrapping_sub(borrow);
+        v.push(d as u32);
+        borrow = d >> 63;
+    }
+    (v, borrow as u32)
+}
+
+// return (a + b mod 2^(32 * len), carry)
+fn add_limbs(a: &[u32], b: &[u32]) -> (Vec<u32>, u32) {
+    let mut v = Vec::with_capacity(a.len());
+    let mut carry = 0u64;
+    for i in 0..a.len() {
+        let s = (a[i] as u64) + (b[i] as u64) + carry;
+        v.push(s as u32);
+        carry = s >> 32;
+    }
+    (v, carry as u32)
+}
+
+impl Modulus {
+    /// `m` is big-endian. returns `None` if `m` is even or less than 3.
+    pub fn new(m: &[u8]) -> Option<Modulus> {
+        let start = m.iter().position(|&b| b != 0).unwrap_or(m.len());
+        let m = &m[start..];
+        if m.len() == 0 || m[m.len() - 1] & 1 == 0 || (m.len() == 1 && m[0] < 3) {
+            return None;
+        }
+
+        let limbs = (m.len() + 3) / 4;
+        let mut v = vec![0u32; limbs];
+        for (i, &b) in m.iter().rev().enumerate() {
+            v[i / 4] |= (b as u32) << (8 * (i % 4));
+        }
+
+        // Newton's method: each step doubles the number of correct low bits
+        let mut inv = 1u32;
+        for _ in 0..5 {
+            inv = inv.wrapping_mul(2u32.wrapping_sub(v[0].wrapping_mul(inv)));
+        }
+
+        let mut modulus = Modulus {
+            m: v,
+            m0_inv: inv.wrapping_neg(),
+            rr: Vec::new(),
+            byte_len: m.len(),
+        };
+
+        // R^2 mod m by doubling 1 (2 * 32 * limbs) times
+        let mut rr = vec![0u32; limbs];
+        rr[0] = 1;
+        for _ in 0..(64 * limbs) {
+            rr = modulus.add(&rr, &rr);
+        }
+        modulus.rr = rr;
+
+        Some(modulus)
+    }
+
+    /// number of bytes needed to encode the modulus
+    pub fn byte_len(&self) -> usize {
+        self.byte_len
+    }
+
+    /// number of significant bits of the modulus
+    pub fn bit_len(&self) -> usize {
+        let top = self.m[self.m.len() - 1];
+        32 * self.m.len() - top.leading_zeros() as usize
+    }
+
+    pub fn zero(&self) -> Vec<u32> {
+        vec![0u32; self.m.len()]
+    }
+
+    pub fn one(&self) -> Vec<u32> {
+        let mut one = self.zero();
+        one[0] = 1;
+        one
+    }
+
+    /// parses a big-endian number. returns `None` if it is not less than the modulus.
+    /// leading zeros are allowed.
+    pub fn from_bytes(&self, b: &[u8]) -> Option<Vec<u32>> {
+        let limbs = self.m.len();
+        let mut v = vec![0u32; limbs];
+        for (i, &byte) in b.iter().rev().enumerate() {
+            if i / 4 >= limbs {
+                if byte != 0 {
+                    return None;
+                }
+                continue;
+            }
+            v[i / 4] |= (byte as u32) << (8 * (i % 4));
+        }
+        let (_, borrow) = sub_limbs(&v, &self.m);
+        if borrow == 0 {
+            return None;
+        }
+        Some(v)
+    }
+
+    /// reduces a big-endian number of any length.
+    pub fn reduce_bytes(&self, b: &[u8]) -> Vec<u32> {
+        let limbs = self.m.len();
+        // process `limbs * 4` bytes at a time from the most significant part:
+        // acc = acc * R + chunk
+        let chunk_len = limbs * 4;
+        let first = b.len() % chunk_len;
+        let mut acc = self.zero();
+        let mut rest = b;
+        let mut first_len = if first == 0 { chunk_len } else { first };
+        while rest.len() > 0 {
+            let (chunk, next) = rest.split_at(first_len);
+            let mut v = vec![0u32; limbs];
+            for (i, &byte) in chunk.iter().rev().enumerate() {
+                v[i / 4] |= (byte as u32) << (8 * (i % 4));
+            }
+            // chunk may be much larger than m. mont_reduce() accepts any value below R,
+            // and (chunk * R^-1) * R^2 * R^-1 == chunk
+            let v = self.mont_mul(&self.mont_reduce(&v), &self.rr);
+            // acc * R: mont_mul(acc, R^2)
+            acc = self.mont_mul(&acc, &self.rr);
+            acc = self.add(&acc, &v);
+            rest = next;
+            first_len = chunk_len;
+        }
+        acc
+    }
+
+    /// big-endian encoding with `byte_len()` bytes.
+    pub fn to_bytes(&self, a: &[u32]) -> Vec<u8> {
+        let mut b = vec![0u8; self.byte_len];
+        for i in 0..self.byte_len {
+            b[self.byte_len - 1 - i] = (a[i / 4] >> (8 * (i % 4))) as u8;
+        }
+        b
+    }
+
+    pub fn add(&self, a: &[u32], b: &[u32]) -> Vec<u32> {
+        let (s, carry) = add_limbs(a, b);
+        let (d, borrow) = sub_limbs(&s, &self.m);
+        // use `d` if s >= m, i.e. carry or no borrow
+        let mask = 0u32.wrapping_sub(carry | (borrow ^ 1));
+        select(mask, &s, &d)
+    }
+
+    pub fn sub(&self, a: &[u32], b: &[u32]) -> Vec<u32> {
+        let (d, borrow) = sub_limbs(a, b);
+        let (s, _) = add_limbs(&d, &self.m);
+        select(0u32.wrapping_sub(borrow), &d, &s)
+    }
+
+    /// returns 0 if `a` is zero, 1 otherwise.
+    pub fn is_nonzero(&self, a: &[u32]) -> u32 {
+        let acc = a.iter().fold(0u32, |acc, &x| acc | x);
+        1 ^ (ct_eq_mask(acc, 0) & 1)
+    }
+
+    // a * b * R^-1 mod m (CIOS method)
+    fn mont_mul(&self, a: &[u32], b: &[u32]) -> Vec<u32> {
+        let m = &self.m;
+        let k = m.len();
+        let mut t = vec![0u32; k + 2];
+        for i in 0..k {
+            let mut c = 0u64;
+            for j in 0..k {
+                let s = (t[j] as u64) + (a[j] as u64) * (b[i] as u64) + c;
+                t[j] = s as u32;
+                c = s >> 32;
+            }
+            let s = (t[k] as u64) + c;
+            t[k] = s as u32;
+            t[k + 1] = (s >> 32) as u32;
+
+            let u = t[0].wrapping_mul(self.m0_inv);
+            let s = (t[0] as u64) + (u as u64) * (m[0] as u64);
+            let mut c = s >> 32;
+            for j in 1..k {
+                let s = (t[j] as u64) + (u as u64) * (m[j] as u64) + c;
+                t[j - 1] = s as u32;
+                c = s >> 32;
+            }
+            let s = (t[k] as u64) + c;
+            t[k - 1] = s as u32;
+            t[k] = t[k + 1] + ((s >> 32) as u32);
+        }
+
+        // t < 2m
+        let (d, borrow) = sub_limbs(&t[..k], m);
+        let mask = 0u32.wrapping_sub(t[k] | (borrow ^ 1));
+        select(mask, &t[..k], &d)
+    }
+
+    // a * R^-1 mod m, for any `a < R`
+    fn mont_reduce(&self, a: &[u32]) -> Vec<u32> {
+        self.mont_mul(a, &self.one())
+    }
+
+    pub fn mul(&self, a: &[u32], b: &[u32]) -> Vec<u32> {
+        let ab = self.mont_mul(a, b);
+        self.mont_mul(&ab, &self.rr)
+    }
+
+    /// `a ^ exp mod m`. `exp` is big-endian; only its length is leaked.
+    pub fn pow(&self, a: &[u32], exp: &[u8]) -> Vec<u32> {
+        let a = self.mont_mul(a, &self.rr);
+        let one = self.mont_mul(&self.one(), &self.rr);
+
+        // 4-bit fixed window
+        let mut table = Vec::with_capacity(16);
+        table.push(one.clone());
+        for i in 1..16 {
+            let next = self.mont_mul(&table[i - 1], &a);
+            table.push(next);
+        }
+
+        let mut acc = one;
+        for &byte in exp.iter() {
+            for &nibble in [byte >> 4, byte & 0xf].iter() {
+                for _ in 0..4 {
+                    acc = self.mont_mul(&acc, &acc);
+                }
+                let mut entry = self.zero();
+                for i in 0..16 {
+                    entry = select(ct_eq_mask(i as u32, nibble as u32), &entry, &table[i]);
+                }
+                acc = self.mont_mul(&acc, &entry);
+            }
+        }
+
+        self.mont_reduce(&acc)
+    }
+
+    /// `a^-1 mod m` for prime `m` (Fermat's little theorem).
+    /// returns zero if `a` is zero.
+    pub fn inverse_prime(&self, a: &[u32]) -> Vec<u32> {
+        let two = {
+            let mut two = self.zero();
+            two[0] = 2;
+            two
+        };
+        let m_minus_2 = sub_limbs(&self.m, &two).0;
+        self.pow(a, &self.to_bytes(&m_minus_2))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Modulus;
+
+    fn to_u64(v: &[u32]) -> u64 {
+        let mut x = 0u64;
+        for (i, &limb) in v.iter().enumerate().take(2) {
+            x |= (limb as u64) << (32 * i);
+        }
+        x
+    }
+
+    fn to_be(x: u64) -> Vec<u8> {
+        (0..8).rev().map(|i| (x >> (8 * i)) as u8).collect()
+    }
+
+    fn pow_u64(a: u64, e: u64, m: u64) -> u64 {
+        let mut ret = 1u128;
+        let mut base = (a % m) as u128;
+        let mut e = e;
+        while e > 0 {
+            if e & 1 == 1 {
+                ret = ret * base % (m as u128);
+            }
+            base = base * base % (m as u128);
+            e >>= 1;
+        }
+        ret as u64
+    }
+
+    #[test]
+    fn test_modulus_arith() {
+        assert!(Modulus::new(&[0x10]).is_none());
+        assert!(Modulus::new(&[0x00, 0x01]).is_none());
+
+        for &m in [0xfffffffbu64, 0xffffffff00000001, 0x1000000000000001, 0x5bd1].iter() {
+            let modulus = Modulus::new(&to_be(m)).unwrap();
+            assert_eq!(modulus.to_bytes(&modulus.from_bytes(&to_be(m - 1)).unwrap()).len(),
+                       modulus.byte_len());
+            assert!(modulus.from_bytes(&to_be(m)).is_none());
+
+            for &a in [0u64, 1, 2, 0x1234567 % m, m - 1, m / 3].iter() {
+                let av = modulus.from_bytes(&to_be(a)).unwrap();
+                for &b in [0u64, 1, 0xdeadbeef % m, m - 2].iter() {
+                    let bv = modulus.from_bytes(&to_be(b)).unwrap();
+                    let mm = m as u128;
+                    assert_eq!(to_u64(&modulus.add(&av, &bv)),
+                               ((a as u128 + b as u128) % mm) as u64);
+                    assert_eq!(to_u64(&modulus.sub(&av, &bv)),
+                               ((a as u128 + mm - b as u128) % mm) as u64);
+                    assert_eq!(to_u64(&modulus.mul(&av, &bv)),
+                               ((a as u128 * b as u128) % mm) as u64);
+                }
+                for &e in [0u64, 1, 2, 65537, 0xfedcba9876543210].iter() {
+                    assert_eq!(to_u64(&modulus.pow(&av, &to_be(e))), pow_u64(a, e, m));
+                }
+            }
+
+            // reduce more than one chunk
+            let big = [0xffu8; 37];
+            let mut expected = 0u128;
+            for &b in big.iter() {
+                expected = ((expected << 8) | b as u128) % (m as u128);
+            }
+            assert_eq!(to_u64(&modulus.reduce_bytes(&big)), expected as u64);
+        }
+
+        // prime
+        let p = 0xffffffff00000001u64;
+        let modulus = Modulus::new(&to_be(p)).unwrap();
+        let a = modulus.from_bytes(&to_be(0x123456789)).unwrap();
+        let a_inv = modulus.inverse_prime(&a);
+        assert_eq!(to_u64(&modulus.mul(&a, &a_inv)), 1);
+    }
+}
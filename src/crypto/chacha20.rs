@@ -50,6 +50,15 @@ impl ChaCha20 {
         }
     }
 
+    // RFC 8439 variant with a 32-bit block counter and a 96-bit nonce.
+    // key: SECRET
+    pub fn new_ietf(key: &[u8], nonce: &[u8]) -> ChaCha20 {
+        assert_eq!(nonce.len(), 12);
+        let mut chacha20 = ChaCha20::new(key, &nonce[4..]);
+        chacha20.vals[13] = to_le_u32!(nonce[0]);
+        chacha20
+    }
+
     fn round20(&self) -> [w32; 16] {
         // $e must be > 0 and < 32
         macro_rules! rot {
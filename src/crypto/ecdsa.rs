@@ -0,0 +1,208 @@
+// ECDSA signatures (FIPS 186-4 6, SEC 1 4.1).
+// verification inputs are public, so it need not be constant-time.
+
+use crypto::bigint::Modulus;
+use crypto::hash::HashFunction;
+use crypto::hmac::hmac;
+use crypto::p256::{self, NPoint256};
+use crypto::p256::int256::Int256;
+use crypto::p384::{self, P384, NPoint384};
+
+// order of the P-256 base point
+static P256_N: &'static [u8] = b"\xff\xff\xff\xff\x00\x00\x00\x00\xff\xff\xff\xff\xff\xff\xff\xff\
+                                  \xbc\xe6\xfa\xad\xa7\x17\x9e\x84\xf3\xb9\xca\xc2\xfc\x63\x25\x51";
+
+// the leftmost `len` bytes of `hash`, reduced mod n.
+fn hash_to_scalar(n: &Modulus, hash: &[u8], len: usize) -> Vec<u32> {
+    let hash = if hash.len() > len { &hash[..len] } else { hash };
+    n.reduce_bytes(hash)
+}
+
+// `r` and `s` are big-endian, `hash` is the message digest.
+pub fn verify_p256(public: &NPoint256, hash: &[u8], r: &[u8], s: &[u8]) -> bool {
+    let n = Modulus::new(P256_N).unwrap();
+
+    // 1 <= r, s < n
+    let (r, s) = match (n.from_bytes(r), n.from_bytes(s)) {
+        (Some(r), Some(s)) => (r, s),
+        _ => return false,
+    };
+    if n.is_nonzero(&r) == 0 || n.is_nonzero(&s) == 0 {
+        return false;
+    }
+
+    let e = hash_to_scalar(&n, hash, 32);
+    let w = n.inverse_prime(&s);
+    let u1 = Int256::from_bytes(&n.to_bytes(&n.mul(&e, &w))).unwrap();
+    let u2 = Int256::from_bytes(&n.to_bytes(&n.mul(&r, &w))).unwrap();
+
+    let q = NPoint256 {
+        x: public.x,
+        y: public.y,
+    };
+    let point = p256::G.mult_scalar(&u1).add(&q.to_point().mult_scalar(&u2));
+    if point.is_infinity() {
+        return false;
+    }
+
+    let x = n.reduce_bytes(&point.normalize().x.to_bytes());
+    x == r
+}
+
+// `r` and `s` are big-endian, `hash` is the message digest.
+pub fn verify_p384(public: &NPoint384, hash: &[u8], r: &[u8], s: &[u8]) -> bool {
+    let n = Modulus::new(p384::N).unwrap();
+
+    // 1 <= r, s < n
+    let (r, s) = match (n.from_bytes(r), n.from_bytes(s)) {
+        (Some(r), Some(s)) => (r, s),
+        _ => return false,
+    };
+    if n.is_nonzero(&r) == 0 || n.is_nonzero(&s) == 0 {
+        return false;
+    }
+
+    let e = hash_to_scalar(&n, hash, p384::BYTE_LEN);
+    let w = n.inverse_prime(&s);
+    let u1 = n.to_bytes(&n.mul(&e, &w));
+    let u2 = n.to_bytes(&n.mul(&r, &w));
+
+    let curve = P384::new();
+    let point = curve.add(&curve.mult_scalar(&curve.generator(), &u1),
+                          &curve.mult_scalar(&curve.to_point(public), &u2));
+    match curve.normalize(&point) {
+        Some(point) => n.reduce_bytes(&point.x) == r,
+        None => false,
+    }
+}
+
+// returns the public point of the SECRET scalar `private` (big-endian),
+// or `None` if it is not in [1, n - 1].
+pub fn p256_public_key(private: &[u8]) -> Option<NPoint256> {
+    let n = Modulus::new(P256_N).unwrap();
+    let d = match n.from_bytes(private) {
+        Some(d) => d,
+        None => return None,
+    };
+    if n.is_nonzero(&d) == 0 {
+        return None;
+    }
+    let d = Int256::from_bytes(&n.to_bytes(&d)).unwrap();
+    Some(p256::G.mult_scalar(&d).normalize())
+}
+
+// deterministic nonce generation (RFC 6979 3.2) for private key `x`, with HMAC over `hash`.
+// `h` is the message digest. calls `f` with candidate nonces until it returns `Some`.
+fn with_nonce<T, F>(n: &Modulus, hash: &HashFunction, x: &[u8], h: &[u8], mut f: F) -> T
+        where F: FnMut(&[u32]) -> Option<T> {
+    let h = n.to_bytes(&hash_to_scalar(n, h, 32));
+    let mut v = vec![0x01u8; hash.output_len];
+    let mut k = vec![0x00u8; hash.output_len];
+    for &sep in [0x00u8, 0x01].iter() {
+        let mut msg = v.clone();
+        msg.push(sep);
+        msg.extend(x);
+        msg.extend(&h);
+        k = hmac(hash, &k, &msg);
+        v = hmac(hash, &k, &v);
+    }
+
+    loop {
+        let mut t = Vec::with_capacity(32 + hash.output_len);
+        while t.len() < 32 {
+            v = hmac(hash, &k, &v);
+            t.extend(&v);
+        }
+        // only rejected candidates are distinguishable
+        if let Some(nonce) = n.from_bytes(&t[..32]) {
+            if n.is_nonzero(&nonce) == 1 {
+                if let Some(ret) = f(&nonce) {
+                    return ret;
+                }
+            }
+        }
+        let mut msg = v.clone();
+        msg.push(0x00);
+        k = hmac(hash, &k, &msg);
+        v = hmac(hash, &k, &v);
+    }
+}
+
+// signs `msg` hashed with `hash`. `private` is the SECRET scalar (big-endian, less than n).
+// returns big-endian `(r, s)`.
+pub fn sign_p256(private: &[u8], hash: &HashFunction, msg: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let n = Modulus::new(P256_N).unwrap();
+    let d = n.from_bytes(private).unwrap();
+    let x = n.to_bytes(&d);
+    let digest = (hash.digest)(msg);
+    let e = hash_to_scalar(&n, &digest, 32);
+
+    with_nonce(&n, hash, &x, &digest, |k| {
+        let k_int = Int256::from_bytes(&n.to_bytes(k)).unwrap();
+        let point = p256::G.mult_scalar(&k_int).normalize();
+        let r = n.reduce_bytes(&point.x.to_bytes());
+        if n.is_nonzero(&r) == 0 {
+            return None;
+        }
+        // s = k^-1 (e + r d)
+        let s = n.mul(&n.inverse_prime(k), &n.add(&e, &n.mul(&r, &d)));
+        if n.is_nonzero(&s) == 0 {
+            return None;
+        }
+        Some((n.to_bytes(&r), n.to_bytes(&s)))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crypto::hash::{SHA1, SHA256, SHA384};
+    use crypto::p384::P384;
+    use super::{sign_p256, verify_p256, p256_public_key, verify_p384};
+
+    #[test]
+    fn test_sign_p256() {
+        // RFC 6979 A.2.5
+        let x = b"\xc9\xaf\xa9\xd8\x45\xba\x75\x16\x6b\x5c\x21\x57\x67\xb1\xd6\x93\
+                  \x4e\x50\xc3\xdb\x36\xe8\x9b\x12\x7b\x8a\x62\x2b\x12\x0f\x67\x21";
+        let (r, s) = sign_p256(x, &SHA256, b"sample");
+        assert_eq!(&r[..], &b"\xef\xd4\x8b\x2a\xac\xb6\xa8\xfd\x11\x40\xdd\x9c\xd4\x5e\x81\xd6\
+                              \x9d\x2c\x87\x7b\x56\xaa\xf9\x91\xc3\x4d\x0e\xa8\x4e\xaf\x37\x16"[..]);
+        assert_eq!(&s[..], &b"\xf7\xcb\x1c\x94\x2d\x65\x7c\x41\xd4\x36\xc7\xa1\xb6\xe2\x9f\x65\
+                              \xf3\xe9\x00\xdb\xb9\xaf\xf4\x06\x4d\xc4\xab\x2f\x84\x3a\xcd\xa8"[..]);
+
+        let (r1, s1) = sign_p256(x, &SHA1, b"sample");
+        assert_eq!(&r1[..], &b"\x61\x34\x0c\x88\xc3\xaa\xeb\xeb\x4f\x6d\x66\x7f\x67\x2c\xa9\x75\
+                               \x9a\x6c\xca\xa9\xfa\x88\x11\x31\x30\x39\xee\x4a\x35\x47\x1d\x32"[..]);
+        assert_eq!(&s1[..], &b"\x6d\x7f\x14\x7d\xac\x08\x94\x41\xbb\x2e\x2f\xe8\xf7\xa3\xfa\x26\
+                               \x4b\x9c\x47\x50\x98\xfd\xcf\x6e\x00\xd7\xc9\x96\xe1\xb8\xb7\xeb"[..]);
+
+        let public = p256_public_key(x).unwrap();
+        assert!(verify_p256(&public, &(SHA256.digest)(b"sample"), &r, &s));
+        assert!(!verify_p256(&public, &(SHA256.digest)(b"test"), &r, &s));
+    }
+
+    #[test]
+    fn test_verify_p384() {
+        // RFC 6979 A.2.6, with SHA-384
+        let curve = P384::new();
+        let public = curve.from_uncompressed_bytes(
+            b"\x04\
+              \xec\x3a\x4e\x41\x5b\x4e\x19\xa4\x56\x86\x18\x02\x9f\x42\x7f\xa5\
+              \xda\x9a\x8b\xc4\xae\x92\xe0\x2e\x06\xaa\xe5\x28\x6b\x30\x0c\x64\
+              \xde\xf8\xf0\xea\x90\x55\x86\x60\x64\xa2\x54\x51\x54\x80\xbc\x13\
+              \x80\x15\xd9\xb7\x2d\x7d\x57\x24\x4e\xa8\xef\x9a\xc0\xc6\x21\x89\
+              \x67\x08\xa5\x93\x67\xf9\xdf\xb9\xf5\x4c\xa8\x4b\x3f\x1c\x9d\xb1\
+              \x28\x8b\x23\x1c\x3a\xe0\xd4\xfe\x73\x44\xfd\x25\x33\x26\x47\x20").unwrap();
+        let r = b"\x94\xed\xbb\x92\xa5\xec\xb8\xaa\xd4\x73\x6e\x56\xc6\x91\x91\x6b\
+                  \x3f\x88\x14\x06\x66\xce\x9f\xa7\x3d\x64\xc4\xea\x95\xad\x13\x3c\
+                  \x81\xa6\x48\x15\x2e\x44\xac\xf9\x6e\x36\xdd\x1e\x80\xfa\xbe\x46";
+        let s = b"\x99\xef\x4a\xeb\x15\xf1\x78\xce\xa1\xfe\x40\xdb\x26\x03\x13\x8f\
+                  \x13\x0e\x74\x0a\x19\x62\x45\x26\x20\x3b\x63\x51\xd0\xa3\xa9\x4f\
+                  \xa3\x29\xc1\x45\x78\x6e\x67\x9e\x7b\x82\xc7\x1a\x38\x62\x8a\xc8";
+        let hash = (SHA384.digest)(b"sample");
+        assert!(verify_p384(&public, &hash, r, s));
+        assert!(!verify_p384(&public, &(SHA384.digest)(b"test"), r, s));
+        assert!(!verify_p384(&public, &hash, s, r));
+        assert!(!verify_p384(&public, &hash, &[0; 48], s));
+    }
+}
@@ -0,0 +1,342 @@
+// Ed25519 (RFC 8032 5.1).
+//
+// field and scalar arithmetic is `bigint::Modulus`, which does not branch on or index memory
+// by values. points are in extended coordinates and added with the complete formula of
+// Hisil, Wong, Carter and Dawson (https://eprint.iacr.org/2008/522, section 3.1),
+// so doubling and the identity need no special cases.
+//
+// keys and signatures are little-endian as in RFC 8032, while `Modulus` is big-endian.
+
+use crypto::bigint::Modulus;
+use crypto::hash;
+
+pub const SEED_LEN: usize = 32;
+pub const PUBLIC_KEY_LEN: usize = 32;
+pub const SIGNATURE_LEN: usize = 64;
+
+// p = 2^255 - 19
+static P: &'static [u8] = b"\
+    \x7f\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\
+    \xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xed";
+
+// (p - 5) / 8
+static P_5_8: &'static [u8] = b"\
+    \x0f\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\
+    \xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xfd";
+
+// order of B
+static L: &'static [u8] = b"\
+    \x10\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+    \x14\xde\xf9\xde\xa2\xf7\x9c\xd6\x58\x12\x63\x1a\x5c\xf5\xd3\xed";
+
+// -121665 / 121666
+static D: &'static [u8] = b"\
+    \x52\x03\x6c\xee\x2b\x6f\xfe\x73\x8c\xc7\x40\x79\x77\x79\xe8\x98\
+    \x00\x70\x0a\x4d\x41\x41\xd8\xab\x75\xeb\x4d\xca\x13\x59\x78\xa3";
+
+// 2^((p - 1) / 4)
+static SQRT_M1: &'static [u8] = b"\
+    \x2b\x83\x24\x80\x4f\xc1\xdf\x0b\x2b\x4d\x00\x99\x3d\xfb\xd7\xa7\
+    \x2f\x43\x18\x06\xad\x2f\xe4\x78\xc4\xee\x1b\x27\x4a\x0e\xa0\xb0";
+
+static BX: &'static [u8] = b"\
+    \x21\x69\x36\xd3\xcd\x6e\x53\xfe\xc0\xa4\xe2\x31\xfd\xd6\xdc\x5c\
+    \x69\x2c\xc7\x60\x95\x25\xa7\xb2\xc9\x56\x2d\x60\x8f\x25\xd5\x1a";
+
+// 4 / 5
+static BY: &'static [u8] = b"\
+    \x66\x66\x66\x66\x66\x66\x66\x66\x66\x66\x66\x66\x66\x66\x66\x66\
+    \x66\x66\x66\x66\x66\x66\x66\x66\x66\x66\x66\x66\x66\x66\x66\x58";
+
+fn reversed(b: &[u8]) -> Vec<u8> {
+    b.iter().rev().cloned().collect()
+}
+
+// if flag == 1, returns b. if flag == 0, returns a.
+fn choose(flag: u32, a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mask = 0u32.wrapping_sub(flag);
+    a.iter().zip(b.iter()).map(|(&a, &b)| a ^ (mask & (a ^ b))).collect()
+}
+
+// (x, y, z, t): (X, Y) = (x/z, y/z) is a point of -X^2 + Y^2 = 1 + D X^2 Y^2, and t = x y / z.
+// the identity is (0, 1, 1, 0).
+#[derive(Clone)]
+struct Point {
+    x: Vec<u32>,
+    y: Vec<u32>,
+    z: Vec<u32>,
+    t: Vec<u32>,
+}
+
+struct Curve {
+    p: Modulus,
+    d: Vec<u32>,
+    d2: Vec<u32>,
+}
+
+impl Curve {
+    fn new() -> Curve {
+        let p = Modulus::new(P).unwrap();
+        let d = p.from_bytes(D).unwrap();
+        let d2 = p.add(&d, &d);
+        Curve {
+            p: p,
+            d: d,
+            d2: d2,
+        }
+    }
+
+    fn base(&self) -> Point {
+        let x = self.p.from_bytes(BX).unwrap();
+        let y = self.p.from_bytes(BY).unwrap();
+        let t = self.p.mul(&x, &y);
+        Point {
+            x: x,
+            y: y,
+            z: self.p.one(),
+            t: t,
+        }
+    }
+
+    fn identity(&self) -> Point {
+        Point {
+            x: self.p.zero(),
+            y: self.p.one(),
+            z: self.p.one(),
+            t: self.p.zero(),
+        }
+    }
+
+    fn choose(&self, flag: u32, a: &Point, b: &Point) -> Point {
+        Point {
+            x: choose(flag, &a.x, &b.x),
+            y: choose(flag, &a.y, &b.y),
+            z: choose(flag, &a.z, &b.z),
+            t: choose(flag, &a.t, &b.t),
+        }
+    }
+
+    fn add(&self, a: &Point, b: &Point) -> Point {
+        let f = &self.p;
+        let aa = f.mul(&f.sub(&a.y, &a.x), &f.sub(&b.y, &b.x));
+        let bb = f.mul(&f.add(&a.y, &a.x), &f.add(&b.y, &b.x));
+        let cc = f.mul(&f.mul(&a.t, &self.d2), &b.t);
+        let zz = f.mul(&a.z, &b.z);
+        let dd = f.add(&zz, &zz);
+        let e = f.sub(&bb, &aa);
+        let ff = f.sub(&dd, &cc);
+        let g = f.add(&dd, &cc);
+        let h = f.add(&bb, &aa);
+        Point {
+            x: f.mul(&e, &ff),
+            y: f.mul(&g, &h),
+            z: f.mul(&ff, &g),
+            t: f.mul(&e, &h),
+        }
+    }
+
+    fn neg(&self, a: &Point) -> Point {
+        let zero = self.p.zero();
+        Point {
+            x: self.p.sub(&zero, &a.x),
+            y: a.y.clone(),
+            z: a.z.clone(),
+            t: self.p.sub(&zero, &a.t),
+        }
+    }
+
+    // `n` is big-endian. only its length is leaked.
+    fn mult_scalar(&self, a: &Point, n: &[u8]) -> Point {
+        let mut ret = self.identity();
+        for &byte in n.iter() {
+            for j in (0..8).rev() {
+                let bit = ((byte >> j) & 1) as u32;
+                let ret2 = self.add(&ret, &ret);
+                let ret3 = self.add(&ret2, a);
+                ret = self.choose(bit, &ret2, &ret3);
+            }
+        }
+        ret
+    }
+
+    // RFC 8032 5.1.2
+    fn encode(&self, a: &Point) -> Vec<u8> {
+        let f = &self.p;
+        let z_inv = f.inverse_prime(&a.z);
+        let x = f.to_bytes(&f.mul(&a.x, &z_inv));
+        let mut b = reversed(&f.to_bytes(&f.mul(&a.y, &z_inv)));
+        b[31] |= (x[31] & 1) << 7;
+        b
+    }
+
+    // RFC 8032 5.1.3. returns `None` unless `b` is a canonical encoding of a point.
+    fn decode(&self, b: &[u8]) -> Option<Point> {
+        if b.len() != 32 {
+            return None;
+        }
+        let f = &self.p;
+        let mut y = reversed(b);
+        let sign = y[0] >> 7;
+        y[0] &= 0x7f;
+        let y = match f.from_bytes(&y) {
+            Some(y) => y,
+            None => return None,
+        };
+
+        let zero = f.zero();
+        let one = f.one();
+        let yy = f.mul(&y, &y);
+        let u = f.sub(&yy, &one);
+        let v = f.add(&f.mul(&self.d, &yy), &one);
+
+        // x = u v^3 (u v^7)^((p - 5) / 8)
+        let v3 = f.mul(&f.mul(&v, &v), &v);
+        let v7 = f.mul(&f.mul(&v3, &v3), &v);
+        let mut x = f.mul(&f.mul(&u, &v3), &f.pow(&f.mul(&u, &v7), P_5_8));
+        let vxx = f.mul(&v, &f.mul(&x, &x));
+        if vxx != u {
+            if vxx != f.sub(&zero, &u) {
+                return None;
+            }
+            x = f.mul(&x, &f.from_bytes(SQRT_M1).unwrap());
+        }
+        if f.is_nonzero(&x) == 0 && sign == 1 {
+            return None;
+        }
+        if f.to_bytes(&x)[31] & 1 != sign {
+            x = f.sub(&zero, &x);
+        }
+
+        let t = f.mul(&x, &y);
+        Some(Point {
+            x: x,
+            y: y,
+            z: one,
+            t: t,
+        })
+    }
+}
+
+// SHA-512 of the concatenation of `parts`, reduced mod L
+fn hash_to_scalar(l: &Modulus, parts: &[&[u8]]) -> Vec<u32> {
+    let mut data = Vec::new();
+    for part in parts.iter() {
+        data.extend(*part);
+    }
+    l.reduce_bytes(&reversed(&(hash::SHA512.digest)(&data)))
+}
+
+// returns the SECRET scalar `a` (big-endian) and prefix of RFC 8032 5.1.5.
+fn expand_seed(seed: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    assert_eq!(seed.len(), SEED_LEN);
+    let h = (hash::SHA512.digest)(seed);
+    let mut a = h[..32].to_vec();
+    a[0] &= 248;
+    a[31] &= 127;
+    a[31] |= 64;
+    (reversed(&a), h[32..].to_vec())
+}
+
+/// Derives the public key from a 32-byte private key (`seed`).
+pub fn public_key(seed: &[u8]) -> Vec<u8> {
+    let curve = Curve::new();
+    let (a, _) = expand_seed(seed);
+    curve.encode(&curve.mult_scalar(&curve.base(), &a))
+}
+
+/// Signs `msg` with a 32-byte private key (`seed`). RFC 8032 5.1.6.
+pub fn sign(seed: &[u8], msg: &[u8]) -> Vec<u8> {
+    let curve = Curve::new();
+    let l = Modulus::new(L).unwrap();
+    let (a, prefix) = expand_seed(seed);
+    let public = curve.encode(&curve.mult_scalar(&curve.base(), &a));
+
+    let r = hash_to_scalar(&l, &[&prefix, msg]);
+    let big_r = curve.encode(&curve.mult_scalar(&curve.base(), &l.to_bytes(&r)));
+    let k = hash_to_scalar(&l, &[&big_r, &public, msg]);
+    let s = l.add(&r, &l.mul(&k, &l.reduce_bytes(&a)));
+
+    let mut signature = big_r;
+    signature.extend(&reversed(&l.to_bytes(&s)));
+    signature
+}
+
+/// Verifies `signature` of `msg` by `public`. RFC 8032 5.1.7, without the cofactor.
+pub fn verify(public: &[u8], msg: &[u8], signature: &[u8]) -> bool {
+    if public.len() != PUBLIC_KEY_LEN || signature.len() != SIGNATURE_LEN {
+        return false;
+    }
+    let curve = Curve::new();
+    let l = Modulus::new(L).unwrap();
+    let a = match curve.decode(public) {
+        Some(a) => a,
+        None => return false,
+    };
+    let (big_r, s) = signature.split_at(32);
+    // S >= L is rejected for non-malleability
+    let s = match l.from_bytes(&reversed(s)) {
+        Some(s) => s,
+        None => return false,
+    };
+    let k = hash_to_scalar(&l, &[big_r, public, msg]);
+
+    // [S]B - [k]A == R
+    let sb = curve.mult_scalar(&curve.base(), &l.to_bytes(&s));
+    let ka = curve.mult_scalar(&curve.neg(&a), &l.to_bytes(&k));
+    curve.encode(&curve.add(&sb, &ka)) == big_r
+}
+
+#[cfg(test)]
+mod test {
+    use test::unhex;
+    use super::{public_key, sign, verify};
+
+    // RFC 8032 7.1, tests 1, 2 and 3
+    #[test]
+    fn test_ed25519() {
+        let tests = [
+            ("9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60",
+             "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+             "",
+             "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e06522490155\
+              5fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b"),
+            ("4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb",
+             "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c",
+             "72",
+             "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da\
+              085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00"),
+            ("c5aa8df43f9f837bedb7442f31dcb7b166d38535076f094b85ce3a2e0b4458f7",
+             "fc51cd8e6218a1a38da47ed00230f0580816ed13ba3303ac5deb911548908025",
+             "af82",
+             "6291d657deec24024827e69c3abe01a30ce548a284743a445e3680d7db5ac3ac\
+              18ff9b538d16f290ae67f760984dc6594a7c15e9716ed28dc027beceea1ec40a"),
+        ];
+        for &(seed, public, msg, signature) in tests.iter() {
+            let (seed, public, msg) = (unhex(seed), unhex(public), unhex(msg));
+            let signature = unhex(signature);
+            assert_eq!(public_key(&seed), public);
+            assert_eq!(sign(&seed, &msg), signature);
+            assert!(verify(&public, &msg, &signature));
+
+            let mut bad = signature.clone();
+            bad[0] ^= 1;
+            assert!(!verify(&public, &msg, &bad));
+            let mut bad = msg.clone();
+            bad.push(0);
+            assert!(!verify(&public, &bad, &signature));
+        }
+
+        // S + L is the same signature mod L, but must be rejected
+        let public = unhex("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a");
+        let mut signature = unhex("e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e06522490155\
+                                   5fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b");
+        let l = unhex("edd3f55c1a631258d69cf7a2def9de1400000000000000000000000000000010");
+        let mut carry = 0u16;
+        for i in 0..32 {
+            let sum = signature[32 + i] as u16 + l[i] as u16 + carry;
+            signature[32 + i] = sum as u8;
+            carry = sum >> 8;
+        }
+        assert!(!verify(&public, b"", &signature));
+    }
+}
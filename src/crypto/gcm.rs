@@ -0,0 +1,199 @@
+// AES-GCM (NIST SP 800-38D) with 96-bit nonces.
+// http://csrc.nist.gov/publications/nistpubs/800-38D/SP-800-38D.pdf
+//
+// GHASH multiplies bit by bit with masks instead of tables,
+// so the hash key does not leak via cache timing.
+
+use crypto::aes::Aes;
+use util::crypto_compare;
+
+pub const NONCE_LEN: usize = 12;
+pub const TAG_LEN: usize = 16;
+
+// element of GF(2^128) as two big-endian halves.
+// GCM uses reflected bit order: bit 0 is the MSB of the first byte.
+type Block = [u64; 2];
+
+fn load(b: &[u8]) -> Block {
+    let mut x = [0u64; 2];
+    for i in 0..16 {
+        x[i / 8] |= (b[i] as u64) << (56 - 8 * (i % 8));
+    }
+    x
+}
+
+fn store(x: &Block) -> [u8; 16] {
+    let mut b = [0u8; 16];
+    for i in 0..16 {
+        b[i] = (x[i / 8] >> (56 - 8 * (i % 8))) as u8;
+    }
+    b
+}
+
+// multiplication in GF(2^128) = GF(2)[x] / (x^128 + x^7 + x^2 + x + 1) (SP 800-38D 6.3)
+fn gf_mult(x: &Block, y: &Block) -> Block {
+    let mut z = [0u64; 2];
+    let mut v = *y;
+    for i in 0..128 {
+        let bit = (x[i / 64] >> (63 - i % 64)) & 1;
+        let mask = 0u64.wrapping_sub(bit);
+        z[0] ^= v[0] & mask;
+        z[1] ^= v[1] & mask;
+
+        let lsb = v[1] & 1;
+        v[1] = (v[1] >> 1) | (v[0] << 63);
+        v[0] = (v[0] >> 1) ^ (0xe1 << 56 & 0u64.wrapping_sub(lsb));
+    }
+    z
+}
+
+struct GHash {
+    h: Block,
+    acc: Block,
+}
+
+impl GHash {
+    fn new(h: Block) -> GHash {
+        GHash {
+            h: h,
+            acc: [0, 0],
+        }
+    }
+
+    // absorbs `data` zero-padded to the block size
+    fn update(&mut self, data: &[u8]) {
+        for chunk in data.chunks(16) {
+            let mut block = [0u8; 16];
+            block[..chunk.len()].copy_from_slice(chunk);
+            let x = load(&block);
+            self.acc[0] ^= x[0];
+            self.acc[1] ^= x[1];
+            self.acc = gf_mult(&self.acc, &self.h);
+        }
+    }
+
+    fn finish(mut self, ad_len: usize, data_len: usize) -> [u8; 16] {
+        let lengths = [(ad_len as u64) * 8, (data_len as u64) * 8];
+        self.update(&store(&lengths));
+        store(&self.acc)
+    }
+}
+
+pub struct AesGcm {
+    aes: Aes,
+    // SECRET. H = E(K, 0^128)
+    h: Block,
+}
+
+impl AesGcm {
+    pub fn new(key: &[u8]) -> AesGcm {
+        let aes = Aes::new(key);
+        let mut h = [0u8; 16];
+        aes.encrypt_block(&mut h);
+        AesGcm {
+            aes: aes,
+            h: load(&h),
+        }
+    }
+
+    // GCTR starting from counter block `inc32(J0)`
+    fn ctr(&self, nonce: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(data.len());
+        for (i, chunk) in data.chunks(16).enumerate() {
+            let counter = (i as u32).wrapping_add(2);
+            let mut block = counter_block(nonce, counter);
+            self.aes.encrypt_block(&mut block);
+            for (&d, &k) in chunk.iter().zip(block.iter()) {
+                out.push(d ^ k);
+            }
+        }
+        out
+    }
+
+    fn tag(&self, nonce: &[u8], ad: &[u8], encrypted: &[u8]) -> [u8; TAG_LEN] {
+        let mut ghash = GHash::new(self.h);
+        ghash.update(ad);
+        ghash.update(encrypted);
+        let mut tag = ghash.finish(ad.len(), encrypted.len());
+
+        let mut j0 = counter_block(nonce, 1);
+        self.aes.encrypt_block(&mut j0);
+        for i in 0..TAG_LEN {
+            tag[i] ^= j0[i];
+        }
+        tag
+    }
+
+    // returns `ciphertext || tag`.
+    pub fn seal(&self, nonce: &[u8], ad: &[u8], plain: &[u8]) -> Vec<u8> {
+        assert_eq!(nonce.len(), NONCE_LEN);
+        let mut encrypted = self.ctr(nonce, plain);
+        let tag = self.tag(nonce, ad, &encrypted);
+        encrypted.extend(&tag);
+        encrypted
+    }
+
+    // `data` is `ciphertext || tag`. returns `None` if the tag is wrong.
+    pub fn open(&self, nonce: &[u8], ad: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+        assert_eq!(nonce.len(), NONCE_LEN);
+        if data.len() < TAG_LEN {
+            return None;
+        }
+        let (encrypted, tag_expected) = data.split_at(data.len() - TAG_LEN);
+        let tag_computed = self.tag(nonce, ad, encrypted);
+        if !crypto_compare(&tag_computed, tag_expected) {
+            return None;
+        }
+        Some(self.ctr(nonce, encrypted))
+    }
+}
+
+// `nonce || counter` (SP 800-38D 7.1, for 96-bit IV)
+fn counter_block(nonce: &[u8], counter: u32) -> [u8; 16] {
+    let mut block = [0u8; 16];
+    block[..NONCE_LEN].copy_from_slice(nonce);
+    block[12] = (counter >> 24) as u8;
+    block[13] = (counter >> 16) as u8;
+    block[14] = (counter >> 8) as u8;
+    block[15] = counter as u8;
+    block
+}
+
+#[cfg(test)]
+mod test {
+    use test::unhex;
+    use super::AesGcm;
+
+    // test cases 1, 2 and 4 of "The Galois/Counter Mode of Operation (GCM)" by McGrew and Viega
+    #[test]
+    fn test_aes_gcm() {
+        let tests = [
+            ("00000000000000000000000000000000", "000000000000000000000000", "", "",
+             "", "58e2fccefa7e3061367f1d57a4e7455a"),
+            ("00000000000000000000000000000000", "000000000000000000000000", "",
+             "00000000000000000000000000000000",
+             "0388dace60b6a392f328c2b971b2fe78", "ab6e47d42cec13bdf53a67b21257bddf"),
+            ("feffe9928665731c6d6a8f9467308308", "cafebabefacedbaddecaf888",
+             "feedfacedeadbeeffeedfacedeadbeefabaddad2",
+             "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a72\
+              1c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39",
+             "42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e\
+              21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e091",
+             "5bc94fbc3221a5db94fae95ae7121a47"),
+        ];
+        for &(key, nonce, ad, plain, encrypted, tag) in tests.iter() {
+            let gcm = AesGcm::new(&unhex(key));
+            let nonce = unhex(nonce);
+            let mut expected = unhex(encrypted);
+            expected.extend(&unhex(tag));
+            let sealed = gcm.seal(&nonce, &unhex(ad), &unhex(plain));
+            assert_eq!(sealed, expected);
+            assert_eq!(gcm.open(&nonce, &unhex(ad), &sealed), Some(unhex(plain)));
+
+            let mut bad = sealed.clone();
+            let last = bad.len() - 1;
+            bad[last] ^= 1;
+            assert_eq!(gcm.open(&nonce, &unhex(ad), &bad), None);
+        }
+    }
+}
@@ -0,0 +1,56 @@
+// hash functions in this module, usable via a common interface.
+
+use crypto::sha1::sha1;
+use crypto::sha2::{sha256, sha384, sha512};
+
+pub struct HashFunction {
+    pub block_len: usize,
+    pub output_len: usize,
+    pub digest: fn(&[u8]) -> Vec<u8>,
+    // DER `DigestInfo` prefix for PKCS #1 v1.5 signatures (RFC 8017 9.2)
+    pub digest_info: &'static [u8],
+}
+
+fn sha1_vec(msg: &[u8]) -> Vec<u8> {
+    sha1(msg).to_vec()
+}
+
+fn sha256_vec(msg: &[u8]) -> Vec<u8> {
+    sha256(msg).to_vec()
+}
+
+fn sha384_vec(msg: &[u8]) -> Vec<u8> {
+    sha384(msg).to_vec()
+}
+
+fn sha512_vec(msg: &[u8]) -> Vec<u8> {
+    sha512(msg).to_vec()
+}
+
+pub static SHA1: HashFunction = HashFunction {
+    block_len: 64,
+    output_len: 20,
+    digest: sha1_vec,
+    digest_info: b"\x30\x21\x30\x09\x06\x05\x2b\x0e\x03\x02\x1a\x05\x00\x04\x14",
+};
+
+pub static SHA256: HashFunction = HashFunction {
+    block_len: 64,
+    output_len: 32,
+    digest: sha256_vec,
+    digest_info: b"\x30\x31\x30\x0d\x06\x09\x60\x86\x48\x01\x65\x03\x04\x02\x01\x05\x00\x04\x20",
+};
+
+pub static SHA384: HashFunction = HashFunction {
+    block_len: 128,
+    output_len: 48,
+    digest: sha384_vec,
+    digest_info: b"\x30\x41\x30\x0d\x06\x09\x60\x86\x48\x01\x65\x03\x04\x02\x02\x05\x00\x04\x30",
+};
+
+pub static SHA512: HashFunction = HashFunction {
+    block_len: 128,
+    output_len: 64,
+    digest: sha512_vec,
+    digest_info: b"\x30\x51\x30\x0d\x06\x09\x60\x86\x48\x01\x65\x03\x04\x02\x03\x05\x00\x04\x40",
+};
@@ -0,0 +1,45 @@
+// HMAC (RFC 2104) over hash functions in this module.
+
+use crypto::hash::HashFunction;
+
+// key is SECRET, but the length is publicly known.
+pub fn hmac(hash: &HashFunction, key: &[u8], msg: &[u8]) -> Vec<u8> {
+    let key = if key.len() > hash.block_len {
+        (hash.digest)(key)
+    } else {
+        key.to_vec()
+    };
+
+    let mut i_msg = vec![0x36u8; hash.block_len];
+    let mut o_msg = vec![0x5cu8; hash.block_len];
+    for i in 0..key.len() {
+        i_msg[i] ^= key[i];
+        o_msg[i] ^= key[i];
+    }
+
+    i_msg.extend(msg);
+    let h_i = (hash.digest)(&i_msg);
+    o_msg.extend(&h_i);
+    (hash.digest)(&o_msg)
+}
+
+#[cfg(test)]
+mod test {
+    use super::hmac;
+    use crypto::hash::{SHA1, SHA256};
+
+    #[test]
+    fn test_hmac() {
+        // RFC 2202 and RFC 4231 test case 6 (key longer than block size)
+        let key = [0xaau8; 131];
+        let msg = b"Test Using Larger Than Block-Size Key - Hash Key First";
+        let expected: &[u8] = b"\x60\xe4\x31\x59\x1e\xe0\xb6\x7f\x0d\x8a\x26\xaa\xcb\xf5\xb7\x7f\
+                                \x8e\x0b\xc6\x21\x37\x28\xc5\x14\x05\x46\x04\x0f\x0e\xe3\x7f\x54";
+        assert_eq!(&hmac(&SHA256, &key, msg)[..], expected);
+
+        let key = [0x0bu8; 20];
+        let expected: &[u8] = b"\xb6\x17\x31\x86\x55\x05\x72\x64\xe2\x8b\xc0\xb6\xfb\x37\x8c\x8e\
+                                \xf1\x46\xbe\x00";
+        assert_eq!(&hmac(&SHA1, &key, b"Hi There")[..], expected);
+    }
+}
@@ -0,0 +1,584 @@
+// ML-KEM-768 (FIPS 203).
+//
+// coefficients are `u32` in `0..Q`. arithmetic on secret values does not branch on or index
+// memory by them, and divisions by `Q` are done with multiplications (cf. KyberSlash).
+// decapsulation selects the implicit rejection secret with a mask.
+// comments refer to algorithm numbers of FIPS 203.
+
+use crypto::sha3::{Keccak, sha3_256, sha3_512};
+use util::crypto_compare;
+
+const N: usize = 256;
+const Q: u32 = 3329;
+const K: usize = 3;
+const ETA1: usize = 2;
+const ETA2: usize = 2;
+const DU: usize = 10;
+const DV: usize = 4;
+
+pub const ENCAPSULATION_KEY_LEN: usize = 384 * K + 32;
+pub const DECAPSULATION_KEY_LEN: usize = 768 * K + 96;
+pub const CIPHERTEXT_LEN: usize = 32 * (DU * K + DV);
+pub const SHARED_SECRET_LEN: usize = 32;
+/// length of the seeds `d`, `z` and `m`.
+pub const SEED_LEN: usize = 32;
+
+type Poly = [u32; N];
+
+// 17^BitRev7(i) mod Q
+static ZETAS: [u32; 128] = [
+    1, 1729, 2580, 3289, 2642, 630, 1897, 848, 1062, 1919, 193, 797, 2786, 3260, 569, 1746,
+    296, 2447, 1339, 1476, 3046, 56, 2240, 1333, 1426, 2094, 535, 2882, 2393, 2879, 1974, 821,
+    289, 331, 3253, 1756, 1197, 2304, 2277, 2055, 650, 1977, 2513, 632, 2865, 33, 1320, 1915,
+    2319, 1435, 807, 452, 1438, 2868, 1534, 2402, 2647, 2617, 1481, 648, 2474, 3110, 1227, 910,
+    17, 2761, 583, 2649, 1637, 723, 2288, 1100, 1409, 2662, 3281, 233, 756, 2156, 3015, 3050,
+    1703, 1651, 2789, 1789, 1847, 952, 1461, 2687, 939, 2308, 2437, 2388, 733, 2337, 268, 641,
+    1584, 2298, 2037, 3220, 375, 2549, 2090, 1645, 1063, 319, 2773, 757, 2099, 561, 2466, 2594,
+    2804, 1092, 403, 1026, 1143, 2150, 2775, 886, 1722, 1212, 1874, 1029, 2110, 2935, 885, 2154,
+];
+
+// 17^(2 BitRev7(i) + 1) mod Q
+static GAMMAS: [u32; 128] = [
+    17, 3312, 2761, 568, 583, 2746, 2649, 680, 1637, 1692, 723, 2606, 2288, 1041, 1100, 2229,
+    1409, 1920, 2662, 667, 3281, 48, 233, 3096, 756, 2573, 2156, 1173, 3015, 314, 3050, 279,
+    1703, 1626, 1651, 1678, 2789, 540, 1789, 1540, 1847, 1482, 952, 2377, 1461, 1868, 2687, 642,
+    939, 2390, 2308, 1021, 2437, 892, 2388, 941, 733, 2596, 2337, 992, 268, 3061, 641, 2688,
+    1584, 1745, 2298, 1031, 2037, 1292, 3220, 109, 375, 2954, 2549, 780, 2090, 1239, 1645, 1684,
+    1063, 2266, 319, 3010, 2773, 556, 757, 2572, 2099, 1230, 561, 2768, 2466, 863, 2594, 735,
+    2804, 525, 1092, 2237, 403, 2926, 1026, 2303, 1143, 2186, 2150, 1179, 2775, 554, 886, 2443,
+    1722, 1607, 1212, 2117, 1874, 1455, 1029, 2300, 2110, 1219, 2935, 394, 885, 2444, 2154, 1175,
+];
+
+// a mod Q for a < 2Q
+fn csub(a: u32) -> u32 {
+    let b = a.wrapping_sub(Q);
+    // all ones if a < Q
+    let mask = 0u32.wrapping_sub(b >> 31);
+    b ^ (mask & (a ^ b))
+}
+
+// a mod Q (Barrett reduction). `a - t * Q < 2Q` for every `a`.
+fn reduce(a: u32) -> u32 {
+    let t = ((a as u64 * 1290167) >> 32) as u32;
+    csub(a - t * Q)
+}
+
+fn mul(a: u32, b: u32) -> u32 {
+    reduce(a * b)
+}
+
+fn poly_add(f: &Poly, g: &Poly) -> Poly {
+    let mut h = [0; N];
+    for i in 0..N {
+        h[i] = csub(f[i] + g[i]);
+    }
+    h
+}
+
+fn poly_sub(f: &Poly, g: &Poly) -> Poly {
+    let mut h = [0; N];
+    for i in 0..N {
+        h[i] = csub(f[i] + Q - g[i]);
+    }
+    h
+}
+
+// Algorithm 9
+fn ntt(f: &mut Poly) {
+    let mut i = 1;
+    let mut len = 128;
+    while len >= 2 {
+        let mut start = 0;
+        while start < N {
+            let zeta = ZETAS[i];
+            i += 1;
+            for j in start..(start + len) {
+                let t = mul(zeta, f[j + len]);
+                f[j + len] = csub(f[j] + Q - t);
+                f[j] = csub(f[j] + t);
+            }
+            start += 2 * len;
+        }
+        len /= 2;
+    }
+}
+
+// Algorithm 10
+fn inv_ntt(f: &mut Poly) {
+    let mut i = 127;
+    let mut len = 2;
+    while len <= 128 {
+        let mut start = 0;
+        while start < N {
+            let zeta = ZETAS[i];
+            i -= 1;
+            for j in start..(start + len) {
+                let t = f[j];
+                f[j] = csub(t + f[j + len]);
+                f[j + len] = mul(zeta, f[j + len] + Q - t);
+            }
+            start += 2 * len;
+        }
+        len *= 2;
+    }
+    // 128^-1 mod Q
+    for c in f.iter_mut() {
+        *c = mul(*c, 3303);
+    }
+}
+
+// Algorithms 11 and 12
+fn multiply_ntts(f: &Poly, g: &Poly) -> Poly {
+    let mut h = [0; N];
+    for i in 0..128 {
+        let (a0, a1) = (f[2 * i], f[2 * i + 1]);
+        let (b0, b1) = (g[2 * i], g[2 * i + 1]);
+        h[2 * i] = reduce(a0 * b0 + mul(mul(a1, b1), GAMMAS[i]));
+        h[2 * i + 1] = reduce(a0 * b1 + a1 * b0);
+    }
+    h
+}
+
+// Algorithm 5. bits are little-endian.
+fn byte_encode(f: &Poly, d: usize, out: &mut Vec<u8>) {
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for &a in f.iter() {
+        acc |= a << bits;
+        bits += d;
+        while bits >= 8 {
+            out.push(acc as u8);
+            acc >>= 8;
+            bits -= 8;
+        }
+    }
+}
+
+// Algorithm 6. `b` is `32 * d` bytes.
+fn byte_decode(b: &[u8], d: usize) -> Poly {
+    debug_assert_eq!(b.len(), 32 * d);
+    let mut f = [0; N];
+    let mut acc = 0u32;
+    let mut bits = 0;
+    let mut bytes = b.iter();
+    for c in f.iter_mut() {
+        while bits < d {
+            acc |= (*bytes.next().unwrap() as u32) << bits;
+            bits += 8;
+        }
+        *c = acc & ((1 << d) - 1);
+        acc >>= d;
+        bits -= d;
+        // values of 12 bits are taken mod Q
+        if d == 12 {
+            *c = csub(*c);
+        }
+    }
+    f
+}
+
+// round(2^d x / Q) mod 2^d. `(n * 2580335) >> 33 == n / Q` for `n < 2^23`.
+fn compress(x: u32, d: usize) -> u32 {
+    let n = ((x as u64) << d) + (Q as u64 / 2);
+    ((n * 2580335) >> 33) as u32 & ((1 << d) - 1)
+}
+
+// round(Q y / 2^d)
+fn decompress(y: u32, d: usize) -> u32 {
+    (y * Q + (1 << (d - 1))) >> d
+}
+
+fn compress_poly(f: &Poly, d: usize) -> Poly {
+    let mut g = [0; N];
+    for i in 0..N {
+        g[i] = compress(f[i], d);
+    }
+    g
+}
+
+fn decompress_poly(f: &Poly, d: usize) -> Poly {
+    let mut g = [0; N];
+    for i in 0..N {
+        g[i] = decompress(f[i], d);
+    }
+    g
+}
+
+// Algorithm 7. the input is `rho || j || i`. samples are public.
+fn sample_ntt(rho: &[u8], j: u8, i: u8) -> Poly {
+    let mut xof = Keccak::shake128();
+    xof.update(rho);
+    xof.update(&[j, i]);
+
+    let mut a = [0; N];
+    let mut n = 0;
+    let mut c = [0u8; 3];
+    while n < N {
+        xof.squeeze(&mut c);
+        let d1 = c[0] as u32 + 256 * (c[1] as u32 % 16);
+        let d2 = c[1] as u32 / 16 + 16 * c[2] as u32;
+        if d1 < Q {
+            a[n] = d1;
+            n += 1;
+        }
+        if d2 < Q && n < N {
+            a[n] = d2;
+            n += 1;
+        }
+    }
+    a
+}
+
+// Algorithm 8. `b` is `64 * eta` bytes.
+fn sample_cbd(b: &[u8], eta: usize) -> Poly {
+    let bit = |k: usize| ((b[k / 8] >> (k % 8)) & 1) as u32;
+    let mut f = [0; N];
+    for i in 0..N {
+        let mut x = 0;
+        let mut y = 0;
+        for j in 0..eta {
+            x += bit(2 * i * eta + j);
+            y += bit(2 * i * eta + eta + j);
+        }
+        f[i] = csub(x + Q - y);
+    }
+    f
+}
+
+// PRF_eta(s, b) = SHAKE256(s || b, 64 * eta)
+fn prf(eta: usize, s: &[u8], b: u8) -> Vec<u8> {
+    let mut shake = Keccak::shake256();
+    shake.update(s);
+    shake.update(&[b]);
+    let mut out = vec![0; 64 * eta];
+    shake.squeeze(&mut out);
+    out
+}
+
+// `A[i][j]` is `a[i * K + j]`, in the NTT domain.
+fn matrix_a(rho: &[u8]) -> Vec<Poly> {
+    let mut a = Vec::with_capacity(K * K);
+    for i in 0..K {
+        for j in 0..K {
+            a.push(sample_ntt(rho, j as u8, i as u8));
+        }
+    }
+    a
+}
+
+// samples `K` polynomials with PRF counters starting at `*n`.
+fn sample_vector(eta: usize, sigma: &[u8], n: &mut u8) -> Vec<Poly> {
+    let mut v = Vec::with_capacity(K);
+    for _ in 0..K {
+        v.push(sample_cbd(&prf(eta, sigma, *n), eta));
+        *n += 1;
+    }
+    v
+}
+
+// Algorithm 13. returns `(ek, dk)`.
+fn pke_key_gen(d: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let mut seed = d.to_vec();
+    seed.push(K as u8);
+    let g = sha3_512(&seed);
+    let (rho, sigma) = g.split_at(32);
+
+    let a = matrix_a(rho);
+    let mut n = 0;
+    let mut s = sample_vector(ETA1, sigma, &mut n);
+    let mut e = sample_vector(ETA1, sigma, &mut n);
+    for p in s.iter_mut().chain(e.iter_mut()) {
+        ntt(p);
+    }
+
+    // t = A s + e
+    let mut ek = Vec::with_capacity(ENCAPSULATION_KEY_LEN);
+    for i in 0..K {
+        let mut t = e[i];
+        for j in 0..K {
+            t = poly_add(&t, &multiply_ntts(&a[i * K + j], &s[j]));
+        }
+        byte_encode(&t, 12, &mut ek);
+    }
+    ek.extend_from_slice(rho);
+
+    let mut dk = Vec::with_capacity(384 * K);
+    for p in s.iter() {
+        byte_encode(p, 12, &mut dk);
+    }
+    (ek, dk)
+}
+
+// Algorithm 14
+fn pke_encrypt(ek: &[u8], m: &[u8], r: &[u8]) -> Vec<u8> {
+    let t: Vec<Poly> = (0..K).map(|i| byte_decode(&ek[(384 * i)..(384 * (i + 1))], 12)).collect();
+    let a = matrix_a(&ek[(384 * K)..]);
+
+    let mut n = 0;
+    let mut y = sample_vector(ETA1, r, &mut n);
+    let e1 = sample_vector(ETA2, r, &mut n);
+    let e2 = sample_cbd(&prf(ETA2, r, n), ETA2);
+    for p in y.iter_mut() {
+        ntt(p);
+    }
+
+    let mut c = Vec::with_capacity(CIPHERTEXT_LEN);
+
+    // u = NTT^-1(A^T y) + e1
+    for i in 0..K {
+        let mut u = [0; N];
+        for j in 0..K {
+            u = poly_add(&u, &multiply_ntts(&a[j * K + i], &y[j]));
+        }
+        inv_ntt(&mut u);
+        let u = poly_add(&u, &e1[i]);
+        byte_encode(&compress_poly(&u, DU), DU, &mut c);
+    }
+
+    // v = NTT^-1(t^T y) + e2 + mu
+    let mut v = [0; N];
+    for i in 0..K {
+        v = poly_add(&v, &multiply_ntts(&t[i], &y[i]));
+    }
+    inv_ntt(&mut v);
+    let mu = decompress_poly(&byte_decode(m, 1), 1);
+    let v = poly_add(&poly_add(&v, &e2), &mu);
+    byte_encode(&compress_poly(&v, DV), DV, &mut c);
+
+    c
+}
+
+// Algorithm 15
+fn pke_decrypt(dk: &[u8], c: &[u8]) -> Vec<u8> {
+    let (c1, c2) = c.split_at(32 * DU * K);
+
+    // w = v - NTT^-1(s^T NTT(u))
+    let mut w = [0; N];
+    for i in 0..K {
+        let mut u = decompress_poly(&byte_decode(&c1[(32 * DU * i)..(32 * DU * (i + 1))], DU),
+                                    DU);
+        ntt(&mut u);
+        let s = byte_decode(&dk[(384 * i)..(384 * (i + 1))], 12);
+        w = poly_add(&w, &multiply_ntts(&s, &u));
+    }
+    inv_ntt(&mut w);
+    let v = decompress_poly(&byte_decode(c2, DV), DV);
+    let w = poly_sub(&v, &w);
+
+    let mut m = Vec::with_capacity(32);
+    byte_encode(&compress_poly(&w, 1), 1, &mut m);
+    m
+}
+
+/// Generates a key pair `(ek, dk)` from the 32-byte seeds `d` and `z` (Algorithm 16).
+/// The seeds are SECRET and must be random.
+pub fn key_gen(d: &[u8], z: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    assert_eq!(d.len(), SEED_LEN);
+    assert_eq!(z.len(), SEED_LEN);
+
+    let (ek, mut dk) = pke_key_gen(d);
+    dk.extend_from_slice(&ek);
+    dk.extend_from_slice(&sha3_256(&ek));
+    dk.extend_from_slice(z);
+    (ek, dk)
+}
+
+/// Encapsulates to `ek` with the 32-byte SECRET random `m` (Algorithm 17),
+/// returning `(shared_secret, ciphertext)`. Returns `None` if `ek` is invalid (7.2).
+pub fn encaps(ek: &[u8], m: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
+    assert_eq!(m.len(), SEED_LEN);
+
+    if ek.len() != ENCAPSULATION_KEY_LEN {
+        return None;
+    }
+    // modulus check: every coefficient is less than Q
+    let mut reencoded = Vec::with_capacity(384 * K);
+    for i in 0..K {
+        byte_encode(&byte_decode(&ek[(384 * i)..(384 * (i + 1))], 12), 12, &mut reencoded);
+    }
+    if reencoded[..] != ek[..(384 * K)] {
+        return None;
+    }
+
+    let mut seed = m.to_vec();
+    seed.extend_from_slice(&sha3_256(ek));
+    let g = sha3_512(&seed);
+    let (shared_secret, r) = g.split_at(32);
+    let c = pke_encrypt(ek, m, r);
+    Some((shared_secret.to_vec(), c))
+}
+
+/// Decapsulates `c` with `dk` (Algorithm 18). Returns `None` if the lengths are wrong
+/// or the hash check of `dk` fails (7.3). Invalid ciphertexts give a pseudorandom secret.
+pub fn decaps(dk: &[u8], c: &[u8]) -> Option<Vec<u8>> {
+    if dk.len() != DECAPSULATION_KEY_LEN || c.len() != CIPHERTEXT_LEN {
+        return None;
+    }
+    let (dk_pke, rest) = dk.split_at(384 * K);
+    let (ek, rest) = rest.split_at(ENCAPSULATION_KEY_LEN);
+    let (h, z) = rest.split_at(32);
+    if &sha3_256(ek)[..] != h {
+        return None;
+    }
+
+    let m = pke_decrypt(dk_pke, c);
+    let mut seed = m.clone();
+    seed.extend_from_slice(h);
+    let g = sha3_512(&seed);
+    let (shared_secret, r) = g.split_at(32);
+
+    let mut j = Keccak::shake256();
+    j.update(z);
+    j.update(c);
+    let mut rejected = [0u8; SHARED_SECRET_LEN];
+    j.squeeze(&mut rejected);
+
+    // re-encrypt, and select without branching
+    let c2 = pke_encrypt(ek, &m, r);
+    let mask = 0u8.wrapping_sub(crypto_compare(c, &c2) as u8);
+    let ret = shared_secret.iter()
+                           .zip(rejected.iter())
+                           .map(|(&k, &rejected)| rejected ^ (mask & (k ^ rejected)))
+                           .collect();
+    Some(ret)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{key_gen, encaps, decaps, ENCAPSULATION_KEY_LEN, DECAPSULATION_KEY_LEN,
+                CIPHERTEXT_LEN};
+    use std::collections::HashMap;
+
+    use crypto::sha3::sha3_256;
+    use test::unhex;
+
+    // cases of `testdata/mlkem768_acvp.txt` with their section.
+    // fields of blocks without `tcId` are shared by the cases after them.
+    fn acvp_cases() -> Vec<(&'static str, HashMap<&'static str, &'static str>)> {
+        static DATA: &'static str = include_str!("../../testdata/mlkem768_acvp.txt");
+
+        let mut cases = Vec::new();
+        let mut section = "";
+        let mut shared = HashMap::new();
+        for block in DATA.split("\n\n") {
+            let block = block.trim();
+            if block.starts_with('[') {
+                section = block.trim_matches(|c| c == '[' || c == ']');
+                shared.clear();
+                continue;
+            }
+            let mut fields = HashMap::new();
+            for line in block.lines().filter(|line| !line.starts_with('#')) {
+                let mut parts = line.splitn(2, " = ");
+                fields.insert(parts.next().unwrap(), parts.next().unwrap());
+            }
+            if !fields.contains_key("tcId") {
+                shared.extend(fields);
+                continue;
+            }
+            for (name, value) in shared.iter() {
+                fields.insert(*name, *value);
+            }
+            cases.push((section, fields));
+        }
+        cases
+    }
+
+    #[test]
+    fn test_known_answers() {
+        // (d, z, m, SHA3-256(ek), SHA3-256(dk), SHA3-256(c), shared secret,
+        //  shared secret of `c` with the lowest bit flipped)
+        // generated with the ML-KEM-768 of OpenSSL 3.5.6 from fixed seeds. These are
+        // cross-checks against another implementation; see `test_acvp` for official vectors.
+        static ANSWERS: &'static [(&'static str, &'static str, &'static str, &'static str,
+                                   &'static str, &'static str, &'static str, &'static str)] = &[
+            ("8a15f36840726357e69cca9a5b55d10c4007595e9c86bb7e6098b0b758a87a81",
+             "305477b058eba4560cc3a6ae25892e33ae04f30ecf8ae83e4a9ab70840b21cf5",
+             "71c19b34fa02f78148baf0fce1a8dcb060e426afbf1e4c87a882c4052ade34f8",
+             "c4242b8c9e68d9e2d56119eae9387bcb4ec2125b9431e8bc4caa4cab7d39f4d6",
+             "a986cf9206e45d8effc25fc2ab3542f4905e4d46b37d176ecc0a69643cf918ea",
+             "47a085997619c92f069f3625a676cff6d4abfbbc395e84947b6971b755d9a237",
+             "9a8c4d73e5a960608fceb6a1d8a932ae187196329726f76f61950f6b128fe813",
+             "1ca9392d28d820d8153b2d5951669d2ef0565253c7e5dd994dd85aeec90e586f"),
+            ("c89e9de8b62b83a4f8e3d3226ca011da8ece5347530d63a54b5efe19a6de75db",
+             "931e8c20b7136476959386f36312ae400656b7c79c4e198c18a8beb2a8688174",
+             "3e92e4a94bdfcacdffd3af9becf37e120fff6059705b9cdb3b37850e8b94c76a",
+             "729bfd90024255275e7d585a357fe06e7d07535830bd09f2f4ad36db88c09391",
+             "672def60d15c7e3f34d74d46cf93efe76a8c03203a2298c7ea6aa04897ae11a5",
+             "dfb26ac1015950bac29ecf42ae782889e3b04a80a78b3eb5247917bc1d0b7e67",
+             "fa31a011faec143944996fc08a2b7d1ec27e4fc33b24f5034943e76dc50bbc80",
+             "f4164f34386b782b2349b34e671cf3989e6fd670020b2229d20775811cd8934e"),
+            ("ad74ac378731f2e470d0192a33f2485649388a9edcfb91c2f8403aa868eb179b",
+             "13132a673f33c1822dde78581b4ab00d4c226f51b57593f833887b5b3feacb76",
+             "51841ad69188cef539a5e7a1389f4a821cf2b5ce0dad3cbbefccce3a9f4c7a3d",
+             "d3d82cda6df8ea8c27bf46af5e6e6e7693581932d650feda493e3e68614b6b5e",
+             "707b2875538e87c62292c0e4b068092c7b8816c4b9f315a11cc9866e3912b046",
+             "1e1b13bba833c92041c49b7024582e802d7e210803d1cbca6dc305d1e0cd348d",
+             "e9946aa7f7accfa85a5a3665b1097756887a80eefa811135fecf60e4a2794c3e",
+             "3c065f2d370c3d6079d4bb6d2561922450a14d72ecf3864717c20395ed3e98c7"),
+        ];
+
+        for &(d, z, m, ek_hash, dk_hash, c_hash, ss, rejected) in ANSWERS.iter() {
+            let (ek, dk) = key_gen(&unhex(d), &unhex(z));
+            assert_eq!(ek.len(), ENCAPSULATION_KEY_LEN);
+            assert_eq!(dk.len(), DECAPSULATION_KEY_LEN);
+            assert_eq!(&sha3_256(&ek)[..], &unhex(ek_hash)[..]);
+            assert_eq!(&sha3_256(&dk)[..], &unhex(dk_hash)[..]);
+
+            let (shared_secret, c) = encaps(&ek, &unhex(m)).unwrap();
+            assert_eq!(c.len(), CIPHERTEXT_LEN);
+            assert_eq!(&sha3_256(&c)[..], &unhex(c_hash)[..]);
+            assert_eq!(shared_secret, unhex(ss));
+            assert_eq!(decaps(&dk, &c).unwrap(), unhex(ss));
+
+            let mut c = c;
+            c[0] ^= 1;
+            assert_eq!(decaps(&dk, &c).unwrap(), unhex(rejected));
+        }
+    }
+
+    #[test]
+    fn test_acvp() {
+        let cases = acvp_cases();
+        assert_eq!(cases.len(), 16);
+        for &(section, ref case) in cases.iter() {
+            let field = |name: &str| unhex(case[name]);
+            match section {
+                "keyGen" => {
+                    let (ek, dk) = key_gen(&field("d"), &field("z"));
+                    assert_eq!(ek, field("ek"));
+                    assert_eq!(dk, field("dk"));
+                }
+                "encapsulation" => {
+                    let (k, c) = encaps(&field("ek"), &field("m")).unwrap();
+                    assert_eq!(c, field("c"));
+                    assert_eq!(k, field("k"));
+                }
+                // `reason = modify ciphertext` cases take the implicit rejection path
+                "decapsulation" => {
+                    assert_eq!(decaps(&field("dk"), &field("c")).unwrap(), field("k"));
+                }
+                _ => panic!("unknown section: {}", section),
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_keys() {
+        let (ek, dk) = key_gen(&[1; 32], &[2; 32]);
+        let (_, c) = encaps(&ek, &[3; 32]).unwrap();
+
+        assert!(encaps(&ek[1..], &[3; 32]).is_none());
+        // the first coefficient is 0xfff >= Q
+        let mut bad_ek = ek.clone();
+        bad_ek[0] = 0xff;
+        bad_ek[1] |= 0x0f;
+        assert!(encaps(&bad_ek, &[3; 32]).is_none());
+
+        assert!(decaps(&dk, &c[1..]).is_none());
+        // the stored hash of ek no longer matches
+        let mut bad_dk = dk.clone();
+        bad_dk[384 * 3] ^= 1;
+        assert!(decaps(&bad_dk, &c).is_none());
+    }
+}
@@ -2,7 +2,20 @@
 
 pub mod wrapping;
 
+pub mod sha1;
 pub mod sha2;
+pub mod sha3;
+pub mod hash;
+pub mod hmac;
+pub mod bigint;
 pub mod p256;
+pub mod p384;
+pub mod rsa;
+pub mod ecdsa;
+pub mod ed25519;
+pub mod x25519;
+pub mod mlkem;
 pub mod poly1305;
 pub mod chacha20;
+pub mod aes;
+pub mod gcm;
@@ -124,7 +124,11 @@ impl Point256 {
         ret
     }
 
-    fn add(&self, b: &Point256) -> Point256 {
+    pub fn is_infinity(&self) -> bool {
+        self.z.compare(&ZERO).0 == 0
+    }
+
+    pub fn add(&self, b: &Point256) -> Point256 {
         let self_is_zero = self.z.compare(&ZERO);
         let b_is_zero = b.z.compare(&ZERO);
 
@@ -191,8 +195,8 @@ impl Point256 {
 
     pub fn mult_scalar(&self, n: &Int256) -> Point256 {
         let mut ret = INFTY.clone();
-        for i in (0..7).rev() {
-            for j in (0..8).rev() {
+        for i in (0..8).rev() {
+            for j in (0..32).rev() {
                 let bit = (n.v[i] >> j) & W(1);
 
                 let ret2 = ret.double();
@@ -260,6 +264,50 @@ impl NPoint256 {
         Some(p)
     }
 
+    // 0x02 or 0x03 (parity of y) || x (big endian)
+    pub fn from_compressed_bytes(data: &[u8]) -> Option<NPoint256> {
+        if data.len() != 1 + 32 {
+            return None;
+        }
+        if data[0] != 0x02 && data[0] != 0x03 {
+            return None;
+        }
+
+        let x = match Int256::from_bytes(&data[1..]) {
+            Some(x) => x,
+            None => return None,
+        };
+        // x must be reduced
+        if x.reduce_once_zero().compare(&x).0 != 0 {
+            return None;
+        }
+
+        // y^2 = x^3 - 3 * x + B
+        let x3 = x.square().mult(&x);
+        let rhs = x3.sub(&x.double().add(&x)).add(&B);
+        let y = rhs.sqrt();
+        if y.square().compare(&rhs).0 != 0 {
+            // no point with this x
+            return None;
+        }
+
+        // there is no point with y == 0, so y and -y have different parity
+        let flip = (y.v[0] ^ w8(data[0]).to_w32()) & W(1);
+        let y = Int256::choose(flip, &y, &ZERO.sub(&y));
+
+        Some(NPoint256 {
+            x: x,
+            y: y,
+        })
+    }
+
+    pub fn to_compressed_bytes(&self) -> Vec<u8> {
+        let mut b = Vec::with_capacity(1 + 256 / 8);
+        b.push(0x02 | (self.y.v[0] & W(1)).to_w8().0);
+        b.extend(&self.x.to_bytes());
+        b
+    }
+
     pub fn to_uncompressed_bytes(&self) -> Vec<u8> {
         // 0x04 || self.x (big endian) || self.y (big endian)
         let mut b = Vec::with_capacity(1 + (256 / 8) * 2);
@@ -270,6 +318,83 @@ impl NPoint256 {
     }
 }
 
+#[cfg(test)]
+mod test {
+    use crypto::wrapping::Wrapping as W;
+    use super::{G, NPoint256};
+    use super::int256::{Int256, P256, ZERO};
+
+    #[test]
+    fn test_mult_scalar() {
+        // n - 1, where n is the order of G
+        let n1 = Int256::from_bytes(b"\xff\xff\xff\xff\x00\x00\x00\x00\xff\xff\xff\xff\xff\xff\xff\xff\
+                                      \xbc\xe6\xfa\xad\xa7\x17\x9e\x84\xf3\xb9\xca\xc2\xfc\x63\x25\x50")
+                     .unwrap();
+        // (n - 1) G == -G
+        let p = G.mult_scalar(&n1).normalize();
+        let g = G.normalize();
+        assert_eq!(p.x.compare(&g.x).0, 0);
+        assert_eq!(p.y.compare(&P256.sub(&g.y)).0, 0);
+
+        // n G == O
+        let n = n1.add(&Int256::from_bytes(&[0; 31].iter().chain(&[1]).cloned()
+                                                   .collect::<Vec<u8>>()).unwrap());
+        assert!(G.mult_scalar(&n).is_infinity());
+
+        // k = 112233445566778899 (http://point-at-infinity.org/ecc/nisttv)
+        let k = Int256::from_bytes(b"\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+                                     \x00\x00\x00\x00\x00\x00\x00\x00\x01\x8e\xbb\xb9\x5e\xed\x0e\x13")
+                    .unwrap();
+        let expected = NPoint256::from_uncompressed_bytes(
+            b"\x04\x33\x91\x50\x84\x4e\xc1\x52\x34\x80\x7f\xe8\x62\xa8\x6b\xe7\x79\
+              \x77\xdb\xfb\x3a\xe3\xd9\x6f\x4c\x22\x79\x55\x13\xae\xaa\xb8\x2f\
+              \xb1\xc1\x4d\xdf\xdc\x8e\xc1\xb2\x58\x3f\x51\xe8\x5a\x5e\xb3\xa1\
+              \x55\x84\x0f\x20\x34\x73\x0e\x9b\x5a\xda\x38\xb6\x74\x33\x6a\x21")
+            .unwrap();
+        let p = G.mult_scalar(&k).normalize();
+        assert_eq!(p.x.compare(&expected.x).0, 0);
+        assert_eq!(p.y.compare(&expected.y).0, 0);
+    }
+
+    #[test]
+    fn test_compressed() {
+        // 2 G has odd y, 3 G has even y
+        let mut two = ZERO;
+        two.v[0] = W(2);
+        let mut three = ZERO;
+        three.v[0] = W(3);
+        let compressed: [(&Int256, &[u8]); 2] = [
+            (&two, b"\x03\x7c\xf2\x7b\x18\x8d\x03\x4f\x7e\x8a\x52\x38\x03\x04\xb5\x1a\
+                    \xc3\xc0\x89\x69\xe2\x77\xf2\x1b\x35\xa6\x0b\x48\xfc\x47\x66\x99\
+                    \x78"),
+            (&three, b"\x02\x5e\xcb\xe4\xd1\xa6\x33\x0a\x44\xc8\xf7\xef\x95\x1d\x4b\xf1\
+                      \x65\xe6\xc6\xb7\x21\xef\xad\xa9\x85\xfb\x41\x66\x1b\xc6\xe7\xfd\
+                      \x6c"),
+        ];
+        for &(k, expected) in compressed.iter() {
+            let p = G.mult_scalar(k).normalize();
+            assert_eq!(p.to_compressed_bytes(), expected);
+            let q = NPoint256::from_compressed_bytes(expected).unwrap();
+            assert_eq!(q.to_uncompressed_bytes(), p.to_uncompressed_bytes());
+        }
+
+        // x = 1: x^3 - 3 x + B is not a square
+        let mut data = vec![0; 33];
+        data[0] = 0x02;
+        data[32] = 1;
+        assert!(NPoint256::from_compressed_bytes(&data).is_none());
+
+        // x = P256 is not reduced
+        let mut data = vec![0x02];
+        data.extend(&P256.to_bytes());
+        assert!(NPoint256::from_compressed_bytes(&data).is_none());
+
+        let mut data = G.normalize().to_compressed_bytes();
+        data[0] = 0x04;
+        assert!(NPoint256::from_compressed_bytes(&data).is_none());
+    }
+}
+
 pub mod int256 {
     use crypto::wrapping::*;
     use crypto::wrapping::Wrapping as W;
@@ -297,6 +422,22 @@ pub mod int256 {
         }
     }
 
+    // compute a^(2^n)
+    fn square_n(a: &Int256, n: usize) -> Int256 {
+        let mut y = a.clone();
+        for _ in (0..n) {
+            y = y.square();
+        }
+        y
+    }
+
+    // compute z^(2^n + 1)
+    // if z == a^(2^n - 1), it returns a^(2^(2n) - 1)
+    fn z_n(z: &Int256, n: usize) -> Int256 {
+        let y = square_n(z, n);
+        y.mult(z)
+    }
+
     impl Int256 {
         // return 0 if self == b.
         // otherwise return 1.
@@ -504,22 +645,6 @@ pub mod int256 {
             // 2^256 = (2^32)^8
             // 2^224 = (2^32)^7
 
-            // compute a^(2^n)
-            fn square_n(a: &Int256, n: usize) -> Int256 {
-                let mut y = a.clone();
-                for _ in (0..n) {
-                    y = y.square();
-                }
-                y
-            }
-
-            // compute z^(2^n + 1)
-            // if z == self^(2^n - 1), it returns self^(2^(2n) - 1)
-            fn z_n(z: &Int256, n: usize) -> Int256 {
-                let y = square_n(z, n);
-                y.mult(z)
-            }
-
             // for given z_n = a^(2^n - 1), return z_{n+1} = a^(2^(n+1) - 1)
             fn z_1(z: &Int256, a: &Int256) -> Int256 {
                 z.square().mult(a)
@@ -557,6 +682,22 @@ pub mod int256 {
             y256_224.mult(&z192).mult(&y96_2)
         }
 
+        // return self^((P256 + 1) / 4), which is a square root of self if one exists
+        // since P256 == 3 mod 4. the caller must check that the result squares to self.
+        pub fn sqrt(&self) -> Int256 {
+            // (P256 + 1) / 4 = 2^254 - 2^222 + 2^190 + 2^94
+            //                = 2^94 (2^96 (2^32 (2^32 - 1) + 1) + 1)
+            let z2 = z_n(self, 1);
+            let z4 = z_n(&z2, 2);
+            let z8 = z_n(&z4, 4);
+            let z16 = z_n(&z8, 8);
+            let z32 = z_n(&z16, 16);
+
+            let y = square_n(&z32, 32).mult(self);
+            let y = square_n(&y, 96).mult(self);
+            square_n(&y, 94)
+        }
+
         pub fn divide_by_2(&self) -> Int256 {
             let is_odd = self.v[0] & W(1);
 
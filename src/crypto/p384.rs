@@ -0,0 +1,343 @@
+// NIST P-384 (FIPS 186-4 D.1.2.4).
+//
+// field arithmetic is `bigint::Modulus`, which does not branch on or index memory by values.
+// points are in projective coordinates and added with the complete formulas of
+// Renes, Costello and Batina (https://eprint.iacr.org/2015/1060, algorithm 4),
+// so doubling, the identity and `P + (-P)` need no special cases.
+
+use crypto::bigint::Modulus;
+
+pub const BYTE_LEN: usize = 48;
+
+// p = 2^384 - 2^128 - 2^96 + 2^32 - 1
+static P: &'static [u8] = b"\
+    \xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\
+    \xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xfe\
+    \xff\xff\xff\xff\x00\x00\x00\x00\x00\x00\x00\x00\xff\xff\xff\xff";
+
+// (p + 1) / 4. since p == 3 mod 4, `a^SQRT_EXP` is a square root of `a` if one exists.
+static SQRT_EXP: &'static [u8] = b"\
+    \x3f\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\
+    \xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\
+    \xbf\xff\xff\xff\xc0\x00\x00\x00\x00\x00\x00\x00\x40\x00\x00\x00";
+
+// order of G
+pub static N: &'static [u8] = b"\
+    \xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\
+    \xff\xff\xff\xff\xff\xff\xff\xff\xc7\x63\x4d\x81\xf4\x37\x2d\xdf\
+    \x58\x1a\x0d\xb2\x48\xb0\xa7\x7a\xec\xec\x19\x6a\xcc\xc5\x29\x73";
+
+static B: &'static [u8] = b"\
+    \xb3\x31\x2f\xa7\xe2\x3e\xe7\xe4\x98\x8e\x05\x6b\xe3\xf8\x2d\x19\
+    \x18\x1d\x9c\x6e\xfe\x81\x41\x12\x03\x14\x08\x8f\x50\x13\x87\x5a\
+    \xc6\x56\x39\x8d\x8a\x2e\xd1\x9d\x2a\x85\xc8\xed\xd3\xec\x2a\xef";
+
+static GX: &'static [u8] = b"\
+    \xaa\x87\xca\x22\xbe\x8b\x05\x37\x8e\xb1\xc7\x1e\xf3\x20\xad\x74\
+    \x6e\x1d\x3b\x62\x8b\xa7\x9b\x98\x59\xf7\x41\xe0\x82\x54\x2a\x38\
+    \x55\x02\xf2\x5d\xbf\x55\x29\x6c\x3a\x54\x5e\x38\x72\x76\x0a\xb7";
+
+static GY: &'static [u8] = b"\
+    \x36\x17\xde\x4a\x96\x26\x2c\x6f\x5d\x9e\x98\xbf\x92\x92\xdc\x29\
+    \xf8\xf4\x1d\xbd\x28\x9a\x14\x7c\xe9\xda\x31\x13\xb5\xf0\xb8\xc0\
+    \x0a\x60\xb1\xce\x1d\x7e\x81\x9d\x7a\x43\x1d\x7c\x90\xea\x0e\x5f";
+
+// if flag == 1, returns b. if flag == 0, returns a.
+fn choose(flag: u32, a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mask = 0u32.wrapping_sub(flag);
+    a.iter().zip(b.iter()).map(|(&a, &b)| a ^ (mask & (a ^ b))).collect()
+}
+
+// (x, y, z): (X, Y) = (x/z, y/z) is a point of Y^2 = X^3 - 3 X + B.
+// the identity is (0, 1, 0).
+#[derive(Clone)]
+pub struct Point384 {
+    x: Vec<u32>,
+    y: Vec<u32>,
+    z: Vec<u32>,
+}
+
+// normalized
+pub struct NPoint384 {
+    // big-endian
+    pub x: Vec<u8>,
+    pub y: Vec<u8>,
+}
+
+pub struct P384 {
+    p: Modulus,
+    b: Vec<u32>,
+}
+
+impl P384 {
+    pub fn new() -> P384 {
+        let p = Modulus::new(P).unwrap();
+        let b = p.from_bytes(B).unwrap();
+        P384 {
+            p: p,
+            b: b,
+        }
+    }
+
+    pub fn generator(&self) -> Point384 {
+        Point384 {
+            x: self.p.from_bytes(GX).unwrap(),
+            y: self.p.from_bytes(GY).unwrap(),
+            z: self.p.one(),
+        }
+    }
+
+    pub fn infinity(&self) -> Point384 {
+        Point384 {
+            x: self.p.zero(),
+            y: self.p.one(),
+            z: self.p.zero(),
+        }
+    }
+
+    pub fn is_infinity(&self, a: &Point384) -> bool {
+        self.p.is_nonzero(&a.z) == 0
+    }
+
+    fn choose(&self, flag: u32, a: &Point384, b: &Point384) -> Point384 {
+        Point384 {
+            x: choose(flag, &a.x, &b.x),
+            y: choose(flag, &a.y, &b.y),
+            z: choose(flag, &a.z, &b.z),
+        }
+    }
+
+    pub fn add(&self, a: &Point384, b: &Point384) -> Point384 {
+        let f = &self.p;
+        let (x1, y1, z1) = (&a.x, &a.y, &a.z);
+        let (x2, y2, z2) = (&b.x, &b.y, &b.z);
+
+        let t0 = f.mul(x1, x2);
+        let t1 = f.mul(y1, y2);
+        let t2 = f.mul(z1, z2);
+        let t3 = f.mul(&f.add(x1, y1), &f.add(x2, y2));
+        let t3 = f.sub(&t3, &f.add(&t0, &t1));
+        let t4 = f.mul(&f.add(y1, z1), &f.add(y2, z2));
+        let t4 = f.sub(&t4, &f.add(&t1, &t2));
+        let x3 = f.mul(&f.add(x1, z1), &f.add(x2, z2));
+        let y3 = f.sub(&x3, &f.add(&t0, &t2));
+        let z3 = f.mul(&self.b, &t2);
+        let x3 = f.sub(&y3, &z3);
+        let z3 = f.add(&x3, &x3);
+        let x3 = f.add(&x3, &z3);
+        let z3 = f.sub(&t1, &x3);
+        let x3 = f.add(&t1, &x3);
+        let y3 = f.mul(&self.b, &y3);
+        let t1 = f.add(&t2, &t2);
+        let t2 = f.add(&t1, &t2);
+        let y3 = f.sub(&f.sub(&y3, &t2), &t0);
+        let t1 = f.add(&y3, &y3);
+        let y3 = f.add(&t1, &y3);
+        let t1 = f.add(&t0, &t0);
+        let t0 = f.sub(&f.add(&t1, &t0), &t2);
+        let t1 = f.mul(&t4, &y3);
+        let t2 = f.mul(&t0, &y3);
+        let y3 = f.add(&f.mul(&x3, &z3), &t2);
+        let x3 = f.sub(&f.mul(&t3, &x3), &t1);
+        let z3 = f.add(&f.mul(&t4, &z3), &f.mul(&t3, &t0));
+
+        Point384 {
+            x: x3,
+            y: y3,
+            z: z3,
+        }
+    }
+
+    // `n` is big-endian. only its length is leaked.
+    pub fn mult_scalar(&self, a: &Point384, n: &[u8]) -> Point384 {
+        let mut ret = self.infinity();
+        for &byte in n.iter() {
+            for j in (0..8).rev() {
+                let bit = ((byte >> j) & 1) as u32;
+                let ret2 = self.add(&ret, &ret);
+                let ret3 = self.add(&ret2, a);
+                ret = self.choose(bit, &ret2, &ret3);
+            }
+        }
+        ret
+    }
+
+    // returns `None` for the identity.
+    pub fn normalize(&self, a: &Point384) -> Option<NPoint384> {
+        if self.is_infinity(a) {
+            return None;
+        }
+        let z_inv = self.p.inverse_prime(&a.z);
+        Some(NPoint384 {
+            x: self.p.to_bytes(&self.p.mul(&a.x, &z_inv)),
+            y: self.p.to_bytes(&self.p.mul(&a.y, &z_inv)),
+        })
+    }
+
+    pub fn to_point(&self, a: &NPoint384) -> Point384 {
+        Point384 {
+            x: self.p.from_bytes(&a.x).unwrap(),
+            y: self.p.from_bytes(&a.y).unwrap(),
+            z: self.p.one(),
+        }
+    }
+
+    // returns `None` unless `data` is an uncompressed point on the curve.
+    pub fn from_uncompressed_bytes(&self, data: &[u8]) -> Option<NPoint384> {
+        if data.len() != 1 + BYTE_LEN * 2 || data[0] != 0x04 {
+            return None;
+        }
+        let f = &self.p;
+        let (x, y) = match (f.from_bytes(&data[1..(1 + BYTE_LEN)]),
+                            f.from_bytes(&data[(1 + BYTE_LEN)..])) {
+            (Some(x), Some(y)) => (x, y),
+            _ => return None,
+        };
+
+        // y^2 + 3 x == x^3 + B
+        let lhs = f.add(&f.mul(&y, &y), &f.add(&x, &f.add(&x, &x)));
+        let rhs = f.add(&f.mul(&f.mul(&x, &x), &x), &self.b);
+        if lhs != rhs {
+            return None;
+        }
+
+        Some(NPoint384 {
+            x: data[1..(1 + BYTE_LEN)].to_vec(),
+            y: data[(1 + BYTE_LEN)..].to_vec(),
+        })
+    }
+
+    // returns `None` unless `data` is a compressed point (0x02 or 0x03 by the parity of y,
+    // then x) on the curve.
+    pub fn from_compressed_bytes(&self, data: &[u8]) -> Option<NPoint384> {
+        if data.len() != 1 + BYTE_LEN || (data[0] != 0x02 && data[0] != 0x03) {
+            return None;
+        }
+        let f = &self.p;
+        let x = match f.from_bytes(&data[1..]) {
+            Some(x) => x,
+            None => return None,
+        };
+
+        // y^2 = x^3 - 3 x + B
+        let rhs = f.sub(&f.add(&f.mul(&f.mul(&x, &x), &x), &self.b),
+                        &f.add(&x, &f.add(&x, &x)));
+        let y = f.pow(&rhs, SQRT_EXP);
+        if f.mul(&y, &y) != rhs {
+            return None;
+        }
+
+        // there is no point with y == 0, so y and -y have different parity
+        let flip = (y[0] ^ data[0] as u32) & 1;
+        let y = choose(flip, &y, &f.sub(&f.zero(), &y));
+
+        Some(NPoint384 {
+            x: data[1..].to_vec(),
+            y: f.to_bytes(&y),
+        })
+    }
+}
+
+impl NPoint384 {
+    pub fn to_compressed_bytes(&self) -> Vec<u8> {
+        let mut b = Vec::with_capacity(1 + BYTE_LEN);
+        b.push(0x02 | (self.y[BYTE_LEN - 1] & 1));
+        b.extend(&self.x);
+        b
+    }
+
+    pub fn to_uncompressed_bytes(&self) -> Vec<u8> {
+        let mut b = Vec::with_capacity(1 + BYTE_LEN * 2);
+        b.push(0x04);
+        b.extend(&self.x);
+        b.extend(&self.y);
+        b
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{P384, P, N, GX, GY};
+
+    #[test]
+    fn test_mult_scalar() {
+        let curve = P384::new();
+        let g = curve.generator();
+
+        // (n - 1) G == -G
+        let mut n1 = N.to_vec();
+        n1[47] -= 1;
+        let p = curve.normalize(&curve.mult_scalar(&g, &n1)).unwrap();
+        assert_eq!(&p.x[..], GX);
+        assert!(&p.y[..] != GY);
+        let sum = curve.add(&curve.to_point(&p), &g);
+        assert!(curve.is_infinity(&sum));
+
+        // n G == O
+        assert!(curve.is_infinity(&curve.mult_scalar(&g, N)));
+
+        // G + G == 2 G
+        let two = curve.normalize(&curve.mult_scalar(&g, &[2])).unwrap();
+        let double = curve.normalize(&curve.add(&g, &g)).unwrap();
+        assert_eq!(two.x, double.x);
+        assert_eq!(two.y, double.y);
+
+        // k = 0x0123456789abcdef, computed with Python `cryptography`
+        let expected = curve.from_uncompressed_bytes(
+            b"\x04\
+              \x2c\xa8\xc5\x60\xf9\x22\x80\x75\x6d\x4d\x4e\x59\x03\x04\x3a\x37\
+              \x05\x32\xb2\x46\x66\x9e\x61\x10\xb0\x33\xf9\xab\x15\x0d\x51\x86\
+              \x32\x08\xe2\x4b\xd7\x0c\xe3\x73\x8d\x02\x76\x38\xbb\xd5\x4c\xd2\
+              \x1f\xc9\x1a\x81\xa4\xcd\x7e\xa2\xab\x6c\x20\x59\x86\x76\x7e\x83\
+              \x09\x26\xc1\xbb\xf7\xef\xf7\x1c\x25\xa2\x24\x5d\x78\x7d\xd8\xcc\
+              \x01\x73\x1d\xb7\xc2\xd9\xb1\xf4\xab\x7c\xe1\x95\x9c\x83\x62\x4f").unwrap();
+        let p = curve.normalize(&curve.mult_scalar(&g, b"\x01\x23\x45\x67\x89\xab\xcd\xef"))
+                     .unwrap();
+        assert_eq!(p.x, expected.x);
+        assert_eq!(p.y, expected.y);
+
+        // not on the curve
+        let mut bad = expected.to_uncompressed_bytes();
+        bad[96] ^= 1;
+        assert!(curve.from_uncompressed_bytes(&bad).is_none());
+    }
+
+    #[test]
+    fn test_compressed() {
+        let curve = P384::new();
+        let g = curve.generator();
+
+        // 2 G has even y, 3 G has odd y
+        let compressed: [(u8, &[u8]); 2] = [
+            (2, b"\x02\x08\xd9\x99\x05\x7b\xa3\xd2\xd9\x69\x26\x00\x45\xc5\x5b\x97\
+                \xf0\x89\x02\x59\x59\xa6\xf4\x34\xd6\x51\xd2\x07\xd1\x9f\xb9\x6e\
+                \x9e\x4f\xe0\xe8\x6e\xbe\x0e\x64\xf8\x5b\x96\xa9\xc7\x52\x95\xdf\
+                \x61"),
+            (3, b"\x03\x07\x7a\x41\xd4\x60\x6f\xfa\x14\x64\x79\x3c\x7e\x5f\xdc\x7d\
+                \x98\xcb\x9d\x39\x10\x20\x2d\xcd\x06\xbe\xa4\xf2\x40\xd3\x56\x6d\
+                \xa6\xb4\x08\xbb\xae\x50\x26\x58\x0d\x02\xd7\xe5\xc7\x05\x00\xc8\
+                \x31"),
+        ];
+        for &(k, expected) in compressed.iter() {
+            let p = curve.normalize(&curve.mult_scalar(&g, &[k])).unwrap();
+            assert_eq!(p.to_compressed_bytes(), expected);
+            let q = curve.from_compressed_bytes(expected).unwrap();
+            assert_eq!(q.to_uncompressed_bytes(), p.to_uncompressed_bytes());
+        }
+
+        // x = 1: x^3 - 3 x + B is not a square
+        let mut data = vec![0; 49];
+        data[0] = 0x02;
+        data[48] = 1;
+        assert!(curve.from_compressed_bytes(&data).is_none());
+
+        // x = p is not reduced
+        let mut data = vec![0x03];
+        data.extend(P);
+        assert!(curve.from_compressed_bytes(&data).is_none());
+
+        let mut data = curve.normalize(&g).unwrap().to_compressed_bytes();
+        data[0] = 0x04;
+        assert!(curve.from_compressed_bytes(&data).is_none());
+    }
+}
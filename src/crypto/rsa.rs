@@ -0,0 +1,340 @@
+// RSA signatures (PKCS#1 v1.5 and PSS) and PKCS#1 v1.5 encryption (RFC 8017).
+
+use rand::Rng;
+
+use crypto::bigint::Modulus;
+use crypto::hash::HashFunction;
+use util::crypto_compare;
+
+// keys smaller than this are rejected
+pub const MIN_BITS: usize = 1024;
+// bound the cost of modular exponentiation for untrusted keys
+pub const MAX_BITS: usize = 8192;
+
+pub struct RsaPublicKey {
+    n: Modulus,
+    // big-endian
+    e: Vec<u8>,
+}
+
+impl RsaPublicKey {
+    // `n` and `e` are big-endian.
+    pub fn new(n: &[u8], e: &[u8]) -> Option<RsaPublicKey> {
+        let n = match Modulus::new(n) {
+            Some(n) => n,
+            None => return None,
+        };
+        if n.bit_len() < MIN_BITS || n.bit_len() > MAX_BITS {
+            return None;
+        }
+
+        let start = e.iter().position(|&b| b != 0).unwrap_or(e.len());
+        let e = &e[start..];
+        // e must be odd and >= 3
+        if e.len() == 0 || e[e.len() - 1] & 1 == 0 || (e.len() == 1 && e[0] < 3) {
+            return None;
+        }
+        if e.len() > n.byte_len() {
+            return None;
+        }
+
+        Some(RsaPublicKey {
+            n: n,
+            e: e.to_vec(),
+        })
+    }
+
+    pub fn bit_len(&self) -> usize {
+        self.n.bit_len()
+    }
+
+    // RSAVP1: sig^e mod n. returns the encoded message.
+    fn public_op(&self, sig: &[u8]) -> Option<Vec<u8>> {
+        if sig.len() != self.n.byte_len() {
+            return None;
+        }
+        let s = match self.n.from_bytes(sig) {
+            Some(s) => s,
+            None => return None,
+        };
+        let m = self.n.pow(&s, &self.e);
+        Some(self.n.to_bytes(&m))
+    }
+
+    // RSASSA-PKCS1-v1_5 (RFC 8017 8.2.2).
+    // we compare against the re-encoded message instead of parsing it,
+    // which avoids signature forgery via lax parsing (Bleichenbacher 2006).
+    pub fn verify_pkcs1(&self, hash: &HashFunction, msg: &[u8], sig: &[u8]) -> bool {
+        let em = match self.public_op(sig) {
+            Some(em) => em,
+            None => return false,
+        };
+        let expected = match pkcs1_encode(hash, msg, em.len()) {
+            Some(expected) => expected,
+            None => return false,
+        };
+        crypto_compare(&em, &expected)
+    }
+
+    // RSASSA-PSS (RFC 8017 8.1.2) with MGF1 over `hash` and a salt as long as the hash,
+    // as required by TLS (RFC 8446 4.2.3).
+    pub fn verify_pss(&self, hash: &HashFunction, msg: &[u8], sig: &[u8]) -> bool {
+        let em = match self.public_op(sig) {
+            Some(em) => em,
+            None => return false,
+        };
+        // emBits = modBits - 1, so EM is one byte shorter if modBits = 1 mod 8
+        let em_bits = self.n.bit_len() - 1;
+        let em = if em_bits % 8 == 0 {
+            if em[0] != 0 {
+                return false;
+            }
+            &em[1..]
+        } else {
+            &em[..]
+        };
+        pss_verify(hash, msg, em, em_bits)
+    }
+
+    // RSAES-PKCS1-v1_5 encryption (RFC 8017 7.2.1).
+    // returns `None` if `msg` is too long for the key.
+    pub fn encrypt_pkcs1<R: Rng>(&self, msg: &[u8], rng: &mut R) -> Option<Vec<u8>> {
+        let k = self.n.byte_len();
+        if msg.len() + 11 > k {
+            return None;
+        }
+        // EM = 0x00 || 0x02 || PS || 0x00 || M, where PS is non-zero random bytes
+        let mut em = Vec::with_capacity(k);
+        em.push(0x00);
+        em.push(0x02);
+        for _ in 0..(k - msg.len() - 3) {
+            let mut b = 0u8;
+            while b == 0 {
+                b = rng.gen();
+            }
+            em.push(b);
+        }
+        em.push(0x00);
+        em.extend(msg);
+
+        let m = self.n.from_bytes(&em).unwrap();
+        Some(self.n.to_bytes(&self.n.pow(&m, &self.e)))
+    }
+}
+
+// all-one mask if `a == b`
+fn ct_eq(a: u32, b: u32) -> u32 {
+    let x = a ^ b;
+    ((x | x.wrapping_neg()) >> 31).wrapping_sub(1)
+}
+
+// all-one mask if `a < b`. `a` and `b` must be less than 2^31.
+fn ct_lt(a: u32, b: u32) -> u32 {
+    0u32.wrapping_sub(a.wrapping_sub(b) >> 31)
+}
+
+fn mgf1(hash: &HashFunction, seed: &[u8], len: usize) -> Vec<u8> {
+    let mut mask = Vec::with_capacity(len + hash.output_len);
+    let mut counter = 0u32;
+    while mask.len() < len {
+        let mut data = seed.to_vec();
+        data.extend(&[(counter >> 24) as u8, (counter >> 16) as u8, (counter >> 8) as u8,
+                      counter as u8]);
+        mask.extend(&(hash.digest)(&data));
+        counter += 1;
+    }
+    mask.truncate(len);
+    mask
+}
+
+// H = Hash(0x00 * 8 || Hash(M) || salt)
+fn pss_hash(hash: &HashFunction, msg: &[u8], salt: &[u8]) -> Vec<u8> {
+    let mut data = vec![0u8; 8];
+    data.extend(&(hash.digest)(msg));
+    data.extend(salt);
+    (hash.digest)(&data)
+}
+
+// EMSA-PSS-ENCODE (RFC 8017 9.1.1). returns `None` if `em_bits` is too small.
+fn pss_encode(hash: &HashFunction, msg: &[u8], salt: &[u8], em_bits: usize) -> Option<Vec<u8>> {
+    let h_len = hash.output_len;
+    let em_len = (em_bits + 7) / 8;
+    if em_len < h_len + salt.len() + 2 {
+        return None;
+    }
+    let h = pss_hash(hash, msg, salt);
+
+    // DB = PS || 0x01 || salt
+    let mut db = vec![0u8; em_len - salt.len() - h_len - 2];
+    db.push(0x01);
+    db.extend(salt);
+    let mask = mgf1(hash, &h, db.len());
+    let mut em: Vec<u8> = db.iter().zip(mask.iter()).map(|(&d, &m)| d ^ m).collect();
+    em[0] &= 0xff >> (8 * em_len - em_bits);
+    em.extend(&h);
+    em.push(0xbc);
+    Some(em)
+}
+
+// EMSA-PSS-VERIFY (RFC 8017 9.1.2) with sLen = hLen.
+fn pss_verify(hash: &HashFunction, msg: &[u8], em: &[u8], em_bits: usize) -> bool {
+    let h_len = hash.output_len;
+    let s_len = h_len;
+    let em_len = (em_bits + 7) / 8;
+    if em.len() != em_len || em_len < h_len + s_len + 2 || em[em_len - 1] != 0xbc {
+        return false;
+    }
+    let (masked_db, h) = em[..(em_len - 1)].split_at(em_len - h_len - 1);
+    let top_mask = 0xff >> (8 * em_len - em_bits);
+    if masked_db[0] & !top_mask != 0 {
+        return false;
+    }
+    let mask = mgf1(hash, h, masked_db.len());
+    let mut db: Vec<u8> = masked_db.iter().zip(mask.iter()).map(|(&d, &m)| d ^ m).collect();
+    db[0] &= top_mask;
+
+    let ps_len = em_len - h_len - s_len - 2;
+    if db[..ps_len].iter().any(|&b| b != 0) || db[ps_len] != 0x01 {
+        return false;
+    }
+    let salt = &db[(ps_len + 1)..];
+    crypto_compare(&pss_hash(hash, msg, salt), h)
+}
+
+// EMSA-PKCS1-v1_5 (RFC 8017 9.2)
+fn pkcs1_encode(hash: &HashFunction, msg: &[u8], em_len: usize) -> Option<Vec<u8>> {
+    let t_len = hash.digest_info.len() + hash.output_len;
+    if em_len < t_len + 11 {
+        return None;
+    }
+    let mut em = Vec::with_capacity(em_len);
+    em.push(0x00);
+    em.push(0x01);
+    for _ in 0..(em_len - t_len - 3) {
+        em.push(0xff);
+    }
+    em.push(0x00);
+    em.extend(hash.digest_info);
+    em.extend(&(hash.digest)(msg));
+    Some(em)
+}
+
+pub struct RsaPrivateKey {
+    public: RsaPublicKey,
+    // SECRET. big-endian, padded to the length of n
+    d: Vec<u8>,
+}
+
+impl RsaPrivateKey {
+    // `n`, `e` and `d` are big-endian. CRT parameters are not used:
+    // the private operation is a plain constant-time exponentiation.
+    pub fn new(n: &[u8], e: &[u8], d: &[u8]) -> Option<RsaPrivateKey> {
+        let public = match RsaPublicKey::new(n, e) {
+            Some(public) => public,
+            None => return None,
+        };
+        let len = public.n.byte_len();
+        let start = d.iter().position(|&b| b != 0).unwrap_or(d.len());
+        let d = &d[start..];
+        if d.len() == 0 || d.len() > len {
+            return None;
+        }
+        let mut padded = vec![0u8; len - d.len()];
+        padded.extend(d);
+
+        let key = RsaPrivateKey {
+            public: public,
+            d: padded,
+        };
+        // catch inconsistent keys early, instead of making bad signatures
+        let mut two = vec![0u8; len];
+        two[len - 1] = 2;
+        if key.public.public_op(&key.private_op(&two)).as_ref() != Some(&two) {
+            return None;
+        }
+        Some(key)
+    }
+
+    pub fn public_key(&self) -> &RsaPublicKey {
+        &self.public
+    }
+
+    // RSASP1: m^d mod n. `m` must be less than n.
+    fn private_op(&self, m: &[u8]) -> Vec<u8> {
+        let n = &self.public.n;
+        let m = n.from_bytes(m).unwrap();
+        n.to_bytes(&n.pow(&m, &self.d))
+    }
+
+    // RSASSA-PKCS1-v1_5 (RFC 8017 8.2.1).
+    // returns `None` if the key is too small for `hash`.
+    pub fn sign_pkcs1(&self, hash: &HashFunction, msg: &[u8]) -> Option<Vec<u8>> {
+        let em = match pkcs1_encode(hash, msg, self.public.n.byte_len()) {
+            Some(em) => em,
+            None => return None,
+        };
+        Some(self.private_op(&em))
+    }
+
+    // RSASSA-PSS (RFC 8017 8.1.1) with a random salt as long as the hash.
+    // returns `None` if the key is too small for `hash`.
+    pub fn sign_pss<R: Rng>(&self, hash: &HashFunction, msg: &[u8], rng: &mut R)
+                            -> Option<Vec<u8>> {
+        let mut salt = vec![0u8; hash.output_len];
+        rng.fill_bytes(&mut salt);
+        let n = &self.public.n;
+        let em_bits = n.bit_len() - 1;
+        let mut em = match pss_encode(hash, msg, &salt, em_bits) {
+            Some(em) => em,
+            None => return None,
+        };
+        if em.len() < n.byte_len() {
+            em.insert(0, 0);
+        }
+        Some(self.private_op(&em))
+    }
+
+    // RSAES-PKCS1-v1_5 decryption (RFC 8017 7.2.2) with implicit rejection:
+    // if the padding is bad or the message is not `fallback.len()` bytes long,
+    // `fallback` is returned instead of an error.
+    // the result does not reveal (even by timing) which case happened, so callers must
+    // pass a random `fallback` and let a later check fail (Bleichenbacher 1998).
+    // returns `None` only if `encrypted` is not a valid ciphertext length or value,
+    // which is public.
+    pub fn decrypt_pkcs1_implicit(&self, encrypted: &[u8], fallback: &[u8]) -> Option<Vec<u8>> {
+        let n = &self.public.n;
+        let k = n.byte_len();
+        if encrypted.len() != k || fallback.len() + 11 > k {
+            return None;
+        }
+        let c = match n.from_bytes(encrypted) {
+            Some(c) => c,
+            None => return None,
+        };
+        // SECRET
+        let em = n.to_bytes(&n.pow(&c, &self.d));
+
+        let mut good = ct_eq(em[0] as u32, 0x00) & ct_eq(em[1] as u32, 0x02);
+        // index of the first zero byte after the padding string
+        let mut looking = !0u32;
+        let mut zero_index = 0u32;
+        for i in 2..k {
+            let found = looking & ct_eq(em[i] as u32, 0);
+            zero_index |= i as u32 & found;
+            looking &= !found;
+        }
+        good &= !looking;
+        // PS must be at least 8 bytes
+        good &= !ct_lt(zero_index, 2 + 8);
+        good &= ct_eq(k as u32 - zero_index - 1, fallback.len() as u32);
+
+        // if `good`, the message is at the end of `em`
+        let start = k - fallback.len();
+        let mask = good as u8;
+        let msg = (0..fallback.len()).map(|i| {
+            (em[start + i] & mask) | (fallback[i] & !mask)
+        }).collect();
+        Some(msg)
+    }
+}
+
@@ -0,0 +1,117 @@
+// http://csrc.nist.gov/publications/fips/fips180-4/fips-180-4.pdf
+// SHA-1 is only used for legacy HMAC-SHA1 cipher suites.
+
+use crypto::wrapping::*;
+
+pub const INITIAL_STATE: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+
+// process a 64-byte block.
+pub fn compress(val: &mut [w32], block: &[u8]) {
+    fn rot(a: w32, b: usize) -> w32 {
+        (a << b) | (a >> (32 - b))
+    }
+
+    debug_assert_eq!(val.len(), 5);
+    debug_assert_eq!(block.len(), 64);
+
+    let mut w = [w32(0u32); 80];
+    for j in 0..16 {
+        let b0 = w8(block[j * 4 + 0]).to_w32();
+        let b1 = w8(block[j * 4 + 1]).to_w32();
+        let b2 = w8(block[j * 4 + 2]).to_w32();
+        let b3 = w8(block[j * 4 + 3]).to_w32();
+        w[j] = (b0 << 8 * 3) | (b1 << 8 * 2) | (b2 << 8 * 1) | b3;
+    }
+    for j in 16..80 {
+        w[j] = rot(w[j - 3] ^ w[j - 8] ^ w[j - 14] ^ w[j - 16], 1);
+    }
+
+    let mut a = val[0];
+    let mut b = val[1];
+    let mut c = val[2];
+    let mut d = val[3];
+    let mut e = val[4];
+
+    for j in 0..80 {
+        let (f, k) = if j < 20 {
+            ((b & c) | ((!b) & d), w32(0x5a827999))
+        } else if j < 40 {
+            (b ^ c ^ d, w32(0x6ed9eba1))
+        } else if j < 60 {
+            ((b & c) | (b & d) | (c & d), w32(0x8f1bbcdc))
+        } else {
+            (b ^ c ^ d, w32(0xca62c1d6))
+        };
+
+        let t = rot(a, 5) + f + e + k + w[j];
+        e = d;
+        d = c;
+        c = rot(b, 30);
+        b = a;
+        a = t;
+    }
+
+    val[0] = val[0] + a;
+    val[1] = val[1] + b;
+    val[2] = val[2] + c;
+    val[3] = val[3] + d;
+    val[4] = val[4] + e;
+}
+
+pub fn sha1(msg: &[u8]) -> [u8; 20] {
+    let len = msg.len();
+    let mut msg = msg.to_vec(); // FIXME: do not allocate
+
+    msg.push(0x80);
+    let padding_len = (w64(64 - 8 - 1) - w64(len as u64)) & w64(63);
+    for _ in 0..(padding_len.0) {
+        msg.push(0);
+    }
+
+    let bitlen = (len as u64) * 8;
+    for i in (0..8).rev() {
+        let b = (w64(bitlen) >> (8 * i)).to_w8().0;
+        msg.push(b);
+    }
+
+    debug_assert_eq!(msg.len() % 64, 0);
+
+    let mut val = [w32(0); 5];
+    for i in 0..5 {
+        val[i] = w32(INITIAL_STATE[i]);
+    }
+    for block in msg.chunks(64) {
+        compress(&mut val, block);
+    }
+
+    let mut ret = [0u8; 20];
+    for i in 0..5 {
+        ret[i * 4 + 0] = (val[i] >> 8 * 3).to_w8().0;
+        ret[i * 4 + 1] = (val[i] >> 8 * 2).to_w8().0;
+        ret[i * 4 + 2] = (val[i] >> 8 * 1).to_w8().0;
+        ret[i * 4 + 3] = val[i].to_w8().0;
+    }
+    ret
+}
+
+#[cfg(test)]
+mod test {
+    use super::sha1;
+
+    #[test]
+    fn test_sha1() {
+        static ANSWERS: &'static [(&'static [u8], &'static [u8])] = &[
+            (b"",
+             b"\xda\x39\xa3\xee\x5e\x6b\x4b\x0d\x32\x55\xbf\xef\x95\x60\x18\x90\xaf\xd8\x07\x09"),
+            (b"abc",
+             b"\xa9\x99\x3e\x36\x47\x06\x81\x6a\xba\x3e\x25\x71\x78\x50\xc2\x6c\x9c\xd0\xd8\x9d"),
+            (b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+             b"\x84\x98\x3e\x44\x1c\x3b\xd2\x6e\xba\xae\x4a\xa1\xf9\x51\x29\xe5\xe5\x46\x70\xf1"),
+        ];
+
+        for &(input, expected) in ANSWERS.iter() {
+            let computed = sha1(input);
+            assert_eq!(expected, &computed);
+        }
+    }
+}
@@ -15,11 +15,82 @@ static K: [u32; 64] = [
     0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2
 ];
 
-pub fn sha256(msg: &[u8]) -> [u8; 32] {
+pub const SHA256_INITIAL_STATE: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a,
+    0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+// process a 64-byte block.
+pub fn sha256_compress(val: &mut [w32], block: &[u8]) {
     fn rot(a: w32, b: usize) -> w32 {
         (a >> b) | (a << (32 - b))
     }
 
+    debug_assert_eq!(val.len(), 8);
+    debug_assert_eq!(block.len(), 64);
+
+    let w = {
+        let mut w = [w32(0u32); 64];
+        for j in 0..16 {
+            let b0 = w8(block[j * 4 + 0]).to_w32();
+            let b1 = w8(block[j * 4 + 1]).to_w32();
+            let b2 = w8(block[j * 4 + 2]).to_w32();
+            let b3 = w8(block[j * 4 + 3]).to_w32();
+            w[j] = (b0 << 8 * 3) | (b1 << 8 * 2) | (b2 << 8 * 1) | b3;
+        }
+
+        for j in 16..64 {
+            let wj15 = w[j - 15];
+            let sig0 = rot(wj15, 7) ^ rot(wj15, 18) ^ (wj15 >> 3);
+
+            let wj2 = w[j - 2];
+            let sig1 = rot(wj2, 17) ^ rot(wj2, 19) ^ (wj2 >> 10);
+            w[j] = sig1 + w[j - 7] + sig0 + w[j - 16];
+        }
+
+        w
+    };
+
+    let mut a: w32 = val[0];
+    let mut b: w32 = val[1];
+    let mut c: w32 = val[2];
+    let mut d: w32 = val[3];
+    let mut e: w32 = val[4];
+    let mut f: w32 = val[5];
+    let mut g: w32 = val[6];
+    let mut h: w32 = val[7];
+
+    for j in 0..64 {
+        let ch = (e & f) ^ ((!e) & g);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+
+        let sig0 = rot(a, 2) ^ rot(a, 13) ^ rot(a, 22);
+        let sig1 = rot(e, 6) ^ rot(e, 11) ^ rot(e, 25);
+
+        let t1 = h + sig1 + ch + w32(K[j]) + w[j];
+        let t2 = sig0 + maj;
+
+        h = g;
+        g = f;
+        f = e;
+        e = d + t1;
+        d = c;
+        c = b;
+        b = a;
+        a = t1 + t2;
+    }
+
+    val[0] = val[0] + a;
+    val[1] = val[1] + b;
+    val[2] = val[2] + c;
+    val[3] = val[3] + d;
+    val[4] = val[4] + e;
+    val[5] = val[5] + f;
+    val[6] = val[6] + g;
+    val[7] = val[7] + h;
+}
+
+pub fn sha256(msg: &[u8]) -> [u8; 32] {
     let len = msg.len();
     let mut msg = msg.to_vec(); // FIXME: do not allocate
 
@@ -37,73 +108,13 @@ pub fn sha256(msg: &[u8]) -> [u8; 32] {
 
     debug_assert_eq!(msg.len() % (512 / 8), 0);
 
-    let nblk = msg.len() / (512 / 8);
-
-    let mut val: [w32; 8] = [
-        w32(0x6a09e667), w32(0xbb67ae85), w32(0x3c6ef372), w32(0xa54ff53a),
-        w32(0x510e527f), w32(0x9b05688c), w32(0x1f83d9ab), w32(0x5be0cd19),
-    ];
-
-    for i in 0..nblk {
-        let w = {
-            let mut w = [w32(0u32); 64];
-            for j in 0..16 {
-                let b0 = w8(msg[i * 64 + j * 4 + 0]).to_w32();
-                let b1 = w8(msg[i * 64 + j * 4 + 1]).to_w32();
-                let b2 = w8(msg[i * 64 + j * 4 + 2]).to_w32();
-                let b3 = w8(msg[i * 64 + j * 4 + 3]).to_w32();
-                w[j] = (b0 << 8 * 3) | (b1 << 8 * 2) | (b2 << 8 * 1) | b3;
-            }
-
-            for j in 16..64 {
-                let wj15 = w[j - 15];
-                let sig0 = rot(wj15, 7) ^ rot(wj15, 18) ^ (wj15 >> 3);
-
-                let wj2 = w[j - 2];
-                let sig1 = rot(wj2, 17) ^ rot(wj2, 19) ^ (wj2 >> 10);
-                w[j] = sig1 + w[j - 7] + sig0 + w[j - 16];
-            }
-
-            w
-        };
-
-        let mut a: w32 = val[0];
-        let mut b: w32 = val[1];
-        let mut c: w32 = val[2];
-        let mut d: w32 = val[3];
-        let mut e: w32 = val[4];
-        let mut f: w32 = val[5];
-        let mut g: w32 = val[6];
-        let mut h: w32 = val[7];
-
-        for j in 0..64 {
-            let ch = (e & f) ^ ((!e) & g);
-            let maj = (a & b) ^ (a & c) ^ (b & c);
-
-            let sig0 = rot(a, 2) ^ rot(a, 13) ^ rot(a, 22);
-            let sig1 = rot(e, 6) ^ rot(e, 11) ^ rot(e, 25);
-
-            let t1 = h + sig1 + ch + w32(K[j]) + w[j];
-            let t2 = sig0 + maj;
-
-            h = g;
-            g = f;
-            f = e;
-            e = d + t1;
-            d = c;
-            c = b;
-            b = a;
-            a = t1 + t2;
-        }
+    let mut val = [w32(0); 8];
+    for i in 0..8 {
+        val[i] = w32(SHA256_INITIAL_STATE[i]);
+    }
 
-        val[0] = val[0] + a;
-        val[1] = val[1] + b;
-        val[2] = val[2] + c;
-        val[3] = val[3] + d;
-        val[4] = val[4] + e;
-        val[5] = val[5] + f;
-        val[6] = val[6] + g;
-        val[7] = val[7] + h;
+    for block in msg.chunks(512 / 8) {
+        sha256_compress(&mut val, block);
     }
 
     let mut ret = [0u8; 32];
@@ -116,9 +127,139 @@ pub fn sha256(msg: &[u8]) -> [u8; 32] {
     ret
 }
 
+static K512: [u64; 80] = [
+    0x428a2f98d728ae22, 0x7137449123ef65cd, 0xb5c0fbcfec4d3b2f, 0xe9b5dba58189dbbc,
+    0x3956c25bf348b538, 0x59f111f1b605d019, 0x923f82a4af194f9b, 0xab1c5ed5da6d8118,
+    0xd807aa98a3030242, 0x12835b0145706fbe, 0x243185be4ee4b28c, 0x550c7dc3d5ffb4e2,
+    0x72be5d74f27b896f, 0x80deb1fe3b1696b1, 0x9bdc06a725c71235, 0xc19bf174cf692694,
+    0xe49b69c19ef14ad2, 0xefbe4786384f25e3, 0x0fc19dc68b8cd5b5, 0x240ca1cc77ac9c65,
+    0x2de92c6f592b0275, 0x4a7484aa6ea6e483, 0x5cb0a9dcbd41fbd4, 0x76f988da831153b5,
+    0x983e5152ee66dfab, 0xa831c66d2db43210, 0xb00327c898fb213f, 0xbf597fc7beef0ee4,
+    0xc6e00bf33da88fc2, 0xd5a79147930aa725, 0x06ca6351e003826f, 0x142929670a0e6e70,
+    0x27b70a8546d22ffc, 0x2e1b21385c26c926, 0x4d2c6dfc5ac42aed, 0x53380d139d95b3df,
+    0x650a73548baf63de, 0x766a0abb3c77b2a8, 0x81c2c92e47edaee6, 0x92722c851482353b,
+    0xa2bfe8a14cf10364, 0xa81a664bbc423001, 0xc24b8b70d0f89791, 0xc76c51a30654be30,
+    0xd192e819d6ef5218, 0xd69906245565a910, 0xf40e35855771202a, 0x106aa07032bbd1b8,
+    0x19a4c116b8d2d0c8, 0x1e376c085141ab53, 0x2748774cdf8eeb99, 0x34b0bcb5e19b48a8,
+    0x391c0cb3c5c95a63, 0x4ed8aa4ae3418acb, 0x5b9cca4f7763e373, 0x682e6ff3d6b2b8a3,
+    0x748f82ee5defb2fc, 0x78a5636f43172f60, 0x84c87814a1f0ab72, 0x8cc702081a6439ec,
+    0x90befffa23631e28, 0xa4506cebde82bde9, 0xbef9a3f7b2c67915, 0xc67178f2e372532b,
+    0xca273eceea26619c, 0xd186b8c721c0c207, 0xeada7dd6cde0eb1e, 0xf57d4f7fee6ed178,
+    0x06f067aa72176fba, 0x0a637dc5a2c898a6, 0x113f9804bef90dae, 0x1b710b35131c471b,
+    0x28db77f523047d84, 0x32caab7b40c72493, 0x3c9ebe0a15c9bebc, 0x431d67c49c100d4c,
+    0x4cc5d4becb3e42b6, 0x597f299cfc657e2a, 0x5fcb6fab3ad6faec, 0x6c44198c4a475817,
+];
+
+pub const SHA384_INITIAL_STATE: [u64; 8] = [
+    0xcbbb9d5dc1059ed8, 0x629a292a367cd507, 0x9159015a3070dd17, 0x152fecd8f70e5939,
+    0x67332667ffc00b31, 0x8eb44a8768581511, 0xdb0c2e0d64f98fa7, 0x47b5481dbefa4fa4,
+];
+
+pub const SHA512_INITIAL_STATE: [u64; 8] = [
+    0x6a09e667f3bcc908, 0xbb67ae8584caa73b, 0x3c6ef372fe94f82b, 0xa54ff53a5f1d36f1,
+    0x510e527fade682d1, 0x9b05688c2b3e6c1f, 0x1f83d9abfb41bd6b, 0x5be0cd19137e2179,
+];
+
+// process a 128-byte block.
+pub fn sha512_compress(val: &mut [w64], block: &[u8]) {
+    fn rot(a: w64, b: usize) -> w64 {
+        (a >> b) | (a << (64 - b))
+    }
+
+    debug_assert_eq!(val.len(), 8);
+    debug_assert_eq!(block.len(), 128);
+
+    let mut w = [w64(0u64); 80];
+    for j in 0..16 {
+        let mut wj = w64(0);
+        for k in 0..8 {
+            wj = (wj << 8) | w8(block[j * 8 + k]).to_w64();
+        }
+        w[j] = wj;
+    }
+    for j in 16..80 {
+        let wj15 = w[j - 15];
+        let sig0 = rot(wj15, 1) ^ rot(wj15, 8) ^ (wj15 >> 7);
+
+        let wj2 = w[j - 2];
+        let sig1 = rot(wj2, 19) ^ rot(wj2, 61) ^ (wj2 >> 6);
+        w[j] = sig1 + w[j - 7] + sig0 + w[j - 16];
+    }
+
+    let mut s = [w64(0); 8];
+    s.copy_from_slice(val);
+
+    for j in 0..80 {
+        let (a, b, c, d, e, f, g, h) = (s[0], s[1], s[2], s[3], s[4], s[5], s[6], s[7]);
+        let ch = (e & f) ^ ((!e) & g);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+
+        let sig0 = rot(a, 28) ^ rot(a, 34) ^ rot(a, 39);
+        let sig1 = rot(e, 14) ^ rot(e, 18) ^ rot(e, 41);
+
+        let t1 = h + sig1 + ch + w64(K512[j]) + w[j];
+        let t2 = sig0 + maj;
+
+        s = [t1 + t2, a, b, c, d + t1, e, f, g];
+    }
+
+    for i in 0..8 {
+        val[i] = val[i] + s[i];
+    }
+}
+
+fn sha512_common(initial_state: &[u64; 8], msg: &[u8], out_len: usize) -> Vec<u8> {
+    let len = msg.len();
+    let mut msg = msg.to_vec(); // FIXME: do not allocate
+
+    msg.push(0x80);
+    let padding_len = (w64(128 - 16 - 1) - w64(len as u64)) & w64(127);
+    for _ in 0..(padding_len.0 + 8) {
+        msg.push(0);
+    }
+
+    let bitlen = (len as u64) * 8;
+    for i in (0..8).rev() {
+        let b = (w64(bitlen) >> (8 * i)).to_w8().0;
+        msg.push(b);
+    }
+
+    debug_assert_eq!(msg.len() % 128, 0);
+
+    let mut val = [w64(0); 8];
+    for i in 0..8 {
+        val[i] = w64(initial_state[i]);
+    }
+
+    for block in msg.chunks(128) {
+        sha512_compress(&mut val, block);
+    }
+
+    let mut ret = Vec::with_capacity(64);
+    for i in 0..8 {
+        for j in (0..8).rev() {
+            ret.push((val[i] >> 8 * j).to_w8().0);
+        }
+    }
+    ret.truncate(out_len);
+    ret
+}
+
+pub fn sha384(msg: &[u8]) -> [u8; 48] {
+    let mut ret = [0u8; 48];
+    ret.copy_from_slice(&sha512_common(&SHA384_INITIAL_STATE, msg, 48));
+    ret
+}
+
+pub fn sha512(msg: &[u8]) -> [u8; 64] {
+    let mut ret = [0u8; 64];
+    ret.copy_from_slice(&sha512_common(&SHA512_INITIAL_STATE, msg, 64));
+    ret
+}
+
 #[cfg(test)]
 mod test {
-    use super::sha256;
+    use super::{sha256, sha384, sha512};
 
     #[test]
     fn test_sha256() {
@@ -139,4 +280,32 @@ mod test {
             assert_eq!(expected, &computed);
         }
     }
+
+    #[test]
+    fn test_sha512() {
+        static ANSWERS: &'static [(&'static [u8], &'static [u8], &'static [u8])] = &[
+            (b"abc",
+             b"\xcb\x00\x75\x3f\x45\xa3\x5e\x8b\xb5\xa0\x3d\x69\x9a\xc6\x50\x07\
+               \x27\x2c\x32\xab\x0e\xde\xd1\x63\x1a\x8b\x60\x5a\x43\xff\x5b\xed\
+               \x80\x86\x07\x2b\xa1\xe7\xcc\x23\x58\xba\xec\xa1\x34\xc8\x25\xa7",
+             b"\xdd\xaf\x35\xa1\x93\x61\x7a\xba\xcc\x41\x73\x49\xae\x20\x41\x31\
+               \x12\xe6\xfa\x4e\x89\xa9\x7e\xa2\x0a\x9e\xee\xe6\x4b\x55\xd3\x9a\
+               \x21\x92\x99\x2a\x27\x4f\xc1\xa8\x36\xba\x3c\x23\xa3\xfe\xeb\xbd\
+               \x45\x4d\x44\x23\x64\x3c\xe8\x0e\x2a\x9a\xc9\x4f\xa5\x4c\xa4\x9f"),
+            (b"abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmno\
+               ijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu",
+             b"\x09\x33\x0c\x33\xf7\x11\x47\xe8\x3d\x19\x2f\xc7\x82\xcd\x1b\x47\
+               \x53\x11\x1b\x17\x3b\x3b\x05\xd2\x2f\xa0\x80\x86\xe3\xb0\xf7\x12\
+               \xfc\xc7\xc7\x1a\x55\x7e\x2d\xb9\x66\xc3\xe9\xfa\x91\x74\x60\x39",
+             b"\x8e\x95\x9b\x75\xda\xe3\x13\xda\x8c\xf4\xf7\x28\x14\xfc\x14\x3f\
+               \x8f\x77\x79\xc6\xeb\x9f\x7f\xa1\x72\x99\xae\xad\xb6\x88\x90\x18\
+               \x50\x1d\x28\x9e\x49\x00\xf7\xe4\x33\x1b\x99\xde\xc4\xb5\x43\x3a\
+               \xc7\xd3\x29\xee\xb6\xdd\x26\x54\x5e\x96\xe5\x5b\x87\x4b\xe9\x09"),
+        ];
+
+        for &(input, expected384, expected512) in ANSWERS.iter() {
+            assert_eq!(expected384, &sha384(input)[..]);
+            assert_eq!(expected512, &sha512(input)[..]);
+        }
+    }
 }
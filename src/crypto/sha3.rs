@@ -0,0 +1,204 @@
+// SHA-3 and SHAKE (FIPS 202), for ML-KEM.
+// not seriously audited.
+// no bit-level support.
+
+static RC: [u64; 24] = [
+    0x0000000000000001, 0x0000000000008082, 0x800000000000808a, 0x8000000080008000,
+    0x000000000000808b, 0x0000000080000001, 0x8000000080008081, 0x8000000000008009,
+    0x000000000000008a, 0x0000000000000088, 0x0000000080008009, 0x000000008000000a,
+    0x000000008000808b, 0x800000000000008b, 0x8000000000008089, 0x8000000000008003,
+    0x8000000000008002, 0x8000000000000080, 0x000000000000800a, 0x800000008000000a,
+    0x8000000080008081, 0x8000000000008080, 0x0000000080000001, 0x8000000080008008,
+];
+
+// rotation offsets and destination lanes of rho and pi, in the order of the walk
+// starting at lane 1
+static ROTC: [u32; 24] = [
+    1, 3, 6, 10, 15, 21, 28, 36, 45, 55, 2, 14, 27, 41, 56, 8, 25, 43, 62, 18, 39, 61, 20, 44,
+];
+static PILN: [usize; 24] = [
+    10, 7, 11, 17, 18, 3, 5, 16, 8, 21, 24, 4, 15, 23, 19, 13, 12, 2, 20, 14, 22, 9, 6, 1,
+];
+
+// lane (x, y) is a[x + 5 * y]
+fn keccak_f(a: &mut [u64; 25]) {
+    for round in 0..24 {
+        // theta
+        let mut c = [0u64; 5];
+        for x in 0..5 {
+            c[x] = a[x] ^ a[x + 5] ^ a[x + 10] ^ a[x + 15] ^ a[x + 20];
+        }
+        for x in 0..5 {
+            let d = c[(x + 4) % 5] ^ c[(x + 1) % 5].rotate_left(1);
+            for y in 0..5 {
+                a[x + 5 * y] ^= d;
+            }
+        }
+
+        // rho and pi
+        let mut last = a[1];
+        for i in 0..24 {
+            let j = PILN[i];
+            let tmp = a[j];
+            a[j] = last.rotate_left(ROTC[i]);
+            last = tmp;
+        }
+
+        // chi
+        for y in 0..5 {
+            let mut row = [0u64; 5];
+            row.copy_from_slice(&a[(5 * y)..(5 * y + 5)]);
+            for x in 0..5 {
+                a[x + 5 * y] = row[x] ^ (!row[(x + 1) % 5] & row[(x + 2) % 5]);
+            }
+        }
+
+        // iota
+        a[0] ^= RC[round];
+    }
+}
+
+/// Keccak sponge. Absorb the input with `update`, then read any amount of output
+/// with `squeeze`.
+pub struct Keccak {
+    state: [u64; 25],
+    // bytes per block
+    rate: usize,
+    // domain separation bits and the first bit of the padding
+    suffix: u8,
+    // position in the current block
+    pos: usize,
+    squeezing: bool,
+}
+
+impl Keccak {
+    fn new(rate: usize, suffix: u8) -> Keccak {
+        Keccak {
+            state: [0; 25],
+            rate: rate,
+            suffix: suffix,
+            pos: 0,
+            squeezing: false,
+        }
+    }
+
+    pub fn shake128() -> Keccak {
+        Keccak::new(168, 0x1f)
+    }
+
+    pub fn shake256() -> Keccak {
+        Keccak::new(136, 0x1f)
+    }
+
+    fn xor_byte(&mut self, i: usize, b: u8) {
+        self.state[i / 8] ^= (b as u64) << (8 * (i % 8));
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        assert!(!self.squeezing);
+        for &b in data.iter() {
+            let pos = self.pos;
+            self.xor_byte(pos, b);
+            self.pos += 1;
+            if self.pos == self.rate {
+                keccak_f(&mut self.state);
+                self.pos = 0;
+            }
+        }
+    }
+
+    pub fn squeeze(&mut self, out: &mut [u8]) {
+        if !self.squeezing {
+            let (pos, suffix, rate) = (self.pos, self.suffix, self.rate);
+            self.xor_byte(pos, suffix);
+            self.xor_byte(rate - 1, 0x80);
+            keccak_f(&mut self.state);
+            self.pos = 0;
+            self.squeezing = true;
+        }
+        for b in out.iter_mut() {
+            if self.pos == self.rate {
+                keccak_f(&mut self.state);
+                self.pos = 0;
+            }
+            *b = (self.state[self.pos / 8] >> (8 * (self.pos % 8))) as u8;
+            self.pos += 1;
+        }
+    }
+}
+
+pub fn sha3_256(msg: &[u8]) -> [u8; 32] {
+    let mut keccak = Keccak::new(136, 0x06);
+    keccak.update(msg);
+    let mut ret = [0u8; 32];
+    keccak.squeeze(&mut ret);
+    ret
+}
+
+pub fn sha3_512(msg: &[u8]) -> [u8; 64] {
+    let mut keccak = Keccak::new(72, 0x06);
+    keccak.update(msg);
+    let mut ret = [0u8; 64];
+    keccak.squeeze(&mut ret);
+    ret
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Keccak, sha3_256, sha3_512};
+
+    #[test]
+    fn test_sha3() {
+        static ANSWERS: &'static [(&'static [u8], &'static [u8], &'static [u8])] = &[
+            (b"",
+             b"\xa7\xff\xc6\xf8\xbf\x1e\xd7\x66\x51\xc1\x47\x56\xa0\x61\xd6\x62\
+               \xf5\x80\xff\x4d\xe4\x3b\x49\xfa\x82\xd8\x0a\x4b\x80\xf8\x43\x4a",
+             b"\xa6\x9f\x73\xcc\xa2\x3a\x9a\xc5\xc8\xb5\x67\xdc\x18\x5a\x75\x6e\
+               \x97\xc9\x82\x16\x4f\xe2\x58\x59\xe0\xd1\xdc\xc1\x47\x5c\x80\xa6\
+               \x15\xb2\x12\x3a\xf1\xf5\xf9\x4c\x11\xe3\xe9\x40\x2c\x3a\xc5\x58\
+               \xf5\x00\x19\x9d\x95\xb6\xd3\xe3\x01\x75\x85\x86\x28\x1d\xcd\x26"),
+            (b"abc",
+             b"\x3a\x98\x5d\xa7\x4f\xe2\x25\xb2\x04\x5c\x17\x2d\x6b\xd3\x90\xbd\
+               \x85\x5f\x08\x6e\x3e\x9d\x52\x5b\x46\xbf\xe2\x45\x11\x43\x15\x32",
+             b"\xb7\x51\x85\x0b\x1a\x57\x16\x8a\x56\x93\xcd\x92\x4b\x6b\x09\x6e\
+               \x08\xf6\x21\x82\x74\x44\xf7\x0d\x88\x4f\x5d\x02\x40\xd2\x71\x2e\
+               \x10\xe1\x16\xe9\x19\x2a\xf3\xc9\x1a\x7e\xc5\x76\x47\xe3\x93\x40\
+               \x57\x34\x0b\x4c\xf4\x08\xd5\xa5\x65\x92\xf8\x27\x4e\xec\x53\xf0"),
+        ];
+
+        for &(input, expected256, expected512) in ANSWERS.iter() {
+            assert_eq!(expected256, &sha3_256(input)[..]);
+            assert_eq!(expected512, &sha3_512(input)[..]);
+        }
+
+        // 200 bytes of 0xa3, longer than a block
+        let input = [0xa3; 200];
+        assert_eq!(&sha3_256(&input)[..],
+                   &b"\x79\xf3\x8a\xde\xc5\xc2\x03\x07\xa9\x8e\xf7\x6e\x83\x24\xaf\xbf\
+                      \xd4\x6c\xfd\x81\xb2\x2e\x39\x73\xc6\x5f\xa1\xbd\x9d\xe3\x17\x87"[..]);
+    }
+
+    #[test]
+    fn test_shake() {
+        let input = [0xa3; 200];
+
+        // bytes 168...199 of the output, after the first squeezed block
+        let mut shake = Keccak::shake128();
+        shake.update(&input[..100]);
+        shake.update(&input[100..]);
+        let mut out = [0u8; 200];
+        shake.squeeze(&mut out[..1]);
+        shake.squeeze(&mut out[1..]);
+        assert_eq!(&out[168..],
+                   &b"\x09\xba\x9e\x94\xf7\x26\x61\x22\xed\x7a\xc2\x4e\x5e\x26\x6c\x42\
+                      \xa8\x2f\xa1\xbb\xef\xb7\xb8\xdb\x00\x66\xe1\x6a\x85\xe0\x49\x3f"[..]);
+
+        let mut shake = Keccak::shake256();
+        shake.update(b"abc");
+        let mut out = [0u8; 32];
+        shake.squeeze(&mut out);
+        assert_eq!(&out[..],
+                   &b"\x48\x33\x66\x60\x13\x60\xa8\x77\x1c\x68\x63\x08\x0c\xc4\x11\x4d\
+                      \x8d\xb4\x45\x30\xf8\xf1\xe1\xee\x4f\x94\xea\x37\xe7\x8b\x57\x39"[..]);
+    }
+}
@@ -0,0 +1,129 @@
+// X25519 (RFC 7748 5).
+//
+// field arithmetic is `bigint::Modulus`, which does not branch on or index memory by values.
+// the Montgomery ladder swaps with masks, so only the fixed bit length of the scalar is leaked.
+//
+// keys are little-endian as in RFC 7748, while `Modulus` is big-endian.
+
+use crypto::bigint::Modulus;
+
+pub const KEY_LEN: usize = 32;
+
+/// u-coordinate of the base point.
+pub static BASE_POINT: [u8; KEY_LEN] = [
+    9, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+];
+
+// p = 2^255 - 19
+static P: &'static [u8] = b"\
+    \x7f\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\
+    \xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xed";
+
+// (A - 2) / 4
+const A24: u32 = 121665;
+
+// if flag == 1, returns (b, a). if flag == 0, returns (a, b).
+fn cswap(flag: u32, a: Vec<u32>, b: Vec<u32>) -> (Vec<u32>, Vec<u32>) {
+    let mask = 0u32.wrapping_sub(flag);
+    let mut a = a;
+    let mut b = b;
+    for (a, b) in a.iter_mut().zip(b.iter_mut()) {
+        let t = mask & (*a ^ *b);
+        *a ^= t;
+        *b ^= t;
+    }
+    (a, b)
+}
+
+/// Computes `X25519(scalar, u)`. Both are 32 bytes. `scalar` is SECRET.
+/// The result is all zero if `u` has small order, which callers may need to reject.
+pub fn x25519(scalar: &[u8], u: &[u8]) -> Vec<u8> {
+    assert_eq!(scalar.len(), KEY_LEN);
+    assert_eq!(u.len(), KEY_LEN);
+
+    let mut k = scalar.to_vec();
+    k[0] &= 248;
+    k[31] &= 127;
+    k[31] |= 64;
+
+    let f = Modulus::new(P).unwrap();
+    // the top bit is ignored, and non-canonical values are accepted
+    let mut u_be: Vec<u8> = u.iter().rev().cloned().collect();
+    u_be[0] &= 0x7f;
+    let x1 = f.reduce_bytes(&u_be);
+    let a24 = {
+        let mut a24 = f.zero();
+        a24[0] = A24;
+        a24
+    };
+
+    let mut x2 = f.one();
+    let mut z2 = f.zero();
+    let mut x3 = x1.clone();
+    let mut z3 = f.one();
+    let mut swap = 0;
+    for t in (0..255).rev() {
+        let k_t = ((k[t / 8] >> (t % 8)) & 1) as u32;
+        swap ^= k_t;
+        let (a, b) = cswap(swap, x2, x3);
+        x2 = a;
+        x3 = b;
+        let (a, b) = cswap(swap, z2, z3);
+        z2 = a;
+        z3 = b;
+        swap = k_t;
+
+        let a = f.add(&x2, &z2);
+        let aa = f.mul(&a, &a);
+        let b = f.sub(&x2, &z2);
+        let bb = f.mul(&b, &b);
+        let e = f.sub(&aa, &bb);
+        let c = f.add(&x3, &z3);
+        let d = f.sub(&x3, &z3);
+        let da = f.mul(&d, &a);
+        let cb = f.mul(&c, &b);
+        let sum = f.add(&da, &cb);
+        let diff = f.sub(&da, &cb);
+        x3 = f.mul(&sum, &sum);
+        z3 = f.mul(&x1, &f.mul(&diff, &diff));
+        x2 = f.mul(&aa, &bb);
+        z2 = f.mul(&e, &f.add(&aa, &f.mul(&a24, &e)));
+    }
+    let (x2, _) = cswap(swap, x2, x3);
+    let (z2, _) = cswap(swap, z2, z3);
+
+    let x = f.mul(&x2, &f.inverse_prime(&z2));
+    f.to_bytes(&x).into_iter().rev().collect()
+}
+
+#[cfg(test)]
+mod test {
+    use test::unhex;
+    use super::{x25519, BASE_POINT};
+
+    #[test]
+    fn test_x25519() {
+        // RFC 7748 5.2
+        let scalar = unhex("a546e36bf0527c9d3b16154b82465edd62144c0ac1fc5a18506a2244ba449ac4");
+        let u = unhex("e6db6867583030db3594c1a424b15f7c726624ec26b3353b10a903a6d0ab1c4c");
+        let out = x25519(&scalar, &u);
+        assert_eq!(out, unhex("c3da55379de9c6908e94ea4df28d084f32eccf03491c71f754b4075577a28552"));
+
+        // RFC 7748 6.1
+        let alice = unhex("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a");
+        let bob = unhex("5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb");
+        let alice_public = x25519(&alice, &BASE_POINT);
+        let bob_public = x25519(&bob, &BASE_POINT);
+        assert_eq!(alice_public,
+                   unhex("8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a"));
+        assert_eq!(bob_public,
+                   unhex("de9edb7d7b7dc1b4d35b61c2ece435373f8343c85b78674dadfc7e146f882b4f"));
+        let shared = unhex("4a5d9d5ba4ce2de1728e3bf480350f25e07e21c947d19e3376f09b3c1e161742");
+        assert_eq!(x25519(&alice, &bob_public), shared);
+        assert_eq!(x25519(&bob, &alice_public), shared);
+
+        // u = 0 has small order
+        assert_eq!(x25519(&alice, &[0; 32]), vec![0; 32]);
+    }
+}
@@ -0,0 +1,335 @@
+//! Certificate Transparency (RFC 6962).
+//!
+//! Signed certificate timestamps (SCTs) are taken from the `signed_certificate_timestamp`
+//! extension and from the certificate itself, and verified against logs known to the client.
+//! Whether the valid SCTs are enough is decided by a `CtPolicy`.
+
+use crypto::sha2::sha256;
+use der::{self, Reader};
+use handshake::SignedCertificateTimestampList;
+use signature::{DigitallySigned, HashAlgorithm};
+use tls_item::TlsItem;
+use tls_result::TlsResult;
+use tls_result::TlsErrorKind::DecodeError;
+use util::{ReadExt, WriteExt, u64_be_array};
+use x509::{Certificate, oid};
+
+tls_array!(LogId = [u8, ..32]);
+tls_vec!(CtExtensions = u8(0, (1 << 16) - 1));
+
+// RFC 6962 3.2
+tls_struct!(struct SignedCertificateTimestamp {
+    // v1(0)
+    sct_version: u8,
+    log_id: LogId,
+    // milliseconds since the Unix epoch
+    timestamp: u64,
+    extensions: CtExtensions,
+    signature: DigitallySigned
+});
+
+const SCT_VERSION_V1: u8 = 0;
+const SIGNATURE_TYPE_CERTIFICATE_TIMESTAMP: u8 = 0;
+const LOG_ENTRY_TYPE_X509: u16 = 0;
+const LOG_ENTRY_TYPE_PRECERT: u16 = 1;
+
+/// A CT log trusted by the client.
+pub struct Log {
+    /// SHA-256 hash of `key`
+    pub id: [u8; 32],
+    /// raw DER `SubjectPublicKeyInfo`
+    pub key: Vec<u8>,
+}
+
+impl Log {
+    pub fn new(key: Vec<u8>) -> Log {
+        Log {
+            id: sha256(&key),
+            key: key,
+        }
+    }
+}
+
+/// Where an SCT was delivered.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SctSource {
+    /// the certificate extension, issued for the precertificate
+    Embedded,
+    /// the `signed_certificate_timestamp` TLS extension
+    TlsExtension,
+}
+
+/// An SCT whose signature is verified.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct VerifiedSct {
+    pub log_id: [u8; 32],
+    /// milliseconds since the Unix epoch
+    pub timestamp: u64,
+    pub source: SctSource,
+}
+
+/// Decides whether a server certificate has enough SCTs.
+pub trait CtPolicy {
+    /// `scts` are the verified SCTs for `leaf`, possibly several from the same log.
+    fn is_compliant(&self, leaf: &Certificate, scts: &[VerifiedSct]) -> bool;
+}
+
+/// Requires valid SCTs from at least the given number of distinct logs.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct MinimumLogs(pub usize);
+
+impl CtPolicy for MinimumLogs {
+    fn is_compliant(&self, _leaf: &Certificate, scts: &[VerifiedSct]) -> bool {
+        let mut logs: Vec<&[u8; 32]> = scts.iter().map(|sct| &sct.log_id).collect();
+        logs.sort();
+        logs.dedup();
+        logs.len() >= self.0
+    }
+}
+
+enum LogEntry<'a> {
+    X509(&'a [u8]),
+    Precert {
+        issuer_key_hash: [u8; 32],
+        tbs_certificate: Vec<u8>,
+    },
+}
+
+fn write_u24_vec(out: &mut Vec<u8>, data: &[u8]) {
+    let len = data.len();
+    out.extend(&[(len >> 16) as u8, (len >> 8) as u8, len as u8]);
+    out.extend(data);
+}
+
+// `digitally-signed struct` of RFC 6962 3.2
+fn signed_data(sct: &SignedCertificateTimestamp, entry: &LogEntry) -> Vec<u8> {
+    let mut data = vec![sct.sct_version, SIGNATURE_TYPE_CERTIFICATE_TIMESTAMP];
+    data.extend(&u64_be_array(sct.timestamp));
+    match *entry {
+        LogEntry::X509(cert) => {
+            data.extend(&[(LOG_ENTRY_TYPE_X509 >> 8) as u8, LOG_ENTRY_TYPE_X509 as u8]);
+            write_u24_vec(&mut data, cert);
+        }
+        LogEntry::Precert { ref issuer_key_hash, ref tbs_certificate } => {
+            data.extend(&[(LOG_ENTRY_TYPE_PRECERT >> 8) as u8, LOG_ENTRY_TYPE_PRECERT as u8]);
+            data.extend(&issuer_key_hash[..]);
+            write_u24_vec(&mut data, tbs_certificate);
+        }
+    }
+    let ext_len = sct.extensions.len();
+    data.extend(&[(ext_len >> 8) as u8, ext_len as u8]);
+    data.extend(&sct.extensions[..]);
+    data
+}
+
+/// Returns `TBSCertificate` of the precertificate, i.e. that of `cert` without
+/// the embedded SCT list extension (RFC 6962 3.2).
+fn precert_tbs_certificate(cert: &Certificate) -> TlsResult<Vec<u8>> {
+    let mut tbs = try!(Reader::new(cert.tbs_certificate).read_nested(der::SEQUENCE));
+    let mut contents = Vec::new();
+    while !tbs.is_empty() {
+        let field = try!(tbs.read_any());
+        if field.tag != der::context(3) {
+            contents.extend(field.raw);
+            continue;
+        }
+        let mut exts = try!(Reader::new(field.value).read_nested(der::SEQUENCE));
+        let mut kept = Vec::new();
+        while !exts.is_empty() {
+            let ext = try!(exts.read_raw(der::SEQUENCE));
+            let id = try!(try!(Reader::new(ext).read_nested(der::SEQUENCE)).read(der::OID));
+            if id != oid::CT_PRECERT_SCTS {
+                kept.extend(ext);
+            }
+        }
+        if !kept.is_empty() {
+            let mut seq = Vec::new();
+            der::write_tlv(&mut seq, der::SEQUENCE, &kept);
+            der::write_tlv(&mut contents, field.tag, &seq);
+        }
+    }
+    let mut out = Vec::new();
+    der::write_tlv(&mut out, der::SEQUENCE, &contents);
+    Ok(out)
+}
+
+/// Returns the SCT list embedded in `cert`, if any.
+pub fn embedded_scts(cert: &Certificate) -> TlsResult<Option<SignedCertificateTimestampList>> {
+    let ext = match cert.extension(oid::CT_PRECERT_SCTS) {
+        Some(ext) => ext,
+        None => return Ok(None),
+    };
+    let mut reader = Reader::new(ext.value);
+    let data = try!(reader.read(der::OCTET_STRING));
+    try!(reader.finish());
+    let mut data_reader = data;
+    let list: SignedCertificateTimestampList = try!(TlsItem::tls_read(&mut data_reader));
+    if data_reader.len() > 0 {
+        return tls_err!(DecodeError, "trailing data after SCT list");
+    }
+    Ok(Some(list))
+}
+
+// returns `None` if the SCT is not v1 and must be ignored.
+fn parse_sct(data: &[u8]) -> TlsResult<Option<SignedCertificateTimestamp>> {
+    if data[0] != SCT_VERSION_V1 {
+        return Ok(None);
+    }
+    let mut reader = data;
+    let sct: SignedCertificateTimestamp = try!(TlsItem::tls_read(&mut reader));
+    if reader.len() > 0 {
+        return tls_err!(DecodeError, "trailing data after SCT");
+    }
+    Ok(Some(sct))
+}
+
+fn verify_sct(logs: &[Log],
+              sct: &SignedCertificateTimestamp,
+              entry: &LogEntry,
+              now: i64) -> Option<VerifiedSct> {
+    let log = match logs.iter().find(|log| &log.id[..] == &sct.log_id[..]) {
+        Some(log) => log,
+        None => return None,
+    };
+    // SCTs from the future are invalid
+    if now < 0 || sct.timestamp > (now as u64).saturating_mul(1000) {
+        return None;
+    }
+    // RFC 6962 2.1.4: SHA-256 with ECDSA or RSA
+    if sct.signature.algorithm.hash != HashAlgorithm::sha256 {
+        return None;
+    }
+    if sct.signature.verify(&log.key, &signed_data(sct, entry)).is_err() {
+        return None;
+    }
+    Some(VerifiedSct {
+        log_id: log.id,
+        timestamp: sct.timestamp,
+        source: match *entry {
+            LogEntry::X509(..) => SctSource::TlsExtension,
+            LogEntry::Precert { .. } => SctSource::Embedded,
+        },
+    })
+}
+
+/// Verifies SCTs for the certificate `leaf_der` against `logs` at `now`, in seconds since
+/// the Unix epoch. `tls_scts` is the list sent in the TLS extension. Embedded SCTs are only
+/// checked if `issuer` is known.
+///
+/// SCTs from unknown logs, of unknown versions, from the future or with bad signatures
+/// are ignored. Malformed SCT lists are errors.
+pub fn verify_scts(logs: &[Log],
+                   leaf_der: &[u8],
+                   issuer: Option<&Certificate>,
+                   tls_scts: Option<&SignedCertificateTimestampList>,
+                   now: i64) -> TlsResult<Vec<VerifiedSct>> {
+    let leaf = try!(Certificate::parse(leaf_der));
+    let mut verified = Vec::new();
+
+    if let Some(list) = tls_scts {
+        let entry = LogEntry::X509(leaf_der);
+        for data in list.iter() {
+            if let Some(sct) = try!(parse_sct(&data[..])) {
+                verified.extend(verify_sct(logs, &sct, &entry, now));
+            }
+        }
+    }
+
+    if let (Some(list), Some(issuer)) = (try!(embedded_scts(&leaf)), issuer) {
+        let entry = LogEntry::Precert {
+            issuer_key_hash: sha256(issuer.subject_public_key_info),
+            tbs_certificate: try!(precert_tbs_certificate(&leaf)),
+        };
+        for data in list.iter() {
+            if let Some(sct) = try!(parse_sct(&data[..])) {
+                verified.extend(verify_sct(logs, &sct, &entry, now));
+            }
+        }
+    }
+
+    Ok(verified)
+}
+
+#[cfg(test)]
+mod test {
+    use handshake::SignedCertificateTimestampList;
+    use tls_item::TlsItem;
+    use x509::Certificate;
+    use super::{Log, SctSource, CtPolicy, MinimumLogs, verify_scts, embedded_scts,
+                precert_tbs_certificate};
+
+    static CA: &'static [u8] = include_bytes!("../testdata/test_ca.der");
+    static SERVER: &'static [u8] = include_bytes!("../testdata/test_server.der");
+    static EMBEDDED: &'static [u8] = include_bytes!("../testdata/test_ct_embedded.der");
+    static SERVER_SCTS: &'static [u8] = include_bytes!("../testdata/sct_list_server.bin");
+    static LOG_EC: &'static [u8] = include_bytes!("../testdata/test_ct_log_ec.der");
+    static LOG_RSA: &'static [u8] = include_bytes!("../testdata/test_ct_log_rsa.der");
+
+    // 2026-10-18 13:58:12, an hour after the SCTs are issued
+    const NOW: i64 = 1792328292 + 3600;
+
+    fn logs() -> Vec<Log> {
+        vec![Log::new(LOG_EC.to_vec()), Log::new(LOG_RSA.to_vec())]
+    }
+
+    #[test]
+    fn test_embedded_scts() {
+        let ca = Certificate::parse(CA).unwrap();
+        let leaf = Certificate::parse(EMBEDDED).unwrap();
+        assert_eq!(embedded_scts(&leaf).unwrap().unwrap().len(), 2);
+        assert!(embedded_scts(&ca).unwrap().is_none());
+
+        // the precertificate has the other extensions, and its length fields are fixed
+        let tbs = precert_tbs_certificate(&leaf).unwrap();
+        assert!(tbs.len() < leaf.tbs_certificate.len());
+        assert_eq!(&precert_tbs_certificate(&ca).unwrap()[..], ca.tbs_certificate);
+
+        let logs = logs();
+        let scts = verify_scts(&logs, EMBEDDED, Some(&ca), None, NOW).unwrap();
+        assert_eq!(scts.len(), 2);
+        assert_eq!(scts[0].log_id, logs[0].id);
+        assert_eq!(scts[1].log_id, logs[1].id);
+        assert_eq!(scts[0].timestamp, 1792328292000);
+        assert_eq!(scts[0].source, SctSource::Embedded);
+        assert!(MinimumLogs(2).is_compliant(&leaf, &scts));
+        assert!(!MinimumLogs(3).is_compliant(&leaf, &scts));
+
+        // unknown issuer, unknown logs, SCTs from the future
+        assert_eq!(verify_scts(&logs, EMBEDDED, None, None, NOW).unwrap().len(), 0);
+        assert_eq!(verify_scts(&logs[1..], EMBEDDED, Some(&ca), None, NOW).unwrap().len(), 1);
+        assert_eq!(verify_scts(&logs, EMBEDDED, Some(&ca), None, 1792328291).unwrap().len(), 0);
+
+        // signed for another issuer key
+        let server = Certificate::parse(SERVER).unwrap();
+        assert_eq!(verify_scts(&logs, EMBEDDED, Some(&server), None, NOW).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_tls_scts() {
+        let logs = logs();
+        let list: SignedCertificateTimestampList =
+            TlsItem::tls_read(&mut &SERVER_SCTS[..]).unwrap();
+        let scts = verify_scts(&logs, SERVER, None, Some(&list), NOW).unwrap();
+        assert_eq!(scts.len(), 1);
+        assert_eq!(scts[0].log_id, logs[0].id);
+        assert_eq!(scts[0].source, SctSource::TlsExtension);
+
+        // the SCT is for another certificate
+        assert_eq!(verify_scts(&logs, EMBEDDED, None, Some(&list), NOW).unwrap().len(), 0);
+
+        // bad signature
+        let mut data = SERVER_SCTS.to_vec();
+        let len = data.len();
+        data[len - 5] ^= 1;
+        let list: SignedCertificateTimestampList = TlsItem::tls_read(&mut &data[..]).unwrap();
+        assert_eq!(verify_scts(&logs, SERVER, None, Some(&list), NOW).unwrap().len(), 0);
+
+        // unknown versions are ignored, but malformed v1 SCTs are not
+        let list: SignedCertificateTimestampList =
+            TlsItem::tls_read(&mut &[0, 4, 0, 2, 1, 0][..]).unwrap();
+        assert_eq!(verify_scts(&logs, SERVER, None, Some(&list), NOW).unwrap().len(), 0);
+        let list: SignedCertificateTimestampList =
+            TlsItem::tls_read(&mut &[0, 4, 0, 2, 0, 0][..]).unwrap();
+        assert!(verify_scts(&logs, SERVER, None, Some(&list), NOW).is_err());
+    }
+}
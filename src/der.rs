@@ -0,0 +1,284 @@
+//! Minimal DER (X.690) reader and writer for certificates and related structures.
+//!
+//! Only definite-length encodings with single-byte tags are supported,
+//! which is enough for X.509 and friends.
+
+use tls_result::TlsResult;
+use tls_result::TlsErrorKind::DecodeError;
+
+pub const BOOLEAN: u8 = 0x01;
+pub const INTEGER: u8 = 0x02;
+pub const BIT_STRING: u8 = 0x03;
+pub const OCTET_STRING: u8 = 0x04;
+pub const NULL: u8 = 0x05;
+pub const OID: u8 = 0x06;
+pub const ENUMERATED: u8 = 0x0a;
+pub const UTF8_STRING: u8 = 0x0c;
+pub const PRINTABLE_STRING: u8 = 0x13;
+pub const IA5_STRING: u8 = 0x16;
+pub const UTC_TIME: u8 = 0x17;
+pub const GENERALIZED_TIME: u8 = 0x18;
+pub const SEQUENCE: u8 = 0x30;
+pub const SET: u8 = 0x31;
+
+/// Tag of `[n] EXPLICIT` or constructed `[n] IMPLICIT` fields.
+pub fn context(n: u8) -> u8 {
+    0xa0 | n
+}
+
+/// Tag of primitive `[n] IMPLICIT` fields.
+pub fn context_primitive(n: u8) -> u8 {
+    0x80 | n
+}
+
+/// A DER element.
+pub struct Tlv<'a> {
+    pub tag: u8,
+    /// contents octets
+    pub value: &'a [u8],
+    /// whole encoding including tag and length
+    pub raw: &'a [u8],
+}
+
+pub struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data: data }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.len() == 0
+    }
+
+    pub fn peek_tag(&self) -> Option<u8> {
+        self.data.first().map(|&tag| tag)
+    }
+
+    pub fn read_any(&mut self) -> TlsResult<Tlv<'a>> {
+        let data = self.data;
+        if data.len() < 2 {
+            return tls_err!(DecodeError, "DER: unexpected end");
+        }
+        let tag = data[0];
+        if tag & 0x1f == 0x1f {
+            return tls_err!(DecodeError, "DER: multi-byte tag is not supported");
+        }
+
+        let (len, header_len) = match data[1] {
+            n if n < 0x80 => (n as usize, 2),
+            0x81..=0x84 => {
+                let num_bytes = (data[1] & 0x7f) as usize;
+                if data.len() < 2 + num_bytes {
+                    return tls_err!(DecodeError, "DER: unexpected end");
+                }
+                let mut len = 0usize;
+                for i in 0..num_bytes {
+                    len = (len << 8) | (data[2 + i] as usize);
+                }
+                // DER requires the shortest form
+                if len < 0x80 || data[2] == 0 {
+                    return tls_err!(DecodeError, "DER: non-minimal length");
+                }
+                (len, 2 + num_bytes)
+            }
+            _ => return tls_err!(DecodeError, "DER: unsupported length"),
+        };
+
+        if data.len() - header_len < len {
+            return tls_err!(DecodeError, "DER: unexpected end");
+        }
+        let end = header_len + len;
+        self.data = &data[end..];
+        Ok(Tlv {
+            tag: tag,
+            value: &data[header_len..end],
+            raw: &data[..end],
+        })
+    }
+
+    /// Reads an element with `tag` and returns its contents.
+    pub fn read(&mut self, tag: u8) -> TlsResult<&'a [u8]> {
+        let tlv = try!(self.read_any());
+        if tlv.tag != tag {
+            return tls_err!(DecodeError, "DER: expected tag {:#x}, found {:#x}", tag, tlv.tag);
+        }
+        Ok(tlv.value)
+    }
+
+    /// Reads an element with `tag` and returns the whole encoding.
+    pub fn read_raw(&mut self, tag: u8) -> TlsResult<&'a [u8]> {
+        let tlv = try!(self.read_any());
+        if tlv.tag != tag {
+            return tls_err!(DecodeError, "DER: expected tag {:#x}, found {:#x}", tag, tlv.tag);
+        }
+        Ok(tlv.raw)
+    }
+
+    /// Reads an element if the next tag is `tag`.
+    pub fn read_optional(&mut self, tag: u8) -> TlsResult<Option<&'a [u8]>> {
+        if self.peek_tag() == Some(tag) {
+            Ok(Some(try!(self.read(tag))))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Reads an element with `tag` and returns a reader for its contents.
+    pub fn read_nested(&mut self, tag: u8) -> TlsResult<Reader<'a>> {
+        let value = try!(self.read(tag));
+        Ok(Reader::new(value))
+    }
+
+    pub fn read_bool(&mut self) -> TlsResult<bool> {
+        let value = try!(self.read(BOOLEAN));
+        match value {
+            [0x00] => Ok(false),
+            [0xff] => Ok(true),
+            _ => tls_err!(DecodeError, "DER: bad BOOLEAN"),
+        }
+    }
+
+    /// Reads a non-negative INTEGER and returns its big-endian magnitude
+    /// without leading zeros.
+    pub fn read_unsigned(&mut self) -> TlsResult<&'a [u8]> {
+        let value = try!(self.read(INTEGER));
+        if value.len() == 0 || value[0] & 0x80 != 0 {
+            return tls_err!(DecodeError, "DER: negative or empty INTEGER");
+        }
+        if value.len() > 1 && value[0] == 0 && value[1] & 0x80 == 0 {
+            return tls_err!(DecodeError, "DER: non-minimal INTEGER");
+        }
+        if value.len() > 1 && value[0] == 0 {
+            Ok(&value[1..])
+        } else {
+            Ok(value)
+        }
+    }
+
+    /// Reads a small non-negative INTEGER.
+    pub fn read_small_unsigned(&mut self) -> TlsResult<u64> {
+        let value = try!(self.read_unsigned());
+        if value.len() > 8 {
+            return tls_err!(DecodeError, "DER: INTEGER too large");
+        }
+        let mut n = 0u64;
+        for &b in value.iter() {
+            n = (n << 8) | (b as u64);
+        }
+        Ok(n)
+    }
+
+    /// Reads a BIT STRING without unused bits.
+    pub fn read_bit_string(&mut self) -> TlsResult<&'a [u8]> {
+        let value = try!(self.read(BIT_STRING));
+        if value.len() == 0 || value[0] != 0 {
+            return tls_err!(DecodeError, "DER: BIT STRING with unused bits");
+        }
+        Ok(&value[1..])
+    }
+
+    /// Checks that all data is consumed.
+    pub fn finish(&self) -> TlsResult<()> {
+        if !self.is_empty() {
+            return tls_err!(DecodeError, "DER: trailing data");
+        }
+        Ok(())
+    }
+}
+
+/// Appends an element with `tag` and contents `value` to `out`.
+pub fn write_tlv(out: &mut Vec<u8>, tag: u8, value: &[u8]) {
+    out.push(tag);
+    let len = value.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let mut num_bytes = 1;
+        while num_bytes < 4 && len >> (num_bytes * 8) != 0 {
+            num_bytes += 1;
+        }
+        out.push(0x80 | num_bytes as u8);
+        for i in (0..num_bytes).rev() {
+            out.push((len >> (i * 8)) as u8);
+        }
+    }
+    out.extend(value);
+}
+
+/// Appends a non-negative INTEGER whose big-endian magnitude is `value`.
+pub fn write_unsigned(out: &mut Vec<u8>, value: &[u8]) {
+    let start = value.iter().position(|&b| b != 0).unwrap_or(value.len());
+    let value = &value[start..];
+    let mut contents = Vec::with_capacity(value.len() + 1);
+    if value.len() == 0 || value[0] & 0x80 != 0 {
+        contents.push(0);
+    }
+    contents.extend(value);
+    write_tlv(out, INTEGER, &contents);
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Reader, SEQUENCE, INTEGER, OCTET_STRING, write_tlv, write_unsigned};
+
+    #[test]
+    fn test_der_reader() {
+        // SEQUENCE { INTEGER 5, INTEGER 128, BOOLEAN TRUE }
+        let data = [0x30, 0x0a, 0x02, 0x01, 0x05, 0x02, 0x02, 0x00, 0x80, 0x01, 0x01, 0xff];
+        let mut reader = Reader::new(&data);
+        let mut seq = reader.read_nested(SEQUENCE).unwrap();
+        assert!(reader.finish().is_ok());
+        assert_eq!(seq.read_small_unsigned().unwrap(), 5);
+        assert_eq!(seq.read_unsigned().unwrap(), &[0x80]);
+        assert_eq!(seq.read_bool().unwrap(), true);
+        assert!(seq.finish().is_ok());
+
+        // long form length
+        let mut data = vec![0x04, 0x81, 0x80];
+        data.extend(&[0u8; 0x80][..]);
+        let mut reader = Reader::new(&data);
+        assert_eq!(reader.read_any().unwrap().value.len(), 0x80);
+
+        let mut written = Vec::new();
+        write_tlv(&mut written, OCTET_STRING, &[0u8; 0x80]);
+        assert_eq!(written, data);
+        let mut written = Vec::new();
+        write_tlv(&mut written, SEQUENCE, &[0u8; 0x1234]);
+        assert_eq!(&written[..4], &[0x30, 0x82, 0x12, 0x34]);
+        assert_eq!(Reader::new(&written).read(SEQUENCE).unwrap().len(), 0x1234);
+
+        for &(value, encoded) in [(&[][..], &[0x02, 0x01, 0x00][..]),
+                                  (&[0x00, 0x00, 0x05], &[0x02, 0x01, 0x05]),
+                                  (&[0x80], &[0x02, 0x02, 0x00, 0x80])].iter() {
+            let mut written = Vec::new();
+            write_unsigned(&mut written, value);
+            assert_eq!(written, encoded);
+        }
+
+        let bad: &[&[u8]] = &[
+            // truncated
+            &[0x02, 0x02, 0x00],
+            // non-minimal length
+            &[0x02, 0x81, 0x01, 0x00],
+            // indefinite length
+            &[0x30, 0x80, 0x00, 0x00],
+            // negative
+            &[0x02, 0x01, 0x80],
+            // non-minimal integer
+            &[0x02, 0x02, 0x00, 0x01],
+        ];
+        for &data in bad.iter() {
+            let mut reader = Reader::new(data);
+            assert!(reader.read_unsigned().is_err());
+        }
+
+        let mut reader = Reader::new(&[0x02, 0x01, 0x00]);
+        assert!(reader.read(SEQUENCE).is_err());
+        let mut reader = Reader::new(&[0x02, 0x01, 0x00]);
+        assert!(reader.read_optional(SEQUENCE).unwrap().is_none());
+        assert!(reader.read_optional(INTEGER).unwrap().is_some());
+    }
+}
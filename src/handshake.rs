@@ -1,11 +1,11 @@
 use std::io::prelude::*;
 
 use util::{ReadExt, WriteExt};
-use tls::TLS_VERSION;
 use tls_result::TlsResult;
 use tls_result::TlsErrorKind::{InternalError, UnexpectedMessage, DecodeError};
 use tls_item::{TlsItem, DummyItem, ObscureData};
-use signature::SignatureAndHashAlgorithmVec;
+use signature::{SignatureAndHashAlgorithm, SignatureAndHashAlgorithmVec, DigitallySigned,
+                Signature};
 use cipher::CipherSuite;
 
 // This is actually `struct { gmt_unix_time: u32, random_bytes: [u8, ..28] }`
@@ -28,7 +28,7 @@ tls_vec!(Asn1Cert = u8(1, (1 << 24) - 1));
 
 // RFC 4492
 
-tls_enum!(u16, enum NamedCurve {
+tls_enum!(u16, #[derive(Debug)] enum NamedCurve {
     sect163k1 (1), sect163r1 (2), sect163r2 (3),
     sect193r1 (4), sect193r2 (5), sect233k1 (6),
     sect233r1 (7), sect239k1 (8), sect283k1 (9),
@@ -38,11 +38,76 @@ tls_enum!(u16, enum NamedCurve {
     secp192r1 (19), secp224k1 (20), secp224r1 (21),
     secp256k1 (22), secp256r1 (23), secp384r1 (24),
     secp521r1 (25),
+    // RFC 7919
+    ffdhe2048 (256), ffdhe3072 (257), ffdhe4096 (258),
+    ffdhe6144 (259), ffdhe8192 (260),
+    // draft-ietf-tls-ecdhe-mlkem
+    X25519MLKEM768 (0x11EC),
     arbitrary_explicit_prime_curves(0xFF01),
     arbitrary_explicit_char2_curves(0xFF02)
 });
 tls_vec!(EllipticCurveList = NamedCurve(1, (1 << 16) - 1));
 
+// RFC 6066 3
+tls_enum!(u8, #[derive(Debug)] enum NameType {
+    host_name(0)
+});
+tls_vec!(HostName = u8(1, (1 << 16) - 1));
+tls_struct!(struct ServerName {
+    name_type: NameType,
+    name: HostName
+});
+tls_vec!(ServerNameList = ServerName(1, (1 << 16) - 1));
+tls_option!(ServerNameList);
+// ClientHello sends the name. ServerHello echoes an empty extension.
+pub type ServerNameExtension = Option<ServerNameList>;
+
+// RFC 6066 4
+tls_enum!(u8, #[derive(Debug)] enum MaxFragmentLength {
+    length_512(1), length_1024(2), length_2048(3), length_4096(4)
+});
+
+impl MaxFragmentLength {
+    pub fn len(&self) -> usize {
+        1 << (8 + *self as usize)
+    }
+}
+
+// RFC 6066 8
+tls_enum!(u8, #[derive(Debug)] enum CertificateStatusType {
+    ocsp(1)
+});
+tls_vec!(ResponderId = u8(1, (1 << 16) - 1));
+tls_vec!(ResponderIdList = ResponderId(0, (1 << 16) - 1));
+tls_vec!(OcspExtensions = u8(0, (1 << 16) - 1));
+tls_struct!(struct OcspStatusRequest {
+    responder_id_list: ResponderIdList,
+    request_extensions: OcspExtensions
+});
+tls_struct!(struct CertificateStatusRequest {
+    status_type: CertificateStatusType,
+    request: OcspStatusRequest
+});
+tls_option!(CertificateStatusRequest);
+// ClientHello sends a request. ServerHello echoes an empty extension.
+pub type StatusRequest = Option<CertificateStatusRequest>;
+
+tls_vec!(OcspResponse = u8(1, (1 << 24) - 1));
+tls_struct!(struct CertificateStatus {
+    status_type: CertificateStatusType,
+    response: OcspResponse
+});
+
+// RFC 6962 3.3
+tls_vec!(SerializedSct = u8(1, (1 << 16) - 1));
+tls_vec!(SignedCertificateTimestampList = SerializedSct(1, (1 << 16) - 1));
+tls_option!(SignedCertificateTimestampList);
+// ClientHello sends an empty extension. ServerHello carries SCTs.
+pub type SctExtension = Option<SignedCertificateTimestampList>;
+
+// RFC 5746
+tls_vec!(RenegotiationInfo = u8(0, (1 << 8) - 1));
+
 tls_enum!(u8, enum ECPointFormat {
     uncompressed (0), ansiX962_compressed_prime (1),
     ansiX962_compressed_char2 (2)
@@ -103,10 +168,14 @@ macro_rules! tls_hello_extension {
             fn tls_read<R: ReadExt>(reader: &mut R) -> TlsResult<$enum_name> {
                 let extension_type = try_read_num!(u16, reader);
                 let extension_data_size = try_read_num!(u16, reader);
+                // parse the body within `extension_data`, so that a body can depend on
+                // its size (e.g. empty `status_request` of ServerHello)
+                let data: Vec<u8> = try!(ReadExt::read_exact(reader, extension_data_size as usize));
                 match extension_type {
                     $(
                         tt_to_pat!($ext_num) => {
-                            let body: $body_ty = try!(TlsItem::tls_read(reader));
+                            let mut data_reader = ::std::io::Cursor::new(data);
+                            let body: $body_ty = try!(TlsItem::tls_read(&mut data_reader));
                             let body_size = body.tls_size();
                             if extension_data_size as u64 != body_size {
                                 return tls_err!(DecodeError, "Hello Extension has wrong size");
@@ -114,10 +183,7 @@ macro_rules! tls_hello_extension {
                             Ok($enum_name::$ext_name(body))
                         }
                     )+
-                    _ => {
-                        let body: Vec<u8> = try!(ReadExt::read_exact(reader, extension_data_size as usize));
-                        Ok($enum_name::Unknown(extension_type, body))
-                    }
+                    _ => Ok($enum_name::Unknown(extension_type, data)),
                 }
             }
 
@@ -132,22 +198,41 @@ macro_rules! tls_hello_extension {
                 4 + body_size
             }
         }
+
+        impl $enum_name {
+            pub fn extension_type(&self) -> u16 {
+                match *self {
+                    $(
+                        $enum_name::$ext_name(..) => tt_to_expr!($ext_num),
+                    )+
+                    $enum_name::Unknown(extension_type, _) => extension_type,
+                }
+            }
+        }
     )
 }
 
 tls_hello_extension!(enum Extension {
     // RFC 6066
-    //server_name(0),
-    //max_fragment_length(1),
+    server_name(ServerNameExtension) = 0,
+    max_fragment_length(MaxFragmentLength) = 1,
     //client_certificate_url(2),
     //trusted_ca_keys(3),
     //truncated_hmac(4),
-    //status_request(5),
+    status_request(StatusRequest) = 5,
     // RFC 4492
     elliptic_curves(EllipticCurveList) = 10,
-    ec_point_formats(ECPointFormatList) = 11
+    ec_point_formats(ECPointFormatList) = 11,
     // RFC 5246
-    //signature_algorithms(13)
+    signature_algorithms(SignatureAndHashAlgorithmVec) = 13,
+    // RFC 6962
+    signed_certificate_timestamp(SctExtension) = 18,
+    // RFC 7366
+    encrypt_then_mac(DummyItem) = 22,
+    // RFC 8449
+    record_size_limit(u16) = 28,
+    // RFC 5746
+    renegotiation_info(RenegotiationInfo) = 0xff01
 });
 
 impl Extension {
@@ -162,6 +247,51 @@ impl Extension {
         let list = Extension::ec_point_formats(list);
         Ok(list)
     }
+
+    /// `server_name` with a DNS host name. IP addresses must not be sent (RFC 6066 3).
+    pub fn new_server_name(host_name: &str) -> TlsResult<Extension> {
+        let name = ServerName {
+            name_type: NameType::host_name,
+            name: try!(HostName::new(host_name.as_bytes().to_vec())),
+        };
+        let list = try!(ServerNameList::new(vec![name]));
+        Ok(Extension::server_name(Some(list)))
+    }
+
+    pub fn new_signature_algorithms(list: Vec<SignatureAndHashAlgorithm>)
+                                    -> TlsResult<Extension> {
+        let list = try!(SignatureAndHashAlgorithmVec::new(list));
+        Ok(Extension::signature_algorithms(list))
+    }
+
+    pub fn new_max_fragment_length(len: MaxFragmentLength) -> TlsResult<Extension> {
+        Ok(Extension::max_fragment_length(len))
+    }
+
+    /// `status_request` for OCSP without responder ids and request extensions.
+    pub fn new_status_request() -> TlsResult<Extension> {
+        let request = OcspStatusRequest {
+            responder_id_list: try!(ResponderIdList::new(Vec::new())),
+            request_extensions: try!(OcspExtensions::new(Vec::new())),
+        };
+        Ok(Extension::status_request(Some(CertificateStatusRequest {
+            status_type: CertificateStatusType::ocsp,
+            request: request,
+        })))
+    }
+
+    pub fn new_signed_certificate_timestamp() -> TlsResult<Extension> {
+        Ok(Extension::signed_certificate_timestamp(None))
+    }
+
+    pub fn new_record_size_limit(limit: u16) -> TlsResult<Extension> {
+        Ok(Extension::record_size_limit(limit))
+    }
+
+    pub fn new_renegotiation_info(data: Vec<u8>) -> TlsResult<Extension> {
+        let data = try!(RenegotiationInfo::new(data));
+        Ok(Extension::renegotiation_info(data))
+    }
 }
 
 tls_vec!(ExtensionVec = Extension(0, (1 << 16) - 1));
@@ -260,9 +390,10 @@ tls_handshake!(
     server_key_exchange(ObscureData) = 12,
     certificate_request(CertificateRequest) = 13,
     server_hello_done(DummyItem) = 14,
-    // certificate_verify = 15,
+    certificate_verify(DigitallySigned) = 15,
     client_key_exchange(ObscureData) = 16,
     finished(VerifyData) = 20,
+    certificate_status(CertificateStatus) = 22,
 );
 
 tls_struct!(struct ClientHello {
@@ -285,10 +416,12 @@ tls_struct!(struct ServerHello {
 
 tls_vec!(CertificateList = Asn1Cert(0, (1 << 24) - 1));
 
-tls_enum!(u8, enum ClientCertificateType {
+tls_enum!(u8, #[derive(Debug)] enum ClientCertificateType {
       rsa_sign(1), dss_sign(2), rsa_fixed_dh(3), dss_fixed_dh(4),
       rsa_ephemeral_dh_RESERVED(5), dss_ephemeral_dh_RESERVED(6),
-      fortezza_dms_RESERVED(20)
+      fortezza_dms_RESERVED(20),
+      // RFC 4492
+      ecdsa_sign(64), rsa_fixed_ecdh(65), ecdsa_fixed_ecdh(66)
 });
 tls_vec!(CertificiateTypeVec = ClientCertificateType(1, (1 << 8) - 1));
 
@@ -354,12 +487,13 @@ impl HandshakeBuffer {
 }
 
 impl Handshake {
-    pub fn new_client_hello(random: Random,
-                            cipher_suite: CipherSuite,
+    pub fn new_client_hello(version: (u8, u8),
+                            random: Random,
+                            cipher_suites: Vec<CipherSuite>,
                             extensions: Vec<Extension>) -> TlsResult<Handshake> {
         let client_hello_body = {
             let client_version = {
-                let (major, minor) = TLS_VERSION;
+                let (major, minor) = version;
 
                 ProtocolVersion {
                     major: major,
@@ -373,10 +507,7 @@ impl Handshake {
                 try!(SessionId::new(data))
             };
 
-            let cipher_suites = {
-                let data = vec!(cipher_suite);
-                try!(CipherSuiteVec::new(data))
-            };
+            let cipher_suites = try!(CipherSuiteVec::new(cipher_suites));
 
             let compression_methods = {
                 let data = vec!(CompressionMethod::null);
@@ -402,6 +533,25 @@ impl Handshake {
         Ok(Handshake::client_hello(client_hello_body))
     }
 
+    /// `chain` is a list of DER certificates, leaf first. It can be empty.
+    pub fn new_certificate(chain: Vec<Vec<u8>>) -> TlsResult<Handshake> {
+        let mut list = Vec::with_capacity(chain.len());
+        for cert in chain.into_iter() {
+            list.push(try!(Asn1Cert::new(cert)));
+        }
+        let list = try!(CertificateList::new(list));
+        Ok(Handshake::certificate(list))
+    }
+
+    pub fn new_certificate_verify(algorithm: SignatureAndHashAlgorithm,
+                                  signature: Vec<u8>) -> TlsResult<Handshake> {
+        let signature = try!(Signature::new(signature));
+        Ok(Handshake::certificate_verify(DigitallySigned {
+            algorithm: algorithm,
+            signature: signature,
+        }))
+    }
+
     pub fn new_client_key_exchange(data: Vec<u8>) -> TlsResult<Handshake> {
         let data = ObscureData::new(data);
         Ok(Handshake::client_key_exchange(data))
@@ -420,7 +570,11 @@ mod test {
     use cipher::CipherSuite;
 
     use super::{ProtocolVersion, SessionId, CipherSuiteVec, CompressionMethod,
-                CompressionMethodVec, ClientHello, Handshake, Random};
+                CompressionMethodVec, ClientHello, Handshake, Random, Extension,
+                MaxFragmentLength, ClientCertificateType, CertificateStatusType,
+                ExtensionVec};
+    use signature::{SignatureAndHashAlgorithm, HashAlgorithm, SignatureAlgorithm,
+                    SUPPORTED_ALGORITHMS};
 
     #[test]
     fn test_parse_client_hello() {
@@ -479,4 +633,153 @@ mod test {
 
         assert_eq!(packet, packet_2);
     }
+
+    #[test]
+    fn test_record_size_extensions() {
+        let ext = Extension::new_max_fragment_length(MaxFragmentLength::length_2048).unwrap();
+        let mut data = Vec::new();
+        ext.tls_write(&mut data).unwrap();
+        assert_eq!(data, vec![0x00, 0x01, 0x00, 0x01, 0x03]);
+        match TlsItem::tls_read(&mut &data[..]).unwrap() {
+            Extension::max_fragment_length(len) => assert_eq!(len.len(), 2048),
+            _ => panic!(),
+        }
+
+        let ext = Extension::new_record_size_limit(0x1234).unwrap();
+        let mut data = Vec::new();
+        ext.tls_write(&mut data).unwrap();
+        assert_eq!(data, vec![0x00, 0x1c, 0x00, 0x02, 0x12, 0x34]);
+        let ext: Extension = TlsItem::tls_read(&mut &data[..]).unwrap();
+        assert_eq!(ext.extension_type(), 28);
+        match ext {
+            Extension::record_size_limit(limit) => assert_eq!(limit, 0x1234),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_parse_certificate_request() {
+        // sent by OpenSSL 3.5, including TLS 1.3 signature schemes
+        let data = [13, 0, 0, 48,
+                    3, 1, 2, 64,
+                    0, 40,
+                    4, 3, 5, 3, 6, 3, 8, 7, 8, 8, 8, 9, 8, 10, 8, 11, 8, 4, 8, 5,
+                    8, 6, 4, 1, 5, 1, 6, 1, 3, 3, 3, 1, 3, 2, 4, 2, 5, 2, 6, 2,
+                    0, 0];
+        let msg: Handshake = TlsItem::tls_read(&mut &data[..]).unwrap();
+        let request = match msg {
+            Handshake::certificate_request(request) => request,
+            _ => panic!("expected CertificateRequest"),
+        };
+        assert_eq!(request.certificate_types[2], ClientCertificateType::ecdsa_sign);
+        assert_eq!(request.supported_signature_algorithms.len(), 20);
+        assert_eq!(request.supported_signature_algorithms[3],
+                   SignatureAndHashAlgorithm {
+                       hash: HashAlgorithm::intrinsic,
+                       signature: SignatureAlgorithm::ed25519,
+                   });
+        assert_eq!(request.certificate_authorities.len(), 0);
+    }
+
+    #[test]
+    fn test_signature_algorithms() {
+        let ext = Extension::new_signature_algorithms(SUPPORTED_ALGORITHMS.to_vec()).unwrap();
+        let mut data = Vec::new();
+        ext.tls_write(&mut data).unwrap();
+        let len = SUPPORTED_ALGORITHMS.len() * 2;
+        assert_eq!(&data[..8], &[0, 13, 0, len as u8 + 2, 0, len as u8, 8, 7][..]);
+        // rsa_pss_rsae_sha256
+        assert!(data.chunks(2).any(|pair| pair == &[8, 4][..]));
+        match TlsItem::tls_read(&mut &data[..]).unwrap() {
+            Extension::signature_algorithms(list) => assert_eq!(&list[..], SUPPORTED_ALGORITHMS),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_certificate_verify() {
+        let algorithm = SignatureAndHashAlgorithm {
+            hash: HashAlgorithm::sha256,
+            signature: SignatureAlgorithm::ecdsa,
+        };
+        let msg = Handshake::new_certificate_verify(algorithm, vec![1, 2, 3]).unwrap();
+        let mut data = Vec::new();
+        msg.tls_write(&mut data).unwrap();
+        assert_eq!(data, vec![15, 0, 0, 7, 4, 3, 0, 3, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_status_request() {
+        let ext = Extension::new_status_request().unwrap();
+        let mut data = Vec::new();
+        ext.tls_write(&mut data).unwrap();
+        assert_eq!(data, vec![0, 5, 0, 5, 1, 0, 0, 0, 0]);
+        match TlsItem::tls_read(&mut &data[..]).unwrap() {
+            Extension::status_request(Some(request)) => {
+                assert_eq!(request.status_type, CertificateStatusType::ocsp);
+            }
+            _ => panic!(),
+        }
+
+        // ServerHello echoes an empty extension, possibly followed by others
+        let data = [0, 8, 0, 5, 0, 0, 0, 22, 0, 0];
+        let exts: ExtensionVec = TlsItem::tls_read(&mut &data[..]).unwrap();
+        assert_eq!(exts.len(), 2);
+        match exts[0] {
+            Extension::status_request(None) => {}
+            _ => panic!(),
+        }
+        assert_eq!(exts[1].extension_type(), 22);
+
+        // extension_data shorter than the body
+        let data = [0, 5, 0, 1, 1, 0, 0, 0, 0];
+        assert!(<Extension as TlsItem>::tls_read(&mut &data[..]).is_err());
+
+        let data = [22, 0, 0, 7, 1, 0, 0, 3, 0xaa, 0xbb, 0xcc];
+        match TlsItem::tls_read(&mut &data[..]).unwrap() {
+            Handshake::certificate_status(status) => {
+                assert_eq!(status.status_type, CertificateStatusType::ocsp);
+                assert_eq!(&status.response[..], &[0xaa, 0xbb, 0xcc]);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_signed_certificate_timestamp() {
+        let ext = Extension::new_signed_certificate_timestamp().unwrap();
+        let mut data = Vec::new();
+        ext.tls_write(&mut data).unwrap();
+        assert_eq!(data, vec![0, 18, 0, 0]);
+
+        let data = [0, 18, 0, 7, 0, 5, 0, 3, 0xaa, 0xbb, 0xcc];
+        match TlsItem::tls_read(&mut &data[..]).unwrap() {
+            Extension::signed_certificate_timestamp(Some(list)) => {
+                assert_eq!(list.len(), 1);
+                assert_eq!(&list[0][..], &[0xaa, 0xbb, 0xcc]);
+            }
+            _ => panic!(),
+        }
+
+        // empty list
+        let data = [0, 18, 0, 2, 0, 0];
+        assert!(<Extension as TlsItem>::tls_read(&mut &data[..]).is_err());
+    }
+
+    #[test]
+    fn test_server_name() {
+        let ext = Extension::new_server_name("example.com").unwrap();
+        let mut data = Vec::new();
+        ext.tls_write(&mut data).unwrap();
+        let mut expected = vec![0, 0, 0, 16, 0, 14, 0, 0, 11];
+        expected.extend(b"example.com");
+        assert_eq!(data, expected);
+
+        let data = [0, 0, 0, 0];
+        match TlsItem::tls_read(&mut &data[..]).unwrap() {
+            Extension::server_name(None) => {}
+            _ => panic!(),
+        }
+        assert!(Extension::new_server_name("").is_err());
+    }
 }
@@ -0,0 +1,517 @@
+//! Private keys for signing handshake messages.
+//!
+//! Keys are loaded from PKCS#8 `PrivateKeyInfo` (RFC 5208, RFC 8410 for Ed25519),
+//! SEC 1 `ECPrivateKey` (RFC 5915) or PKCS#1 `RSAPrivateKey` (RFC 8017 A.1.2), in DER or PEM.
+//! Encrypted keys are not supported.
+
+use rand::OsRng;
+
+use crypto::ecdsa;
+use crypto::ed25519;
+use crypto::rsa::RsaPrivateKey;
+use der::{self, Reader};
+use pem;
+use signature::{SigningKey, SignatureAndHashAlgorithm, HashAlgorithm, SignatureAlgorithm};
+use x509::{oid, AlgorithmIdentifier};
+use tls_result::TlsResult;
+use tls_result::TlsErrorKind::{DecodeError, InternalError};
+
+pub struct RsaSigningKey {
+    key: RsaPrivateKey,
+    /// raw DER `RSAPrivateKey`
+    pkcs1: Vec<u8>,
+    spki: Vec<u8>,
+}
+
+pub struct EcdsaSigningKey {
+    /// SECRET scalar, big-endian
+    private: Vec<u8>,
+    /// uncompressed point
+    public: Vec<u8>,
+}
+
+pub struct Ed25519SigningKey {
+    /// SECRET 32-byte private key
+    seed: Vec<u8>,
+    public: Vec<u8>,
+}
+
+pub enum PrivateKey {
+    Rsa(RsaSigningKey),
+    EcP256(EcdsaSigningKey),
+    Ed25519(Ed25519SigningKey),
+}
+
+fn rsa_algorithm_identifier(out: &mut Vec<u8>) {
+    let mut alg = Vec::new();
+    der::write_tlv(&mut alg, der::OID, oid::RSA_ENCRYPTION);
+    der::write_tlv(&mut alg, der::NULL, &[]);
+    der::write_tlv(out, der::SEQUENCE, &alg);
+}
+
+fn ec_algorithm_identifier(out: &mut Vec<u8>) {
+    let mut alg = Vec::new();
+    der::write_tlv(&mut alg, der::OID, oid::EC_PUBLIC_KEY);
+    der::write_tlv(&mut alg, der::OID, oid::SECP256R1);
+    der::write_tlv(out, der::SEQUENCE, &alg);
+}
+
+fn ed25519_algorithm_identifier(out: &mut Vec<u8>) {
+    let mut alg = Vec::new();
+    der::write_tlv(&mut alg, der::OID, oid::ED25519);
+    der::write_tlv(out, der::SEQUENCE, &alg);
+}
+
+fn bit_string(data: &[u8]) -> Vec<u8> {
+    let mut value = vec![0u8];
+    value.extend(data);
+    value
+}
+
+impl PrivateKey {
+    /// Parses DER PKCS#1 `RSAPrivateKey`.
+    pub fn from_pkcs1(data: &[u8]) -> TlsResult<PrivateKey> {
+        let mut reader = Reader::new(data);
+        let mut seq = try!(reader.read_nested(der::SEQUENCE));
+        try!(reader.finish());
+        // multi-prime keys have version 1
+        if try!(seq.read_small_unsigned()) != 0 {
+            return tls_err!(DecodeError, "unsupported RSA private key version");
+        }
+        let n = try!(seq.read_unsigned());
+        let e = try!(seq.read_unsigned());
+        let d = try!(seq.read_unsigned());
+        // p, q, d mod (p - 1), d mod (q - 1), q^-1 mod p: not used
+        for _ in 0..5 {
+            try!(seq.read_unsigned());
+        }
+        try!(seq.finish());
+
+        let key = match RsaPrivateKey::new(n, e, d) {
+            Some(key) => key,
+            None => return tls_err!(DecodeError, "unsupported or inconsistent RSA key"),
+        };
+
+        let mut public = Vec::new();
+        der::write_unsigned(&mut public, n);
+        der::write_unsigned(&mut public, e);
+        let mut rsa_public_key = Vec::new();
+        der::write_tlv(&mut rsa_public_key, der::SEQUENCE, &public);
+        let mut spki = Vec::new();
+        rsa_algorithm_identifier(&mut spki);
+        der::write_tlv(&mut spki, der::BIT_STRING, &bit_string(&rsa_public_key));
+        let mut seq = Vec::new();
+        der::write_tlv(&mut seq, der::SEQUENCE, &spki);
+
+        Ok(PrivateKey::Rsa(RsaSigningKey {
+            key: key,
+            pkcs1: data.to_vec(),
+            spki: seq,
+        }))
+    }
+
+    /// Parses DER SEC 1 `ECPrivateKey`. The key must have the curve parameters.
+    pub fn from_sec1(data: &[u8]) -> TlsResult<PrivateKey> {
+        PrivateKey::parse_sec1(data, None)
+    }
+
+    // `curve` is given by the PKCS#8 algorithm identifier.
+    fn parse_sec1(data: &[u8], curve: Option<&[u8]>) -> TlsResult<PrivateKey> {
+        let mut reader = Reader::new(data);
+        let mut seq = try!(reader.read_nested(der::SEQUENCE));
+        try!(reader.finish());
+        if try!(seq.read_small_unsigned()) != 1 {
+            return tls_err!(DecodeError, "unknown EC private key version");
+        }
+        let private = try!(seq.read(der::OCTET_STRING));
+        let params = match try!(seq.read_optional(der::context(0))) {
+            Some(params) => {
+                let mut reader = Reader::new(params);
+                let params = try!(reader.read(der::OID));
+                try!(reader.finish());
+                Some(params)
+            }
+            None => None,
+        };
+        let public = match try!(seq.read_optional(der::context(1))) {
+            Some(public) => {
+                let mut reader = Reader::new(public);
+                let public = try!(reader.read_bit_string());
+                try!(reader.finish());
+                Some(public)
+            }
+            None => None,
+        };
+        try!(seq.finish());
+
+        let curve = match (curve, params) {
+            (Some(a), Some(b)) if a != b => {
+                return tls_err!(DecodeError, "mismatched EC key parameters");
+            }
+            (Some(curve), _) | (None, Some(curve)) => curve,
+            (None, None) => return tls_err!(DecodeError, "EC key without named curve"),
+        };
+        if curve != oid::SECP256R1 {
+            return tls_err!(DecodeError, "unsupported curve");
+        }
+
+        if private.len() != 32 {
+            return tls_err!(DecodeError, "bad EC private key length");
+        }
+        let point = match ecdsa::p256_public_key(private) {
+            Some(point) => point.to_uncompressed_bytes(),
+            None => return tls_err!(DecodeError, "bad EC private key"),
+        };
+        if let Some(public) = public {
+            if public != &point[..] {
+                return tls_err!(DecodeError, "EC public key does not match the private key");
+            }
+        }
+
+        Ok(PrivateKey::EcP256(EcdsaSigningKey {
+            private: private.to_vec(),
+            public: point,
+        }))
+    }
+
+    /// Parses DER PKCS#8 `PrivateKeyInfo` with an RSA, P-256 or Ed25519 key.
+    pub fn from_pkcs8(data: &[u8]) -> TlsResult<PrivateKey> {
+        let mut reader = Reader::new(data);
+        let mut seq = try!(reader.read_nested(der::SEQUENCE));
+        try!(reader.finish());
+        // version 1 is `OneAsymmetricKey` (RFC 5958), which may have the public key
+        if try!(seq.read_small_unsigned()) > 1 {
+            return tls_err!(DecodeError, "unknown private key version");
+        }
+        let algorithm = try!(AlgorithmIdentifier::parse(&mut seq));
+        let private_key = try!(seq.read(der::OCTET_STRING));
+        // attributes, and the public key which is derived from the private key anyway
+        try!(seq.read_optional(der::context(0)));
+        try!(seq.read_optional(der::context_primitive(1)));
+        try!(seq.finish());
+
+        match algorithm.algorithm {
+            oid::RSA_ENCRYPTION => {
+                if algorithm.parameters != Some(&[der::NULL, 0][..]) {
+                    return tls_err!(DecodeError, "bad RSA key parameters");
+                }
+                PrivateKey::from_pkcs1(private_key)
+            }
+            oid::EC_PUBLIC_KEY => {
+                let curve = match algorithm.parameters {
+                    Some(params) => {
+                        let mut reader = Reader::new(params);
+                        let curve = try!(reader.read(der::OID));
+                        try!(reader.finish());
+                        curve
+                    }
+                    None => return tls_err!(DecodeError, "EC key without named curve"),
+                };
+                PrivateKey::parse_sec1(private_key, Some(curve))
+            }
+            oid::ED25519 => {
+                if algorithm.parameters.is_some() {
+                    return tls_err!(DecodeError, "bad Ed25519 key parameters");
+                }
+                // RFC 8410 7: `CurvePrivateKey ::= OCTET STRING`
+                let mut reader = Reader::new(private_key);
+                let seed = try!(reader.read(der::OCTET_STRING));
+                try!(reader.finish());
+                if seed.len() != ed25519::SEED_LEN {
+                    return tls_err!(DecodeError, "bad Ed25519 private key");
+                }
+                Ok(PrivateKey::Ed25519(Ed25519SigningKey {
+                    seed: seed.to_vec(),
+                    public: ed25519::public_key(seed),
+                }))
+            }
+            _ => tls_err!(DecodeError, "unsupported private key algorithm"),
+        }
+    }
+
+    /// Parses the first private key in PEM `data`, which may be PKCS#8 (`PRIVATE KEY`),
+    /// PKCS#1 (`RSA PRIVATE KEY`) or SEC 1 (`EC PRIVATE KEY`).
+    pub fn from_pem(data: &str) -> TlsResult<PrivateKey> {
+        for block in try!(pem::parse(data)).into_iter() {
+            match &block.label[..] {
+                pem::PRIVATE_KEY => return PrivateKey::from_pkcs8(&block.contents),
+                pem::RSA_PRIVATE_KEY => return PrivateKey::from_pkcs1(&block.contents),
+                pem::EC_PRIVATE_KEY => return PrivateKey::from_sec1(&block.contents),
+                pem::ENCRYPTED_PRIVATE_KEY => {
+                    return tls_err!(DecodeError, "encrypted private keys are not supported");
+                }
+                // e.g. `EC PARAMETERS`
+                _ => {}
+            }
+        }
+        tls_err!(DecodeError, "no private key found")
+    }
+
+    /// Encodes the key as DER PKCS#8 `PrivateKeyInfo`.
+    pub fn to_pkcs8(&self) -> Vec<u8> {
+        let mut info = Vec::new();
+        der::write_unsigned(&mut info, &[0]);
+        match *self {
+            PrivateKey::Rsa(ref key) => {
+                rsa_algorithm_identifier(&mut info);
+                der::write_tlv(&mut info, der::OCTET_STRING, &key.pkcs1);
+            }
+            PrivateKey::EcP256(ref key) => {
+                ec_algorithm_identifier(&mut info);
+                // the curve is given by the algorithm identifier
+                let mut sec1 = Vec::new();
+                der::write_unsigned(&mut sec1, &[1]);
+                der::write_tlv(&mut sec1, der::OCTET_STRING, &key.private);
+                let mut public = Vec::new();
+                der::write_tlv(&mut public, der::BIT_STRING, &bit_string(&key.public));
+                der::write_tlv(&mut sec1, der::context(1), &public);
+                let mut seq = Vec::new();
+                der::write_tlv(&mut seq, der::SEQUENCE, &sec1);
+                der::write_tlv(&mut info, der::OCTET_STRING, &seq);
+            }
+            PrivateKey::Ed25519(ref key) => {
+                ed25519_algorithm_identifier(&mut info);
+                let mut seed = Vec::new();
+                der::write_tlv(&mut seed, der::OCTET_STRING, &key.seed);
+                der::write_tlv(&mut info, der::OCTET_STRING, &seed);
+            }
+        }
+        let mut out = Vec::new();
+        der::write_tlv(&mut out, der::SEQUENCE, &info);
+        out
+    }
+
+    /// Encodes the key as PEM PKCS#8 (`PRIVATE KEY`).
+    pub fn to_pem(&self) -> String {
+        pem::encode(pem::PRIVATE_KEY, &self.to_pkcs8())
+    }
+
+    /// DER `SubjectPublicKeyInfo` of the public key.
+    pub fn public_key_info(&self) -> Vec<u8> {
+        match *self {
+            PrivateKey::Rsa(ref key) => key.spki.clone(),
+            PrivateKey::EcP256(ref key) => {
+                let mut spki = Vec::new();
+                ec_algorithm_identifier(&mut spki);
+                der::write_tlv(&mut spki, der::BIT_STRING, &bit_string(&key.public));
+                let mut seq = Vec::new();
+                der::write_tlv(&mut seq, der::SEQUENCE, &spki);
+                seq
+            }
+            PrivateKey::Ed25519(ref key) => {
+                let mut spki = Vec::new();
+                ed25519_algorithm_identifier(&mut spki);
+                der::write_tlv(&mut spki, der::BIT_STRING, &bit_string(&key.public));
+                let mut seq = Vec::new();
+                der::write_tlv(&mut seq, der::SEQUENCE, &spki);
+                seq
+            }
+        }
+    }
+
+    /// The RSA key, for the server side of static RSA key exchange
+    /// (`cipher::rsa::decrypt_pre_master_secret`).
+    pub fn rsa_private_key(&self) -> Option<&RsaPrivateKey> {
+        match *self {
+            PrivateKey::Rsa(ref key) => Some(&key.key),
+            _ => None,
+        }
+    }
+
+    fn signature_algorithm(&self) -> SignatureAlgorithm {
+        match *self {
+            PrivateKey::Rsa(_) => SignatureAlgorithm::rsa,
+            PrivateKey::EcP256(_) => SignatureAlgorithm::ecdsa,
+            PrivateKey::Ed25519(_) => SignatureAlgorithm::ed25519,
+        }
+    }
+}
+
+impl SigningKey for PrivateKey {
+    fn algorithms(&self) -> Vec<SignatureAndHashAlgorithm> {
+        let mut algorithms = Vec::new();
+        match *self {
+            PrivateKey::Ed25519(_) => {
+                algorithms.push(SignatureAndHashAlgorithm {
+                    hash: HashAlgorithm::intrinsic,
+                    signature: SignatureAlgorithm::ed25519,
+                });
+                return algorithms;
+            }
+            // RSA-PSS is preferred over PKCS#1 v1.5
+            PrivateKey::Rsa(_) => {
+                for &signature in [SignatureAlgorithm::rsa_pss_rsae_sha256,
+                                   SignatureAlgorithm::rsa_pss_rsae_sha384,
+                                   SignatureAlgorithm::rsa_pss_rsae_sha512].iter() {
+                    algorithms.push(SignatureAndHashAlgorithm {
+                        hash: HashAlgorithm::intrinsic,
+                        signature: signature,
+                    });
+                }
+            }
+            PrivateKey::EcP256(_) => {}
+        }
+        let signature = self.signature_algorithm();
+        for &hash in [HashAlgorithm::sha256, HashAlgorithm::sha384, HashAlgorithm::sha512].iter() {
+            algorithms.push(SignatureAndHashAlgorithm {
+                hash: hash,
+                signature: signature,
+            });
+        }
+        algorithms
+    }
+
+    fn sign(&self, algorithm: SignatureAndHashAlgorithm, msg: &[u8]) -> TlsResult<Vec<u8>> {
+        if !self.algorithms().contains(&algorithm) {
+            return tls_err!(InternalError, "signature algorithm does not match the key");
+        }
+        let hash = match algorithm.hash_function() {
+            Some(hash) => hash,
+            None => return tls_err!(InternalError, "unsupported hash: {:?}", algorithm.hash),
+        };
+
+        match *self {
+            PrivateKey::Rsa(ref key) => {
+                let signature = if algorithm.hash == HashAlgorithm::intrinsic {
+                    let mut rng = match OsRng::new() {
+                        Ok(rng) => rng,
+                        Err(..) => return tls_err!(InternalError, "failed to create OsRng"),
+                    };
+                    key.key.sign_pss(hash, msg, &mut rng)
+                } else {
+                    key.key.sign_pkcs1(hash, msg)
+                };
+                match signature {
+                    Some(signature) => Ok(signature),
+                    None => tls_err!(InternalError, "RSA key too small for {:?}", algorithm),
+                }
+            }
+            PrivateKey::EcP256(ref key) => {
+                let (r, s) = ecdsa::sign_p256(&key.private, hash, msg);
+                let mut sig = Vec::new();
+                der::write_unsigned(&mut sig, &r);
+                der::write_unsigned(&mut sig, &s);
+                let mut seq = Vec::new();
+                der::write_tlv(&mut seq, der::SEQUENCE, &sig);
+                Ok(seq)
+            }
+            PrivateKey::Ed25519(ref key) => Ok(ed25519::sign(&key.seed, msg)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pem;
+    use signature::{SigningKey, DigitallySigned, Signature, SignatureAndHashAlgorithm,
+                    HashAlgorithm, SignatureAlgorithm};
+    use x509::Certificate;
+    use super::PrivateKey;
+
+    static SERVER: &'static [u8] = include_bytes!("../testdata/test_server.der");
+    static CLIENT: &'static [u8] = include_bytes!("../testdata/test_client.der");
+    // PKCS#8 RSA
+    static SERVER_KEY: &'static str = include_str!("../testdata/test_server.key");
+    // PKCS#1
+    static SERVER_KEY_PKCS1: &'static str = include_str!("../testdata/test_server_pkcs1.key");
+    // SEC 1
+    static CLIENT_KEY: &'static str = include_str!("../testdata/test_client.key");
+    // PKCS#8 EC
+    static LOG_KEY: &'static str = include_str!("../testdata/test_ct_log_ec.key");
+    static LOG_SPKI: &'static [u8] = include_bytes!("../testdata/test_ct_log_ec.der");
+    // PKCS#8 Ed25519
+    static ED_LEAF: &'static [u8] = include_bytes!("../testdata/pki/ed_leaf.der");
+    static ED_LEAF_KEY: &'static str = include_str!("../testdata/pki/ed_leaf.key");
+
+    fn check_signature(key: &PrivateKey, spki: &[u8]) {
+        for &algorithm in key.algorithms().iter() {
+            let signature = key.sign(algorithm, b"message").unwrap();
+            let signed = DigitallySigned {
+                algorithm: algorithm,
+                signature: Signature::new(signature).unwrap(),
+            };
+            signed.verify(spki, b"message").unwrap();
+            assert!(signed.verify(spki, b"massage").is_err());
+        }
+    }
+
+    #[test]
+    fn test_load_private_key() {
+        let server = Certificate::parse(SERVER).unwrap();
+        let client = Certificate::parse(CLIENT).unwrap();
+
+        let key = PrivateKey::from_pem(SERVER_KEY).unwrap();
+        assert_eq!(&key.public_key_info()[..], server.subject_public_key_info);
+        assert_eq!(key.to_pem(), SERVER_KEY);
+        check_signature(&key, server.subject_public_key_info);
+
+        let pkcs1 = PrivateKey::from_pem(SERVER_KEY_PKCS1).unwrap();
+        assert_eq!(pkcs1.to_pem(), SERVER_KEY);
+
+        let key = PrivateKey::from_pem(CLIENT_KEY).unwrap();
+        assert_eq!(&key.public_key_info()[..], client.subject_public_key_info);
+        check_signature(&key, client.subject_public_key_info);
+        // PKCS#8 of a SEC 1 key
+        let reloaded = PrivateKey::from_pkcs8(&key.to_pkcs8()).unwrap();
+        assert_eq!(reloaded.public_key_info(), key.public_key_info());
+
+        let key = PrivateKey::from_pem(LOG_KEY).unwrap();
+        assert_eq!(&key.public_key_info()[..], LOG_SPKI);
+        assert_eq!(key.to_pem(), LOG_KEY);
+
+        let wrong = SignatureAndHashAlgorithm {
+            hash: HashAlgorithm::sha256,
+            signature: SignatureAlgorithm::rsa,
+        };
+        assert!(key.sign(wrong, b"message").is_err());
+    }
+
+    #[test]
+    fn test_ed25519_key() {
+        let leaf = Certificate::parse(ED_LEAF).unwrap();
+        let key = PrivateKey::from_pem(ED_LEAF_KEY).unwrap();
+        assert_eq!(&key.public_key_info()[..], leaf.subject_public_key_info);
+        assert_eq!(key.to_pem(), ED_LEAF_KEY);
+
+        let ed25519 = SignatureAndHashAlgorithm {
+            hash: HashAlgorithm::intrinsic,
+            signature: SignatureAlgorithm::ed25519,
+        };
+        assert_eq!(key.algorithms(), vec![ed25519]);
+        check_signature(&key, leaf.subject_public_key_info);
+
+        // `ed25519` is only valid with `intrinsic`
+        let wrong = SignatureAndHashAlgorithm {
+            hash: HashAlgorithm::sha256,
+            signature: SignatureAlgorithm::ed25519,
+        };
+        assert!(key.sign(wrong, b"message").is_err());
+        let signed = DigitallySigned {
+            algorithm: wrong,
+            signature: Signature::new(key.sign(ed25519, b"message").unwrap()).unwrap(),
+        };
+        assert!(signed.verify(leaf.subject_public_key_info, b"message").is_err());
+    }
+
+    #[test]
+    fn test_load_bad_private_key() {
+        assert!(PrivateKey::from_pem(&pem::encode(pem::CERTIFICATE, SERVER)).is_err());
+        assert!(PrivateKey::from_pem(&pem::encode(pem::ENCRYPTED_PRIVATE_KEY, &[0x30, 0])).is_err());
+
+        // inconsistent public key
+        let der = pem::parse(CLIENT_KEY).unwrap().remove(0).contents;
+        let mut bad = der.clone();
+        let len = bad.len();
+        bad[len - 1] ^= 1;
+        assert!(PrivateKey::from_sec1(&bad).is_err());
+
+        // RSA private exponent does not match
+        let der = pem::parse(SERVER_KEY_PKCS1).unwrap().remove(0).contents;
+        let mut bad = der.clone();
+        // the last byte of d (see `openssl asn1parse`)
+        bad[532] ^= 1;
+        assert!(PrivateKey::from_pkcs1(&der).is_ok());
+        assert!(PrivateKey::from_pkcs1(&bad).is_err());
+        assert!(PrivateKey::from_pkcs8(&der).is_err());
+    }
+}
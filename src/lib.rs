@@ -10,11 +10,15 @@ extern crate num;
 #[macro_use]
 extern crate enum_primitive;
 
-pub use client::TlsClient;
+pub use client::{TlsClient, ClientConfig, ClientCertificate, OcspPolicy, CrlPolicy, PinPolicy,
+                 CtConfig};
+pub use key::PrivateKey;
+pub use roots::RootStore;
 
 #[macro_use]
 pub mod macros;
 pub mod util;
+pub mod base64;
 
 // basic crypto primitives
 pub mod crypto;
@@ -27,6 +31,15 @@ pub mod tls_item;
 pub mod cipher;
 
 pub mod signature;
+pub mod der;
+pub mod pem;
+pub mod x509;
+pub mod key;
+pub mod ocsp;
+pub mod crl;
+pub mod roots;
+pub mod verify;
+pub mod ct;
 pub mod alert;
 pub mod handshake;
 
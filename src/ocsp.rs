@@ -0,0 +1,436 @@
+//! OCSP responses (RFC 6960) stapled in the TLS handshake (RFC 6066 8).
+//!
+//! Only `BasicOCSPResponse` is supported. The response must be signed by the issuer
+//! of the certificate or by a responder certificate issued by it (RFC 6960 4.2.2.2).
+
+use crypto::hash::{self, HashFunction};
+use der::{self, Reader};
+use x509::{self, AlgorithmIdentifier, Certificate, oid};
+use tls_result::TlsResult;
+use tls_result::TlsErrorKind::{DecodeError, BadCertificateStatusResponse};
+
+/// Allowed clock difference between us and the responder, in seconds.
+pub const CLOCK_SKEW: i64 = 5 * 60;
+/// Maximum age of a response without `nextUpdate`, in seconds.
+pub const MAX_AGE_WITHOUT_NEXT_UPDATE: i64 = 7 * 24 * 60 * 60;
+
+const OCSP_BASIC: &'static [u8] = b"\x2b\x06\x01\x05\x05\x07\x30\x01\x01";
+// TLS Feature `status_request` (RFC 7633)
+const TLS_FEATURE_STATUS_REQUEST: u64 = 5;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum CertStatus {
+    Good,
+    /// revocation time in seconds since the Unix epoch
+    Revoked(i64),
+    Unknown,
+}
+
+pub enum ResponderId<'a> {
+    /// raw DER of the responder `Name`
+    ByName(&'a [u8]),
+    /// SHA-1 hash of the responder public key
+    ByKey(&'a [u8]),
+}
+
+pub struct SingleResponse<'a> {
+    pub hash_algorithm: AlgorithmIdentifier<'a>,
+    pub issuer_name_hash: &'a [u8],
+    pub issuer_key_hash: &'a [u8],
+    pub serial_number: &'a [u8],
+    pub status: CertStatus,
+    pub this_update: i64,
+    pub next_update: Option<i64>,
+}
+
+/// `BasicOCSPResponse`.
+pub struct Response<'a> {
+    /// raw DER of `ResponseData`, which is signed by the responder
+    pub tbs_response_data: &'a [u8],
+    pub responder_id: ResponderId<'a>,
+    pub produced_at: i64,
+    pub responses: Vec<SingleResponse<'a>>,
+    pub signature_algorithm: AlgorithmIdentifier<'a>,
+    pub signature: &'a [u8],
+    /// raw DER certificates which may help to verify the signature
+    pub certs: Vec<&'a [u8]>,
+}
+
+fn read_generalized_time(reader: &mut Reader) -> TlsResult<i64> {
+    let value = try!(reader.read(der::GENERALIZED_TIME));
+    x509::parse_time(der::GENERALIZED_TIME, value)
+}
+
+fn parse_single_response<'a>(reader: &mut Reader<'a>) -> TlsResult<SingleResponse<'a>> {
+    let mut single = try!(reader.read_nested(der::SEQUENCE));
+
+    let mut cert_id = try!(single.read_nested(der::SEQUENCE));
+    let hash_algorithm = try!(AlgorithmIdentifier::parse(&mut cert_id));
+    let issuer_name_hash = try!(cert_id.read(der::OCTET_STRING));
+    let issuer_key_hash = try!(cert_id.read(der::OCTET_STRING));
+    let serial_number = try!(cert_id.read(der::INTEGER));
+    try!(cert_id.finish());
+
+    let status = try!(single.read_any());
+    let status = if status.tag == der::context_primitive(0) && status.value.len() == 0 {
+        CertStatus::Good
+    } else if status.tag == der::context(1) {
+        let mut revoked = Reader::new(status.value);
+        let time = try!(read_generalized_time(&mut revoked));
+        // revocationReason
+        try!(revoked.read_optional(der::context(0)));
+        try!(revoked.finish());
+        CertStatus::Revoked(time)
+    } else if status.tag == der::context_primitive(2) && status.value.len() == 0 {
+        CertStatus::Unknown
+    } else {
+        return tls_err!(DecodeError, "OCSP: bad certStatus");
+    };
+
+    let this_update = try!(read_generalized_time(&mut single));
+    let next_update = match try!(single.read_optional(der::context(0))) {
+        Some(next_update) => {
+            let mut next_update = Reader::new(next_update);
+            let time = try!(read_generalized_time(&mut next_update));
+            try!(next_update.finish());
+            Some(time)
+        }
+        None => None,
+    };
+    // singleExtensions
+    try!(single.read_optional(der::context(1)));
+    try!(single.finish());
+
+    Ok(SingleResponse {
+        hash_algorithm: hash_algorithm,
+        issuer_name_hash: issuer_name_hash,
+        issuer_key_hash: issuer_key_hash,
+        serial_number: serial_number,
+        status: status,
+        this_update: this_update,
+        next_update: next_update,
+    })
+}
+
+impl<'a> Response<'a> {
+    /// Parses DER `OCSPResponse`. Unsuccessful responses are errors.
+    pub fn parse(data: &'a [u8]) -> TlsResult<Response<'a>> {
+        let mut reader = Reader::new(data);
+        let mut ocsp_response = try!(reader.read_nested(der::SEQUENCE));
+        try!(reader.finish());
+
+        let status = try!(ocsp_response.read(der::ENUMERATED));
+        if status != &[0] {
+            return tls_err!(DecodeError, "OCSP: unsuccessful response: {:?}", status);
+        }
+        let mut response_bytes = {
+            let mut explicit = try!(ocsp_response.read_nested(der::context(0)));
+            let response_bytes = try!(explicit.read_nested(der::SEQUENCE));
+            try!(explicit.finish());
+            response_bytes
+        };
+        try!(ocsp_response.finish());
+
+        let response_type = try!(response_bytes.read(der::OID));
+        if response_type != OCSP_BASIC {
+            return tls_err!(DecodeError, "OCSP: unknown response type");
+        }
+        let basic = try!(response_bytes.read(der::OCTET_STRING));
+        try!(response_bytes.finish());
+
+        let mut reader = Reader::new(basic);
+        let mut basic = try!(reader.read_nested(der::SEQUENCE));
+        try!(reader.finish());
+        let tbs_response_data = try!(basic.read_raw(der::SEQUENCE));
+        let signature_algorithm = try!(AlgorithmIdentifier::parse(&mut basic));
+        let signature = try!(basic.read_bit_string());
+        let mut certs = Vec::new();
+        if let Some(explicit) = try!(basic.read_optional(der::context(0))) {
+            let mut explicit = Reader::new(explicit);
+            let mut seq = try!(explicit.read_nested(der::SEQUENCE));
+            try!(explicit.finish());
+            while !seq.is_empty() {
+                certs.push(try!(seq.read_raw(der::SEQUENCE)));
+            }
+        }
+        try!(basic.finish());
+
+        let mut tbs = try!(Reader::new(tbs_response_data).read_nested(der::SEQUENCE));
+        if let Some(version) = try!(tbs.read_optional(der::context(0))) {
+            let mut version = Reader::new(version);
+            if try!(version.read_small_unsigned()) != 0 {
+                return tls_err!(DecodeError, "OCSP: unknown version");
+            }
+            try!(version.finish());
+        }
+        let responder_id = {
+            let tlv = try!(tbs.read_any());
+            let mut inner = Reader::new(tlv.value);
+            let id = if tlv.tag == der::context(1) {
+                ResponderId::ByName(try!(inner.read_raw(der::SEQUENCE)))
+            } else if tlv.tag == der::context(2) {
+                ResponderId::ByKey(try!(inner.read(der::OCTET_STRING)))
+            } else {
+                return tls_err!(DecodeError, "OCSP: bad responderID");
+            };
+            try!(inner.finish());
+            id
+        };
+        let produced_at = try!(read_generalized_time(&mut tbs));
+        let mut responses = Vec::new();
+        {
+            let mut seq = try!(tbs.read_nested(der::SEQUENCE));
+            while !seq.is_empty() {
+                responses.push(try!(parse_single_response(&mut seq)));
+            }
+        }
+        // responseExtensions. we don't send nonce since the response is stapled.
+        try!(tbs.read_optional(der::context(1)));
+        try!(tbs.finish());
+
+        Ok(Response {
+            tbs_response_data: tbs_response_data,
+            responder_id: responder_id,
+            produced_at: produced_at,
+            responses: responses,
+            signature_algorithm: signature_algorithm,
+            signature: signature,
+            certs: certs,
+        })
+    }
+}
+
+// contents of `subjectPublicKey` BIT STRING
+fn public_key_bits<'a>(cert: &Certificate<'a>) -> TlsResult<&'a [u8]> {
+    let mut reader = Reader::new(cert.subject_public_key_info);
+    let mut spki = try!(reader.read_nested(der::SEQUENCE));
+    try!(AlgorithmIdentifier::parse(&mut spki));
+    spki.read_bit_string()
+}
+
+fn matches_responder_id(id: &ResponderId, cert: &Certificate) -> TlsResult<bool> {
+    match *id {
+        ResponderId::ByName(name) => Ok(name == cert.subject),
+        ResponderId::ByKey(key_hash) => {
+            let bits = try!(public_key_bits(cert));
+            Ok(key_hash == &(hash::SHA1.digest)(bits)[..])
+        }
+    }
+}
+
+fn is_valid_at(cert: &Certificate, now: i64) -> bool {
+    cert.not_before <= now + CLOCK_SKEW && now - CLOCK_SKEW <= cert.not_after
+}
+
+// checks that the response is signed by `issuer` or its delegated responder.
+fn verify_signer(response: &Response, issuer: &Certificate, now: i64) -> TlsResult<()> {
+    let signer_spki = if try!(matches_responder_id(&response.responder_id, issuer)) {
+        issuer.subject_public_key_info
+    } else {
+        // delegated responder (RFC 6960 4.2.2.2)
+        let mut found = None;
+        for &der in response.certs.iter() {
+            let cert = try!(Certificate::parse(der));
+            if !try!(matches_responder_id(&response.responder_id, &cert)) {
+                continue;
+            }
+            if cert.issuer != issuer.subject || cert.verify_signature(issuer).is_err() {
+                return tls_err!(BadCertificateStatusResponse,
+                                "OCSP responder is not issued by the issuer");
+            }
+            let authorized = match try!(cert.extended_key_usage()) {
+                Some(purposes) => purposes.contains(&oid::KP_OCSP_SIGNING),
+                None => false,
+            };
+            if !authorized {
+                return tls_err!(BadCertificateStatusResponse,
+                                "OCSP responder is not authorized");
+            }
+            if !is_valid_at(&cert, now) {
+                return tls_err!(BadCertificateStatusResponse, "OCSP responder expired");
+            }
+            found = Some(cert.subject_public_key_info);
+            break;
+        }
+        match found {
+            Some(spki) => spki,
+            None => return tls_err!(BadCertificateStatusResponse, "OCSP responder not found"),
+        }
+    };
+
+    match x509::verify_signed_data(signer_spki,
+                                   &response.signature_algorithm,
+                                   response.tbs_response_data,
+                                   response.signature) {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            tls_err!(BadCertificateStatusResponse, "OCSP signature: {}", err.desc)
+        }
+    }
+}
+
+fn cert_id_hash(algorithm: &AlgorithmIdentifier) -> Option<&'static HashFunction> {
+    let params_ok = match algorithm.parameters {
+        None => true,
+        Some(params) => params == &[der::NULL, 0][..],
+    };
+    if !params_ok {
+        return None;
+    }
+    match algorithm.algorithm {
+        oid::SHA1 => Some(&hash::SHA1),
+        oid::SHA256 => Some(&hash::SHA256),
+        _ => None,
+    }
+}
+
+/// Verifies DER `OCSPResponse` for `cert` issued by `issuer` at `now`
+/// (seconds since the Unix epoch), and returns the status of `cert`.
+pub fn verify_response(data: &[u8],
+                       cert: &Certificate,
+                       issuer: &Certificate,
+                       now: i64)
+                       -> TlsResult<CertStatus> {
+    let response = match Response::parse(data) {
+        Ok(response) => response,
+        Err(err) => return tls_err!(BadCertificateStatusResponse, "{}", err.desc),
+    };
+    if cert.issuer != issuer.subject {
+        return tls_err!(BadCertificateStatusResponse, "wrong issuer");
+    }
+    try!(verify_signer(&response, issuer, now));
+
+    let issuer_key = try!(public_key_bits(issuer));
+    let single = response.responses.iter().find(|single| {
+        match cert_id_hash(&single.hash_algorithm) {
+            Some(hash) => {
+                single.serial_number == cert.serial_number &&
+                single.issuer_name_hash == &(hash.digest)(issuer.subject)[..] &&
+                single.issuer_key_hash == &(hash.digest)(issuer_key)[..]
+            }
+            None => false,
+        }
+    });
+    let single = match single {
+        Some(single) => single,
+        None => return tls_err!(BadCertificateStatusResponse, "OCSP: certificate not found"),
+    };
+
+    if single.this_update > now + CLOCK_SKEW {
+        return tls_err!(BadCertificateStatusResponse, "OCSP response is not yet valid");
+    }
+    let expires = match single.next_update {
+        Some(next_update) => next_update,
+        None => single.this_update + MAX_AGE_WITHOUT_NEXT_UPDATE,
+    };
+    if expires < now - CLOCK_SKEW {
+        return tls_err!(BadCertificateStatusResponse, "OCSP response expired");
+    }
+
+    Ok(single.status)
+}
+
+/// Returns true if `cert` has the TLS Feature extension with `status_request`
+/// (RFC 7633), i.e. the server must staple an OCSP response.
+pub fn must_staple(cert: &Certificate) -> TlsResult<bool> {
+    let ext = match cert.extension(oid::TLS_FEATURE) {
+        Some(ext) => ext,
+        None => return Ok(false),
+    };
+    let mut reader = Reader::new(ext.value);
+    let mut features = try!(reader.read_nested(der::SEQUENCE));
+    try!(reader.finish());
+    let mut found = false;
+    while !features.is_empty() {
+        if try!(features.read_small_unsigned()) == TLS_FEATURE_STATUS_REQUEST {
+            found = true;
+        }
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Response, CertStatus, verify_response, must_staple};
+    use x509::Certificate;
+    use tls_result::TlsErrorKind::BadCertificateStatusResponse;
+
+    static CA: &'static [u8] = include_bytes!("../testdata/test_ca.der");
+    static SERVER: &'static [u8] = include_bytes!("../testdata/test_server.der");
+    static MUST_STAPLE: &'static [u8] = include_bytes!("../testdata/test_must_staple.der");
+    static OCSP_GOOD: &'static [u8] = include_bytes!("../testdata/ocsp_good.der");
+    static OCSP_REVOKED: &'static [u8] = include_bytes!("../testdata/ocsp_revoked.der");
+    static OCSP_MUST_STAPLE: &'static [u8] = include_bytes!("../testdata/ocsp_must_staple.der");
+
+    #[test]
+    fn test_parse_response() {
+        let response = Response::parse(OCSP_GOOD).unwrap();
+        assert_eq!(response.responses.len(), 1);
+        assert_eq!(response.responses[0].serial_number, &[3]);
+        assert_eq!(response.responses[0].status, CertStatus::Good);
+        assert!(response.responses[0].next_update.is_some());
+
+        let response = Response::parse(OCSP_REVOKED).unwrap();
+        // delegated responder
+        assert_eq!(response.certs.len(), 1);
+        match response.responses[0].status {
+            // 2026-10-01 00:00:00
+            CertStatus::Revoked(time) => assert_eq!(time, 1790812800),
+            _ => panic!("expected revoked"),
+        }
+
+        // tryLater
+        assert!(Response::parse(&[0x30, 0x03, 0x0a, 0x01, 0x03]).is_err());
+    }
+
+    #[test]
+    fn test_verify_response() {
+        let ca = Certificate::parse(CA).unwrap();
+        let server = Certificate::parse(SERVER).unwrap();
+        let staple = Certificate::parse(MUST_STAPLE).unwrap();
+        let this_update = Response::parse(OCSP_GOOD).unwrap().responses[0].this_update;
+        let now = this_update + 3600;
+
+        // signed by the issuer (ECDSA)
+        assert_eq!(verify_response(OCSP_GOOD, &server, &ca, now).unwrap(), CertStatus::Good);
+        // signed by a delegated responder (RSA)
+        match verify_response(OCSP_REVOKED, &server, &ca, now).unwrap() {
+            CertStatus::Revoked(..) => {}
+            status => panic!("unexpected status: {:?}", status),
+        }
+        assert_eq!(verify_response(OCSP_MUST_STAPLE, &staple, &ca, now).unwrap(),
+                   CertStatus::Good);
+
+        let check_err = |data: &[u8], cert: &Certificate, now: i64| {
+            let err = verify_response(data, cert, &ca, now).unwrap_err();
+            assert_eq!(err.kind, BadCertificateStatusResponse);
+        };
+
+        // for another certificate
+        check_err(OCSP_GOOD, &staple, now);
+        // wrong issuer
+        assert!(verify_response(OCSP_GOOD, &server, &server, now).is_err());
+        // not yet valid, expired
+        check_err(OCSP_GOOD, &server, this_update - 3600);
+        check_err(OCSP_GOOD, &server, this_update + 101 * 365 * 86400);
+
+        // bad signature
+        let signature = Response::parse(OCSP_GOOD).unwrap().signature;
+        let pos = OCSP_GOOD.windows(signature.len()).position(|w| w == signature).unwrap();
+        let mut bad_sig = OCSP_GOOD.to_vec();
+        bad_sig[pos + signature.len() - 1] ^= 1;
+        check_err(&bad_sig, &server, now);
+
+        // tampered status
+        let mut tampered = OCSP_GOOD.to_vec();
+        let pos = tampered.windows(2).position(|w| w == &[0x80, 0x00]).unwrap();
+        tampered[pos] = 0x82;
+        check_err(&tampered, &server, now);
+    }
+
+    #[test]
+    fn test_must_staple() {
+        assert!(must_staple(&Certificate::parse(MUST_STAPLE).unwrap()).unwrap());
+        assert!(!must_staple(&Certificate::parse(SERVER).unwrap()).unwrap());
+    }
+}
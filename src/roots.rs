@@ -0,0 +1,268 @@
+//! Trust anchors for certificate validation.
+//!
+//! `RootStore` keeps trusted DER certificates, without duplicates, indexed by subject name and
+//! `SubjectPublicKeyInfo`. Only the name, key and signature of an anchor matter for validation.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::Path;
+
+use pem;
+use x509::Certificate;
+use tls_result::TlsResult;
+use tls_result::TlsErrorKind::DecodeError;
+
+/// The system CA bundle on Debian and derived distributions.
+pub const SYSTEM_BUNDLE: &'static str = "/etc/ssl/certs/ca-certificates.crt";
+/// The OpenSSL-style hashed directory on Debian and derived distributions.
+pub const SYSTEM_DIR: &'static str = "/etc/ssl/certs";
+
+pub struct RootStore {
+    anchors: Vec<Vec<u8>>,
+    // indexes of `anchors`
+    by_subject: HashMap<Vec<u8>, Vec<usize>>,
+    by_spki: HashMap<Vec<u8>, Vec<usize>>,
+}
+
+// file names of certificates in hashed directories are `<8 hex digits>.<n>`.
+// CRLs are `<hash>.r<n>`.
+fn is_hashed_name(name: &str) -> bool {
+    let mut parts = name.splitn(2, '.');
+    let hash = parts.next().unwrap();
+    let n = match parts.next() {
+        Some(n) => n,
+        None => return false,
+    };
+    hash.len() == 8 && hash.chars().all(|c| c.is_digit(16)) &&
+    n.len() > 0 && n.chars().all(|c| c.is_digit(10))
+}
+
+impl RootStore {
+    pub fn new() -> RootStore {
+        RootStore {
+            anchors: Vec::new(),
+            by_subject: HashMap::new(),
+            by_spki: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.anchors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.anchors.is_empty()
+    }
+
+    /// DER certificates of all anchors, in the order they were added.
+    pub fn anchors(&self) -> &[Vec<u8>] {
+        &self.anchors
+    }
+
+    /// Adds a DER certificate. Returns `false` if it is already in the store.
+    pub fn add(&mut self, der: Vec<u8>) -> TlsResult<bool> {
+        if self.contains(&der) {
+            return Ok(false);
+        }
+        let (subject, spki) = {
+            let cert = match Certificate::parse(&der) {
+                Ok(cert) => cert,
+                Err(err) => return tls_err!(DecodeError, "bad trust anchor: {}", err.desc),
+            };
+            (cert.subject.to_vec(), cert.subject_public_key_info.to_vec())
+        };
+        let index = self.anchors.len();
+        self.anchors.push(der);
+        self.by_subject.entry(subject).or_insert(Vec::new()).push(index);
+        self.by_spki.entry(spki).or_insert(Vec::new()).push(index);
+        Ok(true)
+    }
+
+    /// Adds certificates in PEM `data` and returns the number of new anchors.
+    /// Certificates that cannot be parsed are skipped, since system bundles may have
+    /// certificates this crate does not understand.
+    pub fn add_pem(&mut self, data: &str) -> TlsResult<usize> {
+        let mut added = 0;
+        for block in try!(pem::parse(data)).into_iter() {
+            if block.label != pem::CERTIFICATE {
+                continue;
+            }
+            match self.add(block.contents) {
+                Ok(true) => added += 1,
+                Ok(false) => {}
+                Err(err) => debug!("skipping trust anchor: {}", err.desc),
+            }
+        }
+        Ok(added)
+    }
+
+    /// Adds certificates in a PEM bundle file, e.g. `SYSTEM_BUNDLE` (see `add_pem`).
+    pub fn add_pem_file<P: AsRef<Path>>(&mut self, path: P) -> TlsResult<usize> {
+        let mut data = String::new();
+        try!(try!(File::open(path)).read_to_string(&mut data));
+        self.add_pem(&data)
+    }
+
+    /// Adds certificates in an OpenSSL-style hashed directory (see `openssl rehash`),
+    /// e.g. `SYSTEM_DIR`. All files named like `<hash>.<n>` are loaded at once, either PEM or
+    /// DER; other files are ignored. Returns the number of new anchors.
+    pub fn add_hashed_dir<P: AsRef<Path>>(&mut self, path: P) -> TlsResult<usize> {
+        let mut paths = Vec::new();
+        for entry in try!(fs::read_dir(path)) {
+            let entry = try!(entry);
+            let is_hashed = entry.file_name().to_str().map_or(false, is_hashed_name);
+            if is_hashed {
+                paths.push(entry.path());
+            }
+        }
+        paths.sort();
+
+        let mut added = 0;
+        for path in paths.iter() {
+            let mut data = Vec::new();
+            try!(try!(File::open(path)).read_to_end(&mut data));
+            let is_pem = data.windows(11).any(|w| w == b"-----BEGIN ");
+            if is_pem {
+                match String::from_utf8(data) {
+                    Ok(text) => added += try!(self.add_pem(&text)),
+                    Err(..) => debug!("skipping {}: not UTF-8", path.display()),
+                }
+            } else {
+                match self.add(data) {
+                    Ok(true) => added += 1,
+                    Ok(false) => {}
+                    Err(err) => debug!("skipping {}: {}", path.display(), err.desc),
+                }
+            }
+        }
+        Ok(added)
+    }
+
+    /// Checks whether DER certificate `der` is an anchor.
+    pub fn contains(&self, der: &[u8]) -> bool {
+        let subject = match Certificate::parse(der) {
+            Ok(cert) => cert.subject.to_vec(),
+            Err(..) => return false,
+        };
+        self.find_by_subject(&subject).iter().any(|&anchor| anchor == der)
+    }
+
+    /// Anchors whose subject is `subject` (raw DER `Name`), i.e. candidate issuers
+    /// of certificates whose issuer is `subject`.
+    pub fn find_by_subject(&self, subject: &[u8]) -> Vec<&[u8]> {
+        match self.by_subject.get(subject) {
+            Some(indexes) => indexes.iter().map(|&i| &self.anchors[i][..]).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Anchors with public key `spki` (raw DER `SubjectPublicKeyInfo`).
+    pub fn find_by_spki(&self, spki: &[u8]) -> Vec<&[u8]> {
+        match self.by_spki.get(spki) {
+            Some(indexes) => indexes.iter().map(|&i| &self.anchors[i][..]).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Distinct subject names of the anchors, in the order they were added.
+    pub fn subjects(&self) -> Vec<Vec<u8>> {
+        let mut subjects: Vec<Vec<u8>> = Vec::new();
+        for anchor in self.anchors.iter() {
+            let subject = Certificate::parse(anchor).unwrap().subject.to_vec();
+            if !subjects.contains(&subject) {
+                subjects.push(subject);
+            }
+        }
+        subjects
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::prelude::*;
+    use std::path::PathBuf;
+
+    use pem;
+    use x509::Certificate;
+    use super::{RootStore, is_hashed_name};
+
+    static CA: &'static [u8] = include_bytes!("../testdata/test_ca.der");
+    static SERVER: &'static [u8] = include_bytes!("../testdata/test_server.der");
+    static CLIENT: &'static [u8] = include_bytes!("../testdata/test_client.der");
+    static CHAIN_PEM: &'static str = include_str!("../testdata/test_server_chain.pem");
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("suruga-{}-{}", name, ::std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(path: PathBuf, data: &[u8]) {
+        File::create(path).unwrap().write_all(data).unwrap();
+    }
+
+    #[test]
+    fn test_root_store() {
+        let mut store = RootStore::new();
+        assert!(store.is_empty());
+        assert!(store.add(CA.to_vec()).unwrap());
+        assert!(!store.add(CA.to_vec()).unwrap());
+        assert!(store.add(b"garbage".to_vec()).is_err());
+        assert_eq!(store.len(), 1);
+
+        // the CA is already there
+        assert_eq!(store.add_pem(CHAIN_PEM).unwrap(), 1);
+        assert_eq!(store.anchors(), &[CA.to_vec(), SERVER.to_vec()][..]);
+        assert!(store.contains(SERVER));
+        assert!(!store.contains(CLIENT));
+
+        let ca = Certificate::parse(CA).unwrap();
+        let server = Certificate::parse(SERVER).unwrap();
+        assert_eq!(store.find_by_subject(server.issuer), vec![CA]);
+        assert_eq!(store.find_by_subject(server.subject), vec![SERVER]);
+        assert_eq!(store.find_by_spki(ca.subject_public_key_info), vec![CA]);
+        assert!(store.find_by_subject(b"\x30\x00").is_empty());
+        assert_eq!(store.subjects(), vec![ca.subject.to_vec(), server.subject.to_vec()]);
+
+        // unparsable certificates in bundles are skipped
+        let mut bundle = pem::encode(pem::CERTIFICATE, b"garbage");
+        bundle.push_str(&pem::encode(pem::CERTIFICATE, CLIENT));
+        assert_eq!(store.add_pem(&bundle).unwrap(), 1);
+        assert!(store.add_pem("-----BEGIN CERTIFICATE-----\n").is_err());
+    }
+
+    #[test]
+    fn test_load_root_store() {
+        assert!(is_hashed_name("1234abcd.0"));
+        assert!(is_hashed_name("1234ABCD.12"));
+        assert!(!is_hashed_name("1234abcd.r0"));
+        assert!(!is_hashed_name("1234abcd"));
+        assert!(!is_hashed_name("1234abc.0"));
+        assert!(!is_hashed_name("ca.pem"));
+
+        let dir = temp_dir("bundle");
+        let bundle = dir.join("bundle.pem");
+        write(bundle.clone(), CHAIN_PEM.as_bytes());
+        let mut store = RootStore::new();
+        assert_eq!(store.add_pem_file(&bundle).unwrap(), 2);
+        assert!(store.add_pem_file(dir.join("missing.pem")).is_err());
+
+        let dir = temp_dir("hashed");
+        write(dir.join("5d1c2a3b.0"), pem::encode(pem::CERTIFICATE, CA).as_bytes());
+        // DER, and a duplicate
+        write(dir.join("5d1c2a3b.1"), SERVER);
+        write(dir.join("0011aabb.0"), pem::encode(pem::CERTIFICATE, SERVER).as_bytes());
+        // not certificates
+        write(dir.join("5d1c2a3b.r0"), b"CRL");
+        write(dir.join("README"), b"garbage");
+        let mut store = RootStore::new();
+        assert_eq!(store.add_hashed_dir(&dir).unwrap(), 2);
+        // sorted by file name
+        assert_eq!(store.anchors(), &[SERVER.to_vec(), CA.to_vec()][..]);
+        assert!(store.add_hashed_dir(dir.join("missing")).is_err());
+    }
+}
@@ -1,25 +1,121 @@
 // 7.4.1.4.1 Signature algorithm
 
+use crypto::hash::{self, HashFunction};
 use util::{ReadExt, WriteExt};
 use tls_item::TlsItem;
+use tls_result::TlsResult;
+use tls_result::TlsErrorKind::{DecryptError, IllegalParameter};
+use x509::PublicKey;
 
-tls_enum!(u8, enum HashAlgorithm {
+tls_enum!(u8, #[derive(Debug)] enum HashAlgorithm {
    none(0), md5(1), sha1(2), sha224(3), sha256(4), sha384(5),
-   sha512(6)
+   sha512(6),
+   // RFC 8422: the signature algorithm determines the hash
+   intrinsic(8)
 });
 
-tls_enum!(u8, enum SignatureAlgorithm {
-  anonymous(0), rsa(1), dsa(2), ecdsa(3)
+// with `intrinsic`, values 4...11 are TLS 1.3 signature schemes (RFC 8446 4.2.3)
+// which are also used in TLS 1.2.
+tls_enum!(u8, #[derive(Debug)] enum SignatureAlgorithm {
+  anonymous(0), rsa(1), dsa(2), ecdsa(3),
+  rsa_pss_rsae_sha256(4), rsa_pss_rsae_sha384(5), rsa_pss_rsae_sha512(6),
+  ed25519(7), ed448(8),
+  rsa_pss_pss_sha256(9), rsa_pss_pss_sha384(10), rsa_pss_pss_sha512(11)
 });
 
-tls_struct!(struct SignatureAndHashAlgorithm {
+tls_struct!(#[derive(Copy, Clone, PartialEq, Debug)] struct SignatureAndHashAlgorithm {
     hash: HashAlgorithm,
     signature: SignatureAlgorithm
 });
 tls_vec!(SignatureAndHashAlgorithmVec = SignatureAndHashAlgorithm(2, (1 << 16) - 2));
 
+/// Algorithms supported by `DigitallySigned::verify`, in order of preference.
+/// This is the default of `ClientConfig::signature_algorithms`.
+/// MD5 and SHA-1 are not supported (RFC 9155).
+pub static SUPPORTED_ALGORITHMS: &'static [SignatureAndHashAlgorithm] = &[
+    SignatureAndHashAlgorithm { hash: HashAlgorithm::intrinsic, signature: SignatureAlgorithm::ed25519 },
+    SignatureAndHashAlgorithm { hash: HashAlgorithm::sha256, signature: SignatureAlgorithm::ecdsa },
+    SignatureAndHashAlgorithm { hash: HashAlgorithm::sha384, signature: SignatureAlgorithm::ecdsa },
+    SignatureAndHashAlgorithm { hash: HashAlgorithm::sha512, signature: SignatureAlgorithm::ecdsa },
+    SignatureAndHashAlgorithm { hash: HashAlgorithm::intrinsic, signature: SignatureAlgorithm::rsa_pss_rsae_sha256 },
+    SignatureAndHashAlgorithm { hash: HashAlgorithm::intrinsic, signature: SignatureAlgorithm::rsa_pss_rsae_sha384 },
+    SignatureAndHashAlgorithm { hash: HashAlgorithm::intrinsic, signature: SignatureAlgorithm::rsa_pss_rsae_sha512 },
+    SignatureAndHashAlgorithm { hash: HashAlgorithm::sha256, signature: SignatureAlgorithm::rsa },
+    SignatureAndHashAlgorithm { hash: HashAlgorithm::sha384, signature: SignatureAlgorithm::rsa },
+    SignatureAndHashAlgorithm { hash: HashAlgorithm::sha512, signature: SignatureAlgorithm::rsa },
+];
+
 tls_vec!(Signature = u8(0, (1 << 16) - 1));
 tls_struct!(struct DigitallySigned {
     algorithm: SignatureAndHashAlgorithm,
     signature: Signature
 });
+
+impl SignatureAndHashAlgorithm {
+    /// The hash function for the message, or `None` if unsupported.
+    /// Ed25519 takes the message unhashed and uses SHA-512 internally.
+    pub fn hash_function(&self) -> Option<&'static HashFunction> {
+        let hash: &'static HashFunction = match (self.hash, self.signature) {
+            (HashAlgorithm::sha256, _) |
+            (HashAlgorithm::intrinsic, SignatureAlgorithm::rsa_pss_rsae_sha256) => &hash::SHA256,
+            (HashAlgorithm::sha384, _) |
+            (HashAlgorithm::intrinsic, SignatureAlgorithm::rsa_pss_rsae_sha384) => &hash::SHA384,
+            (HashAlgorithm::sha512, _) |
+            (HashAlgorithm::intrinsic, SignatureAlgorithm::rsa_pss_rsae_sha512) |
+            (HashAlgorithm::intrinsic, SignatureAlgorithm::ed25519) => &hash::SHA512,
+            _ => return None,
+        };
+        Some(hash)
+    }
+}
+
+impl DigitallySigned {
+    /// Checks that the algorithm is one of `offered`, i.e. sent in the
+    /// `signature_algorithms` extension (RFC 5246 7.4.1.4.1).
+    pub fn check_offered(&self, offered: &[SignatureAndHashAlgorithm]) -> TlsResult<()> {
+        if !offered.contains(&self.algorithm) {
+            return tls_err!(IllegalParameter,
+                            "signature algorithm not offered: {:?}",
+                            self.algorithm);
+        }
+        Ok(())
+    }
+
+    /// Verifies that `msg` is signed by the owner of `spki` (raw DER `SubjectPublicKeyInfo`).
+    /// Supported are RSA PKCS#1 v1.5 and ECDSA with SHA-2, RSA-PSS with
+    /// an `rsaEncryption` key (`rsa_pss_rsae_*`), and Ed25519.
+    pub fn verify(&self, spki: &[u8], msg: &[u8]) -> TlsResult<()> {
+        let hash = match self.algorithm.hash_function() {
+            Some(hash) => hash,
+            None => return tls_err!(DecryptError, "unsupported hash: {:?}", self.algorithm.hash),
+        };
+        // RFC 8446 4.2.3 codepoints have no separate hash
+        let intrinsic = self.algorithm.hash == HashAlgorithm::intrinsic;
+        let key = try!(PublicKey::parse(spki));
+        match (&key, self.algorithm.signature) {
+            (&PublicKey::Rsa(_), SignatureAlgorithm::rsa) |
+            (&PublicKey::EcP256(_), SignatureAlgorithm::ecdsa) |
+            (&PublicKey::EcP384(_), SignatureAlgorithm::ecdsa) => {
+                key.verify(hash, msg, &self.signature)
+            }
+            (&PublicKey::Ed25519(_), SignatureAlgorithm::ed25519) if intrinsic => {
+                key.verify(hash, msg, &self.signature)
+            }
+            (&PublicKey::Rsa(_), SignatureAlgorithm::rsa_pss_rsae_sha256) |
+            (&PublicKey::Rsa(_), SignatureAlgorithm::rsa_pss_rsae_sha384) |
+            (&PublicKey::Rsa(_), SignatureAlgorithm::rsa_pss_rsae_sha512) if intrinsic => {
+                key.verify_pss(hash, msg, &self.signature)
+            }
+            _ => tls_err!(DecryptError, "signature algorithm does not match the key"),
+        }
+    }
+}
+
+/// A private key which signs handshake messages, e.g. `CertificateVerify`.
+pub trait SigningKey {
+    /// Signature algorithms supported by the key, in order of preference.
+    fn algorithms(&self) -> Vec<SignatureAndHashAlgorithm>;
+
+    /// Signs `msg` with `algorithm`. `msg` is not hashed yet.
+    fn sign(&self, algorithm: SignatureAndHashAlgorithm, msg: &[u8]) -> TlsResult<Vec<u8>>;
+}
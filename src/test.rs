@@ -10,8 +10,8 @@ use tls::RECORD_MAX_LEN;
 
 // ROT26 is a [Caesar cipher][1] with highly optimized diffusion table.
 // [1]: http://www.anagram.com/jcrap/Volume_3/caesar.pdf
-struct NullEncryptor;
-struct NullDecryptor;
+pub struct NullEncryptor;
+pub struct NullDecryptor;
 
 impl Encryptor for NullEncryptor {
     fn encrypt(&mut self, _nonce: &[u8], plain: &[u8], _ad: &[u8]) -> Vec<u8> {
@@ -26,7 +26,7 @@ impl Decryptor for NullDecryptor {
     fn mac_len(&self) -> usize { 0 }
 }
 
-fn null_tls<R: Read, W: Write>(reader: R, writer: W) -> (TlsReader<R>, TlsWriter<W>) {
+pub fn null_tls<R: Read, W: Write>(reader: R, writer: W) -> (TlsReader<R>, TlsWriter<W>) {
     let mut reader = TlsReader::new(reader);
     let null_decryptor = Box::new(NullDecryptor) as Box<Decryptor + Send>;
     reader.set_decryptor(null_decryptor);
@@ -38,6 +38,12 @@ fn null_tls<R: Read, W: Write>(reader: R, writer: W) -> (TlsReader<R>, TlsWriter
     (reader, writer)
 }
 
+// decodes a hex string of test vectors.
+pub fn unhex(s: &str) -> Vec<u8> {
+    (0..(s.len() / 2)).map(|i| u8::from_str_radix(&s[(2 * i)..(2 * i + 2)], 16).unwrap())
+                      .collect()
+}
+
 #[test]
 fn test_change_cipher_spec_message() {
     let mut writer = Vec::new();
@@ -98,3 +104,51 @@ fn test_application_message() {
         }
     }
 }
+
+#[test]
+fn test_application_data_close_notify() {
+    use alert::{Alert, AlertDescription};
+
+    let mut writer = Vec::new();
+    {
+        let mut reader = Cursor::new(Vec::new());
+        let mut tls = null_tls(&mut reader, &mut writer);
+        tls.1.write_application_data(b"hello").unwrap();
+        tls.1.write_application_data(b"").unwrap();
+        tls.1.write_application_data(b"world").unwrap();
+        tls.1.write_alert(&Alert::new_warning(AlertDescription::close_notify)).unwrap();
+    }
+
+    let mut reader = Cursor::new(writer);
+    let mut writer = Vec::new();
+    let mut tls = null_tls(&mut reader, &mut writer);
+    assert_eq!(tls.0.read_application_data().unwrap(), b"hello");
+    // the empty record is skipped
+    assert_eq!(tls.0.read_application_data().unwrap(), b"world");
+    // end of stream
+    assert_eq!(tls.0.read_application_data().unwrap(), b"");
+}
+
+#[test]
+fn test_warning_alerts_between_empty_records() {
+    use alert::{Alert, AlertDescription};
+    use tls_result::TlsErrorKind::UnexpectedMessage;
+
+    let mut writer = Vec::new();
+    {
+        let mut reader = Cursor::new(Vec::new());
+        let mut tls = null_tls(&mut reader, &mut writer);
+        for _ in 0..3 {
+            tls.1.write_alert(&Alert::new_warning(AlertDescription::no_renegotiation)).unwrap();
+            tls.1.write_application_data(b"").unwrap();
+        }
+        tls.1.write_application_data(b"hello").unwrap();
+    }
+
+    let mut reader = Cursor::new(writer);
+    let mut writer = Vec::new();
+    let mut tls = null_tls(&mut reader, &mut writer);
+    tls.0.set_max_warning_alerts(2);
+    let err = tls.0.read_application_data().unwrap_err();
+    assert_eq!(err.kind, UnexpectedMessage);
+}
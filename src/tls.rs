@@ -2,8 +2,9 @@ use std::io::prelude::*;
 use num::traits::FromPrimitive;
 
 use tls_result::TlsResult;
-use tls_result::TlsErrorKind::{UnexpectedMessage, RecordOverflow, BadRecordMac, AlertReceived};
-use alert::Alert;
+use tls_result::TlsErrorKind::{UnexpectedMessage, RecordOverflow, BadRecordMac, AlertReceived,
+                               ProtocolVersion, InappropriateFallback};
+use alert::{Alert, AlertDescription, AlertCallback};
 use handshake::{Handshake, HandshakeBuffer};
 use util::u64_be_array;
 use util::{ReadExt, WriteExt};
@@ -16,6 +17,11 @@ use self::Message::{HandshakeMessage, ChangeCipherSpecMessage, AlertMessage,
 
 pub static TLS_VERSION: (u8, u8) = (3, 3);
 
+pub const TLS_1_0: (u8, u8) = (3, 1);
+pub const TLS_1_1: (u8, u8) = (3, 2);
+pub const TLS_1_2: (u8, u8) = (3, 3);
+pub const TLS_1_3: (u8, u8) = (3, 4);
+
 enum_from_primitive! {
     #[repr(u8)]
     #[derive(Copy, Clone, PartialEq, Debug)]
@@ -31,6 +37,9 @@ enum_from_primitive! {
 /// maximum length of Record (excluding content_type, version, length fields)
 pub const RECORD_MAX_LEN: usize = 1 << 14;
 
+/// default number of consecutive warning alerts accepted by `TlsReader`.
+pub const DEFAULT_MAX_WARNING_ALERTS: usize = 5;
+
 /// maximum length of EncryptedRecord (excluding content_type, version, length fields)
 pub const ENC_RECORD_MAX_LEN: usize = (1 << 14) + 2048;
 
@@ -70,6 +79,8 @@ pub struct TlsWriter<W: Write> {
     // if encryptor is None, handshake is not done yet.
     encryptor: Option<Box<Encryptor + Send + 'static>>,
     write_count: u64,
+    // maximum length of outgoing fragments
+    max_fragment_len: usize,
 }
 
 impl<W: Write> TlsWriter<W> {
@@ -80,7 +91,17 @@ impl<W: Write> TlsWriter<W> {
             writer: writer,
             encryptor: None,
             write_count: 0,
+            max_fragment_len: RECORD_MAX_LEN,
+        }
+    }
+
+    /// Set the maximum plaintext length of records written by `write_data`.
+    /// `len` must be in `1...RECORD_MAX_LEN`.
+    pub fn set_max_fragment_len(&mut self, len: usize) {
+        if len == 0 || len > RECORD_MAX_LEN {
+            panic!("bad fragment length: {}", len);
         }
+        self.max_fragment_len = len;
     }
 
     #[inline]
@@ -89,9 +110,8 @@ impl<W: Write> TlsWriter<W> {
     }
 
     /// Set encryptor and reset count.
-    /// This must be called only once.
+    /// This is called again when the connection is renegotiated.
     pub fn set_encryptor(&mut self, encryptor: Box<Encryptor + Send + 'static>) {
-        assert!(self.encryptor.is_none());
         self.encryptor = Some(encryptor);
         self.write_count = 0;
     }
@@ -136,8 +156,7 @@ impl<W: Write> TlsWriter<W> {
 
     pub fn write_data(&mut self, ty: ContentType, data: &[u8]) -> TlsResult<()> {
         let (major, minor) = TLS_VERSION;
-        // TODO: configurable maxlen
-        for fragment in data.chunks(RECORD_MAX_LEN) {
+        for fragment in data.chunks(self.max_fragment_len) {
             let fragment = fragment.to_vec();
             let record = Record::new(ty, major, minor, fragment);
             try!(self.write_record(record));
@@ -184,6 +203,14 @@ pub struct TlsReader<R: ReadExt> {
     decryptor: Option<Box<Decryptor + Send + 'static>>,
     read_count: u64,
     handshake_buffer: HandshakeBuffer,
+    // maximum length of incoming fragments
+    max_fragment_len: usize,
+    // number of warning alerts received since the last non-alert record.
+    warning_count: usize,
+    max_warning_alerts: usize,
+    alert_callback: Option<AlertCallback>,
+    // negotiated version. records with other versions are rejected once this is set.
+    version: Option<(u8, u8)>,
 }
 
 /// Reads `Record` or `Message` from a readable object.
@@ -195,6 +222,11 @@ impl<R: ReadExt> TlsReader<R> {
             decryptor: None,
             read_count: 0,
             handshake_buffer: HandshakeBuffer::new(),
+            max_fragment_len: RECORD_MAX_LEN,
+            warning_count: 0,
+            max_warning_alerts: DEFAULT_MAX_WARNING_ALERTS,
+            alert_callback: None,
+            version: None,
         }
     }
 
@@ -203,14 +235,40 @@ impl<R: ReadExt> TlsReader<R> {
         &mut self.reader
     }
 
+    /// Set the negotiated protocol version.
+    /// From now on, records with a different version are rejected.
+    pub fn set_version(&mut self, version: (u8, u8)) {
+        self.version = Some(version);
+    }
+
     /// Set decryptor and reset count.
-    /// This must be called only once.
+    /// This is called again when the connection is renegotiated.
     pub fn set_decryptor(&mut self, decryptor: Box<Decryptor + Send + 'static>) {
-        assert!(self.decryptor.is_none());
         self.decryptor = Some(decryptor);
         self.read_count = 0;
     }
 
+    /// Set the maximum plaintext length of records accepted by the reader.
+    /// Longer records are rejected with `RecordOverflow`.
+    /// `len` must be in `1...RECORD_MAX_LEN`.
+    pub fn set_max_fragment_len(&mut self, len: usize) {
+        if len == 0 || len > RECORD_MAX_LEN {
+            panic!("bad fragment length: {}", len);
+        }
+        self.max_fragment_len = len;
+    }
+
+    /// Set the number of consecutive warning alerts after which the reader gives up.
+    /// This prevents a peer from keeping us busy with an endless stream of warnings.
+    pub fn set_max_warning_alerts(&mut self, max: usize) {
+        self.max_warning_alerts = max;
+    }
+
+    /// Set a callback which is notified of ignored warning alerts.
+    pub fn set_alert_callback(&mut self, callback: AlertCallback) {
+        self.alert_callback = Some(callback);
+    }
+
     /// Read a record from readable stream.
     ///
     /// Any record with unknown content type is treated as an error.
@@ -226,10 +284,17 @@ impl<R: ReadExt> TlsReader<R> {
 
         let major = try!(self.reader.read_u8());
         let minor = try!(self.reader.read_u8());
+        if let Some(version) = self.version {
+            if (major, minor) != version {
+                return tls_err!(ProtocolVersion, "wrong record version: {} {}", major, minor);
+            }
+        }
 
         let len = {
             let len = try!(self.reader.read_be_u16()) as usize;
-            if len > ENC_RECORD_MAX_LEN {
+            // RFC 6066 and RFC 8449 allow the same expansion as usual records
+            let max_len = self.max_fragment_len + (ENC_RECORD_MAX_LEN - RECORD_MAX_LEN);
+            if len > max_len {
                 return tls_err!(RecordOverflow, "TLSEncryptedText too long: {}", len);
             }
             len
@@ -239,7 +304,7 @@ impl<R: ReadExt> TlsReader<R> {
 
         let record = match self.decryptor {
             None => {
-                if fragment.len() > RECORD_MAX_LEN {
+                if fragment.len() > self.max_fragment_len {
                     return tls_err!(RecordOverflow,
                                     "decrypted record too long: {}",
                                     fragment.len());
@@ -266,9 +331,8 @@ impl<R: ReadExt> TlsReader<R> {
 
                 // TODO: "seq_num as nonce" is chacha20poly1305-specific
                 let data = try!(decryptor.decrypt(&seq_num, &fragment, &ad));
-                if data.len() > RECORD_MAX_LEN {
-                    // decryption routine went wrong.
-                    return panic!("decrypted record too long: {}", data.len());
+                if data.len() > self.max_fragment_len {
+                    return tls_err!(RecordOverflow, "decrypted record too long: {}", data.len());
                 }
 
                 Record::new(content_type, major, minor, data)
@@ -291,6 +355,10 @@ impl<R: ReadExt> TlsReader<R> {
     /// (Incorrect handling leads to [Alert attack](http://www.mitls.org/wsgi/alert-attack).)
     ///
     /// We treat partial alert message as an error and returns `UnexpectedMessage`.
+    ///
+    /// Alerts are processed as described in RFC 5246 7.2:
+    /// fatal alerts return `AlertReceived`, `close_notify` is returned as `AlertMessage`
+    /// and other warnings are passed to the alert callback and skipped.
     pub fn read_message(&mut self) -> TlsResult<Message> {
         match try!(self.handshake_buffer.get_message()) {
             Some(handshake_msg) => return Ok(HandshakeMessage(handshake_msg)),
@@ -303,6 +371,10 @@ impl<R: ReadExt> TlsReader<R> {
             // other record comes? is it legal?
 
             let record = try!(self.read_record());
+            // empty records carry nothing, so they don't end a series of warnings
+            if record.content_type != AlertTy && record.fragment.len() > 0 {
+                self.warning_count = 0;
+            }
             match record.content_type {
                 ChangeCipherSpecTy => {
                     if record.fragment.len() != 1 || record.fragment[0] != 1 {
@@ -320,13 +392,32 @@ impl<R: ReadExt> TlsReader<R> {
                     }
                     let level = FromPrimitive::from_u8(record.fragment[0]);
                     let desc = FromPrimitive::from_u8(record.fragment[1]);
-                    match (level, desc) {
-                        (Some(level), Some(desc)) => {
-                            return Ok(AlertMessage(try!(Alert::new(level, desc))));
-                        }
+                    let alert = match (level, desc) {
+                        (Some(level), Some(desc)) => try!(Alert::new(level, desc)),
                         _ => return tls_err!(UnexpectedMessage,
                                              "unknown alert: {:?}",
                                              record.fragment),
+                    };
+
+                    if alert.description == AlertDescription::inappropriate_fallback {
+                        return tls_err!(InappropriateFallback, "alert: {:?}", alert.description);
+                    }
+                    if alert.is_fatal() {
+                        return tls_err!(AlertReceived, "alert: {:?}", alert.description);
+                    }
+                    if alert.description == AlertDescription::close_notify {
+                        return Ok(AlertMessage(alert));
+                    }
+
+                    self.warning_count += 1;
+                    if self.warning_count > self.max_warning_alerts {
+                        return tls_err!(UnexpectedMessage,
+                                        "too many warning alerts: last one is {:?}",
+                                        alert.description);
+                    }
+                    debug!("ignoring warning alert: {:?}", alert.description);
+                    if let Some(ref mut callback) = self.alert_callback {
+                        callback(&alert);
                     }
                 }
                 HandshakeTy => {
@@ -347,6 +438,8 @@ impl<R: ReadExt> TlsReader<R> {
         }
     }
 
+    /// Reads the next non-empty application data.
+    /// Returns an empty vector at the end of stream, i.e. after `close_notify`.
     pub fn read_application_data(&mut self) -> TlsResult<Vec<u8>> {
         if self.decryptor.is_none() {
             panic!("ApplicationData called before handshake");
@@ -354,11 +447,23 @@ impl<R: ReadExt> TlsReader<R> {
         loop {
             let msg = try!(self.read_message());
             match msg {
+                // empty records are allowed and carry nothing
+                ApplicationDataMessage(ref msg) if msg.len() == 0 => {}
                 ApplicationDataMessage(msg) => return Ok(msg),
-                // TODO: handle other cases
-                AlertMessage(..) => unimplemented!(),
-                ChangeCipherSpecMessage(..) => unimplemented!(), // this should not come here
-                HandshakeMessage(..) => unimplemented!(), // TODO: re-handshake
+                // fatal alerts are errors of `read_message`
+                AlertMessage(alert) => {
+                    if alert.description == AlertDescription::close_notify {
+                        return Ok(Vec::new());
+                    }
+                    return tls_err!(AlertReceived, "alert: {:?}", alert.description);
+                }
+                ChangeCipherSpecMessage => {
+                    return tls_err!(UnexpectedMessage, "unexpected ChangeCipherSpec");
+                }
+                // renegotiation is handled by `TlsClient`
+                HandshakeMessage(..) => {
+                    return tls_err!(UnexpectedMessage, "unexpected Handshake message");
+                }
             }
         }
     }
@@ -446,6 +551,32 @@ mod test {
         assert_err!(record, RecordOverflow);
     }
 
+    #[test]
+    fn test_reader_max_fragment_len() {
+        let len = 512 + 1;
+        let mut data = vec![0x17, 0x03, 0x03, (len >> 8) as u8, len as u8];
+        for _ in 0..len {
+            data.push(0xFF);
+        }
+
+        let mut rr = new_reader(&data);
+        rr.set_max_fragment_len(512);
+        let record = rr.read_record();
+        assert_err!(record, RecordOverflow);
+    }
+
+    #[test]
+    fn test_writer_max_fragment_len() {
+        let mut rw = TlsWriter::new(Vec::new());
+        rw.set_max_fragment_len(512);
+        rw.write_data(ContentType::HandshakeTy, &[0; 1000]).unwrap();
+
+        let data = rw.get_mut().clone();
+        let mut rr = new_reader(&data);
+        assert_eq!(rr.read_record().unwrap().fragment.len(), 512);
+        assert_eq!(rr.read_record().unwrap().fragment.len(), 1000 - 512);
+    }
+
     #[test]
     fn test_reader_zero_length() {
         for content_type in vec![20, 21, 22] {
@@ -456,6 +587,94 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_reader_warning_alerts() {
+        use std::sync::{Arc, Mutex};
+        use alert::{Alert, AlertDescription};
+
+        // warning(unrecognized_name), warning(no_renegotiation), ChangeCipherSpec
+        let data = [0x15, 0x03, 0x03, 0x00, 0x02, 0x01, 112,
+                    0x15, 0x03, 0x03, 0x00, 0x02, 0x01, 100,
+                    0x14, 0x03, 0x03, 0x00, 0x01, 0x01];
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut rr = new_reader(&data);
+        {
+            let seen = seen.clone();
+            rr.set_alert_callback(Box::new(move |alert: &Alert| {
+                seen.lock().unwrap().push(alert.description);
+            }));
+        }
+        match rr.read_message() {
+            Ok(Message::ChangeCipherSpecMessage) => {}
+            _ => panic!("expected ChangeCipherSpec"),
+        }
+        assert_eq!(*seen.lock().unwrap(),
+                   vec![AlertDescription::unrecognized_name, AlertDescription::no_renegotiation]);
+    }
+
+    #[test]
+    fn test_reader_too_many_warning_alerts() {
+        let mut data = Vec::new();
+        for _ in 0..(DEFAULT_MAX_WARNING_ALERTS + 1) {
+            data.extend(&[0x15, 0x03, 0x03, 0x00, 0x02, 0x01, 90]);
+        }
+        let mut rr = new_reader(&data);
+        assert_err!(rr.read_message(), UnexpectedMessage);
+
+        // the counter is reset by other records
+        let mut data = Vec::new();
+        for _ in 0..2 {
+            for _ in 0..DEFAULT_MAX_WARNING_ALERTS {
+                data.extend(&[0x15, 0x03, 0x03, 0x00, 0x02, 0x01, 90]);
+            }
+            data.extend(&[0x14, 0x03, 0x03, 0x00, 0x01, 0x01]);
+        }
+        let mut rr = new_reader(&data);
+        for _ in 0..2 {
+            assert!(rr.read_message().is_ok());
+        }
+    }
+
+    #[test]
+    fn test_reader_fatal_alerts() {
+        let tests: &[&[u8]] = &[
+            // fatal(handshake_failure)
+            &[0x15, 0x03, 0x03, 0x00, 0x02, 0x02, 40],
+            // fatal(no_renegotiation)
+            &[0x15, 0x03, 0x03, 0x00, 0x02, 0x02, 100],
+            // warning(bad_record_mac) is always fatal
+            &[0x15, 0x03, 0x03, 0x00, 0x02, 0x01, 20],
+        ];
+        for &input in tests {
+            let mut rr = new_reader(input);
+            assert_err!(rr.read_message(), AlertReceived);
+        }
+
+        // close_notify is returned to the caller
+        let data = [0x15, 0x03, 0x03, 0x00, 0x02, 0x01, 0];
+        let mut rr = new_reader(&data);
+        match rr.read_message() {
+            Ok(Message::AlertMessage(alert)) => assert!(!alert.is_fatal()),
+            _ => panic!("expected close_notify"),
+        }
+
+        // fatal(inappropriate_fallback)
+        let data = [0x15, 0x03, 0x03, 0x00, 0x02, 0x02, 86];
+        let mut rr = new_reader(&data);
+        assert_err!(rr.read_message(), InappropriateFallback);
+    }
+
+    #[test]
+    fn test_reader_record_version() {
+        let data = [0x17, 0x03, 0x01, 0x00, 0x01, 0x00,
+                    0x17, 0x03, 0x01, 0x00, 0x01, 0x00];
+        let mut rr = new_reader(&data);
+        assert!(rr.read_message().is_ok());
+
+        rr.set_version((3, 3));
+        assert_err!(rr.read_message(), ProtocolVersion);
+    }
+
     #[test]
     #[should_panic]
     fn test_writer_too_long() {
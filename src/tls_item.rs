@@ -49,12 +49,14 @@ tls_primitive!(u64);
 
 macro_rules! tls_struct {
     (
+        $(#[$a:meta])*
         struct $name:ident {
             $(
                 $item:ident: $t:ty
             ),+
         }
     ) => (
+        $(#[$a])*
         pub struct $name {
             $(
                 pub $item: $t,
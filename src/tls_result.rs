@@ -12,11 +12,24 @@ pub enum TlsErrorKind {
     IllegalParameter,
     DecodeError,
     DecryptError,
+    HandshakeFailure,
+    BadCertificate,
+    CertificateRevoked,
+    CertificateExpired,
+    CertificateUnknown,
+    UnknownCa,
+    UnsupportedExtension,
+    BadCertificateStatusResponse,
+    ProtocolVersion,
+    InsufficientSecurity,
     InternalError,
 
     // we probably can't even send alert?
     IoFailure,
     AlertReceived,
+    // the server sent `inappropriate_fallback` alert (RFC 7507).
+    // the connection must not be retried with a lower version.
+    InappropriateFallback,
 }
 
 #[derive(Debug)]
@@ -43,13 +56,22 @@ impl Error for TlsError {
             TlsErrorKind::IllegalParameter => "illegal parameter during handshake",
             TlsErrorKind::DecodeError => "cannot decode message",
             TlsErrorKind::DecryptError => "failed to verify signature/message",
+            TlsErrorKind::HandshakeFailure => "failed to negotiate security parameters",
+            TlsErrorKind::BadCertificate => "bad certificate",
+            TlsErrorKind::CertificateRevoked => "certificate revoked",
+            TlsErrorKind::CertificateExpired => "certificate expired or not yet valid",
+            TlsErrorKind::CertificateUnknown => "certificate status unknown",
+            TlsErrorKind::UnknownCa => "certificate issued by unknown CA",
+            TlsErrorKind::UnsupportedExtension => "unsupported extension",
+            TlsErrorKind::BadCertificateStatusResponse => "bad certificate status response",
+            TlsErrorKind::ProtocolVersion => "unsupported protocol version",
+            TlsErrorKind::InsufficientSecurity => "security parameters too weak",
             TlsErrorKind::InternalError => "internal error",
 
-            // UnsupportedExtension,
-
             // we probably can't even send alert?
             TlsErrorKind::IoFailure => "i/o error",
             TlsErrorKind::AlertReceived => "received an alert",
+            TlsErrorKind::InappropriateFallback => "server rejected fallback",
         }
     }
 }
@@ -1,6 +1,7 @@
 use std::{mem, fmt};
 use std::error::Error;
 use std::io::{self, Read, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Debug)]
 pub struct SurugaError {
@@ -23,6 +24,14 @@ impl Error for SurugaError {
     // This runs into difficulties with differing trait bounds.
 }
 
+/// Current time in seconds since the Unix epoch.
+pub fn unix_time() -> i64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    }
+}
+
 /// constant-time compare function.
 /// `a` and `b` may be SECRET, but the length is known.
 /// precondition: `a.len() == b.len()`
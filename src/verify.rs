@@ -0,0 +1,879 @@
+//! Certificate verification policies.
+//!
+//! `TlsClient` asks a `ServerCertVerifier` whether to accept the server certificate chain.
+//! `WebPkiVerifier` is the default, which validates the chain against trust anchors
+//! and checks the server name.
+
+use std::net::IpAddr;
+
+use handshake::CertificateList;
+use der::{self, Reader};
+use roots::RootStore;
+use x509::{oid, Certificate, GeneralName, NameConstraints, KEY_USAGE_KEY_CERT_SIGN};
+use tls_result::{TlsResult, TlsError};
+use tls_result::TlsErrorKind::{BadCertificate, CertificateExpired, UnknownCa};
+
+/// Maximum number of certificates in a path, including the trust anchor.
+pub const MAX_CHAIN_LEN: usize = 8;
+/// Maximum number of signatures checked while building a path. This bounds the work for
+/// chains with many candidate issuers, e.g. crafted cross-certificate loops.
+pub const MAX_SIGNATURE_CHECKS: usize = 64;
+
+/// Decides whether the certificate chain of a server is acceptable.
+pub trait ServerCertVerifier {
+    /// Verifies `chain`, the `CertificateList` sent by the server, for `server_name`.
+    /// `now` is in seconds since the Unix epoch.
+    ///
+    /// Returns the validated path, leaf first and ending with the trust anchor, which
+    /// may not be in `chain`. Verifiers which do not build paths return `chain` itself.
+    /// `TlsClient` then checks the stapled OCSP response, CRLs, pins and SCTs
+    /// against this path, as configured in `ClientConfig`.
+    /// The kind of the returned error determines the alert sent to the server.
+    fn verify_server_cert(&self,
+                          chain: &CertificateList,
+                          server_name: Option<&str>,
+                          now: i64) -> TlsResult<Vec<Vec<u8>>>;
+}
+
+/// Decides whether the certificate chain of a client is acceptable.
+pub trait ClientCertVerifier {
+    /// Distinguished names of CAs sent in `CertificateRequest`.
+    fn acceptable_issuers(&self) -> Vec<Vec<u8>>;
+
+    /// Verifies `chain`, the `CertificateList` sent by the client, at `now`.
+    /// The chain is empty if the client has no suitable certificate.
+    ///
+    /// The kind of the returned error determines the alert sent to the client.
+    fn verify_client_cert(&self, chain: &CertificateList, now: i64) -> TlsResult<()>;
+}
+
+/// Where a certificate of a validated path comes from.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum CertSource {
+    /// index in the chain sent by the peer
+    Chain(usize),
+    /// the intermediate cache
+    Intermediate,
+    /// the trust anchors
+    Anchor,
+}
+
+/// What the leaf certificate is used for, checked against extended key usage.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum KeyPurpose {
+    ServerAuth,
+    ClientAuth,
+}
+
+impl KeyPurpose {
+    fn oid(&self) -> &'static [u8] {
+        match *self {
+            KeyPurpose::ServerAuth => oid::KP_SERVER_AUTH,
+            KeyPurpose::ClientAuth => oid::KP_CLIENT_AUTH,
+        }
+    }
+}
+
+// extensions whose semantics are checked, or which are safe to ignore.
+// certificate policies are not enforced, i.e. any policy is accepted (RFC 5280 6.1 with
+// `initial-policy-set` of `anyPolicy` and no explicit policy required).
+const KNOWN_EXTENSIONS: &'static [&'static [u8]] = &[
+    oid::BASIC_CONSTRAINTS,
+    oid::KEY_USAGE,
+    oid::EXTENDED_KEY_USAGE,
+    oid::SUBJECT_ALT_NAME,
+    oid::NAME_CONSTRAINTS,
+    oid::CERTIFICATE_POLICIES,
+    oid::TLS_FEATURE,
+];
+
+/// Validates certificate chains against trust anchors, like web browsers do.
+///
+/// The first certificate of the chain is the leaf. The others are an unordered pool of
+/// candidate intermediates, together with `intermediates`, and any path from the leaf to a
+/// trust anchor through them is accepted. Every certificate of the path except the anchor
+/// must be valid at the time of verification, and issuers must be CAs allowed to sign
+/// certificates. For servers, the leaf must have a subject alternative name matching
+/// the server name.
+///
+/// The path must also satisfy the name constraints and path length constraints of its
+/// intermediates, and extended key usage, where present, must allow the `KeyPurpose` on
+/// the leaf and every intermediate. Certificates with unknown critical extensions are
+/// rejected. Anchors are trusted as they are: their constraints are not applied.
+pub struct WebPkiVerifier {
+    roots: RootStore,
+    /// Intermediate CA certificates to complete chains without some intermediates.
+    /// They are not trusted by themselves: `RootStore` is only used as an index here.
+    pub intermediates: RootStore,
+}
+
+impl WebPkiVerifier {
+    /// Creates a verifier trusting the anchors of `roots`.
+    pub fn new(roots: RootStore) -> WebPkiVerifier {
+        WebPkiVerifier {
+            roots: roots,
+            intermediates: RootStore::new(),
+        }
+    }
+
+    /// Builds a path from the leaf `chain[0]` to a trust anchor for `purpose` at `now`,
+    /// and returns the certificates of the path, leaf first.
+    ///
+    /// Issuers are searched depth-first, preferring anchors, then the chain, then
+    /// the intermediate cache. A complete path violating constraints is abandoned and
+    /// the search goes on. The search is bounded by `MAX_CHAIN_LEN` and
+    /// `MAX_SIGNATURE_CHECKS`. If no path is found, the error of the first rejected
+    /// candidate issuer or path is returned, or `UnknownCa`.
+    pub fn build_path<'a>(&'a self, chain: &[&'a [u8]], purpose: KeyPurpose, now: i64)
+                          -> TlsResult<Vec<(CertSource, &'a [u8])>> {
+        let leaf = match chain.first() {
+            Some(&leaf) => leaf,
+            None => return tls_err!(BadCertificate, "no certificate"),
+        };
+        {
+            let cert = match Certificate::parse(leaf) {
+                Ok(cert) => cert,
+                Err(err) => return tls_err!(BadCertificate, "bad certificate: {}", err.desc),
+            };
+            if now < cert.not_before || cert.not_after < now {
+                return tls_err!(CertificateExpired, "certificate is not valid now");
+            }
+        }
+
+        let mut pool = Vec::new();
+        for (i, &der) in chain.iter().enumerate().skip(1) {
+            match Certificate::parse(der) {
+                Ok(cert) => pool.push((i, der, cert.subject)),
+                Err(err) => debug!("ignoring certificate {}: {}", i, err.desc),
+            }
+        }
+
+        let mut builder = PathBuilder {
+            roots: &self.roots,
+            intermediates: &self.intermediates,
+            pool: pool,
+            purpose: purpose,
+            now: now,
+            signature_checks: 0,
+            error: None,
+        };
+        let mut path = vec![(CertSource::Chain(0), leaf)];
+        if try!(builder.extend(&mut path)) {
+            return Ok(path);
+        }
+        match builder.error {
+            Some(err) => Err(err),
+            None => tls_err!(UnknownCa, "certificate issued by unknown CA"),
+        }
+    }
+
+    /// Validates `chain` for `purpose` at `now` (see `build_path`).
+    pub fn verify_chain(&self, chain: &[&[u8]], purpose: KeyPurpose, now: i64) -> TlsResult<()> {
+        let path = try!(self.build_path(chain, purpose, now));
+        debug!("certificate path: {:?}", path.iter().map(|&(source, _)| source)
+                                               .collect::<Vec<_>>());
+        Ok(())
+    }
+}
+
+struct PathBuilder<'a> {
+    roots: &'a RootStore,
+    intermediates: &'a RootStore,
+    // certificates of the chain except the leaf: index, DER and subject
+    pool: Vec<(usize, &'a [u8], &'a [u8])>,
+    purpose: KeyPurpose,
+    now: i64,
+    signature_checks: usize,
+    // the first error, reported if no path is found
+    error: Option<TlsError>,
+}
+
+impl<'a> PathBuilder<'a> {
+    fn note(&mut self, err: TlsError) {
+        if self.error.is_none() {
+            self.error = Some(err);
+        }
+    }
+
+    fn is_signed_by(&mut self, cert: &Certificate, issuer: &Certificate) -> TlsResult<bool> {
+        if self.signature_checks == MAX_SIGNATURE_CHECKS {
+            return tls_err!(UnknownCa, "too many candidate issuers");
+        }
+        self.signature_checks += 1;
+        Ok(cert.verify_signature(issuer).is_ok())
+    }
+
+    // checks constraints of `path`, which ends with a trust anchor.
+    fn accept(&mut self, path: &[(CertSource, &'a [u8])]) -> bool {
+        match check_path(path, self.purpose) {
+            Ok(()) => true,
+            Err(err) => {
+                self.note(err);
+                false
+            }
+        }
+    }
+
+    // extends `path`, whose certificates are checked except the signature of the last one,
+    // to a trust anchor. returns whether a path is found.
+    fn extend(&mut self, path: &mut Vec<(CertSource, &'a [u8])>) -> TlsResult<bool> {
+        let der = path.last().unwrap().1;
+        // the certificate itself is trusted
+        if self.roots.contains(der) {
+            return Ok(self.accept(path));
+        }
+        let cert = Certificate::parse(der).unwrap();
+
+        for anchor in self.roots.find_by_subject(cert.issuer).into_iter() {
+            let issuer = Certificate::parse(anchor).unwrap();
+            if try!(self.is_signed_by(&cert, &issuer)) {
+                path.push((CertSource::Anchor, anchor));
+                if self.accept(path) {
+                    return Ok(true);
+                }
+                path.pop();
+            }
+        }
+        // room for an intermediate and an anchor
+        if path.len() + 2 > MAX_CHAIN_LEN {
+            self.note(TlsError {
+                kind: BadCertificate,
+                desc: "certificate path too long".to_string(),
+            });
+            return Ok(false);
+        }
+
+        let mut candidates: Vec<(CertSource, &'a [u8])> = self.pool.iter().filter(|c| {
+            c.2 == cert.issuer
+        }).map(|&(i, der, _)| (CertSource::Chain(i), der)).collect();
+        for der in self.intermediates.find_by_subject(cert.issuer).into_iter() {
+            candidates.push((CertSource::Intermediate, der));
+        }
+
+        for (source, der) in candidates.into_iter() {
+            let issuer = Certificate::parse(der).unwrap();
+            // avoid loops, also via certificates with the same name and key
+            let in_path = path.iter().any(|&(_, prev)| {
+                let prev = Certificate::parse(prev).unwrap();
+                prev.subject == issuer.subject &&
+                prev.subject_public_key_info == issuer.subject_public_key_info
+            });
+            if in_path {
+                continue;
+            }
+            if self.now < issuer.not_before || issuer.not_after < self.now {
+                self.note(TlsError {
+                    kind: CertificateExpired,
+                    desc: format!("issuer {:?} is not valid now", source),
+                });
+                continue;
+            }
+            if let Err(err) = check_ca(&issuer) {
+                self.note(err);
+                continue;
+            }
+            if !try!(self.is_signed_by(&cert, &issuer)) {
+                continue;
+            }
+
+            path.push((source, der));
+            if try!(self.extend(path)) {
+                return Ok(true);
+            }
+            path.pop();
+        }
+        Ok(false)
+    }
+}
+
+fn check_ca(cert: &Certificate) -> TlsResult<()> {
+    let is_ca = match cert.basic_constraints() {
+        Ok(Some(constraints)) => constraints.ca,
+        Ok(None) => false,
+        Err(err) => return tls_err!(BadCertificate, "bad basic constraints: {}", err.desc),
+    };
+    if !is_ca {
+        return tls_err!(BadCertificate, "issuer is not a CA");
+    }
+    match cert.key_usage() {
+        Ok(Some(usage)) if usage & KEY_USAGE_KEY_CERT_SIGN == 0 => {
+            tls_err!(BadCertificate, "issuer cannot sign certificates")
+        }
+        Ok(..) => Ok(()),
+        Err(err) => tls_err!(BadCertificate, "bad key usage: {}", err.desc),
+    }
+}
+
+// checks the constraints of `path` except validity, CA status and signatures, which are
+// checked while building it. the last certificate is the trust anchor.
+fn check_path(path: &[(CertSource, &[u8])], purpose: KeyPurpose) -> TlsResult<()> {
+    let certs: Vec<Certificate> = path.iter().map(|&(_, der)| {
+        Certificate::parse(der).unwrap()
+    }).collect();
+    let anchor = certs.len() - 1;
+
+    for (i, cert) in certs[..anchor].iter().enumerate() {
+        let source = path[i].0;
+        if let Some(ext) = cert.extensions.iter().find(|ext| {
+            ext.critical && !KNOWN_EXTENSIONS.contains(&ext.id)
+        }) {
+            return tls_err!(BadCertificate, "unknown critical extension in {:?}: {:?}",
+                            source, ext.id);
+        }
+
+        match cert.extended_key_usage() {
+            Ok(Some(ref purposes)) if !purposes.contains(&purpose.oid()) => {
+                return tls_err!(BadCertificate, "{:?} is not for {:?}", source, purpose);
+            }
+            Ok(..) => {}
+            Err(err) => return tls_err!(BadCertificate, "bad extended key usage: {}", err.desc),
+        }
+
+        if i == 0 {
+            continue;
+        }
+
+        // RFC 5280 6.1.4 (l), (m): self-issued intermediates are not counted
+        let path_len = match cert.basic_constraints() {
+            Ok(constraints) => constraints.and_then(|c| c.path_len_constraint),
+            Err(err) => return tls_err!(BadCertificate, "bad basic constraints: {}", err.desc),
+        };
+        if let Some(path_len) = path_len {
+            let below = certs[1..i].iter().filter(|c| c.subject != c.issuer).count();
+            if below as u64 > path_len {
+                return tls_err!(BadCertificate, "path length constraint of {:?} exceeded",
+                                source);
+            }
+        }
+
+        let constraints = match cert.name_constraints() {
+            Ok(Some(constraints)) => constraints,
+            Ok(None) => continue,
+            Err(err) => return tls_err!(BadCertificate, "bad name constraints: {}", err.desc),
+        };
+        // RFC 5280 6.1.3 (b): names of self-issued intermediates are not checked
+        for (j, sub) in certs[..i].iter().enumerate() {
+            if j > 0 && sub.subject == sub.issuer {
+                continue;
+            }
+            if let Err(err) = check_name_constraints(&constraints, sub) {
+                return tls_err!(BadCertificate, "{:?} violates name constraints of {:?}: {}",
+                                path[j].0, source, err.desc);
+            }
+        }
+    }
+    Ok(())
+}
+
+// checks the subject name and subject alternative names of `cert` (RFC 5280 4.2.1.10).
+fn check_name_constraints(constraints: &NameConstraints, cert: &Certificate) -> TlsResult<()> {
+    let all = constraints.permitted.iter().chain(constraints.excluded.iter());
+    for constraint in all {
+        if let GeneralName::Other(tag, _) = *constraint {
+            return tls_err!(BadCertificate, "unsupported name constraint form {:#x}", tag);
+        }
+    }
+
+    let mut names = Vec::new();
+    // an empty subject is not constrained
+    if cert.subject != b"\x30\x00" {
+        names.push(GeneralName::DirectoryName(cert.subject));
+    }
+    match cert.subject_alt_names() {
+        Ok(Some(alt_names)) => names.extend(alt_names),
+        Ok(None) => {}
+        Err(err) => return tls_err!(BadCertificate, "bad subject alternative names: {}", err.desc),
+    }
+
+    for name in names.iter() {
+        for constraint in constraints.excluded.iter() {
+            if try!(name_in_subtree(name, constraint)) == Some(true) {
+                return tls_err!(BadCertificate, "name is excluded");
+            }
+        }
+        // names are only constrained by permitted subtrees of the same form
+        let mut constrained = false;
+        let mut permitted = false;
+        for constraint in constraints.permitted.iter() {
+            if let Some(matched) = try!(name_in_subtree(name, constraint)) {
+                constrained = true;
+                permitted |= matched;
+            }
+        }
+        if constrained && !permitted {
+            return tls_err!(BadCertificate, "name is not permitted");
+        }
+    }
+    Ok(())
+}
+
+// whether `name` is within the subtree `base`, or `None` if they have different forms.
+fn name_in_subtree(name: &GeneralName, base: &GeneralName) -> TlsResult<Option<bool>> {
+    let matched = match (*name, *base) {
+        (GeneralName::DnsName(name), GeneralName::DnsName(base)) => {
+            dns_name_in_subtree(name, base)
+        }
+        (GeneralName::IpAddress(addr), GeneralName::IpAddress(base)) => {
+            if base.len() != 8 && base.len() != 32 {
+                return tls_err!(BadCertificate, "bad IP address constraint");
+            }
+            let (base, mask) = base.split_at(base.len() / 2);
+            // different address families do not match
+            addr.len() == base.len() && addr.iter().zip(base.iter()).zip(mask.iter()).all(|t| {
+                let ((&a, &b), &m) = t;
+                a & m == b & m
+            })
+        }
+        (GeneralName::DirectoryName(name), GeneralName::DirectoryName(base)) => {
+            let name = try!(relative_names(name));
+            let base = try!(relative_names(base));
+            name.starts_with(&base)
+        }
+        (GeneralName::Uri(uri), GeneralName::Uri(base)) => {
+            match uri_host(uri) {
+                // RFC 5280: a leading period means subdomains only, otherwise the exact host
+                Some(host) => if base.starts_with(b".") {
+                    host.len() > base.len() &&
+                    host[(host.len() - base.len())..].eq_ignore_ascii_case(base)
+                } else {
+                    host.eq_ignore_ascii_case(base)
+                },
+                None => false,
+            }
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(matched))
+}
+
+// strips the trailing dot of an absolute DNS name.
+fn trim_dot(name: &[u8]) -> &[u8] {
+    match name.last() {
+        Some(&b'.') => &name[..(name.len() - 1)],
+        _ => name,
+    }
+}
+
+// RFC 5280 4.2.1.10: `permitted.example` matches itself and its subdomains, while
+// `.permitted.example` only matches subdomains. `notpermitted.example` matches neither.
+fn dns_name_in_subtree(name: &[u8], base: &[u8]) -> bool {
+    let name = trim_dot(name);
+    let base = trim_dot(base);
+    if base.len() == 0 {
+        return true;
+    }
+    if name.len() < base.len() {
+        return false;
+    }
+    let (prefix, suffix) = name.split_at(name.len() - base.len());
+    if !suffix.eq_ignore_ascii_case(base) {
+        return false;
+    }
+    if base.starts_with(b".") {
+        prefix.len() > 0
+    } else {
+        prefix.len() == 0 || prefix.ends_with(b".")
+    }
+}
+
+// raw DER of the `RelativeDistinguishedName`s of `name`.
+fn relative_names(name: &[u8]) -> TlsResult<Vec<&[u8]>> {
+    let mut reader = Reader::new(name);
+    let mut seq = try!(reader.read_nested(der::SEQUENCE));
+    try!(reader.finish());
+    let mut rdns = Vec::new();
+    while !seq.is_empty() {
+        rdns.push(try!(seq.read_raw(der::SET)));
+    }
+    Ok(rdns)
+}
+
+// host of URI `scheme://[userinfo@]host[:port]/...` (RFC 3986). `None` if there is no
+// authority, or the host is an IP literal.
+fn uri_host(uri: &[u8]) -> Option<&[u8]> {
+    let colon = match uri.iter().position(|&c| c == b':') {
+        Some(colon) => colon,
+        None => return None,
+    };
+    let rest = &uri[(colon + 1)..];
+    if !rest.starts_with(b"//") {
+        return None;
+    }
+    let rest = &rest[2..];
+    let end = rest.iter().position(|&c| c == b'/' || c == b'?' || c == b'#').unwrap_or(rest.len());
+    let mut host = &rest[..end];
+    if let Some(at) = host.iter().rposition(|&c| c == b'@') {
+        host = &host[(at + 1)..];
+    }
+    if host.starts_with(b"[") {
+        return None;
+    }
+    if let Some(colon) = host.iter().position(|&c| c == b':') {
+        host = &host[..colon];
+    }
+    if host.len() == 0 {
+        return None;
+    }
+    Some(host)
+}
+
+fn chain_slices(chain: &CertificateList) -> Vec<&[u8]> {
+    chain.iter().map(|cert| &cert[..]).collect()
+}
+
+impl ServerCertVerifier for WebPkiVerifier {
+    fn verify_server_cert(&self,
+                          chain: &CertificateList,
+                          server_name: Option<&str>,
+                          now: i64) -> TlsResult<Vec<Vec<u8>>> {
+        let chain = chain_slices(chain);
+        let path = try!(self.build_path(&chain, KeyPurpose::ServerAuth, now));
+        let server_name = match server_name {
+            Some(name) => name,
+            None => return tls_err!(BadCertificate, "server name is not set"),
+        };
+        let leaf = try!(Certificate::parse(chain[0]));
+        try!(verify_server_name(&leaf, server_name));
+        Ok(path.into_iter().map(|(_, der)| der.to_vec()).collect())
+    }
+}
+
+impl ClientCertVerifier for WebPkiVerifier {
+    fn acceptable_issuers(&self) -> Vec<Vec<u8>> {
+        self.roots.subjects()
+    }
+
+    fn verify_client_cert(&self, chain: &CertificateList, now: i64) -> TlsResult<()> {
+        self.verify_chain(&chain_slices(chain), KeyPurpose::ClientAuth, now)
+    }
+}
+
+// RFC 6125 6.4: a wildcard is only allowed as the whole left-most label,
+// and matches exactly one label.
+fn dns_name_matches(pattern: &[u8], name: &[u8]) -> bool {
+    let pattern = trim_dot(pattern);
+    let name = trim_dot(name);
+    if pattern.len() == 0 || name.len() == 0 {
+        return false;
+    }
+
+    if pattern.starts_with(b"*.") {
+        let suffix = &pattern[1..];
+        // `*.com` is too broad
+        if !suffix[1..].contains(&b'.') {
+            return false;
+        }
+        match name.iter().position(|&c| c == b'.') {
+            Some(i) if i > 0 => name[i..].eq_ignore_ascii_case(suffix),
+            _ => false,
+        }
+    } else {
+        pattern.eq_ignore_ascii_case(name)
+    }
+}
+
+/// Checks that `cert` is issued for `name`, a DNS name or an IP address.
+/// Only subject alternative names are used; the common name is ignored.
+pub fn verify_server_name(cert: &Certificate, name: &str) -> TlsResult<()> {
+    let names = match cert.subject_alt_names() {
+        Ok(Some(names)) => names,
+        Ok(None) => return tls_err!(BadCertificate, "no subject alternative names"),
+        Err(err) => return tls_err!(BadCertificate, "bad subject alternative names: {}", err.desc),
+    };
+    let matched = match name.parse::<IpAddr>() {
+        Ok(addr) => {
+            let octets = match addr {
+                IpAddr::V4(addr) => addr.octets().to_vec(),
+                IpAddr::V6(addr) => addr.octets().to_vec(),
+            };
+            names.iter().any(|n| *n == GeneralName::IpAddress(&octets))
+        }
+        Err(..) => names.iter().any(|n| match *n {
+            GeneralName::DnsName(pattern) => dns_name_matches(pattern, name.as_bytes()),
+            _ => false,
+        }),
+    };
+    if !matched {
+        return tls_err!(BadCertificate, "certificate is not valid for {}", name);
+    }
+    Ok(())
+}
+
+/// Verifiers which do not authenticate the server by themselves.
+/// Only available with the `dangerous_configuration` feature.
+#[cfg(any(test, feature = "dangerous_configuration"))]
+pub mod danger {
+    use handshake::CertificateList;
+    use tls_result::TlsResult;
+    use super::ServerCertVerifier;
+
+    /// Accepts any certificate chain, returning it as the path.
+    ///
+    /// The connection is not authenticated unless something else checks the server key,
+    /// e.g. `PinPolicy` for a self-signed server.
+    pub struct NoCertificateVerification;
+
+    impl ServerCertVerifier for NoCertificateVerification {
+        fn verify_server_cert(&self,
+                              chain: &CertificateList,
+                              _server_name: Option<&str>,
+                              _now: i64) -> TlsResult<Vec<Vec<u8>>> {
+            Ok(chain.iter().map(|cert| cert[..].to_vec()).collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use handshake::{Asn1Cert, CertificateList};
+    use roots::RootStore;
+    use tls_result::TlsErrorKind::{BadCertificate, CertificateExpired, UnknownCa};
+    use super::{WebPkiVerifier, ServerCertVerifier, ClientCertVerifier, KeyPurpose};
+    use super::{dns_name_matches, dns_name_in_subtree, uri_host};
+
+    static CA: &'static [u8] = include_bytes!("../testdata/test_ca.der");
+    static SERVER: &'static [u8] = include_bytes!("../testdata/test_server.der");
+    static CLIENT: &'static [u8] = include_bytes!("../testdata/test_client.der");
+    static RESPONDER: &'static [u8] = include_bytes!("../testdata/test_ocsp_responder.der");
+    static INTER: &'static [u8] = include_bytes!("../testdata/pki/inter.der");
+    static INTER_EXPIRED: &'static [u8] = include_bytes!("../testdata/pki/inter_expired.der");
+    static LEAF: &'static [u8] = include_bytes!("../testdata/pki/leaf.der");
+    static LEAF_BY_LEAF: &'static [u8] = include_bytes!("../testdata/pki/leaf_by_leaf.der");
+    static ROOT_B: &'static [u8] = include_bytes!("../testdata/pki/root_b.der");
+    static ROOT_B_CROSS: &'static [u8] = include_bytes!("../testdata/pki/root_b_cross.der");
+    static INTER_B: &'static [u8] = include_bytes!("../testdata/pki/inter_b.der");
+    static LEAF_B: &'static [u8] = include_bytes!("../testdata/pki/leaf_b.der");
+    static NC_INTER: &'static [u8] = include_bytes!("../testdata/pki/nc_inter.der");
+    static NC_INTER_UNCONSTRAINED: &'static [u8] =
+        include_bytes!("../testdata/pki/nc_inter_unconstrained.der");
+    static NC_OK: &'static [u8] = include_bytes!("../testdata/pki/nc_ok.der");
+    static NC_DNS: &'static [u8] = include_bytes!("../testdata/pki/nc_dns.der");
+    static NC_DNS_EXCLUDED: &'static [u8] = include_bytes!("../testdata/pki/nc_dns_excluded.der");
+    static NC_IP: &'static [u8] = include_bytes!("../testdata/pki/nc_ip.der");
+    static NC_IP_EXCLUDED: &'static [u8] = include_bytes!("../testdata/pki/nc_ip_excluded.der");
+    static NC_DIR: &'static [u8] = include_bytes!("../testdata/pki/nc_dir.der");
+    static NC_URI: &'static [u8] = include_bytes!("../testdata/pki/nc_uri.der");
+    static NC_EMAIL_INTER: &'static [u8] = include_bytes!("../testdata/pki/nc_email_inter.der");
+    static NC_EMAIL_LEAF: &'static [u8] = include_bytes!("../testdata/pki/nc_email_leaf.der");
+    static LEAF_SERVER: &'static [u8] = include_bytes!("../testdata/pki/leaf_server.der");
+    static LEAF_CLIENT: &'static [u8] = include_bytes!("../testdata/pki/leaf_client.der");
+    static INTER_CLIENT: &'static [u8] = include_bytes!("../testdata/pki/inter_client.der");
+    static LEAF_SERVER_BY_CLIENT_CA: &'static [u8] =
+        include_bytes!("../testdata/pki/leaf_server_by_client_ca.der");
+    static INTER_LEN0: &'static [u8] = include_bytes!("../testdata/pki/inter_len0.der");
+    static LEAF_LEN0: &'static [u8] = include_bytes!("../testdata/pki/leaf_len0.der");
+    static INTER_SUB_LEN0: &'static [u8] = include_bytes!("../testdata/pki/inter_sub_len0.der");
+    static LEAF_SUB_LEN0: &'static [u8] = include_bytes!("../testdata/pki/leaf_sub_len0.der");
+    static LEAF_CRITICAL: &'static [u8] = include_bytes!("../testdata/pki/leaf_critical.der");
+    static INTER_CRITICAL: &'static [u8] = include_bytes!("../testdata/pki/inter_critical.der");
+    static LEAF_BY_CRITICAL: &'static [u8] =
+        include_bytes!("../testdata/pki/leaf_by_critical.der");
+
+    const NOW: i64 = 1792328292;
+
+    fn roots(certs: &[&[u8]]) -> RootStore {
+        let mut store = RootStore::new();
+        for cert in certs.iter() {
+            store.add(cert.to_vec()).unwrap();
+        }
+        store
+    }
+
+    fn list(certs: &[&[u8]]) -> CertificateList {
+        let certs = certs.iter().map(|cert| Asn1Cert::new(cert.to_vec()).unwrap()).collect();
+        CertificateList::new(certs).unwrap()
+    }
+
+    #[test]
+    fn test_dns_name_matches() {
+        assert!(dns_name_matches(b"example.com", b"example.com"));
+        assert!(dns_name_matches(b"Example.COM", b"example.com."));
+        assert!(dns_name_matches(b"*.example.com", b"www.example.com"));
+        assert!(dns_name_matches(b"*.Example.com", b"WWW.example.com"));
+        assert!(!dns_name_matches(b"*.example.com", b"example.com"));
+        assert!(!dns_name_matches(b"*.example.com", b".example.com"));
+        assert!(!dns_name_matches(b"*.example.com", b"a.b.example.com"));
+        assert!(!dns_name_matches(b"w*.example.com", b"www.example.com"));
+        assert!(!dns_name_matches(b"*.com", b"example.com"));
+        assert!(!dns_name_matches(b"example.com", b"www.example.com"));
+        assert!(!dns_name_matches(b"", b""));
+    }
+
+    #[test]
+    fn test_webpki_verifier() {
+        let verifier = WebPkiVerifier::new(roots(&[CA]));
+        let verify = |chain: &[&[u8]], name, now| {
+            verifier.verify_server_cert(&list(chain), name, now)
+        };
+
+        assert!(verify(&[SERVER, CA], Some("localhost"), NOW).is_ok());
+        assert!(verify(&[SERVER], Some("localhost"), NOW).is_ok());
+        assert!(verify(&[SERVER], Some("LOCALHOST."), NOW).is_ok());
+        assert_eq!(verify(&[SERVER], Some("example.com"), NOW).unwrap_err().kind,
+                   BadCertificate);
+        assert_eq!(verify(&[SERVER], Some("127.0.0.1"), NOW).unwrap_err().kind,
+                   BadCertificate);
+        assert_eq!(verify(&[SERVER], None, NOW).unwrap_err().kind, BadCertificate);
+        assert_eq!(verify(&[], Some("localhost"), NOW).unwrap_err().kind, BadCertificate);
+
+        // the CA certificate is valid from 2026-10-18
+        assert_eq!(verify(&[SERVER, CA], Some("localhost"), 0).unwrap_err().kind,
+                   CertificateExpired);
+
+        let mut bad = SERVER.to_vec();
+        let len = bad.len();
+        bad[len - 10] ^= 1;
+        assert_eq!(verify(&[&bad], Some("localhost"), NOW).unwrap_err().kind, UnknownCa);
+
+        let untrusted = WebPkiVerifier::new(RootStore::new());
+        let err = untrusted.verify_server_cert(&list(&[SERVER, CA]), Some("localhost"), NOW)
+                           .unwrap_err();
+        assert_eq!(err.kind, UnknownCa);
+        // extra certificates are ignored
+        assert!(verify(&[SERVER, RESPONDER, CA], Some("localhost"), NOW).is_ok());
+        let chain = list(&[SERVER, RESPONDER, CA]);
+        let err = untrusted.verify_server_cert(&chain, Some("localhost"), NOW).unwrap_err();
+        assert_eq!(err.kind, UnknownCa);
+        // a directly trusted leaf
+        let pinned = WebPkiVerifier::new(roots(&[SERVER]));
+        assert!(pinned.verify_server_cert(&list(&[SERVER]), Some("localhost"), NOW).is_ok());
+    }
+
+    #[test]
+    fn test_build_path() {
+        use super::CertSource::{Chain, Intermediate, Anchor};
+        use super::KeyPurpose::ServerAuth;
+
+        let verifier = WebPkiVerifier::new(roots(&[CA]));
+        let sources = |verifier: &WebPkiVerifier, chain: &[&[u8]]| {
+            verifier.build_path(chain, ServerAuth, NOW).map(|path| {
+                path.iter().map(|&(source, _)| source).collect::<Vec<_>>()
+            })
+        };
+
+        assert_eq!(sources(&verifier, &[LEAF, INTER]).unwrap(), vec![Chain(0), Chain(1), Anchor]);
+        // unordered, with unrelated certificates
+        assert_eq!(sources(&verifier, &[LEAF, ROOT_B_CROSS, SERVER, INTER]).unwrap(),
+                   vec![Chain(0), Chain(3), Anchor]);
+        let path = verifier.build_path(&[LEAF, INTER], ServerAuth, NOW).unwrap();
+        assert_eq!(path.iter().map(|&(_, der)| der).collect::<Vec<_>>(), vec![LEAF, INTER, CA]);
+
+        // missing intermediate
+        assert_eq!(sources(&verifier, &[LEAF]).unwrap_err().kind, UnknownCa);
+        let mut cached = WebPkiVerifier::new(roots(&[CA]));
+        cached.intermediates = roots(&[INTER]);
+        assert_eq!(sources(&cached, &[LEAF]).unwrap(), vec![Chain(0), Intermediate, Anchor]);
+        assert_eq!(sources(&cached, &[LEAF, INTER]).unwrap(), vec![Chain(0), Chain(1), Anchor]);
+
+        // an expired intermediate is skipped if there is another one
+        assert_eq!(sources(&verifier, &[LEAF, INTER_EXPIRED]).unwrap_err().kind,
+                   CertificateExpired);
+        assert_eq!(sources(&verifier, &[LEAF, INTER_EXPIRED, INTER]).unwrap(),
+                   vec![Chain(0), Chain(2), Anchor]);
+        // issued by a certificate which is not a CA
+        assert_eq!(sources(&verifier, &[LEAF_BY_LEAF, LEAF, INTER]).unwrap_err().kind,
+                   BadCertificate);
+
+        // root B cross-signed by root A
+        let chain = [LEAF_B, INTER_B, ROOT_B_CROSS];
+        assert_eq!(sources(&verifier, &chain).unwrap(),
+                   vec![Chain(0), Chain(1), Chain(2), Anchor]);
+        assert_eq!(sources(&verifier, &[LEAF_B, INTER_B, ROOT_B, ROOT_B_CROSS]).unwrap(),
+                   vec![Chain(0), Chain(1), Chain(3), Anchor]);
+        let root_b = WebPkiVerifier::new(roots(&[ROOT_B]));
+        assert_eq!(sources(&root_b, &chain).unwrap(), vec![Chain(0), Chain(1), Anchor]);
+        let both = WebPkiVerifier::new(roots(&[CA, ROOT_B]));
+        let path = both.build_path(&chain, ServerAuth, NOW).unwrap();
+        assert_eq!(path.last().unwrap().1, ROOT_B);
+
+        // the search is bounded
+        let mut many: Vec<&[u8]> = vec![LEAF_B];
+        for _ in 0..10 {
+            many.push(INTER_B);
+            many.push(ROOT_B_CROSS);
+        }
+        let untrusted = WebPkiVerifier::new(RootStore::new());
+        let err = untrusted.build_path(&many, ServerAuth, NOW).unwrap_err();
+        assert!(err.desc.contains("too many"));
+        assert!(verifier.build_path(&many, ServerAuth, NOW).is_ok());
+    }
+
+    #[test]
+    fn test_dns_name_in_subtree() {
+        assert!(dns_name_in_subtree(b"permitted.example", b"permitted.example"));
+        assert!(dns_name_in_subtree(b"www.Permitted.example.", b"permitted.example"));
+        assert!(dns_name_in_subtree(b"*.permitted.example", b"permitted.example"));
+        assert!(!dns_name_in_subtree(b"notpermitted.example", b"permitted.example"));
+        assert!(!dns_name_in_subtree(b"permitted.example.com", b"permitted.example"));
+        assert!(!dns_name_in_subtree(b"example", b"permitted.example"));
+        // subdomains only
+        assert!(dns_name_in_subtree(b"www.permitted.example", b".permitted.example"));
+        assert!(!dns_name_in_subtree(b"permitted.example", b".permitted.example"));
+        assert!(!dns_name_in_subtree(b"notpermitted.example", b".permitted.example"));
+        // everything
+        assert!(dns_name_in_subtree(b"example", b""));
+
+        assert_eq!(uri_host(b"https://www.example/path"), Some(&b"www.example"[..]));
+        assert_eq!(uri_host(b"https://user@www.example:8443?q"), Some(&b"www.example"[..]));
+        assert_eq!(uri_host(b"https://[::1]/"), None);
+        assert_eq!(uri_host(b"mailto:user@example"), None);
+        assert_eq!(uri_host(b"file:///etc"), None);
+    }
+
+    #[test]
+    fn test_path_constraints() {
+        let verifier = WebPkiVerifier::new(roots(&[CA]));
+        let verify = |chain: &[&[u8]], purpose| verifier.verify_chain(chain, purpose, NOW);
+        let server = KeyPurpose::ServerAuth;
+        let client = KeyPurpose::ClientAuth;
+
+        // name constraints
+        assert!(verify(&[NC_OK, NC_INTER], server).is_ok());
+        let rejected = [NC_DNS, NC_DNS_EXCLUDED, NC_IP, NC_IP_EXCLUDED, NC_DIR, NC_URI];
+        for &leaf in rejected.iter() {
+            let err = verify(&[leaf, NC_INTER], server).unwrap_err();
+            assert_eq!(err.kind, BadCertificate);
+            assert!(err.desc.contains("name constraints"), "{}", err.desc);
+        }
+        // unsupported constraint forms reject the path
+        assert_eq!(verify(&[NC_EMAIL_LEAF, NC_EMAIL_INTER], server).unwrap_err().kind,
+                   BadCertificate);
+
+        // extended key usage
+        assert!(verify(&[LEAF_SERVER, INTER], server).is_ok());
+        assert_eq!(verify(&[LEAF_SERVER, INTER], client).unwrap_err().kind, BadCertificate);
+        assert!(verify(&[LEAF_CLIENT, INTER], client).is_ok());
+        assert_eq!(verify(&[LEAF_CLIENT, INTER], server).unwrap_err().kind, BadCertificate);
+        // no extended key usage
+        assert!(verify(&[LEAF, INTER], client).is_ok());
+        assert_eq!(verify(&[LEAF_SERVER_BY_CLIENT_CA, INTER_CLIENT], server).unwrap_err().kind,
+                   BadCertificate);
+
+        // path length constraint
+        assert!(verify(&[LEAF_LEN0, INTER_LEN0], server).is_ok());
+        assert_eq!(verify(&[LEAF_SUB_LEN0, INTER_SUB_LEN0, INTER_LEN0], server).unwrap_err().kind,
+                   BadCertificate);
+
+        // unknown critical extensions
+        assert_eq!(verify(&[LEAF_CRITICAL, INTER], server).unwrap_err().kind, BadCertificate);
+        assert_eq!(verify(&[LEAF_BY_CRITICAL, INTER_CRITICAL], server).unwrap_err().kind,
+                   BadCertificate);
+
+        // another path is tried if one violates constraints
+        let path = verifier.build_path(&[NC_DNS, NC_INTER, NC_INTER_UNCONSTRAINED], server, NOW)
+                           .unwrap();
+        assert_eq!(path[1].1, NC_INTER_UNCONSTRAINED);
+        // constraints of anchors are not applied
+        let constrained = WebPkiVerifier::new(roots(&[NC_INTER]));
+        assert!(constrained.verify_chain(&[NC_DNS], server, NOW).is_ok());
+    }
+
+    #[test]
+    fn test_client_cert_verifier() {
+        let verifier = WebPkiVerifier::new(roots(&[CA]));
+        let issuers = verifier.acceptable_issuers();
+        assert_eq!(issuers.len(), 1);
+        assert!(verifier.verify_client_cert(&list(&[CLIENT]), NOW).is_ok());
+        assert!(verifier.verify_client_cert(&list(&[]), NOW).is_err());
+    }
+}
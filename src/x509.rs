@@ -0,0 +1,733 @@
+//! X.509 certificates (RFC 5280).
+//!
+//! Certificates are parsed without copying: fields borrow from the DER data.
+//! Names and public keys are kept as raw DER so that they can be compared bytewise.
+
+use crypto::ecdsa;
+use crypto::ed25519;
+use crypto::hash::{self, HashFunction};
+use crypto::p256::NPoint256;
+use crypto::p384::{P384, NPoint384};
+use crypto::rsa::RsaPublicKey;
+use der::{self, Reader};
+use tls_result::TlsResult;
+use tls_result::TlsErrorKind::{DecodeError, DecryptError};
+
+pub mod oid {
+    //! Contents of object identifiers used in this crate.
+
+    pub const RSA_ENCRYPTION: &'static [u8] = b"\x2a\x86\x48\x86\xf7\x0d\x01\x01\x01";
+    pub const SHA256_WITH_RSA: &'static [u8] = b"\x2a\x86\x48\x86\xf7\x0d\x01\x01\x0b";
+    pub const SHA384_WITH_RSA: &'static [u8] = b"\x2a\x86\x48\x86\xf7\x0d\x01\x01\x0c";
+    pub const SHA512_WITH_RSA: &'static [u8] = b"\x2a\x86\x48\x86\xf7\x0d\x01\x01\x0d";
+
+    pub const EC_PUBLIC_KEY: &'static [u8] = b"\x2a\x86\x48\xce\x3d\x02\x01";
+    pub const SECP256R1: &'static [u8] = b"\x2a\x86\x48\xce\x3d\x03\x01\x07";
+    pub const SECP384R1: &'static [u8] = b"\x2b\x81\x04\x00\x22";
+    pub const ECDSA_WITH_SHA256: &'static [u8] = b"\x2a\x86\x48\xce\x3d\x04\x03\x02";
+    pub const ECDSA_WITH_SHA384: &'static [u8] = b"\x2a\x86\x48\xce\x3d\x04\x03\x03";
+    pub const ECDSA_WITH_SHA512: &'static [u8] = b"\x2a\x86\x48\xce\x3d\x04\x03\x04";
+    pub const ED25519: &'static [u8] = b"\x2b\x65\x70";
+
+    pub const SHA1: &'static [u8] = b"\x2b\x0e\x03\x02\x1a";
+    pub const SHA256: &'static [u8] = b"\x60\x86\x48\x01\x65\x03\x04\x02\x01";
+
+    pub const KEY_USAGE: &'static [u8] = b"\x55\x1d\x0f";
+    pub const SUBJECT_ALT_NAME: &'static [u8] = b"\x55\x1d\x11";
+    pub const BASIC_CONSTRAINTS: &'static [u8] = b"\x55\x1d\x13";
+    pub const NAME_CONSTRAINTS: &'static [u8] = b"\x55\x1d\x1e";
+    pub const CERTIFICATE_POLICIES: &'static [u8] = b"\x55\x1d\x20";
+    pub const EXTENDED_KEY_USAGE: &'static [u8] = b"\x55\x1d\x25";
+    pub const KP_SERVER_AUTH: &'static [u8] = b"\x2b\x06\x01\x05\x05\x07\x03\x01";
+    pub const KP_CLIENT_AUTH: &'static [u8] = b"\x2b\x06\x01\x05\x05\x07\x03\x02";
+    pub const KP_OCSP_SIGNING: &'static [u8] = b"\x2b\x06\x01\x05\x05\x07\x03\x09";
+    pub const TLS_FEATURE: &'static [u8] = b"\x2b\x06\x01\x05\x05\x07\x01\x18";
+    pub const CT_PRECERT_SCTS: &'static [u8] = b"\x2b\x06\x01\x04\x01\xd6\x79\x02\x04\x02";
+}
+
+// KeyUsage bits (RFC 5280 4.2.1.3), as returned by `Certificate::key_usage`.
+pub const KEY_USAGE_DIGITAL_SIGNATURE: u16 = 1 << 0;
+pub const KEY_USAGE_KEY_ENCIPHERMENT: u16 = 1 << 2;
+pub const KEY_USAGE_KEY_CERT_SIGN: u16 = 1 << 5;
+pub const KEY_USAGE_CRL_SIGN: u16 = 1 << 6;
+
+pub struct AlgorithmIdentifier<'a> {
+    /// contents of the OID
+    pub algorithm: &'a [u8],
+    /// raw DER of the parameters, if any
+    pub parameters: Option<&'a [u8]>,
+}
+
+impl<'a> AlgorithmIdentifier<'a> {
+    pub fn parse(reader: &mut Reader<'a>) -> TlsResult<AlgorithmIdentifier<'a>> {
+        let mut seq = try!(reader.read_nested(der::SEQUENCE));
+        let algorithm = try!(seq.read(der::OID));
+        let parameters = if seq.is_empty() {
+            None
+        } else {
+            Some(try!(seq.read_any()).raw)
+        };
+        try!(seq.finish());
+        Ok(AlgorithmIdentifier {
+            algorithm: algorithm,
+            parameters: parameters,
+        })
+    }
+}
+
+pub enum PublicKey {
+    Rsa(RsaPublicKey),
+    EcP256(NPoint256),
+    EcP384(NPoint384),
+    Ed25519(Vec<u8>),
+}
+
+impl PublicKey {
+    /// Parses DER `SubjectPublicKeyInfo`.
+    pub fn parse(spki: &[u8]) -> TlsResult<PublicKey> {
+        let mut reader = Reader::new(spki);
+        let mut seq = try!(reader.read_nested(der::SEQUENCE));
+        try!(reader.finish());
+        let algorithm = try!(AlgorithmIdentifier::parse(&mut seq));
+        let key = try!(seq.read_bit_string());
+        try!(seq.finish());
+
+        match algorithm.algorithm {
+            oid::RSA_ENCRYPTION => {
+                if algorithm.parameters != Some(&[der::NULL, 0][..]) {
+                    return tls_err!(DecodeError, "bad RSA key parameters");
+                }
+                let mut reader = Reader::new(key);
+                let mut seq = try!(reader.read_nested(der::SEQUENCE));
+                try!(reader.finish());
+                let n = try!(seq.read_unsigned());
+                let e = try!(seq.read_unsigned());
+                try!(seq.finish());
+                match RsaPublicKey::new(n, e) {
+                    Some(key) => Ok(PublicKey::Rsa(key)),
+                    None => tls_err!(DecryptError, "unsupported RSA key"),
+                }
+            }
+            oid::EC_PUBLIC_KEY => {
+                let curve = match algorithm.parameters {
+                    Some(params) => {
+                        let mut reader = Reader::new(params);
+                        let curve = try!(reader.read(der::OID));
+                        try!(reader.finish());
+                        curve
+                    }
+                    None => return tls_err!(DecodeError, "EC key without named curve"),
+                };
+                let key = match curve {
+                    oid::SECP256R1 => NPoint256::from_uncompressed_bytes(key)
+                                               .map(PublicKey::EcP256),
+                    oid::SECP384R1 => P384::new().from_uncompressed_bytes(key)
+                                                 .map(PublicKey::EcP384),
+                    _ => return tls_err!(DecryptError, "unsupported curve"),
+                };
+                match key {
+                    Some(key) => Ok(key),
+                    None => tls_err!(DecodeError, "bad EC public key"),
+                }
+            }
+            oid::ED25519 => {
+                // RFC 8410 3: parameters are absent
+                if algorithm.parameters.is_some() {
+                    return tls_err!(DecodeError, "bad Ed25519 key parameters");
+                }
+                if key.len() != ed25519::PUBLIC_KEY_LEN {
+                    return tls_err!(DecodeError, "bad Ed25519 public key");
+                }
+                Ok(PublicKey::Ed25519(key.to_vec()))
+            }
+            _ => tls_err!(DecryptError, "unsupported public key algorithm"),
+        }
+    }
+
+    /// Verifies `signature` of `msg`. `signature` is a raw RSA signature,
+    /// a DER `ECDSA-Sig-Value` or a raw Ed25519 signature.
+    /// `hash` is ignored for Ed25519, which hashes `msg` by itself.
+    pub fn verify(&self, hash: &HashFunction, msg: &[u8], signature: &[u8]) -> TlsResult<()> {
+        let valid = match *self {
+            PublicKey::Rsa(ref key) => key.verify_pkcs1(hash, msg, signature),
+            PublicKey::EcP256(ref point) => {
+                let (r, s) = try!(parse_ecdsa_signature(signature));
+                ecdsa::verify_p256(point, &(hash.digest)(msg), r, s)
+            }
+            PublicKey::EcP384(ref point) => {
+                let (r, s) = try!(parse_ecdsa_signature(signature));
+                ecdsa::verify_p384(point, &(hash.digest)(msg), r, s)
+            }
+            PublicKey::Ed25519(ref key) => ed25519::verify(key, msg, signature),
+        };
+        if !valid {
+            return tls_err!(DecryptError, "bad signature");
+        }
+        Ok(())
+    }
+
+    /// Verifies RSASSA-PSS `signature` of `msg` with MGF1 over `hash` and a salt as long
+    /// as the hash (RFC 8446 4.2.3). The key must be an RSA key.
+    pub fn verify_pss(&self, hash: &HashFunction, msg: &[u8], signature: &[u8]) -> TlsResult<()> {
+        let valid = match *self {
+            PublicKey::Rsa(ref key) => key.verify_pss(hash, msg, signature),
+            _ => return tls_err!(DecryptError, "RSA-PSS needs an RSA key"),
+        };
+        if !valid {
+            return tls_err!(DecryptError, "bad signature");
+        }
+        Ok(())
+    }
+}
+
+// returns `(r, s)` of DER `ECDSA-Sig-Value`.
+fn parse_ecdsa_signature(signature: &[u8]) -> TlsResult<(&[u8], &[u8])> {
+    let mut reader = Reader::new(signature);
+    let mut seq = try!(reader.read_nested(der::SEQUENCE));
+    try!(reader.finish());
+    let r = try!(seq.read_unsigned());
+    let s = try!(seq.read_unsigned());
+    try!(seq.finish());
+    Ok((r, s))
+}
+
+/// Verifies `signature` of `data` made by the owner of `spki` (raw DER `SubjectPublicKeyInfo`)
+/// with X.509 signature `algorithm`. SHA-1 signatures are rejected.
+pub fn verify_signed_data(spki: &[u8],
+                          algorithm: &AlgorithmIdentifier,
+                          data: &[u8],
+                          signature: &[u8])
+                          -> TlsResult<()> {
+    // RFC 8410 6: no hash, and parameters are absent
+    if algorithm.algorithm == oid::ED25519 {
+        if algorithm.parameters.is_some() {
+            return tls_err!(DecodeError, "bad signature algorithm parameters");
+        }
+        let key = try!(PublicKey::parse(spki));
+        if let PublicKey::Ed25519(_) = key {
+            return key.verify(&hash::SHA512, data, signature);
+        }
+        return tls_err!(DecryptError, "signature algorithm does not match the key");
+    }
+
+    let (hash, is_rsa): (&HashFunction, bool) = match algorithm.algorithm {
+        oid::SHA256_WITH_RSA => (&hash::SHA256, true),
+        oid::SHA384_WITH_RSA => (&hash::SHA384, true),
+        oid::SHA512_WITH_RSA => (&hash::SHA512, true),
+        oid::ECDSA_WITH_SHA256 => (&hash::SHA256, false),
+        oid::ECDSA_WITH_SHA384 => (&hash::SHA384, false),
+        oid::ECDSA_WITH_SHA512 => (&hash::SHA512, false),
+        _ => return tls_err!(DecryptError, "unsupported signature algorithm"),
+    };
+    // RFC 4055 and RFC 5758: NULL for RSA, absent for ECDSA
+    // (absent RSA parameters are tolerated as many encoders emit them)
+    let params_ok = match algorithm.parameters {
+        None => true,
+        Some(params) => is_rsa && params == &[der::NULL, 0][..],
+    };
+    if !params_ok {
+        return tls_err!(DecodeError, "bad signature algorithm parameters");
+    }
+
+    let key = try!(PublicKey::parse(spki));
+    match (&key, is_rsa) {
+        (&PublicKey::Rsa(_), true) |
+        (&PublicKey::EcP256(_), false) |
+        (&PublicKey::EcP384(_), false) => {}
+        _ => return tls_err!(DecryptError, "signature algorithm does not match the key"),
+    }
+    key.verify(hash, data, signature)
+}
+
+pub struct BasicConstraints {
+    pub ca: bool,
+    pub path_len_constraint: Option<u64>,
+}
+
+/// `GeneralName` (RFC 5280 4.2.1.6).
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum GeneralName<'a> {
+    DnsName(&'a [u8]),
+    /// raw DER of `Name`
+    DirectoryName(&'a [u8]),
+    Uri(&'a [u8]),
+    /// 4 bytes for IPv4, 16 bytes for IPv6
+    IpAddress(&'a [u8]),
+    /// other forms, with the tag
+    Other(u8, &'a [u8]),
+}
+
+fn parse_general_name<'a>(reader: &mut Reader<'a>) -> TlsResult<GeneralName<'a>> {
+    let tlv = try!(reader.read_any());
+    let name = match tlv.tag {
+        0x82 => GeneralName::DnsName(tlv.value),
+        0xa4 => {
+            let mut reader = Reader::new(tlv.value);
+            let name = try!(reader.read_raw(der::SEQUENCE));
+            try!(reader.finish());
+            GeneralName::DirectoryName(name)
+        }
+        0x86 => GeneralName::Uri(tlv.value),
+        0x87 => GeneralName::IpAddress(tlv.value),
+        tag => GeneralName::Other(tag, tlv.value),
+    };
+    Ok(name)
+}
+
+/// Parses `GeneralNames`.
+pub fn parse_general_names<'a>(reader: &mut Reader<'a>) -> TlsResult<Vec<GeneralName<'a>>> {
+    let mut seq = try!(reader.read_nested(der::SEQUENCE));
+    let mut names = Vec::new();
+    while !seq.is_empty() {
+        names.push(try!(parse_general_name(&mut seq)));
+    }
+    if names.is_empty() {
+        return tls_err!(DecodeError, "empty GeneralNames");
+    }
+    Ok(names)
+}
+
+/// Name constraints extension (RFC 5280 4.2.1.10). For `IpAddress`, the value is
+/// an address followed by a mask, 8 bytes for IPv4 and 32 bytes for IPv6.
+pub struct NameConstraints<'a> {
+    pub permitted: Vec<GeneralName<'a>>,
+    pub excluded: Vec<GeneralName<'a>>,
+}
+
+// `GeneralSubtrees`. `minimum` and `maximum` must be absent (RFC 5280 4.2.1.10).
+fn parse_general_subtrees<'a>(value: &'a [u8]) -> TlsResult<Vec<GeneralName<'a>>> {
+    let mut subtrees = Reader::new(value);
+    let mut names = Vec::new();
+    while !subtrees.is_empty() {
+        let mut subtree = try!(subtrees.read_nested(der::SEQUENCE));
+        names.push(try!(parse_general_name(&mut subtree)));
+        if !subtree.is_empty() {
+            return tls_err!(DecodeError, "name constraints with minimum or maximum");
+        }
+    }
+    if names.is_empty() {
+        return tls_err!(DecodeError, "empty GeneralSubtrees");
+    }
+    Ok(names)
+}
+
+pub struct Extension<'a> {
+    /// contents of the OID
+    pub id: &'a [u8],
+    pub critical: bool,
+    /// contents of `extnValue` OCTET STRING
+    pub value: &'a [u8],
+}
+
+/// Parses `Extensions`. Duplicate extensions are rejected (RFC 5280 4.2).
+pub fn parse_extensions<'a>(reader: &mut Reader<'a>) -> TlsResult<Vec<Extension<'a>>> {
+    let mut exts = try!(reader.read_nested(der::SEQUENCE));
+    let mut extensions = Vec::new();
+    while !exts.is_empty() {
+        let mut ext = try!(exts.read_nested(der::SEQUENCE));
+        let id = try!(ext.read(der::OID));
+        let critical = if ext.peek_tag() == Some(der::BOOLEAN) {
+            try!(ext.read_bool())
+        } else {
+            false
+        };
+        let value = try!(ext.read(der::OCTET_STRING));
+        try!(ext.finish());
+
+        if extensions.iter().any(|e: &Extension| e.id == id) {
+            return tls_err!(DecodeError, "duplicate extension");
+        }
+        extensions.push(Extension {
+            id: id,
+            critical: critical,
+            value: value,
+        });
+    }
+    Ok(extensions)
+}
+
+/// Parses UTCTime or GeneralizedTime into seconds since the Unix epoch.
+/// Only `YYMMDDHHMMSSZ` and `YYYYMMDDHHMMSSZ` forms are allowed (RFC 5280 4.1.2.5).
+pub fn parse_time(tag: u8, value: &[u8]) -> TlsResult<i64> {
+    let digits_len = match tag {
+        der::UTC_TIME => 12,
+        der::GENERALIZED_TIME => 14,
+        _ => return tls_err!(DecodeError, "unexpected time tag: {}", tag),
+    };
+    if value.len() != digits_len + 1 || value[digits_len] != b'Z' {
+        return tls_err!(DecodeError, "bad time format");
+    }
+    let mut digits = Vec::with_capacity(digits_len);
+    for &c in value[..digits_len].iter() {
+        if c < b'0' || c > b'9' {
+            return tls_err!(DecodeError, "bad time format");
+        }
+        digits.push((c - b'0') as i64);
+    }
+    let num = |i: usize| digits[i] * 10 + digits[i + 1];
+
+    let (year, rest) = if tag == der::UTC_TIME {
+        let yy = num(0);
+        (if yy >= 50 { 1900 + yy } else { 2000 + yy }, 2)
+    } else {
+        (num(0) * 100 + num(2), 4)
+    };
+    let month = num(rest);
+    let day = num(rest + 2);
+    let hour = num(rest + 4);
+    let minute = num(rest + 6);
+    let second = num(rest + 8);
+
+    if month < 1 || month > 12 || day < 1 || day > 31 || hour > 23 || minute > 59 ||
+       second > 59 {
+        return tls_err!(DecodeError, "bad time value");
+    }
+
+    // days from 1970-01-01 (http://howardhinnant.github.io/date_algorithms.html)
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    Ok(days * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+pub fn read_time(reader: &mut Reader) -> TlsResult<i64> {
+    let tlv = try!(reader.read_any());
+    parse_time(tlv.tag, tlv.value)
+}
+
+pub struct Certificate<'a> {
+    /// raw DER of `TBSCertificate`, which is signed by the issuer
+    pub tbs_certificate: &'a [u8],
+    /// 0 for v1, 2 for v3
+    pub version: u64,
+    pub serial_number: &'a [u8],
+    pub signature: AlgorithmIdentifier<'a>,
+    /// raw DER of the issuer `Name`
+    pub issuer: &'a [u8],
+    pub not_before: i64,
+    pub not_after: i64,
+    /// raw DER of the subject `Name`
+    pub subject: &'a [u8],
+    /// raw DER of `SubjectPublicKeyInfo`
+    pub subject_public_key_info: &'a [u8],
+    pub extensions: Vec<Extension<'a>>,
+    pub signature_algorithm: AlgorithmIdentifier<'a>,
+    pub signature_value: &'a [u8],
+}
+
+impl<'a> Certificate<'a> {
+    pub fn parse(data: &'a [u8]) -> TlsResult<Certificate<'a>> {
+        let mut reader = Reader::new(data);
+        let mut cert = try!(reader.read_nested(der::SEQUENCE));
+        try!(reader.finish());
+
+        let tbs_certificate = try!(cert.read_raw(der::SEQUENCE));
+        let signature_algorithm = try!(AlgorithmIdentifier::parse(&mut cert));
+        let signature_value = try!(cert.read_bit_string());
+        try!(cert.finish());
+
+        let mut tbs = try!(Reader::new(tbs_certificate).read_nested(der::SEQUENCE));
+        let version = match try!(tbs.read_optional(der::context(0))) {
+            Some(version) => {
+                let mut version = Reader::new(version);
+                let v = try!(version.read_small_unsigned());
+                try!(version.finish());
+                v
+            }
+            None => 0,
+        };
+        if version > 2 {
+            return tls_err!(DecodeError, "unknown certificate version: {}", version);
+        }
+        let serial_number = try!(tbs.read(der::INTEGER));
+        let signature = try!(AlgorithmIdentifier::parse(&mut tbs));
+        let issuer = try!(tbs.read_raw(der::SEQUENCE));
+        let (not_before, not_after) = {
+            let mut validity = try!(tbs.read_nested(der::SEQUENCE));
+            let not_before = try!(read_time(&mut validity));
+            let not_after = try!(read_time(&mut validity));
+            try!(validity.finish());
+            (not_before, not_after)
+        };
+        let subject = try!(tbs.read_raw(der::SEQUENCE));
+        let subject_public_key_info = try!(tbs.read_raw(der::SEQUENCE));
+
+        // issuerUniqueID, subjectUniqueID
+        try!(tbs.read_optional(der::context_primitive(1)));
+        try!(tbs.read_optional(der::context_primitive(2)));
+
+        let extensions = match try!(tbs.read_optional(der::context(3))) {
+            Some(exts) => {
+                if version != 2 {
+                    return tls_err!(DecodeError, "extensions in v1 certificate");
+                }
+                let mut reader = Reader::new(exts);
+                let extensions = try!(parse_extensions(&mut reader));
+                try!(reader.finish());
+                extensions
+            }
+            None => Vec::new(),
+        };
+        try!(tbs.finish());
+
+        Ok(Certificate {
+            tbs_certificate: tbs_certificate,
+            version: version,
+            serial_number: serial_number,
+            signature: signature,
+            issuer: issuer,
+            not_before: not_before,
+            not_after: not_after,
+            subject: subject,
+            subject_public_key_info: subject_public_key_info,
+            extensions: extensions,
+            signature_algorithm: signature_algorithm,
+            signature_value: signature_value,
+        })
+    }
+
+    /// Finds an extension by its OID contents.
+    pub fn extension(&self, id: &[u8]) -> Option<&Extension<'a>> {
+        self.extensions.iter().find(|ext| ext.id == id)
+    }
+
+    /// Returns the basic constraints extension, if present.
+    pub fn basic_constraints(&self) -> TlsResult<Option<BasicConstraints>> {
+        let ext = match self.extension(oid::BASIC_CONSTRAINTS) {
+            Some(ext) => ext,
+            None => return Ok(None),
+        };
+        let mut reader = Reader::new(ext.value);
+        let mut seq = try!(reader.read_nested(der::SEQUENCE));
+        try!(reader.finish());
+        let ca = if seq.peek_tag() == Some(der::BOOLEAN) {
+            try!(seq.read_bool())
+        } else {
+            false
+        };
+        let path_len_constraint = if seq.is_empty() {
+            None
+        } else {
+            Some(try!(seq.read_small_unsigned()))
+        };
+        try!(seq.finish());
+        Ok(Some(BasicConstraints {
+            ca: ca,
+            path_len_constraint: path_len_constraint,
+        }))
+    }
+
+    /// Returns names of the subject alternative name extension, if present.
+    pub fn subject_alt_names(&self) -> TlsResult<Option<Vec<GeneralName<'a>>>> {
+        match self.extension(oid::SUBJECT_ALT_NAME) {
+            Some(ext) => {
+                let mut reader = Reader::new(ext.value);
+                let names = try!(parse_general_names(&mut reader));
+                try!(reader.finish());
+                Ok(Some(names))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the key usage extension, if present. Bit `n` of the result is
+    /// the `n`-th named bit, e.g. `KEY_USAGE_CRL_SIGN`.
+    pub fn key_usage(&self) -> TlsResult<Option<u16>> {
+        let ext = match self.extension(oid::KEY_USAGE) {
+            Some(ext) => ext,
+            None => return Ok(None),
+        };
+        let mut reader = Reader::new(ext.value);
+        let bits = try!(reader.read(der::BIT_STRING));
+        try!(reader.finish());
+        if bits.len() < 2 || bits.len() > 3 || bits[0] > 7 {
+            return tls_err!(DecodeError, "bad key usage");
+        }
+        let mut usage = 0u16;
+        for (i, &byte) in bits[1..].iter().enumerate() {
+            for j in 0..8 {
+                if byte & (0x80 >> j) != 0 {
+                    usage |= 1 << (i * 8 + j);
+                }
+            }
+        }
+        Ok(Some(usage))
+    }
+
+    /// Returns the name constraints extension, if present.
+    pub fn name_constraints(&self) -> TlsResult<Option<NameConstraints<'a>>> {
+        let ext = match self.extension(oid::NAME_CONSTRAINTS) {
+            Some(ext) => ext,
+            None => return Ok(None),
+        };
+        let mut reader = Reader::new(ext.value);
+        let mut seq = try!(reader.read_nested(der::SEQUENCE));
+        try!(reader.finish());
+        let permitted = match try!(seq.read_optional(der::context(0))) {
+            Some(value) => try!(parse_general_subtrees(value)),
+            None => Vec::new(),
+        };
+        let excluded = match try!(seq.read_optional(der::context(1))) {
+            Some(value) => try!(parse_general_subtrees(value)),
+            None => Vec::new(),
+        };
+        try!(seq.finish());
+        if permitted.is_empty() && excluded.is_empty() {
+            return tls_err!(DecodeError, "empty name constraints");
+        }
+        Ok(Some(NameConstraints {
+            permitted: permitted,
+            excluded: excluded,
+        }))
+    }
+
+    /// Returns key purpose OIDs of the extended key usage extension, if present.
+    pub fn extended_key_usage(&self) -> TlsResult<Option<Vec<&'a [u8]>>> {
+        let ext = match self.extension(oid::EXTENDED_KEY_USAGE) {
+            Some(ext) => ext,
+            None => return Ok(None),
+        };
+        let mut reader = Reader::new(ext.value);
+        let mut seq = try!(reader.read_nested(der::SEQUENCE));
+        try!(reader.finish());
+        let mut purposes = Vec::new();
+        while !seq.is_empty() {
+            purposes.push(try!(seq.read(der::OID)));
+        }
+        if purposes.is_empty() {
+            return tls_err!(DecodeError, "empty extended key usage");
+        }
+        Ok(Some(purposes))
+    }
+
+    /// Checks whether this certificate is signed by `issuer`.
+    pub fn verify_signature(&self, issuer: &Certificate) -> TlsResult<()> {
+        if self.signature.algorithm != self.signature_algorithm.algorithm ||
+           self.signature.parameters != self.signature_algorithm.parameters {
+            return tls_err!(DecodeError, "mismatched signature algorithms");
+        }
+        verify_signed_data(issuer.subject_public_key_info,
+                           &self.signature_algorithm,
+                           self.tbs_certificate,
+                           self.signature_value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Certificate, PublicKey, GeneralName, parse_time, oid,
+                KEY_USAGE_DIGITAL_SIGNATURE, KEY_USAGE_KEY_CERT_SIGN, KEY_USAGE_CRL_SIGN};
+    use der;
+
+    static CA: &'static [u8] = include_bytes!("../testdata/test_ca.der");
+    static CLIENT: &'static [u8] = include_bytes!("../testdata/test_client.der");
+    static SERVER: &'static [u8] = include_bytes!("../testdata/test_server.der");
+    static RESPONDER: &'static [u8] = include_bytes!("../testdata/test_ocsp_responder.der");
+    static ED_INTER: &'static [u8] = include_bytes!("../testdata/pki/ed_inter.der");
+    static ED_LEAF: &'static [u8] = include_bytes!("../testdata/pki/ed_leaf.der");
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(parse_time(der::UTC_TIME, b"700101000000Z").unwrap(), 0);
+        assert_eq!(parse_time(der::UTC_TIME, b"491231235959Z").unwrap(), 2524607999);
+        assert_eq!(parse_time(der::GENERALIZED_TIME, b"20000229120000Z").unwrap(), 951825600);
+        assert!(parse_time(der::UTC_TIME, b"700101000000+0900").is_err());
+        assert!(parse_time(der::UTC_TIME, b"701301000000Z").is_err());
+        assert!(parse_time(der::GENERALIZED_TIME, b"700101000000Z").is_err());
+    }
+
+    #[test]
+    fn test_parse_certificate() {
+        let ca = Certificate::parse(CA).unwrap();
+        let client = Certificate::parse(CLIENT).unwrap();
+
+        assert_eq!(ca.version, 2);
+        assert_eq!(ca.serial_number, &[1]);
+        assert_eq!(client.serial_number, &[2]);
+        assert_eq!(ca.subject, ca.issuer);
+        assert_eq!(client.issuer, ca.subject);
+        assert!(client.not_before < client.not_after);
+
+        // ecdsa-with-SHA256
+        let ecdsa_sha256 = [0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+        assert_eq!(client.signature_algorithm.algorithm, &ecdsa_sha256);
+        assert_eq!(client.signature.algorithm, &ecdsa_sha256);
+
+        // basicConstraints
+        let basic_constraints = client.extension(&[0x55, 0x1d, 0x13]).unwrap();
+        assert!(basic_constraints.critical);
+
+        assert!(Certificate::parse(&CLIENT[..(CLIENT.len() - 1)]).is_err());
+        let mut trailing = CLIENT.to_vec();
+        trailing.push(0);
+        assert!(Certificate::parse(&trailing).is_err());
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let ca = Certificate::parse(CA).unwrap();
+        let server = Certificate::parse(SERVER).unwrap();
+        let responder = Certificate::parse(RESPONDER).unwrap();
+
+        // ECDSA P-256
+        ca.verify_signature(&ca).unwrap();
+        server.verify_signature(&ca).unwrap();
+        assert!(ca.verify_signature(&server).is_err());
+
+        match PublicKey::parse(server.subject_public_key_info).unwrap() {
+            PublicKey::Rsa(ref key) => assert_eq!(key.bit_len(), 2048),
+            _ => panic!("expected RSA key"),
+        }
+
+        let mut bad = SERVER.to_vec();
+        let len = bad.len();
+        bad[len - 10] ^= 1;
+        let bad = Certificate::parse(&bad).unwrap();
+        assert!(bad.verify_signature(&ca).is_err());
+
+        assert_eq!(ca.key_usage().unwrap(),
+                   Some(KEY_USAGE_KEY_CERT_SIGN | KEY_USAGE_CRL_SIGN));
+        // digitalSignature, keyEncipherment
+        assert_eq!(server.key_usage().unwrap(), Some(KEY_USAGE_DIGITAL_SIGNATURE | 1 << 2));
+
+        assert!(ca.basic_constraints().unwrap().unwrap().ca);
+        assert!(!server.basic_constraints().unwrap().unwrap().ca);
+        assert_eq!(server.subject_alt_names().unwrap().unwrap(),
+                   vec![GeneralName::DnsName(b"localhost")]);
+        assert!(ca.subject_alt_names().unwrap().is_none());
+
+        let eku = responder.extended_key_usage().unwrap().unwrap();
+        assert_eq!(eku, vec![oid::KP_OCSP_SIGNING]);
+        assert!(ca.extended_key_usage().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_verify_ed25519() {
+        let ca = Certificate::parse(CA).unwrap();
+        let inter = Certificate::parse(ED_INTER).unwrap();
+        let leaf = Certificate::parse(ED_LEAF).unwrap();
+
+        assert_eq!(leaf.signature_algorithm.algorithm, oid::ED25519);
+        assert!(leaf.signature_algorithm.parameters.is_none());
+        match PublicKey::parse(leaf.subject_public_key_info).unwrap() {
+            PublicKey::Ed25519(ref key) => assert_eq!(key.len(), 32),
+            _ => panic!("expected Ed25519 key"),
+        }
+
+        inter.verify_signature(&ca).unwrap();
+        leaf.verify_signature(&inter).unwrap();
+        assert!(leaf.verify_signature(&ca).is_err());
+        assert!(inter.verify_signature(&leaf).is_err());
+
+        let mut bad = ED_LEAF.to_vec();
+        let len = bad.len();
+        bad[len - 10] ^= 1;
+        let bad = Certificate::parse(&bad).unwrap();
+        assert!(bad.verify_signature(&inter).is_err());
+    }
+}
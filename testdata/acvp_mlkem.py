@@ -0,0 +1,60 @@
+#!/usr/bin/env python3
+# Extracts ML-KEM-768 test vectors from the NIST ACVP server (see README.md).
+# Usage: acvp_mlkem.py <ML-KEM-keyGen-FIPS203 internalProjection.json>
+#                      <ML-KEM-encapDecap-FIPS203 internalProjection.json>
+# Writes mlkem768_acvp.txt in this directory.
+
+import json
+import sys
+
+PARAMETER_SET = "ML-KEM-768"
+# number of keyGen and encapsulation cases kept. all decapsulation cases are kept.
+KEY_GEN_CASES = 3
+ENCAPS_CASES = 3
+
+
+def groups(path, function=None):
+    with open(path) as f:
+        data = json.load(f)
+    for group in data["testGroups"]:
+        if group["parameterSet"] != PARAMETER_SET:
+            continue
+        if function is not None and group["function"] != function:
+            continue
+        yield group
+
+
+def write_block(out, fields):
+    for name, value in fields:
+        out.write("%s = %s\n" % (name, value))
+    out.write("\n")
+
+
+def main(key_gen_path, encap_decap_path):
+    with open("mlkem768_acvp.txt", "w") as out:
+        out.write("# %s vectors of the NIST ACVP server, extracted by acvp_mlkem.py.\n"
+                  % PARAMETER_SET)
+        out.write("# Blocks without tcId hold parameters shared by the cases after them.\n\n")
+
+        out.write("[keyGen]\n\n")
+        for group in groups(key_gen_path):
+            for test in group["tests"][:KEY_GEN_CASES]:
+                write_block(out, [(name, test[name])
+                                  for name in ["tcId", "d", "z", "ek", "dk"]])
+
+        out.write("[encapsulation]\n\n")
+        for group in groups(encap_decap_path, "encapsulation"):
+            for test in group["tests"][:ENCAPS_CASES]:
+                write_block(out, [(name, test[name])
+                                  for name in ["tcId", "ek", "m", "c", "k"]])
+
+        out.write("[decapsulation]\n\n")
+        for group in groups(encap_decap_path, "decapsulation"):
+            write_block(out, [("dk", group["dk"])])
+            for test in group["tests"]:
+                write_block(out, [(name, test[name])
+                                  for name in ["tcId", "reason", "c", "k"]])
+
+
+if __name__ == "__main__":
+    main(sys.argv[1], sys.argv[2])
@@ -0,0 +1,118 @@
+#!/usr/bin/env python3
+# Generates Certificate Transparency fixtures (see README.md).
+# Requires the `cryptography` package. Run in this directory.
+
+import datetime
+import hashlib
+import struct
+
+from cryptography import x509
+from cryptography.hazmat.primitives import hashes, serialization
+from cryptography.hazmat.primitives.asymmetric import ec, padding, rsa
+from cryptography.x509.oid import NameOID
+
+# 2026-10-18 12:58:12 UTC, same as the OCSP responses
+TIMESTAMP_MS = 1792328292 * 1000
+SCT_LIST_OID = x509.ObjectIdentifier("1.3.6.1.4.1.11129.2.4.2")
+
+
+def load_key(path):
+    with open(path, "rb") as f:
+        return serialization.load_pem_private_key(f.read(), None)
+
+
+def save_key(path, key):
+    with open(path, "wb") as f:
+        f.write(key.private_bytes(serialization.Encoding.PEM,
+                                  serialization.PrivateFormat.PKCS8,
+                                  serialization.NoEncryption()))
+
+
+def spki(key):
+    return key.public_key().public_bytes(serialization.Encoding.DER,
+                                         serialization.PublicFormat.SubjectPublicKeyInfo)
+
+
+def u16_vec(data):
+    return struct.pack(">H", len(data)) + data
+
+
+def u24_vec(data):
+    return struct.pack(">I", len(data))[1:] + data
+
+
+def make_sct(log_key, entry):
+    # RFC 6962 3.2: v1, certificate_timestamp, timestamp, entry, no extensions
+    signed = b"\x00\x00" + struct.pack(">Q", TIMESTAMP_MS) + entry + u16_vec(b"")
+    if isinstance(log_key, ec.EllipticCurvePrivateKey):
+        sig = log_key.sign(signed, ec.ECDSA(hashes.SHA256()))
+        algorithm = b"\x04\x03"
+    else:
+        sig = log_key.sign(signed, padding.PKCS1v15(), hashes.SHA256())
+        algorithm = b"\x04\x01"
+    log_id = hashlib.sha256(spki(log_key)).digest()
+    return (b"\x00" + log_id + struct.pack(">Q", TIMESTAMP_MS) + u16_vec(b"") + algorithm +
+            u16_vec(sig))
+
+
+def sct_list(scts):
+    return u16_vec(b"".join(u16_vec(sct) for sct in scts))
+
+
+def x509_entry(cert_der):
+    return b"\x00\x00" + u24_vec(cert_der)
+
+
+def precert_entry(issuer_spki, tbs):
+    return b"\x00\x01" + hashlib.sha256(issuer_spki).digest() + u24_vec(tbs)
+
+
+def main():
+    ec_log = ec.generate_private_key(ec.SECP256R1())
+    rsa_log = rsa.generate_private_key(65537, 2048)
+    save_key("test_ct_log_ec.key", ec_log)
+    save_key("test_ct_log_rsa.key", rsa_log)
+    with open("test_ct_log_ec.der", "wb") as f:
+        f.write(spki(ec_log))
+    with open("test_ct_log_rsa.der", "wb") as f:
+        f.write(spki(rsa_log))
+
+    ca_key = load_key("test_ca.key")
+    server_key = load_key("test_server.key")
+    with open("test_ca.der", "rb") as f:
+        ca = x509.load_der_x509_certificate(f.read())
+    with open("test_server.der", "rb") as f:
+        server_der = f.read()
+
+    # SCTs for `test_server.der`, to be sent in the TLS extension
+    with open("sct_list_server.bin", "wb") as f:
+        f.write(sct_list([make_sct(ec_log, x509_entry(server_der))]))
+
+    # `test_ct_embedded.der`: the precertificate is the final certificate without
+    # the SCT list extension, so sign its TBSCertificate first.
+    builder = (x509.CertificateBuilder()
+               .subject_name(x509.Name([x509.NameAttribute(NameOID.COMMON_NAME, "localhost")]))
+               .issuer_name(ca.subject)
+               .public_key(server_key.public_key())
+               .serial_number(6)
+               .not_valid_before(datetime.datetime(2026, 10, 18, tzinfo=datetime.timezone.utc))
+               .not_valid_after(datetime.datetime(2126, 10, 18, tzinfo=datetime.timezone.utc))
+               .add_extension(x509.BasicConstraints(ca=False, path_length=None), critical=True)
+               .add_extension(x509.SubjectAlternativeName([x509.DNSName("localhost")]),
+                              critical=False)
+               .add_extension(x509.ExtendedKeyUsage([x509.oid.ExtendedKeyUsageOID.SERVER_AUTH]),
+                              critical=False))
+    precert = builder.sign(ca_key, hashes.SHA256())
+    entry = precert_entry(spki(ca_key), precert.tbs_certificate_bytes)
+    scts = sct_list([make_sct(ec_log, entry), make_sct(rsa_log, entry)])
+    # extnValue contains an OCTET STRING of the TLS-encoded list
+    assert len(scts) < 0x10000
+    value = b"\x04\x82" + struct.pack(">H", len(scts)) + scts
+    cert = builder.add_extension(x509.UnrecognizedExtension(SCT_LIST_OID, value),
+                                 critical=False).sign(ca_key, hashes.SHA256())
+    with open("test_ct_embedded.der", "wb") as f:
+        f.write(cert.public_bytes(serialization.Encoding.DER))
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,214 @@
+#!/usr/bin/env python3
+# Generates certificates for path building and validation tests in `pki/` (see README.md).
+# Requires the `cryptography` package. Run in this directory.
+# Keys are kept in `pki/*.key` and reused, so existing certificates stay valid.
+
+import datetime
+import ipaddress
+import os
+
+from cryptography import x509
+from cryptography.hazmat.primitives import hashes, serialization
+from cryptography.hazmat.primitives.asymmetric import ec, ed25519
+from cryptography.x509.oid import ExtendedKeyUsageOID, NameOID
+
+UTC = datetime.timezone.utc
+NOT_BEFORE = datetime.datetime(2026, 10, 18, tzinfo=UTC)
+NOT_AFTER = datetime.datetime(2126, 10, 18, tzinfo=UTC)
+# expired before 2026-10-18 12:58:12, the time used by the tests
+EXPIRED = datetime.datetime(2026, 10, 18, 6, 0, tzinfo=UTC)
+
+CA_USAGE = x509.KeyUsage(digital_signature=False, content_commitment=False,
+                         key_encipherment=False, data_encipherment=False, key_agreement=False,
+                         key_cert_sign=True, crl_sign=True, encipher_only=False,
+                         decipher_only=False)
+LEAF_USAGE = x509.KeyUsage(digital_signature=True, content_commitment=False,
+                           key_encipherment=False, data_encipherment=False, key_agreement=False,
+                           key_cert_sign=False, crl_sign=False, encipher_only=False,
+                           decipher_only=False)
+
+
+def load_key(path):
+    with open(path, "rb") as f:
+        return serialization.load_pem_private_key(f.read(), None)
+
+
+def key(name, generate=lambda: ec.generate_private_key(ec.SECP256R1())):
+    path = os.path.join("pki", name + ".key")
+    if not os.path.exists(path):
+        k = generate()
+        with open(path, "wb") as f:
+            f.write(k.private_bytes(serialization.Encoding.PEM,
+                                    serialization.PrivateFormat.PKCS8,
+                                    serialization.NoEncryption()))
+    return load_key(path)
+
+
+def name(cn, org=None):
+    attributes = [x509.NameAttribute(NameOID.COMMON_NAME, cn)]
+    if org is not None:
+        attributes.insert(0, x509.NameAttribute(NameOID.ORGANIZATION_NAME, org))
+    return x509.Name(attributes)
+
+
+def eku(*purposes):
+    return (x509.ExtendedKeyUsage(list(purposes)), False)
+
+
+def issue(out, subject, subject_key, issuer, issuer_key, serial, ca, dns=None,
+          not_after=NOT_AFTER, path_length=None, extensions=()):
+    builder = (x509.CertificateBuilder()
+               .subject_name(subject)
+               .issuer_name(issuer)
+               .public_key(subject_key.public_key())
+               .serial_number(serial)
+               .not_valid_before(NOT_BEFORE)
+               .not_valid_after(not_after)
+               .add_extension(x509.BasicConstraints(ca=ca, path_length=path_length),
+                              critical=True)
+               .add_extension(CA_USAGE if ca else LEAF_USAGE, critical=True))
+    if dns is not None:
+        builder = builder.add_extension(x509.SubjectAlternativeName([x509.DNSName(dns)]),
+                                        critical=False)
+    for ext, critical in extensions:
+        builder = builder.add_extension(ext, critical=critical)
+    # Ed25519 takes no hash
+    algorithm = None if isinstance(issuer_key, ed25519.Ed25519PrivateKey) else hashes.SHA256()
+    cert = builder.sign(issuer_key, algorithm)
+    with open(os.path.join("pki", out + ".der"), "wb") as f:
+        f.write(cert.public_bytes(serialization.Encoding.DER))
+    return cert
+
+
+def crl(out, issuer, issuer_key, revoked=()):
+    builder = (x509.CertificateRevocationListBuilder()
+               .issuer_name(issuer.subject)
+               .last_update(NOT_BEFORE)
+               .next_update(NOT_AFTER)
+               .add_extension(x509.AuthorityKeyIdentifier.from_issuer_public_key(
+                   issuer_key.public_key()), critical=False)
+               .add_extension(x509.CRLNumber(1), critical=False))
+    for serial in revoked:
+        builder = builder.add_revoked_certificate(x509.RevokedCertificateBuilder()
+                                                  .serial_number(serial)
+                                                  .revocation_date(NOT_BEFORE)
+                                                  .build())
+    with open(os.path.join("pki", out + ".der"), "wb") as f:
+        f.write(builder.sign(issuer_key, hashes.SHA256()).public_bytes(
+            serialization.Encoding.DER))
+
+
+def main():
+    os.makedirs("pki", exist_ok=True)
+    with open("test_ca.der", "rb") as f:
+        root_a = x509.load_der_x509_certificate(f.read())
+    root_a_key = load_key("test_ca.key")
+    root_b_key = key("root_b")
+    inter_key = key("inter")
+    leaf_key = key("leaf")
+
+    # root A -> intermediate -> leaf
+    inter = issue("inter", name("Suruga Test Intermediate"), inter_key,
+                  root_a.subject, root_a_key, 100, True)
+    issue("inter_expired", inter.subject, inter_key, root_a.subject, root_a_key, 101, True,
+          not_after=EXPIRED)
+    issue("leaf", name("leaf.example"), leaf_key, inter.subject, inter_key, 102, False,
+          dns="leaf.example")
+    crl("crl_inter", inter, inter_key)
+    crl("crl_inter_revoked", inter, inter_key, revoked=[102])
+    # issued by a certificate which is not a CA
+    issue("leaf_by_leaf", name("sub.leaf.example"), leaf_key, name("leaf.example"), leaf_key,
+          103, False, dns="sub.leaf.example")
+
+    # root B, cross-signed by root A: root B -> intermediate B -> leaf B
+    root_b = issue("root_b", name("Suruga Test Root B"), root_b_key,
+                   name("Suruga Test Root B"), root_b_key, 200, True)
+    issue("root_b_cross", root_b.subject, root_b_key, root_a.subject, root_a_key, 201, True)
+    inter_b_key = key("inter_b")
+    inter_b = issue("inter_b", name("Suruga Test Intermediate B"), inter_b_key,
+                    root_b.subject, root_b_key, 202, True)
+    issue("leaf_b", name("b.example"), leaf_key, inter_b.subject, inter_b_key, 203, False,
+          dns="b.example")
+
+    # name constraints
+    permitted = [
+        x509.DNSName("permitted.example"),
+        x509.IPAddress(ipaddress.ip_network("10.0.0.0/8")),
+        x509.DirectoryName(name("Suruga Permitted", org="Suruga")),
+        x509.UniformResourceIdentifier(".permitted.example"),
+    ]
+    excluded = [
+        x509.DNSName("excluded.permitted.example"),
+        x509.IPAddress(ipaddress.ip_network("10.1.0.0/16")),
+    ]
+    nc = issue("nc_inter", name("Suruga Test Constrained"), inter_key, root_a.subject,
+               root_a_key, 300, True,
+               extensions=[(x509.NameConstraints(permitted, excluded), True)])
+    leaves = [
+        ("nc_ok", "Suruga Permitted", [x509.DNSName("www.permitted.example"),
+                                       x509.IPAddress(ipaddress.ip_address("10.2.3.4")),
+                                       x509.UniformResourceIdentifier(
+                                           "https://www.permitted.example/path")]),
+        ("nc_dns", "Suruga Permitted", [x509.DNSName("www.other.example")]),
+        ("nc_dns_excluded", "Suruga Permitted", [x509.DNSName("a.excluded.permitted.example")]),
+        ("nc_ip", "Suruga Permitted", [x509.IPAddress(ipaddress.ip_address("192.168.1.1"))]),
+        ("nc_ip_excluded", "Suruga Permitted", [x509.IPAddress(ipaddress.ip_address("10.1.2.3"))]),
+        ("nc_dir", "Suruga Other", [x509.DNSName("www.permitted.example")]),
+        ("nc_uri", "Suruga Permitted", [x509.UniformResourceIdentifier(
+            "https://permitted.example/path")]),
+    ]
+    # same name and key without constraints, for another path
+    issue("nc_inter_unconstrained", nc.subject, inter_key, root_a.subject, root_a_key, 301, True)
+    for i, (out, cn, names) in enumerate(leaves):
+        issue(out, name(cn, org="Suruga"), leaf_key, nc.subject, inter_key, 310 + i, False,
+              extensions=[(x509.SubjectAlternativeName(names), False)])
+    email = [x509.RFC822Name("permitted.example")]
+    nc_email = issue("nc_email_inter", name("Suruga Test Email Constrained"), inter_key,
+                     root_a.subject, root_a_key, 320, True,
+                     extensions=[(x509.NameConstraints(email, None), True)])
+    issue("nc_email_leaf", name("www.permitted.example"), leaf_key, nc_email.subject, inter_key,
+          321, False, dns="www.permitted.example")
+
+    # extended key usage
+    server_auth = ExtendedKeyUsageOID.SERVER_AUTH
+    client_auth = ExtendedKeyUsageOID.CLIENT_AUTH
+    issue("leaf_server", name("leaf.example"), leaf_key, inter.subject, inter_key, 400, False,
+          dns="leaf.example", extensions=[eku(server_auth)])
+    issue("leaf_client", name("leaf.example"), leaf_key, inter.subject, inter_key, 401, False,
+          dns="leaf.example", extensions=[eku(client_auth)])
+    inter_client = issue("inter_client", name("Suruga Test Client CA"), inter_key,
+                         root_a.subject, root_a_key, 402, True, extensions=[eku(client_auth)])
+    issue("leaf_server_by_client_ca", name("leaf.example"), leaf_key, inter_client.subject,
+          inter_key, 403, False, dns="leaf.example", extensions=[eku(server_auth)])
+
+    # path length constraint
+    inter_len0 = issue("inter_len0", name("Suruga Test Path Length 0"), inter_key,
+                       root_a.subject, root_a_key, 500, True, path_length=0)
+    issue("leaf_len0", name("leaf.example"), leaf_key, inter_len0.subject, inter_key, 501,
+          False, dns="leaf.example")
+    inter_sub = issue("inter_sub_len0", name("Suruga Test Sub CA"), inter_key,
+                      inter_len0.subject, inter_key, 502, True)
+    issue("leaf_sub_len0", name("leaf.example"), leaf_key, inter_sub.subject, inter_key, 503,
+          False, dns="leaf.example")
+
+    # unknown critical extension
+    unknown = (x509.UnrecognizedExtension(x509.ObjectIdentifier("1.3.6.1.4.1.55555.1"),
+                                          b"\x05\x00"), True)
+    issue("leaf_critical", name("leaf.example"), leaf_key, inter.subject, inter_key, 600, False,
+          dns="leaf.example", extensions=[unknown])
+    inter_critical = issue("inter_critical", name("Suruga Test Critical CA"), inter_key,
+                           root_a.subject, root_a_key, 601, True, extensions=[unknown])
+    issue("leaf_by_critical", name("leaf.example"), leaf_key, inter_critical.subject, inter_key,
+          602, False, dns="leaf.example")
+
+    # Ed25519: root A -> Ed25519 intermediate -> Ed25519 leaf
+    ed_inter_key = key("ed_inter", ed25519.Ed25519PrivateKey.generate)
+    ed_leaf_key = key("ed_leaf", ed25519.Ed25519PrivateKey.generate)
+    ed_inter = issue("ed_inter", name("Suruga Test Ed25519 CA"), ed_inter_key, root_a.subject,
+                     root_a_key, 700, True)
+    issue("ed_leaf", name("localhost"), ed_leaf_key, ed_inter.subject, ed_inter_key, 701, False,
+          dns="localhost", extensions=[eku(server_auth, client_auth)])
+
+
+if __name__ == "__main__":
+    main()